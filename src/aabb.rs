@@ -23,6 +23,10 @@ impl Aabb {
         &self.max
     }
 
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
     /// Returns true iff the ray intersects the bounding box;
     /// follows Andrew Kensler's hit method.
     pub fn hit(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> bool {
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use glam::{vec3, Quat, Vec3};
+
+use crate::{
+    geometry::sphere::Sphere,
+    textures::{image_texture::ImageTexture, texture::Texture},
+};
+
+/// The radiance seen by rays which escape the scene without hitting anything.
+pub enum Background {
+    /// The same color in every direction.
+    Solid(Vec3),
+    /// Blends from `bottom` (looking straight down) to `top` (looking straight up),
+    /// as in the classic "Ray Tracing in One Weekend" sky.
+    Gradient { bottom: Vec3, top: Vec3 },
+    /// An equirectangular (latitude-longitude) image, looked up by ray direction.
+    /// The image is turned by `yaw` degrees about the vertical axis and then tilted by
+    /// `pitch` degrees, so it can be aligned with the scene.
+    Image {
+        texture: Arc<ImageTexture>,
+        yaw: f32,
+        pitch: f32,
+    },
+}
+
+impl Background {
+    /// The classic blue-to-white sky gradient.
+    pub fn sky() -> Background {
+        Background::Gradient {
+            bottom: vec3(1.0, 1.0, 1.0),
+            top: vec3(0.5, 0.7, 1.0),
+        }
+    }
+
+    /// Returns the radiance arriving from the `direction`, which need not be normalized.
+    pub fn value(&self, direction: Vec3) -> Vec3 {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.normalize().y + 1.0);
+                bottom.lerp(*top, t)
+            }
+            Background::Image {
+                texture,
+                yaw,
+                pitch,
+            } => {
+                // Rotate the direction into the image's frame, the inverse of the image's rotation.
+                let rotation = Quat::from_rotation_y(yaw.to_radians())
+                    * Quat::from_rotation_x(pitch.to_radians());
+                let direction = rotation.inverse() * direction.normalize();
+                let (u, v) = Sphere::get_uv(&direction);
+                texture.value(u, v, &direction)
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Manifests of render jobs for the batch subcommand, which renders several scenes and
+//! cameras in one process so that the models and textures they share are loaded once.
+//!
+//! A manifest is a TOML file of `[[job]]` tables, each holding the scene to render and
+//! the command line options to render it with, named as on the command line without the
+//! leading dashes. Options in an optional `[defaults]` table apply to every job which
+//! doesn't set them itself.
+
+/// Parses a batch manifest into the command line arguments of each job, starting with
+/// its scene.
+pub fn parse_manifest(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let manifest: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let defaults = match manifest.get("defaults") {
+        Some(toml::Value::Table(defaults)) => defaults.clone(),
+        Some(_) => return Err(String::from("[defaults] must be a table")),
+        None => toml::value::Table::new(),
+    };
+    let Some(toml::Value::Array(jobs)) = manifest.get("job") else {
+        return Err(String::from("the manifest lists no [[job]]s"));
+    };
+    let scalar = |value: &toml::Value| match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        _ => None,
+    };
+    jobs.iter()
+        .enumerate()
+        .map(|(index, job)| {
+            let Some(job) = job.as_table() else {
+                return Err(format!("job {} isn't a table", index + 1));
+            };
+            let mut options = defaults.clone();
+            options.extend(job.clone());
+            let Some(scene) = options.remove("scene").as_ref().and_then(scalar) else {
+                return Err(format!("job {} has no scene", index + 1));
+            };
+            let mut args = vec![scene];
+            for (key, value) in options.iter() {
+                let flag = format!("--{}", key);
+                match value {
+                    toml::Value::Boolean(true) => args.push(flag),
+                    toml::Value::Boolean(false) => (),
+                    toml::Value::Array(values) if values.iter().all(|v| v.is_str()) => {
+                        for value in values {
+                            args.push(format!("{}={}", flag, value.as_str().unwrap()));
+                        }
+                    }
+                    toml::Value::Array(values) => {
+                        args.push(flag);
+                        for value in values {
+                            args.push(scalar(value).ok_or_else(|| {
+                                format!("job {}: '{}' must hold numbers or strings", index + 1, key)
+                            })?);
+                        }
+                    }
+                    value => match scalar(value) {
+                        Some(value) => args.push(format!("{}={}", flag, value)),
+                        None => {
+                            return Err(format!(
+                                "job {}: '{}' can't be a table or date",
+                                index + 1,
+                                key
+                            ))
+                        }
+                    },
+                }
+            }
+            Ok(args)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_manifest;
+
+    #[test]
+    fn options_become_flags() {
+        let jobs = parse_manifest(
+            r#"
+            [defaults]
+            samples-per-pixel = 16
+            still-time = 0.5
+            seed = 3
+
+            [[job]]
+            scene = "cornell"
+            camera = ["front", "top"]
+            all-cameras = false
+            seed = 4
+
+            [[job]]
+            scene = "bunny"
+            cam-look-from = [278, 278.5, -800]
+            progressive-preview = true
+            "#,
+        )
+        .unwrap();
+        // Options are given in the order of their names.
+        assert_eq!(
+            jobs,
+            vec![
+                vec![
+                    "cornell",
+                    "--camera=front",
+                    "--camera=top",
+                    "--samples-per-pixel=16",
+                    "--seed=4",
+                    "--still-time=0.5",
+                ],
+                vec![
+                    "bunny",
+                    "--cam-look-from",
+                    "278",
+                    "278.5",
+                    "-800",
+                    "--progressive-preview",
+                    "--samples-per-pixel=16",
+                    "--seed=3",
+                    "--still-time=0.5",
+                ],
+            ]
+        );
+        assert!(parse_manifest("[[job]]\nseed = 1").is_err());
+        assert!(parse_manifest("[defaults]\nseed = 1").is_err());
+    }
+}
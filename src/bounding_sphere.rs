@@ -0,0 +1,96 @@
+//! Spheres bounding objects, which complement their bounding boxes: a ray can be tested
+//! against one in a few multiplications, and how large an object appears from a point
+//! follows directly from it, e.g. for choosing how much detail to give it.
+
+use glam::Vec3;
+
+use crate::{aabb::Aabb, ray::Ray};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    /// The sphere through the corners of the `aabb`.
+    pub fn from_aabb(aabb: &Aabb) -> BoundingSphere {
+        let center = (*aabb.min() + *aabb.max()) / 2.0;
+        BoundingSphere::new(center, aabb.max().distance(center))
+    }
+
+    /// The smallest sphere enclosing both `self` and `other`.
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        let offset = other.center - self.center;
+        let distance = offset.length();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        BoundingSphere::new(center, radius)
+    }
+
+    /// Returns true iff the part of the ray from `t_min` to `t_max` passes through the
+    /// sphere. The sphere is padded slightly, so that rays grazing the object it bounds
+    /// aren't rejected by rounding errors.
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let to_center = self.center - ray.origin;
+        // The point of the ray segment closest to the center.
+        let t = (to_center.dot(ray.direction) / ray.direction.length_squared()).clamp(t_min, t_max);
+        let radius = self.radius * (1.0 + 1e-4) + 1e-6;
+        (ray.at(t) - self.center).length_squared() <= radius * radius
+    }
+
+    /// The distance from the `point` to the sphere, or 0 if it's inside.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        (point.distance(self.center) - self.radius).max(0.0)
+    }
+
+    /// The angle between the center of the sphere and its edge as seen from the `point`,
+    /// in radians, or pi if the point is inside it.
+    pub fn angular_radius(&self, point: Vec3) -> f32 {
+        let distance = point.distance(self.center);
+        if distance <= self.radius {
+            std::f32::consts::PI
+        } else {
+            (self.radius / distance).asin()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use crate::{aabb::Aabb, ray::Ray};
+
+    use super::BoundingSphere;
+
+    #[test]
+    fn rays_are_rejected_only_if_they_miss() {
+        let sphere = BoundingSphere::new(vec3(0.0, 0.0, 5.0), 1.0);
+        let ray = |x: f32| Ray::new(vec3(x, 0.0, 0.0), Vec3::Z, 0.0);
+        assert!(sphere.hit(&ray(0.99), 0.0, f32::INFINITY));
+        assert!(!sphere.hit(&ray(1.01), 0.0, f32::INFINITY));
+        // Only the segment between t_min and t_max counts.
+        assert!(!sphere.hit(&ray(0.0), 0.0, 3.9));
+        assert!(!sphere.hit(&ray(0.0), 6.1, f32::INFINITY));
+        assert!(sphere.hit(&ray(0.0), 4.5, 4.6));
+
+        let union = sphere.union(&BoundingSphere::new(vec3(0.0, 0.0, -5.0), 2.0));
+        assert_eq!(union, BoundingSphere::new(vec3(0.0, 0.0, -0.5), 6.5));
+        let aabb = Aabb::new(Vec3::ZERO, vec3(2.0, 2.0, 2.0));
+        assert_eq!(
+            BoundingSphere::from_aabb(&aabb),
+            BoundingSphere::new(Vec3::ONE, 3.0_f32.sqrt())
+        );
+    }
+}
@@ -1,16 +1,23 @@
 use std::{
     cmp::Ordering,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{self, AtomicU8},
+        Arc, Mutex,
+    },
 };
 
 use ahash::AHashMap;
+use glam::Vec3;
 use rand::Rng;
 use uuid::Uuid;
 
 use crate::{
     aabb::Aabb,
-    hittable::{HitRecord, Hittable, HittableList},
-    hrpp::Predictor,
+    hittable::{HitRecord, Hittable, HittableList, Volume},
+    hrpp::{self, PredictionOutcome, Predictor},
+    lbvh::{self, Hierarchy},
+    random,
+    ray::RayMask,
 };
 
 #[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
@@ -33,6 +40,64 @@ enum Child {
     Hittable(Arc<dyn Hittable>),
 }
 
+/// How the hierarchy of a BVH is built.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BvhBuild {
+    /// Recursively splits the objects in half along a random axis. Slow to build for large
+    /// scenes, as it sorts the objects again at each level.
+    #[default]
+    Median,
+    /// As `Median`, splitting along the axis over which the objects' centers are spread
+    /// the farthest rather than a random one. Just as fast to build, and gives tighter
+    /// boxes in scenes spread much farther along some axes than others.
+    LongestAxis,
+    /// Sorts the objects along a Morton curve and builds every node in parallel, as a
+    /// linear BVH. Much faster to build, though rays may traverse more of it.
+    Morton,
+    /// As `Morton`, with the codes and nodes computed on the GPU. Falls back to `Morton`
+    /// if there's no GPU, or without the `gpu` feature.
+    MortonGpu,
+}
+
+/// How rays traverse a BVH to find their closest hit, so that the strategies can be
+/// compared against each other on the same scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Traversal {
+    /// Recurses into both children of each node whose box the ray hits.
+    Recursive,
+    /// Visits nodes from a stack rather than recursing, skipping any whose box is beyond
+    /// the closest hit found so far.
+    Stack,
+    /// As `Stack`, visiting first the child whose center is nearer along the ray, so
+    /// that the closest hit is found sooner and more of the farther nodes are skipped.
+    Ordered,
+    /// Looks the ray up in the BVH's hash-based ray path predictor first, if it has one,
+    /// and otherwise traverses as `Recursive`.
+    #[default]
+    Hrpp,
+}
+
+/// The traversal of BVHs which weren't given their own with `Bvh::with_traversal()`.
+static DEFAULT_TRAVERSAL: AtomicU8 = AtomicU8::new(Traversal::Hrpp as u8);
+
+/// Sets how rays traverse every BVH which wasn't given its own traversal.
+pub fn set_default_traversal(traversal: Traversal) {
+    DEFAULT_TRAVERSAL.store(traversal as u8, atomic::Ordering::Relaxed);
+}
+
+fn default_traversal() -> Traversal {
+    match DEFAULT_TRAVERSAL.load(atomic::Ordering::Relaxed) {
+        t if t == Traversal::Recursive as u8 => Traversal::Recursive,
+        t if t == Traversal::Stack as u8 => Traversal::Stack,
+        t if t == Traversal::Ordered as u8 => Traversal::Ordered,
+        _ => Traversal::Hrpp,
+    }
+}
+
+/// The number of nodes which stack traversals can hold without allocating, which is
+/// enough for BVHs up to this deep.
+const STACK_SIZE: usize = 64;
+
 /// A bounding volume hierarchy implemented via a binary tree.
 /// The binary tree is maintained in a Vec.
 pub struct Bvh {
@@ -40,16 +105,30 @@ pub struct Bvh {
     root_index: usize,
     nodes: Vec<BvhNode>,
     max_depth: u32,
+    /// If present, how rays traverse this BVH, rather than the default traversal.
+    traversal: Option<Traversal>,
 }
 
 impl Bvh {
     pub fn new(list: HittableList, time_0: f32, time_1: f32) -> Bvh {
+        Bvh::build(list, time_0, time_1, BvhBuild::Median)
+    }
+
+    /// Creates a BVH from the *list*, building its hierarchy as *build* says.
+    pub fn build(list: HittableList, time_0: f32, time_1: f32, build: BvhBuild) -> Bvh {
         // 2n + 1 - num nodes in binary tree for n leaf nodes.
         //   This assumes on object per leaf node, which would be the upper bound
         //   on how many leaf nodes we need.
         let mut nodes = Vec::with_capacity(list.objects.len() * 2 + 1);
         let id = BvhId(Uuid::new_v4());
-        let root_index = BvhNode::new(list, time_0, time_1, &mut nodes);
+        // Linear BVHs need at least two objects to have an internal node.
+        let root_index = if matches!(build, BvhBuild::Median | BvhBuild::LongestAxis)
+            || list.objects.len() < 2
+        {
+            BvhNode::new(list, time_0, time_1, build, &mut nodes)
+        } else {
+            BvhNode::from_morton_codes(list, time_0, time_1, build, &mut nodes)
+        };
 
         let max_depth = nodes[root_index].max_depth(&nodes);
 
@@ -58,9 +137,17 @@ impl Bvh {
             root_index,
             nodes,
             max_depth,
+            traversal: None,
         }
     }
 
+    /// Traverses this BVH with `traversal`, rather than the default set by
+    /// `set_default_traversal()`.
+    pub fn with_traversal(mut self, traversal: Traversal) -> Bvh {
+        self.traversal = Some(traversal);
+        self
+    }
+
     /// Creates a BVH from the *list*, and creates a predictor for the BVH,
     /// adding it to the *predictors*.
     /// The predictors are stored separately from the BVH, as they must be modified
@@ -72,7 +159,18 @@ impl Bvh {
         time_1: f32,
         predictors: &mut AHashMap<BvhId, Mutex<Predictor>>,
     ) -> Bvh {
-        let bvh = Bvh::new(list, time_0, time_1);
+        Bvh::build_with_predictor(list, time_0, time_1, BvhBuild::Median, predictors)
+    }
+
+    /// As `with_predictor()`, building the hierarchy as *build* says.
+    pub fn build_with_predictor(
+        list: HittableList,
+        time_0: f32,
+        time_1: f32,
+        build: BvhBuild,
+        predictors: &mut AHashMap<BvhId, Mutex<Predictor>>,
+    ) -> Bvh {
+        let bvh = Bvh::build(list, time_0, time_1, build);
 
         let predictor = Mutex::new(Predictor::new(bvh.id));
         predictors.insert(bvh.id, predictor);
@@ -80,31 +178,71 @@ impl Bvh {
         bvh
     }
 
-    // Goes up the tree from the specified node, go_up_level times
-    // If the top of the tree is reached, returns the top of the tree
-    fn go_up_level(&self, start_node: usize, go_up_level: u32) -> usize {
-        // HRPP’s go up level as the level in the acceleration structure tree the predictor table predicts.
-        // A Go Up Level of 0 predicts the acceleration structure’s leaf nodes.
-        // A Go Up Level of 1 predicts the parent node of the leaf nodes.
-        // A Go Up Level of 2 predicts the grand-parent node of the leaf nodes, etc
-        let mut cur_node_idx = start_node;
-        for _ in 0..go_up_level {
-            if let Some(parent) = self.nodes[cur_node_idx].parent {
-                cur_node_idx = parent;
+    /// Puts the objects of a scene's top-level *list* into a BVH, so that rays don't test
+    /// each of them in turn. Objects without a bounding box (which a BVH can't hold) are
+    /// kept in the returned list alongside it, as are named objects, so that they keep
+    /// their names and can still be replaced. If fewer than two objects would go in the
+    /// BVH, they're left in the list instead.
+    pub fn top_level(list: HittableList, time_0: f32, time_1: f32) -> HittableList {
+        Bvh::top_level_helper(list, time_0, time_1, None)
+    }
+
+    /// As `top_level()`, creating a predictor for the BVH and adding it to the
+    /// *predictors*, so that rays can skip traversing both it and the BVHs with
+    /// predictors nested within it.
+    pub fn top_level_with_predictor(
+        list: HittableList,
+        time_0: f32,
+        time_1: f32,
+        predictors: &mut AHashMap<BvhId, Mutex<Predictor>>,
+    ) -> HittableList {
+        Bvh::top_level_helper(list, time_0, time_1, Some(predictors))
+    }
+
+    fn top_level_helper(
+        mut list: HittableList,
+        time_0: f32,
+        time_1: f32,
+        predictors: Option<&mut AHashMap<BvhId, Mutex<Predictor>>>,
+    ) -> HittableList {
+        let mut names: AHashMap<usize, String> = list
+            .take_names()
+            .into_iter()
+            .map(|(name, index)| (index, name))
+            .collect();
+        let mut world = HittableList::new();
+        list.move_lights_to(&mut world);
+        let mut bounded = Vec::new();
+        for (index, object) in list.objects.into_iter().enumerate() {
+            if let Some(name) = names.remove(&index) {
+                world.add_named(name, object);
+            } else if object.bounding_box(time_0, time_1).is_some() {
+                bounded.push(object);
             } else {
-                return cur_node_idx;
+                world.add(object);
             }
         }
-        cur_node_idx
-    }
-}
-
-impl Hittable for Bvh {
-    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
-        self.nodes[self.root_index].bounding_box(time_0, time_1)
+        if bounded.len() < 2 {
+            for object in bounded {
+                world.add(object);
+            }
+        } else {
+            let mut bounded_list = HittableList::new();
+            for object in bounded {
+                bounded_list.add(object);
+            }
+            let bvh = match predictors {
+                Some(predictors) => Bvh::with_predictor(bounded_list, time_0, time_1, predictors),
+                None => Bvh::new(bounded_list, time_0, time_1),
+            };
+            world.add(Arc::new(bvh));
+        }
+        world
     }
 
-    fn hit(
+    /// Finds the closest hit, looking the ray up in this BVH's predictor first if it has
+    /// one.
+    fn hit_with_predictor(
         &self,
         ray: &crate::ray::Ray,
         t_min: f32,
@@ -118,104 +256,235 @@ impl Hittable for Bvh {
         };
 
         if let Some(predictor_mtx) = this_bvh_predictor_maybe {
-            let predictor = predictor_mtx.lock().unwrap();
-            let predicted_node_idx = predictor.get_predictions(ray).cloned();
-            drop(predictor);
+            // BVHs nested in this one key their predictions by this ray's hash as well.
+            let key = hrpp::key(ray);
+            hrpp::chained(key, || {
+                self.hit_with_prediction(ray, t_min, t_max, predictors, predictor_mtx, key)
+            })
+        } else {
+            // No predictor for this BVH. Simply traverse the tree and get the result.
+            let (hit_record, _) =
+                self.nodes[self.root_index].hit(ray, t_min, t_max, &self.nodes, predictors)?;
+            Some(hit_record)
+        }
+    }
 
-            if let Some(predicted_node_indices) = predicted_node_idx {
-                // We have a prediction(s) for this ray.
-                // Find the closest hit within the predicted nodes.
-
-                let mut closest_so_far = t_max;
-                let mut closest_hit_record_and_leaf_node = None;
-                for predicted_index in predicted_node_indices.into_iter() {
-                    let hit_record_and_leaf_node = self.nodes[predicted_index].hit(
-                        ray,
-                        t_min,
-                        closest_so_far,
-                        &self.nodes,
-                        &predictors,
-                    );
-                    if let Some(hit_record_and_leaf_node) = hit_record_and_leaf_node {
-                        closest_so_far = hit_record_and_leaf_node.0.t;
-                        closest_hit_record_and_leaf_node = Some(hit_record_and_leaf_node);
-                    }
+    /// Finds the closest hit, starting from the nodes predicted for the ray with the hash
+    /// `key` by this BVH's predictor, and records a prediction for it if there wasn't one.
+    fn hit_with_prediction(
+        &self,
+        ray: &crate::ray::Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        predictor_mtx: &Mutex<Predictor>,
+        key: u64,
+    ) -> Option<HitRecord> {
+        let predictor = predictor_mtx.lock().unwrap();
+        let predicted_node_idx = predictor.get_predictions_by_key(key).cloned();
+        drop(predictor);
+
+        if let Some(predicted_node_indices) = predicted_node_idx {
+            // We have a prediction(s) for this ray.
+            // Find the closest hit within the predicted nodes.
+
+            let mut closest_so_far = t_max;
+            let mut closest_hit_record_and_leaf_node = None;
+            for predicted_index in predicted_node_indices.into_iter() {
+                let hit_record_and_leaf_node = self.nodes[predicted_index].hit(
+                    ray,
+                    t_min,
+                    closest_so_far,
+                    &self.nodes,
+                    predictors,
+                );
+                if let Some(hit_record_and_leaf_node) = hit_record_and_leaf_node {
+                    closest_so_far = hit_record_and_leaf_node.0.t;
+                    closest_hit_record_and_leaf_node = Some(hit_record_and_leaf_node);
                 }
+            }
 
-                if let Some(hit_record_and_leaf_node) = closest_hit_record_and_leaf_node {
-                    // A true postive - the ray DID hit something within the predicted node(s).
-                    // This is the best case outcome - we can use this result, thereby skipping traversal up to the predicted node.
-                    // This case can result in the wrong visual output, however, where the ray does not find the closest intersection
-                    // that may lie in a different node. See 4.3 of https://arxiv.org/abs/1910.01304
+            if let Some(hit_record_and_leaf_node) = closest_hit_record_and_leaf_node {
+                // A true postive - the ray DID hit something within the predicted node(s).
+                // This is the best case outcome - we can use this result, thereby skipping traversal up to the predicted node.
+                // This case can result in the wrong visual output, however, where the ray does not find the closest intersection
+                // that may lie in a different node. See 4.3 of https://arxiv.org/abs/1910.01304
 
-                    // Update stats
-                    let mut predictor = predictor_mtx.lock().unwrap();
-                    predictor.true_positive_predictions += 1;
-                    drop(predictor);
+                hrpp::record_outcome(ray, self.id, PredictionOutcome::TruePositive);
 
-                    return Some(hit_record_and_leaf_node.0);
-                } else {
-                    // A false positive - the ray did not hit anything within the predicted node(s).
-                    // Go back and traverse the tree from the root.
-                    // A replacement policy here instead might improve HRPP performance.
+                Some(hit_record_and_leaf_node.0)
+            } else {
+                // A false positive - the ray did not hit anything within the predicted node(s).
+                // Go back and traverse the tree from the root.
+                // A replacement policy here instead might improve HRPP performance.
 
-                    // Update stats
-                    let mut predictor = predictor_mtx.lock().unwrap();
-                    predictor.false_positive_predictions += 1;
-                    drop(predictor);
+                hrpp::record_outcome(ray, self.id, PredictionOutcome::FalsePositive);
 
-                    let hit_rec_and_leaf_node =
-                        self.nodes[self.root_index].hit(ray, t_min, t_max, &self.nodes, predictors);
+                let hit_rec_and_leaf_node =
+                    self.nodes[self.root_index].hit(ray, t_min, t_max, &self.nodes, predictors);
 
-                    return match hit_rec_and_leaf_node {
-                        Some(hit_rec_and_leaf_node) => {
-                            let (_, leaf_node) = hit_rec_and_leaf_node;
+                match hit_rec_and_leaf_node {
+                    Some(hit_rec_and_leaf_node) => {
+                        let (_, leaf_node) = hit_rec_and_leaf_node;
 
-                            let predicted_node_idx = self.go_up_level(leaf_node.0, GO_UP_LEVEL);
+                        let predicted_node_idx = self.go_up_level(leaf_node.0, GO_UP_LEVEL);
 
-                            // Add the predicted node to the table
-                            let mut predictor = predictor_mtx.lock().unwrap();
-                            predictor.insert(ray, predicted_node_idx);
-                            drop(predictor);
+                        // Add the predicted node to the table
+                        let mut predictor = predictor_mtx.lock().unwrap();
+                        predictor.insert_by_key(key, predicted_node_idx);
+                        drop(predictor);
 
-                            Some(hit_rec_and_leaf_node.0)
-                        }
-                        None => None,
-                    };
+                        Some(hit_rec_and_leaf_node.0)
+                    }
+                    None => None,
                 }
-            } else {
-                // No prediction for this ray.
-                // Find a hit_record via regular traversal, and then add a prediction to the table for this ray.
+            }
+        } else {
+            // No prediction for this ray.
+            // Find a hit_record via regular traversal, and then add a prediction to the table for this ray.
 
-                // update stats
-                let mut predictor = predictor_mtx.lock().unwrap();
-                predictor.no_predictions += 1;
-                drop(predictor);
+            hrpp::record_outcome(ray, self.id, PredictionOutcome::NoPrediction);
 
-                // Return if no hit; we won't make a prediction if no geometry is hit.
-                let (hit_record, leaf_node_idx) =
-                    self.nodes[self.root_index].hit(ray, t_min, t_max, &self.nodes, &predictors)?;
+            // Return if no hit; we won't make a prediction if no geometry is hit.
+            let (hit_record, leaf_node_idx) =
+                self.nodes[self.root_index].hit(ray, t_min, t_max, &self.nodes, predictors)?;
 
-                // We will return the hit record, but first add a prediction to the table for this ray.
+            // We will return the hit record, but first add a prediction to the table for this ray.
 
-                // Get the prediction index
-                assert!(self.nodes[leaf_node_idx.0].parent.is_some());
-                let predicted_node_idx = self.go_up_level(leaf_node_idx.0, GO_UP_LEVEL);
+            // Get the prediction index
+            assert!(self.nodes[leaf_node_idx.0].parent.is_some());
+            let predicted_node_idx = self.go_up_level(leaf_node_idx.0, GO_UP_LEVEL);
 
-                // Insert prediction into table
-                let mut predictor = predictor_mtx.lock().unwrap();
-                predictor.insert(&ray, predicted_node_idx);
-                drop(predictor);
+            // Insert prediction into table
+            let mut predictor = predictor_mtx.lock().unwrap();
+            predictor.insert_by_key(key, predicted_node_idx);
+            drop(predictor);
 
-                return Some(hit_record);
-            }
-        } else {
-            // No predictor for this BVH. Simply traverse the tree and get the result.
-            let (hit_record, _) =
-                self.nodes[self.root_index].hit(ray, t_min, t_max, &self.nodes, &predictors)?;
             Some(hit_record)
         }
     }
+
+    /// Finds the closest hit by visiting nodes from a stack, skipping those beyond the
+    /// closest hit so far. If `ordered`, the child nearer along the ray is visited first.
+    fn hit_from_stack(
+        &self,
+        ray: &crate::ray::Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        ordered: bool,
+    ) -> Option<HitRecord> {
+        // The stack never holds more than one node per level, plus the root.
+        let depth = self.max_depth as usize + 1;
+        let mut small_stack = [0; STACK_SIZE];
+        let mut large_stack = Vec::new();
+        let stack = if depth <= STACK_SIZE {
+            &mut small_stack[..]
+        } else {
+            large_stack.resize(depth, 0);
+            &mut large_stack[..]
+        };
+
+        let ray_mask = ray.mask();
+        stack[0] = self.root_index;
+        let mut len = 1;
+        let mut closest_so_far = t_max;
+        let mut closest_hit = None;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len]];
+            if !node.mask.admits(ray_mask) || !node.bounding_box.hit(ray, t_min, closest_so_far) {
+                continue;
+            }
+            let mut children = [&node.left, &node.right];
+            if ordered {
+                let distance = |child: &Child| match child {
+                    Child::Index(i) => {
+                        let bounding_box = &self.nodes[*i].bounding_box;
+                        let center = (*bounding_box.min() + *bounding_box.max()) / 2.0;
+                        (center - ray.origin).dot(ray.direction)
+                    }
+                    Child::Hittable(_) => f32::NEG_INFINITY,
+                };
+                if distance(children[1]) < distance(children[0]) {
+                    children.swap(0, 1);
+                }
+            }
+            // Push the later child first, so that the earlier one is visited first.
+            for child in children.into_iter().rev() {
+                match child {
+                    Child::Index(i) => {
+                        stack[len] = *i;
+                        len += 1;
+                    }
+                    Child::Hittable(hittable) => {
+                        if let Some(hit_record) =
+                            hittable.hit(ray, t_min, closest_so_far, predictors)
+                        {
+                            closest_so_far = hit_record.t;
+                            closest_hit = Some(hit_record);
+                        }
+                    }
+                }
+                // Leaves with a single object hold it in both children.
+                if let (Child::Hittable(left), Child::Hittable(right)) = (&node.left, &node.right) {
+                    if Arc::ptr_eq(left, right) {
+                        break;
+                    }
+                }
+            }
+        }
+        closest_hit
+    }
+
+    // Goes up the tree from the specified node, go_up_level times
+    // If the top of the tree is reached, returns the top of the tree
+    fn go_up_level(&self, start_node: usize, go_up_level: u32) -> usize {
+        // HRPP’s go up level as the level in the acceleration structure tree the predictor table predicts.
+        // A Go Up Level of 0 predicts the acceleration structure’s leaf nodes.
+        // A Go Up Level of 1 predicts the parent node of the leaf nodes.
+        // A Go Up Level of 2 predicts the grand-parent node of the leaf nodes, etc
+        let mut cur_node_idx = start_node;
+        for _ in 0..go_up_level {
+            if let Some(parent) = self.nodes[cur_node_idx].parent {
+                cur_node_idx = parent;
+            } else {
+                return cur_node_idx;
+            }
+        }
+        cur_node_idx
+    }
+}
+
+impl Hittable for Bvh {
+    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
+        self.nodes[self.root_index].bounding_box(time_0, time_1)
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        self.nodes[self.root_index].volumes_containing(point, time, &self.nodes, volumes);
+    }
+
+    fn mask(&self) -> RayMask {
+        self.nodes[self.root_index].mask
+    }
+
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        match self.traversal.unwrap_or_else(default_traversal) {
+            Traversal::Hrpp => self.hit_with_predictor(ray, t_min, t_max, predictors),
+            Traversal::Recursive => self.nodes[self.root_index]
+                .hit(ray, t_min, t_max, &self.nodes, predictors)
+                .map(|(hit_record, _)| hit_record),
+            Traversal::Stack => self.hit_from_stack(ray, t_min, t_max, predictors, false),
+            Traversal::Ordered => self.hit_from_stack(ray, t_min, t_max, predictors, true),
+        }
+    }
 }
 
 impl Drop for Bvh {
@@ -233,6 +502,9 @@ pub struct BvhNode {
     left: Child,
     right: Child,
     bounding_box: Aabb,
+    /// The union of the masks of everything below this node, so that rays which couldn't
+    /// hit any of it skip it.
+    mask: RayMask,
 }
 
 impl BvhNode {
@@ -240,21 +512,28 @@ impl BvhNode {
         mut list: HittableList,
         time_0: f32,
         time_1: f32,
+        build: BvhBuild,
         nodes: &mut Vec<BvhNode>,
     ) -> usize {
-        BvhNode::new_helper(list.objects.as_mut_slice(), time_0, time_1, nodes)
+        BvhNode::new_helper(list.objects.as_mut_slice(), time_0, time_1, build, nodes)
     }
 
     // Creates a BvhNode and adds it the nodes list. Returns the index of that BvhNode in the nodes list.
+    // The objects are divided along their longest axis for `BvhBuild::LongestAxis`, and
+    // along a random axis otherwise.
     fn new_helper(
         objects: &mut [Arc<dyn Hittable>],
         time_0: f32,
         time_1: f32,
+        build: BvhBuild,
         nodes: &mut Vec<BvhNode>,
     ) -> usize {
-        let mut rng = rand::thread_rng();
-        // Random axis on which to divide the objects
-        let axis = rng.gen_range(0..=2);
+        let axis = if build == BvhBuild::LongestAxis {
+            longest_axis(objects, time_0, time_1)
+        } else {
+            // Random axis on which to divide the objects
+            random::rng().gen_range(0..=2)
+        };
         let comparator = match axis {
             0 => box_compare_x,
             1 => box_compare_y,
@@ -284,12 +563,93 @@ impl BvhNode {
                 let mid = objects.len() / 2;
                 let (left_objects, right_objects) = objects.split_at_mut(mid);
                 (
-                    Child::Index(BvhNode::new_helper(left_objects, time_0, time_1, nodes)),
-                    Child::Index(BvhNode::new_helper(right_objects, time_0, time_1, nodes)),
+                    Child::Index(BvhNode::new_helper(
+                        left_objects,
+                        time_0,
+                        time_1,
+                        build,
+                        nodes,
+                    )),
+                    Child::Index(BvhNode::new_helper(
+                        right_objects,
+                        time_0,
+                        time_1,
+                        build,
+                        nodes,
+                    )),
                 )
             }
         };
 
+        BvhNode::push(left, right, time_0, time_1, nodes)
+    }
+
+    /// Creates a BvhNode over objects sorted along a Morton curve and adds it to the nodes
+    /// list, as `new()` does. Returns the index of the root.
+    fn from_morton_codes(
+        list: HittableList,
+        time_0: f32,
+        time_1: f32,
+        build: BvhBuild,
+        nodes: &mut Vec<BvhNode>,
+    ) -> usize {
+        let centroids: Vec<Vec3> = list
+            .objects
+            .iter()
+            .map(|object| {
+                let bounding_box = object
+                    .bounding_box(time_0, time_1)
+                    .expect("Missing bounding box in BVH construction");
+                (*bounding_box.min() + *bounding_box.max()) / 2.0
+            })
+            .collect();
+        let hierarchy = match build {
+            BvhBuild::MortonGpu => lbvh::hierarchy_on_gpu(&centroids).unwrap_or_else(|| {
+                eprintln!("Building the BVH on the CPU instead.");
+                lbvh::hierarchy(&centroids)
+            }),
+            _ => lbvh::hierarchy(&centroids),
+        };
+        BvhNode::from_hierarchy(&hierarchy, 0, &list.objects, time_0, time_1, nodes)
+    }
+
+    // Creates the BvhNode for the *node*th internal node of the *hierarchy* and its
+    // descendants, adding them to the nodes list. Returns the index of the BvhNode.
+    fn from_hierarchy(
+        hierarchy: &Hierarchy,
+        node: usize,
+        objects: &[Arc<dyn Hittable>],
+        time_0: f32,
+        time_1: f32,
+        nodes: &mut Vec<BvhNode>,
+    ) -> usize {
+        let [left, right] = hierarchy.children[node].map(|child| {
+            if child & lbvh::LEAF != 0 {
+                let leaf = (child & !lbvh::LEAF) as usize;
+                Child::Hittable(objects[hierarchy.order[leaf]].clone())
+            } else {
+                Child::Index(BvhNode::from_hierarchy(
+                    hierarchy,
+                    child as usize,
+                    objects,
+                    time_0,
+                    time_1,
+                    nodes,
+                ))
+            }
+        });
+        BvhNode::push(left, right, time_0, time_1, nodes)
+    }
+
+    // Creates a BvhNode with the children, which must already be in the nodes list, and
+    // adds it to the nodes list. Returns the index of the new BvhNode.
+    fn push(
+        left: Child,
+        right: Child,
+        time_0: f32,
+        time_1: f32,
+        nodes: &mut Vec<BvhNode>,
+    ) -> usize {
         let left_box = match &left {
             Child::Index(i) => nodes[*i].bounding_box(time_0, time_1),
             Child::Hittable(hittable) => hittable.bounding_box(time_0, time_1),
@@ -305,6 +665,12 @@ impl BvhNode {
         }
         .unwrap();
 
+        let child_mask = |child: &Child| match child {
+            Child::Index(i) => nodes[*i].mask,
+            Child::Hittable(hittable) => hittable.mask(),
+        };
+        let mask = child_mask(&left) | child_mask(&right);
+
         // Now that we know the parent's index, we can update the children
         // with that information.
         let new_node_idx = nodes.len();
@@ -325,6 +691,7 @@ impl BvhNode {
             left,
             right,
             bounding_box,
+            mask,
         };
 
         nodes.push(new_node);
@@ -353,6 +720,30 @@ impl BvhNode {
         Some(self.bounding_box)
     }
 
+    fn volumes_containing(
+        &self,
+        point: Vec3,
+        time: f32,
+        nodes: &[BvhNode],
+        volumes: &mut Vec<Volume>,
+    ) {
+        if !self.bounding_box.contains(point) {
+            return;
+        }
+        for child in [&self.left, &self.right] {
+            match child {
+                Child::Index(i) => nodes[*i].volumes_containing(point, time, nodes, volumes),
+                Child::Hittable(hittable) => hittable.volumes_containing(point, time, volumes),
+            }
+            // Leaves with a single object hold it in both children.
+            if let (Child::Hittable(left), Child::Hittable(right)) = (&self.left, &self.right) {
+                if Arc::ptr_eq(left, right) {
+                    break;
+                }
+            }
+        }
+    }
+
     // We implement hit as a bespoke function for Bvh rather than as a Hittable
     // implementation because we need to pass the nodes list and don't want
     // to change the Hittable::hit() signature. Since we should never use
@@ -368,7 +759,7 @@ impl BvhNode {
         nodes: &[BvhNode],
         predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Option<(HitRecord, LeafNodeIdx)> {
-        if !self.bounding_box.hit(ray, t_min, t_max) {
+        if !self.mask.admits(ray.mask()) || !self.bounding_box.hit(ray, t_min, t_max) {
             return None;
         }
 
@@ -417,6 +808,29 @@ impl BvhNode {
     }
 }
 
+/// The axis along which the centers of the objects' bounding boxes are spread the
+/// farthest.
+fn longest_axis(objects: &[Arc<dyn Hittable>], time_0: f32, time_1: f32) -> usize {
+    let mut min = Vec3::splat(f32::INFINITY);
+    let mut max = Vec3::splat(f32::NEG_INFINITY);
+    for object in objects {
+        let bounding_box = object
+            .bounding_box(time_0, time_1)
+            .expect("Missing bounding box in BVH construction");
+        let center = (*bounding_box.min() + *bounding_box.max()) / 2.0;
+        min = min.min(center);
+        max = max.max(center);
+    }
+    let extent = max - min;
+    if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    }
+}
+
 fn box_compare(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>, axis: usize) -> std::cmp::Ordering {
     let box_a = a.bounding_box(0.0, 0.0);
     let box_b = b.bounding_box(0.0, 0.0);
@@ -438,3 +852,182 @@ fn box_compare_y(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>) -> std::cmp::Orde
 fn box_compare_z(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>) -> std::cmp::Ordering {
     box_compare(a, b, 2)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ahash::AHashMap;
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        geometry::instance::Translate, geometry::sphere::Sphere, hittable::Hittable,
+        hittable::HittableList, hrpp, materials::lambertian::Lambertian, ray::Ray,
+    };
+
+    use super::{Bvh, BvhBuild, Child, Traversal};
+
+    #[test]
+    fn morton_build_hits_like_median_build() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let spheres = || {
+            let mut list = HittableList::new();
+            for i in 0..500 {
+                let center = vec3((i % 10) as f32, (i / 10 % 10) as f32, (i / 100) as f32);
+                list.add(Arc::new(Sphere::new(center, 0.3, material.clone())));
+            }
+            list
+        };
+        let median = Bvh::build(spheres(), 0.0, 1.0, BvhBuild::Median);
+        let morton = Bvh::build(spheres(), 0.0, 1.0, BvhBuild::Morton);
+        let longest_axis = Bvh::build(spheres(), 0.0, 1.0, BvhBuild::LongestAxis);
+        assert_eq!(median.bounding_box(0.0, 1.0), morton.bounding_box(0.0, 1.0));
+        assert_eq!(
+            median.bounding_box(0.0, 1.0),
+            longest_axis.bounding_box(0.0, 1.0)
+        );
+
+        for i in 0..200 {
+            let direction = vec3((i % 13) as f32 - 6.0, (i % 7) as f32 - 3.0, 20.0);
+            let ray = Ray::new(vec3(4.5, 4.5, -10.0), direction, 0.0);
+            let hit = |bvh: &Bvh| {
+                bvh.hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+                    .map(|hit| hit.t)
+            };
+            assert_eq!(hit(&median), hit(&morton));
+            assert_eq!(hit(&median), hit(&longest_axis));
+        }
+    }
+
+    #[test]
+    fn longest_axis_build_splits_rows_along_them() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let mut list = HittableList::new();
+        for i in 0..64 {
+            // A long row along x, a little uneven in y and z.
+            let center = vec3(i as f32 * 2.0, (i % 3) as f32 * 0.2, (i % 5) as f32 * 0.2);
+            list.add(Arc::new(Sphere::new(center, 0.5, material.clone())));
+        }
+        let bvh = Bvh::build(list, 0.0, 1.0, BvhBuild::LongestAxis);
+
+        let child_box = |child: &Child| match child {
+            Child::Index(index) => bvh.nodes[*index].bounding_box,
+            Child::Hittable(hittable) => hittable.bounding_box(0.0, 1.0).unwrap(),
+        };
+        for node in &bvh.nodes {
+            let (left, right) = (child_box(&node.left), child_box(&node.right));
+            // Each node's children are side by side along the row, without overlapping.
+            assert!(left.max().x < right.min().x || right.max().x < left.min().x);
+        }
+        assert_eq!(bvh.max_depth, 6);
+    }
+
+    #[test]
+    fn traversals_find_the_same_hits() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let bvh = |traversal| {
+            let mut list = HittableList::new();
+            for i in 0..300 {
+                let center = vec3((i % 10) as f32, (i / 10 % 6) as f32, (i / 60) as f32);
+                list.add(Arc::new(Sphere::new(center, 0.4, material.clone())));
+            }
+            Bvh::build(list, 0.0, 1.0, BvhBuild::Morton).with_traversal(traversal)
+        };
+        let traversals = [
+            Traversal::Recursive,
+            Traversal::Stack,
+            Traversal::Ordered,
+            Traversal::Hrpp,
+        ];
+        let bvhs: Vec<Bvh> = traversals.into_iter().map(bvh).collect();
+
+        for i in 0..200 {
+            let direction = vec3((i % 13) as f32 - 6.0, (i % 7) as f32 - 3.0, 20.0);
+            // From in front of and within the spheres, so that rays pass through many.
+            let origin = vec3(4.5, 2.5, if i % 2 == 0 { -10.0 } else { 2.0 });
+            let ray = Ray::new(origin, direction, 0.0);
+            let hits: Vec<_> = bvhs
+                .iter()
+                .map(|bvh| {
+                    bvh.hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+                        .map(|hit| hit.t)
+                })
+                .collect();
+            assert!(hits.iter().all(|hit| *hit == hits[0]), "{:?}", hits);
+        }
+    }
+
+    #[test]
+    fn prediction_outcomes_are_counted_on_the_thread() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let mut list = HittableList::new();
+        for i in 0..50 {
+            let center = vec3((i % 10) as f32, (i / 10) as f32, 0.0);
+            list.add(Arc::new(Sphere::new(center, 0.4, material.clone())));
+        }
+        let mut predictors = AHashMap::new();
+        let bvh = Bvh::with_predictor(list, 0.0, 1.0, &mut predictors);
+        let predictors = Arc::new(Some(predictors));
+
+        let rays = 100;
+        let ((), stats) = hrpp::count_outcomes(|| {
+            for i in 0..rays {
+                let origin = vec3((i % 10) as f32, (i / 10 % 5) as f32, -10.0);
+                let ray = Ray::new(origin, vec3(0.0, 0.0, 1.0), 0.0);
+                bvh.hit(&ray, 0.001, f32::INFINITY, &predictors);
+            }
+        });
+        let counts = stats.get(&bvh.id);
+        assert_eq!(counts.total(), rays);
+
+        // Counts only reach the predictor once they're added to it.
+        let predictors = predictors.as_ref().as_ref().unwrap();
+        assert_eq!(predictors[&bvh.id].lock().unwrap().counts().total(), 0);
+        stats.add_to(predictors);
+        assert_eq!(predictors[&bvh.id].lock().unwrap().counts(), counts);
+    }
+
+    #[test]
+    fn predictions_chain_into_nested_bvhs() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let mut predictors = AHashMap::new();
+        let mut spheres = HittableList::new();
+        for i in 0..20 {
+            let center = vec3((i % 5) as f32, (i / 5) as f32, 0.0);
+            spheres.add(Arc::new(Sphere::new(center, 0.4, material.clone())));
+        }
+        let nested = Bvh::with_predictor(spheres, 0.0, 1.0, &mut predictors);
+        let nested_id = nested.id;
+        let offset = vec3(10.0, 0.0, 0.0);
+        let mut list = HittableList::new();
+        list.add(Arc::new(Translate::new(Arc::new(nested), offset)));
+        for i in 0..4 {
+            let center = vec3(-10.0, i as f32 * 3.0, 0.0);
+            list.add(Arc::new(Sphere::new(center, 1.0, material.clone())));
+        }
+        let world = Bvh::top_level_with_predictor(list, 0.0, 1.0, &mut predictors);
+        let predictors = Arc::new(Some(predictors));
+
+        let ray = Ray::new(vec3(12.0, 1.0, -10.0), Vec3::Z, 0.0);
+        let trace =
+            || hrpp::count_outcomes(|| world.hit(&ray, 0.001, f32::INFINITY, &predictors)).1;
+        let first = trace();
+        let second = trace();
+
+        // The nested BVH keyed its prediction by the ray in the scene, not in its own space.
+        let map = predictors.as_ref().as_ref().unwrap();
+        let nested_predictor = map[&nested_id].lock().unwrap();
+        assert!(nested_predictor
+            .get_predictions_by_key(hrpp::hash(&ray))
+            .is_some());
+        let local_ray = Ray::new(ray.origin - offset, ray.direction, 0.0);
+        assert!(nested_predictor.get_predictions(&local_ray).is_none());
+        drop(nested_predictor);
+
+        // The second time, both levels go straight to their predicted nodes.
+        for id in map.keys() {
+            assert_eq!(first.get(id).no_predictions, 1);
+            assert_eq!(second.get(id).true_positives, 1);
+        }
+    }
+}
@@ -1,7 +1,32 @@
-use crate::{ray::Ray, utils};
+use crate::{
+    random,
+    ray::{Ray, RayMask},
+    utils,
+};
 
-use glam::Vec3;
-use rand::{thread_rng, Rng};
+use glam::{Quat, Vec2, Vec3};
+use rand::Rng;
+
+/// Whether the camera's rays are spread over the time the shutter is open.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MotionBlur {
+    /// Each ray is at a time while the shutter is open, blurring objects which move.
+    On,
+    /// Every ray is at `time`, whatever the shutter interval, for a sharp still of the
+    /// scene at that instant. Moving objects are only found at times within the interval
+    /// their BVHs were built over.
+    Off { time: f32 },
+}
+
+/// The field of view and focus of a camera, wherever it is and whichever way it faces.
+/// See `Camera::new()` for the meaning of each.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lens {
+    pub vertical_field_of_view: f32,
+    pub aspect_ratio: f32,
+    pub aperture: f32,
+    pub focus_dist: f32,
+}
 
 pub struct Camera {
     /// The lens is centered on the origin
@@ -24,6 +49,9 @@ pub struct Camera {
     time_start: f32,
     /// Shutter close time
     time_end: f32,
+    motion_blur: MotionBlur,
+    /// The groups of objects the camera's rays can hit.
+    groups: RayMask,
 }
 
 impl Camera {
@@ -52,21 +80,71 @@ impl Camera {
         time_start: f32,
         time_end: f32,
     ) -> Camera {
-        let theta = f32::to_radians(vertical_field_of_view);
-        let h = f32::tan(theta / 2.0);
-        let viewport_height = 2.0 * h;
-        let viewport_width = aspect_ratio * viewport_height;
-
         let w = (look_from - look_at).normalize();
         let u = view_up.cross(w).normalize();
         let v = w.cross(u);
 
-        let origin = look_from;
+        let lens = Lens {
+            vertical_field_of_view,
+            aspect_ratio,
+            aperture,
+            focus_dist,
+        };
+        Camera::from_basis(look_from, u, v, w, &lens, time_start, time_end)
+    }
+
+    /// Creates a new camera at `position`, oriented by `rotation`, as is common in
+    /// animation systems and game engines.
+    ///
+    /// With no rotation, the camera looks down the -Z axis with +Y up and +X to the right.
+    /// The `lens` and shutter times are as in `new()`.
+    pub fn from_transform(
+        position: Vec3,
+        rotation: Quat,
+        lens: &Lens,
+        time_start: f32,
+        time_end: f32,
+    ) -> Camera {
+        let rotation = rotation.normalize();
+        Camera::from_basis(
+            position,
+            rotation * Vec3::X,
+            rotation * Vec3::Y,
+            rotation * Vec3::Z,
+            lens,
+            time_start,
+            time_end,
+        )
+    }
+
+    /// Creates a camera at `origin` with orthonormal basis vectors `u` (right), `v` (up)
+    /// and `w` (backwards, opposite the view direction).
+    fn from_basis(
+        origin: Vec3,
+        u: Vec3,
+        v: Vec3,
+        w: Vec3,
+        lens: &Lens,
+        time_start: f32,
+        time_end: f32,
+    ) -> Camera {
+        assert!(
+            time_start <= time_end,
+            "The shutter can't close ({}) before it opens ({})",
+            time_end,
+            time_start
+        );
+        let theta = f32::to_radians(lens.vertical_field_of_view);
+        let h = f32::tan(theta / 2.0);
+        let viewport_height = 2.0 * h;
+        let viewport_width = lens.aspect_ratio * viewport_height;
+
+        let focus_dist = lens.focus_dist;
         let horizontal = focus_dist * viewport_width * u;
         let vertical = focus_dist * viewport_height * v;
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
 
-        let lens_radius = aperture / 2.0;
+        let lens_radius = lens.aperture / 2.0;
         Camera {
             origin,
             horizontal,
@@ -77,11 +155,37 @@ impl Camera {
             lens_radius,
             time_start,
             time_end,
+            motion_blur: MotionBlur::On,
+            groups: RayMask::GROUPS,
+        }
+    }
+
+    /// Sets whether the camera's rays are spread over the shutter interval. Motion blur
+    /// is on by default.
+    pub fn with_motion_blur(mut self, motion_blur: MotionBlur) -> Camera {
+        self.motion_blur = motion_blur;
+        self
+    }
+
+    /// Lets the camera's rays, and the rays scattered from them, hit only objects in the
+    /// `groups` (see `RayMask`), e.g. to render a layer of the scene on its own.
+    pub fn with_groups(mut self, groups: RayMask) -> Camera {
+        self.groups = groups;
+        self
+    }
+
+    /// The interval over which the camera's rays are spread in time: the time the shutter
+    /// is open, or just the still's time if motion blur is off.
+    pub fn shutter(&self) -> (f32, f32) {
+        match self.motion_blur {
+            MotionBlur::On => (self.time_start, self.time_end),
+            MotionBlur::Off { time } => (time, time),
         }
     }
 
     /// Gets a ray from the camera from a random location on the lens,
-    /// at a random time while the shutter is open, towards `s` and `t`.
+    /// at a random time while the shutter is open (unless motion blur is off), towards
+    /// `s` and `t`.
     ///
     /// `s` is the horizontal fraction of the camera's view and `t` is the vertical fraction.
     /// `s` and `t` are expected to be roughly within (0..1), but it's expected
@@ -97,11 +201,132 @@ impl Camera {
         let random_in_lens = self.lens_radius * utils::random_in_unit_disk();
         let offset = self.u * random_in_lens.x + self.v * random_in_lens.y;
 
-        let mut rng = thread_rng();
+        let time = match self.motion_blur {
+            MotionBlur::On => random::rng().gen_range(self.time_start..=self.time_end),
+            MotionBlur::Off { time } => time,
+        };
+        Ray::new(
+            self.origin + offset,
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
+        )
+        .with_groups(self.groups)
+    }
+
+    /// As `get_ray()`, from the point of the lens at `lens` and at the time `time` of the
+    /// shutter interval rather than random ones, e.g. to spread the samples of a pixel
+    /// evenly over the lens and the interval. The point of the lens is given in the unit
+    /// square, which is mapped onto the lens, and the time as a fraction of the interval,
+    /// which is ignored if motion blur is off.
+    pub fn get_ray_at(&self, s: f32, t: f32, lens: Vec2, time: f32) -> Ray {
+        let in_lens = self.lens_radius * utils::square_to_unit_disk(lens);
+        let offset = self.u * in_lens.x + self.v * in_lens.y;
+
+        let time = match self.motion_blur {
+            MotionBlur::On => self.time_start + time * (self.time_end - self.time_start),
+            MotionBlur::Off { time } => time,
+        };
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-            rng.gen_range(self.time_start..=self.time_end),
+            time,
         )
+        .with_groups(self.groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Quat, Vec2, Vec3};
+
+    use crate::{
+        geometry::moving_sphere::MovingSphere, hittable::Hittable,
+        materials::lambertian::Lambertian,
+    };
+
+    use super::{Camera, Lens, MotionBlur};
+
+    #[test]
+    fn from_transform_matches_look_at() {
+        let position = vec3(1.0, 2.0, 3.0);
+        let rotation = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let lens = Lens {
+            vertical_field_of_view: 40.0,
+            aspect_ratio: 1.5,
+            aperture: 0.0,
+            focus_dist: 2.0,
+        };
+        let transformed = Camera::from_transform(position, rotation, &lens, 0.0, 0.0);
+        // Turning a quarter turn left about +Y looks down -X.
+        let look_at = Camera::new(
+            position,
+            position - Vec3::X,
+            Vec3::Y,
+            40.0,
+            1.5,
+            0.0,
+            2.0,
+            0.0,
+            0.0,
+        );
+        for (s, t) in [(0.5, 0.5), (0.0, 0.0), (1.0, 0.25)] {
+            let a = transformed.get_ray(s, t);
+            let b = look_at.get_ray(s, t);
+            assert!((a.origin - b.origin).length() < 1e-5);
+            assert!((a.direction - b.direction).length() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn stills_freeze_moving_objects() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        // Moves from x = 0 to x = 2 over the shutter interval.
+        let sphere = MovingSphere::new(Vec3::ZERO, vec3(2.0, 0.0, 0.0), 0.0, 1.0, 0.5, material);
+        let camera = |motion_blur: MotionBlur| {
+            Camera::new(
+                vec3(1.0, 0.0, 10.0),
+                vec3(1.0, 0.0, 0.0),
+                Vec3::Y,
+                20.0,
+                1.0,
+                0.0,
+                10.0,
+                0.0,
+                1.0,
+            )
+            .with_motion_blur(motion_blur)
+        };
+        let still = camera(MotionBlur::Off { time: 0.75 });
+        for _ in 0..16 {
+            assert_eq!(still.get_ray(0.5, 0.5).time, 0.75);
+        }
+        assert_eq!(still.get_ray_at(0.5, 0.5, Vec2::ZERO, 0.1).time, 0.75);
+        assert_eq!(
+            camera(MotionBlur::On)
+                .get_ray_at(0.5, 0.5, Vec2::ZERO, 0.1)
+                .time,
+            0.1
+        );
+        // At 0.75 the sphere is centered at x = 1.5, so a ray through x = 1.9 hits it.
+        let mut ray = still.get_ray(0.5, 0.5);
+        ray.origin.x = 1.9;
+        ray.direction = -Vec3::Z;
+        assert!(sphere
+            .hit(&ray, 0.0, f32::INFINITY, &Arc::new(None))
+            .is_some());
+        ray.origin.x = 0.1;
+        assert!(sphere
+            .hit(&ray, 0.0, f32::INFINITY, &Arc::new(None))
+            .is_none());
+
+        // A sphere with no time between its points stays put rather than vanishing.
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let stuck = MovingSphere::new(Vec3::ZERO, vec3(2.0, 0.0, 0.0), 0.0, 0.0, 0.5, material);
+        ray.origin.x = 0.1;
+        assert!(stuck
+            .hit(&ray, 0.0, f32::INFINITY, &Arc::new(None))
+            .is_some());
     }
 }
@@ -0,0 +1,362 @@
+//! A caustics layer from photon mapping, which lights diffuse surfaces with the light
+//! focused onto them by glass and mirrors. Path tracing only finds such light when a
+//! path happens to bounce through the glass into a light, so caustics otherwise take
+//! very many samples to show up as more than fireflies.
+//!
+//! Photons are sent out from the scene's lights (see `HittableList::add_light()`) and
+//! stored where they land on a diffuse surface after at least one specular bounce.
+//! Camera paths look up the photons around the first diffuse surface they reach, and
+//! then don't count light which reaches that surface through specular bounces, which is
+//! what the photons stand for. Caustics seen after further diffuse bounces are still
+//! path traced, and the background doesn't send out photons.
+//!
+//! Surfaces are taken to be Lambertian where photons are looked up, which is a cheat for
+//! other diffuse materials such as participating media.
+
+use std::collections::BinaryHeap;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use glam::Vec3;
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::{
+    hittable::{Hittable, HittableList},
+    materials::utils::random_cosine_direction,
+    random::{self, random},
+    ray::{Ray, RayKind},
+    utils::Onb,
+};
+
+/// Photons are traced in chunks of this many, each with its own random numbers when
+/// seeded, so the map doesn't depend on how the chunks are spread across threads.
+const CHUNK_SIZE: usize = 4096;
+
+/// Photons are dropped after this many specular bounces.
+const MAX_BOUNCES: u32 = 16;
+
+/// Settings for building a `CausticMap`.
+#[derive(Clone, Debug)]
+pub struct CausticParams {
+    /// The number of photons sent out from the lights. Only those which land on a
+    /// diffuse surface after a specular bounce are kept.
+    pub photons: usize,
+    /// The number of nearby photons which each lookup estimates the light from.
+    pub nearest: usize,
+    /// The farthest from a point that photons are looked up, in world units. By default,
+    /// 1% of the diagonal of the box around the stored photons.
+    pub max_radius: Option<f32>,
+}
+
+impl Default for CausticParams {
+    fn default() -> Self {
+        CausticParams {
+            photons: 500_000,
+            nearest: 50,
+            max_radius: None,
+        }
+    }
+}
+
+/// A photon which landed on a diffuse surface.
+#[derive(Clone, Copy, Debug)]
+struct Photon {
+    position: Vec3,
+    /// The direction the photon was travelling in.
+    direction: Vec3,
+    /// The power (flux) which the photon carries.
+    power: Vec3,
+}
+
+/// The photons of caustics, in a kd-tree for finding those nearest to a point.
+pub struct CausticMap {
+    /// A balanced kd-tree: the median photon of each range is the node splitting it, and
+    /// the photons before and after it are its children.
+    photons: Vec<Photon>,
+    /// The axis which each photon's node splits its range along.
+    axes: Vec<u8>,
+    nearest: usize,
+    max_radius: f32,
+}
+
+impl CausticMap {
+    /// Sends `params.photons` photons out from the lights of the `world`, and keeps
+    /// those which reach a diffuse surface through specular bounces. Photons leave at
+    /// random times within the `shutter` interval, as the camera's rays do. With a `seed`,
+    /// the same map is built regardless of the number of threads.
+    pub fn build(
+        world: &HittableList,
+        params: &CausticParams,
+        shutter: (f32, f32),
+        seed: Option<u64>,
+    ) -> CausticMap {
+        let lights = world.lights();
+        let area: f32 = lights.iter().map(|light| light.area()).sum();
+        let mut photons = if lights.is_empty() || area <= 0.0 || params.photons == 0 {
+            Vec::new()
+        } else {
+            // Lights are chosen by area and points uniformly on them, and photons leave
+            // from either side in a cosine-weighted direction, so each photon carries
+            // the emitted radiance times this power.
+            let power_scale = 2.0 * PI * area / params.photons as f32;
+            let chunks = params.photons.div_ceil(CHUNK_SIZE);
+            (0..chunks)
+                .into_par_iter()
+                .map(|chunk| {
+                    let count = CHUNK_SIZE.min(params.photons - chunk * CHUNK_SIZE);
+                    let trace = || {
+                        (0..count)
+                            .filter_map(|_| trace_photon(world, area, power_scale, shutter))
+                            .collect::<Vec<_>>()
+                    };
+                    match seed {
+                        Some(seed) => random::seeded(
+                            random::sample_seed(seed ^ 0xCA05_71C5, chunk as u64, 0),
+                            trace,
+                        ),
+                        None => trace(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .concat()
+        };
+
+        let max_radius = params.max_radius.unwrap_or_else(|| {
+            let (min, max) = photons.iter().fold(
+                (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+                |(min, max), photon| (min.min(photon.position), max.max(photon.position)),
+            );
+            0.01 * (max - min).length()
+        });
+        let mut axes = vec![0; photons.len()];
+        build_tree(&mut photons, &mut axes);
+        CausticMap {
+            photons,
+            axes,
+            nearest: params.nearest.max(1),
+            max_radius,
+        }
+    }
+
+    /// The number of photons in the map.
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// The caustic light reflected from the `point` of a Lambertian surface with the
+    /// `albedo`, whose `normal` faces the viewer, estimated from the photons near it.
+    pub fn radiance(&self, point: Vec3, normal: Vec3, albedo: Vec3) -> Vec3 {
+        if self.photons.is_empty() || self.max_radius <= 0.0 {
+            return Vec3::ZERO;
+        }
+        let max_distance_squared = self.max_radius * self.max_radius;
+        let mut nearest = BinaryHeap::with_capacity(self.nearest + 1);
+        self.find_nearest(
+            0..self.photons.len(),
+            point,
+            max_distance_squared,
+            &mut nearest,
+        );
+        if nearest.is_empty() {
+            return Vec3::ZERO;
+        }
+        // The photons are spread over the disk reaching the farthest of them, or over the
+        // whole search disk if there were too few to fill it.
+        let radius_squared = if nearest.len() == self.nearest {
+            f32::from_bits(nearest.peek().unwrap().0)
+        } else {
+            max_distance_squared
+        };
+        let power: Vec3 = nearest
+            .iter()
+            .map(|&(_, index)| self.photons[index])
+            // Only photons arriving from the viewer's side light the surface it sees.
+            .filter(|photon| photon.direction.dot(normal) < 0.0)
+            .map(|photon| photon.power)
+            .sum();
+        albedo / PI * power / (PI * radius_squared.max(f32::MIN_POSITIVE))
+    }
+
+    /// Adds the photons of the subtree over `range` within the search radius of the
+    /// `point` to `nearest`, keeping only the `self.nearest` closest. Entries are the
+    /// squared distance, as bits which order like the (non-negative) distance, and the
+    /// photon's index.
+    fn find_nearest(
+        &self,
+        range: std::ops::Range<usize>,
+        point: Vec3,
+        max_distance_squared: f32,
+        nearest: &mut BinaryHeap<(u32, usize)>,
+    ) {
+        if range.is_empty() {
+            return;
+        }
+        let median = range.start + range.len() / 2;
+        let photon = &self.photons[median];
+        let axis = self.axes[median] as usize;
+        let offset = point[axis] - photon.position[axis];
+        let (near, far) = if offset < 0.0 {
+            (range.start..median, median + 1..range.end)
+        } else {
+            (median + 1..range.end, range.start..median)
+        };
+        self.find_nearest(near, point, max_distance_squared, nearest);
+
+        let radius_squared = |nearest: &BinaryHeap<(u32, usize)>| {
+            if nearest.len() == self.nearest {
+                f32::from_bits(nearest.peek().unwrap().0)
+            } else {
+                max_distance_squared
+            }
+        };
+        let distance_squared = photon.position.distance_squared(point);
+        if distance_squared < radius_squared(nearest) {
+            nearest.push((distance_squared.to_bits(), median));
+            if nearest.len() > self.nearest {
+                nearest.pop();
+            }
+        }
+        if offset * offset < radius_squared(nearest) {
+            self.find_nearest(far, point, max_distance_squared, nearest);
+        }
+    }
+}
+
+/// Sends a photon out from a light of the `world`, which have a total `area`, at a time
+/// within the `shutter` interval, returning it if it lands on a diffuse surface after a
+/// specular bounce.
+fn trace_photon(
+    world: &HittableList,
+    area: f32,
+    power_scale: f32,
+    shutter: (f32, f32),
+) -> Option<Photon> {
+    let predictors = Arc::new(None);
+    let lights = world.lights();
+    let mut rng = random::rng();
+
+    let mut chosen = random::<f32>() * area;
+    let light = lights
+        .iter()
+        .find(|light| {
+            chosen -= light.area();
+            chosen < 0.0
+        })
+        .unwrap_or_else(|| lights.last().unwrap());
+    let sample = light.sample_point(&mut rng);
+    // Look back at the point from just off the light to find what it emits there.
+    let look = Ray::new(sample.point + sample.normal, -sample.normal, 0.0);
+    let hit_record = light.hit(&look, 0.0, 2.0, &predictors)?;
+    let emitted = hit_record
+        .material
+        .emit(hit_record.u, hit_record.v, &sample.point);
+    if emitted == Vec3::ZERO {
+        return None;
+    }
+
+    let normal = if random::<bool>() {
+        sample.normal
+    } else {
+        -sample.normal
+    };
+    let direction = Onb::from_w(normal).local(random_cosine_direction());
+    let time = rng.gen_range(shutter.0..=shutter.1);
+    // Photons are indirect light, so objects see them as they see diffuse bounces
+    // rather than the camera's rays.
+    let mut ray = Ray::new(sample.point, direction, time).with_kind(RayKind::Diffuse);
+    let mut power = emitted * power_scale;
+
+    let mut specular = false;
+    for _ in 0..MAX_BOUNCES {
+        let hit_record = world.hit(&ray, world.t_min(&ray), f32::INFINITY, &predictors)?;
+        let scatter_record = hit_record.material.scatter(&ray, &hit_record)?;
+        if scatter_record.ray.kind != RayKind::Specular {
+            return specular.then(|| Photon {
+                position: hit_record.point,
+                direction: ray.direction.normalize(),
+                power,
+            });
+        }
+        specular = true;
+        power *= scatter_record.attenuation;
+        ray = scatter_record.ray;
+    }
+    None
+}
+
+/// Arranges the `photons` into a balanced kd-tree, splitting each range along the axis
+/// in which its photons spread the farthest, which is recorded in `axes`.
+fn build_tree(photons: &mut [Photon], axes: &mut [u8]) {
+    if photons.is_empty() {
+        return;
+    }
+    let (min, max) = photons.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), photon| (min.min(photon.position), max.max(photon.position)),
+    );
+    let extent = max - min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let median = photons.len() / 2;
+    photons.select_nth_unstable_by(median, |a, b| a.position[axis].total_cmp(&b.position[axis]));
+    axes[median] = axis as u8;
+    let (left_photons, rest) = photons.split_at_mut(median);
+    let (left_axes, rest_axes) = axes.split_at_mut(median);
+    build_tree(left_photons, left_axes);
+    build_tree(&mut rest[1..], &mut rest_axes[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::{build_tree, CausticMap, Photon};
+
+    #[test]
+    fn lookups_find_the_nearest_photons() {
+        let mut photons: Vec<Photon> = (0..1000)
+            .map(|i| Photon {
+                position: vec3((i % 10) as f32, (i / 10 % 10) as f32, (i / 100) as f32),
+                direction: -Vec3::Y,
+                power: Vec3::ONE,
+            })
+            .collect();
+        let mut axes = vec![0; photons.len()];
+        build_tree(&mut photons, &mut axes);
+        let map = CausticMap {
+            photons,
+            axes,
+            nearest: 7,
+            max_radius: 100.0,
+        };
+
+        let point = vec3(4.2, 5.1, 3.0);
+        let mut nearest = std::collections::BinaryHeap::new();
+        map.find_nearest(0..map.len(), point, 100.0 * 100.0, &mut nearest);
+        let mut found: Vec<Vec3> = nearest
+            .iter()
+            .map(|&(_, index)| map.photons[index].position)
+            .collect();
+        let mut expected: Vec<Vec3> = map.photons.iter().map(|p| p.position).collect();
+        expected.sort_by(|a, b| a.distance(point).total_cmp(&b.distance(point)));
+        expected.truncate(7);
+        let key = |p: &Vec3| (p.x as i32, p.y as i32, p.z as i32);
+        found.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(found, expected);
+
+        // Photons arriving from below don't light a surface seen from below.
+        assert_eq!(map.radiance(point, -Vec3::Y, Vec3::ONE), Vec3::ZERO);
+        assert!(map.radiance(point, Vec3::Y, Vec3::ONE).x > 0.0);
+    }
+}
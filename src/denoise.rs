@@ -0,0 +1,124 @@
+//! A fast edge-aware filter for cleaning up noisy previews.
+//!
+//! This is the edge-avoiding à-trous wavelet filter of Dammertz et al., "Edge-Avoiding
+//! À-Trous Wavelet Transform for fast Global Illumination Filtering" (2010). Each
+//! iteration blurs with a 5x5 B3 spline kernel whose taps are spread twice as far apart
+//! as the last's, and each tap is weighted down where the color, or the normal or albedo
+//! of the surface first hit, differs from the center pixel's. Noise is smoothed within
+//! surfaces while edges between them are kept.
+
+use glam::Vec3;
+
+/// The number of iterations, which reach out `2 * (2^ITERATIONS - 1)` pixels.
+const ITERATIONS: u32 = 3;
+
+/// The B3 spline kernel, by distance from its center.
+const KERNEL: [f32; 3] = [3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// How much colors may differ before they're considered an edge, in the first iteration.
+/// Later iterations halve this, since their input is already smoother.
+const COLOR_SIGMA: f32 = 1.0;
+const NORMAL_SIGMA: f32 = 0.3;
+const ALBEDO_SIGMA: f32 = 0.1;
+
+/// The surface seen through a pixel, which guides where the filter finds edges.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Guide {
+    /// The normal of the surface first hit, or zero where nothing was hit.
+    pub normal: Vec3,
+    /// The albedo of the surface first hit, or the background where nothing was hit.
+    pub albedo: Vec3,
+}
+
+/// Filters the `colors` of a `width` x `height` image, guided by the surface seen
+/// through each pixel.
+pub(crate) fn a_trous(colors: &[Vec3], guides: &[Guide], width: usize, height: usize) -> Vec<Vec3> {
+    let mut current = colors.to_vec();
+    for iteration in 0..ITERATIONS {
+        let step = 1 << iteration;
+        let color_sigma = COLOR_SIGMA / (1 << iteration) as f32;
+        let mut next = vec![Vec3::ZERO; current.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let center = y * width + x;
+                let mut sum = Vec3::ZERO;
+                let mut weight_sum = 0.0;
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let sample_x = x as isize + dx * step;
+                        let sample_y = y as isize + dy * step;
+                        if sample_x < 0
+                            || sample_y < 0
+                            || sample_x >= width as isize
+                            || sample_y >= height as isize
+                        {
+                            continue;
+                        }
+                        let sample = sample_y as usize * width + sample_x as usize;
+                        let weight = KERNEL[dx.unsigned_abs()]
+                            * KERNEL[dy.unsigned_abs()]
+                            * edge_weight(current[center], current[sample], color_sigma)
+                            * edge_weight(
+                                guides[center].normal,
+                                guides[sample].normal,
+                                NORMAL_SIGMA,
+                            )
+                            * edge_weight(
+                                guides[center].albedo,
+                                guides[sample].albedo,
+                                ALBEDO_SIGMA,
+                            );
+                        sum += weight * current[sample];
+                        weight_sum += weight;
+                    }
+                }
+                // The center always has a weight, so this never divides by zero.
+                next[center] = sum / weight_sum;
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+/// Falls off from 1 as `a` and `b` differ by more than `sigma`.
+fn edge_weight(a: Vec3, b: Vec3, sigma: f32) -> f32 {
+    (-(a - b).length_squared() / (sigma * sigma)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{a_trous, Guide};
+
+    #[test]
+    fn smooths_within_surfaces_and_keeps_edges() {
+        // Two surfaces side by side, with alternating noise over each.
+        let (width, height) = (16, 8);
+        let mut colors = Vec::new();
+        let mut guides = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let albedo = if x < width / 2 {
+                    Vec3::splat(0.2)
+                } else {
+                    Vec3::splat(0.8)
+                };
+                let noise = if (x + y) % 2 == 0 { 0.1 } else { -0.1 };
+                colors.push(albedo + Vec3::splat(noise));
+                guides.push(Guide {
+                    normal: Vec3::Y,
+                    albedo,
+                });
+            }
+        }
+        let filtered = a_trous(&colors, &guides, width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let expected = if x < width / 2 { 0.2 } else { 0.8 };
+                assert!((filtered[y * width + x].x - expected).abs() < 0.05);
+            }
+        }
+    }
+}
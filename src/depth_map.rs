@@ -0,0 +1,176 @@
+//! Rendering linear depth from an orthographic "light camera" and writing it as an EXR
+//! image, to give external real-time engines and tests ground-truth shadow maps of
+//! directional lights, or height maps of terrain seen from above.
+//!
+//! Each pixel holds the distance along the view direction from the view plane to the
+//! surface hit by a single ray through its center, so depths are exact and unfiltered.
+
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use ahash::AHashMap;
+use glam::Vec3;
+use image::{Rgb, Rgb32FImage};
+use rayon::prelude::*;
+
+use crate::{
+    bvh::BvhId,
+    hittable::{Hittable, HittableList},
+    hrpp::Predictor,
+    ray::{Ray, RayKind},
+};
+
+/// An orthographic view, whose parallel rays start on a rectangle facing the direction
+/// they travel in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthographicView {
+    /// The lower left corner of the rectangle.
+    lower_left_corner: Vec3,
+    /// The rectangle's horizontal edge, from left to right.
+    horizontal: Vec3,
+    /// The rectangle's vertical edge, from bottom to top.
+    vertical: Vec3,
+    /// The unit direction of the rays.
+    direction: Vec3,
+    /// The depth past which surfaces are ignored.
+    far: f32,
+}
+
+impl OrthographicView {
+    /// Creates a view of a `width` x `height` rectangle centered on `look_from`, facing
+    /// `look_at` and oriented by `view_up`, as for `Camera::new()`. Only the direction
+    /// towards `look_at` matters, not how far away it is.
+    ///
+    /// Fails if `look_at` is `look_from`, or if the view looks along `view_up`, which then
+    /// can't orient it.
+    pub fn new(
+        look_from: Vec3,
+        look_at: Vec3,
+        view_up: Vec3,
+        width: f32,
+        height: f32,
+    ) -> Result<OrthographicView, String> {
+        let direction = (look_at - look_from)
+            .try_normalize()
+            .ok_or("The view must look towards a point other than its center.")?;
+        let u = direction.cross(view_up).try_normalize().ok_or(
+            "The view can't be oriented by an up direction parallel to the one it looks in.",
+        )?;
+        let v = u.cross(direction);
+        let horizontal = width * u;
+        let vertical = height * v;
+        Ok(OrthographicView {
+            lower_left_corner: look_from - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction,
+            far: f32::INFINITY,
+        })
+    }
+
+    /// Ignores surfaces farther than `far` from the view's rectangle, which then read as
+    /// `far`. By default nothing is ignored, and pixels where rays escape read as infinity.
+    pub fn with_far(mut self, far: f32) -> OrthographicView {
+        self.far = far;
+        self
+    }
+
+    /// The ray through the point `s` of the way across the rectangle and `t` of the way up.
+    fn ray(&self, s: f32, t: f32) -> Ray {
+        let origin = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        Ray::new(origin, self.direction, 0.0).with_kind(RayKind::Diffuse)
+    }
+}
+
+/// The depth seen through each pixel of an orthographic view, with rows stored top-down.
+pub struct DepthMap {
+    width: usize,
+    height: usize,
+    depth: Vec<f32>,
+}
+
+impl DepthMap {
+    /// Traces a ray through the center of each pixel of a `width` x `height` image of
+    /// the `view`.
+    pub fn render(
+        view: &OrthographicView,
+        world: &HittableList,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        width: usize,
+        height: usize,
+    ) -> DepthMap {
+        let depth = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..width).map(move |x| {
+                    let s = (x as f32 + 0.5) / width as f32;
+                    let t = 1.0 - (y as f32 + 0.5) / height as f32;
+                    let ray = view.ray(s, t);
+                    // Rays start on the view's rectangle, so surfaces on it are hit too.
+                    match world.hit(&ray, 0.0, view.far, predictors) {
+                        Some(hit_record) => hit_record.t,
+                        None => view.far,
+                    }
+                })
+            })
+            .collect();
+        DepthMap {
+            width,
+            height,
+            depth,
+        }
+    }
+
+    /// The depth at pixel (x, y), with (0, 0) at the top left.
+    pub fn depth(&self, x: usize, y: usize) -> f32 {
+        self.depth[y * self.width + x]
+    }
+
+    /// Writes the depths to an EXR image at `path`, in all three channels.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let image = Rgb32FImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            Rgb([self.depth(x as usize, y as usize); 3])
+        });
+        image.save(path).map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        geometry::sphere::Sphere, hittable::HittableList, materials::lambertian::Lambertian,
+    };
+
+    use super::{DepthMap, OrthographicView};
+
+    #[test]
+    fn depth_is_measured_from_the_view_plane() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(vec3(2.0, 0.0, 0.0), 1.0, material)));
+        // Looking straight down from y = 10 over a 8 x 4 area, with +z down the image.
+        let view =
+            OrthographicView::new(vec3(0.0, 10.0, 0.0), Vec3::ZERO, -Vec3::Z, 8.0, 4.0).unwrap();
+        let depth_map = DepthMap::render(&view, &world, &Arc::new(None), 16, 8);
+
+        // The pixel whose center is over (2.25, 0.25) sees the top of the sphere.
+        let expected = 10.0 - (1.0 - 0.25_f32 * 0.25 - 0.25 * 0.25).sqrt();
+        assert!((depth_map.depth(12, 3) - expected).abs() < 1e-4);
+        // The far side of the view sees nothing.
+        assert_eq!(depth_map.depth(2, 3), f32::INFINITY);
+        let depth_map = DepthMap::render(&view.with_far(5.0), &world, &Arc::new(None), 16, 8);
+        assert_eq!(depth_map.depth(12, 3), 5.0);
+
+        // Looking down along the up direction leaves the view without an orientation.
+        assert!(
+            OrthographicView::new(vec3(0.0, 10.0, 0.0), Vec3::ZERO, Vec3::Y, 8.0, 4.0).is_err()
+        );
+    }
+}
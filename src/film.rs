@@ -0,0 +1,410 @@
+//! Accumulating the samples of an image, or of a region of one, into its pixels.
+//!
+//! A `Film` receives two kinds of contribution. Camera samples are weighted by the
+//! reconstruction filter and averaged into the pixels around them, as each pixel's
+//! estimate is the weighted mean of its nearby samples. Splats, such as light paths
+//! which happen to connect to the camera, land on whichever pixel they reach and are
+//! summed instead, and divided by the number of samples per pixel when the image is
+//! resolved.
+//!
+//! Films are usually rendered in pieces: each tile traces its samples into a small film
+//! covering the tile and its filter's reach, which is then merged into the film of the
+//! whole image. Splats can land anywhere, so those outside a tile's film are kept aside
+//! and carried into whichever film it's merged into, until one covers their pixels.
+
+use glam::Vec3;
+
+use crate::filter::Filter;
+
+/// Which channels a film accumulates besides the beauty image.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FilmChannels {
+    /// The number of arbitrary output variables (AOVs), e.g. light path expression passes,
+    /// which are filtered and averaged like the beauty image.
+    pub aovs: usize,
+    /// Whether to count the prediction outcomes of each pixel's camera rays.
+    pub prediction_outcomes: bool,
+    /// Whether to count the lengths and terminations of each pixel's paths.
+    pub path_stats: bool,
+}
+
+/// Accumulates the beauty image and any other channels for a rectangle of pixels, which
+/// may extend past the edges of the image.
+pub struct Film {
+    filter: Filter,
+    channels: FilmChannels,
+    pub(crate) beauty: SampleBuffer,
+    pub(crate) aovs: Vec<SampleBuffer>,
+    /// If recorded, the number of true positive, false positive and missing predictions
+    /// for the camera rays through each pixel of the beauty buffer.
+    pub(crate) prediction_outcomes: Option<Vec<[u32; 3]>>,
+    /// If recorded, the number of paths through each pixel of the beauty buffer, their
+    /// total number of bounces, and how many escaped, were absorbed or hit the maximum depth.
+    pub(crate) path_stats: Option<Vec<[u32; 5]>>,
+    /// Splats which landed outside this film's pixels.
+    stray_splats: Vec<Splat>,
+    /// The number of samples taken of each pixel, which splats are divided by.
+    samples_per_pixel: u32,
+}
+
+impl Film {
+    /// A film of `width` x `height` pixels, starting at (`x_start`, `y_start`) in the
+    /// full image.
+    pub fn new(
+        x_start: isize,
+        y_start: isize,
+        width: usize,
+        height: usize,
+        filter: Filter,
+        channels: FilmChannels,
+    ) -> Film {
+        Film {
+            filter,
+            channels,
+            beauty: SampleBuffer::new(x_start, y_start, width, height),
+            aovs: (0..channels.aovs)
+                .map(|_| SampleBuffer::new(x_start, y_start, width, height))
+                .collect(),
+            prediction_outcomes: channels
+                .prediction_outcomes
+                .then(|| vec![[0; 3]; width * height]),
+            path_stats: channels.path_stats.then(|| vec![[0; 5]; width * height]),
+            stray_splats: Vec::new(),
+            samples_per_pixel: 0,
+        }
+    }
+
+    /// An empty film over another rectangle of pixels, with the same filter and channels.
+    pub fn region(&self, x_start: isize, y_start: isize, width: usize, height: usize) -> Film {
+        Film::new(x_start, y_start, width, height, self.filter, self.channels)
+    }
+
+    /// The number of pixels past its own which a sample can contribute to, within the
+    /// `filter`'s radius. Films of tiles are padded by this much to hold all their samples.
+    pub fn padding(filter: &Filter) -> isize {
+        (filter.radius() - 0.5).ceil().max(0.0) as isize
+    }
+
+    /// Adds a camera sample at continuous full-image coordinates (`sample_x`, `sample_y`)
+    /// to the beauty image, weighted by the filter.
+    pub fn add_sample(&mut self, sample_x: f32, sample_y: f32, sample_idx: u32, color: Vec3) {
+        self.beauty
+            .splat(&self.filter, sample_x, sample_y, sample_idx, color);
+    }
+
+    /// As `add_sample()`, for the `aov`th AOV.
+    pub fn add_aov_sample(
+        &mut self,
+        aov: usize,
+        sample_x: f32,
+        sample_y: f32,
+        sample_idx: u32,
+        color: Vec3,
+    ) {
+        self.aovs[aov].splat(&self.filter, sample_x, sample_y, sample_idx, color);
+    }
+
+    /// Adds `color` to the pixel containing the continuous full-image coordinates (`x`,
+    /// `y`), unfiltered. Unlike samples, splats aren't averaged but summed, and divided by
+    /// the number of samples per pixel once the image is resolved.
+    pub fn add_splat(&mut self, x: f32, y: f32, sample_idx: u32, color: Vec3) {
+        let splat = Splat {
+            x: x.floor() as isize,
+            y: y.floor() as isize,
+            sample_idx,
+            color,
+        };
+        if !self.beauty.add_splat(&splat) {
+            self.stray_splats.push(splat);
+        }
+    }
+
+    /// Adds the contributions of `other` to the overlapping pixels of this film, keeping
+    /// its splats which land outside of it.
+    pub fn merge(&mut self, other: &Film) {
+        self.beauty.merge(&other.beauty);
+        for (aov, other_aov) in self.aovs.iter_mut().zip(&other.aovs) {
+            aov.merge(other_aov);
+        }
+        if let (Some(outcomes), Some(other_outcomes)) =
+            (&mut self.prediction_outcomes, &other.prediction_outcomes)
+        {
+            merge_counts(&self.beauty, outcomes, &other.beauty, other_outcomes);
+        }
+        if let (Some(stats), Some(other_stats)) = (&mut self.path_stats, &other.path_stats) {
+            merge_counts(&self.beauty, stats, &other.beauty, other_stats);
+        }
+        for splat in &other.stray_splats {
+            if !self.beauty.add_splat(splat) {
+                self.stray_splats.push(*splat);
+            }
+        }
+    }
+
+    /// Sets the number of samples which were taken of each pixel, which splats are
+    /// divided by.
+    pub fn set_samples_per_pixel(&mut self, samples_per_pixel: u32) {
+        self.samples_per_pixel = samples_per_pixel;
+    }
+
+    /// The color of the pixel at full-image coordinates (`x`, `y`), which must be within
+    /// the film.
+    pub fn color(&self, x: usize, y: usize) -> Vec3 {
+        self.pixel(&self.beauty, x, y).mean(self.samples_per_pixel)
+    }
+
+    /// The color of the pixel from only its even-numbered samples.
+    pub fn color_a(&self, x: usize, y: usize) -> Vec3 {
+        self.pixel(&self.beauty, x, y)
+            .mean_a(self.samples_per_pixel)
+    }
+
+    /// The color of the pixel from only its odd-numbered samples.
+    pub fn color_b(&self, x: usize, y: usize) -> Vec3 {
+        self.pixel(&self.beauty, x, y)
+            .mean_b(self.samples_per_pixel)
+    }
+
+    /// The value of the `aov`th AOV at the pixel.
+    pub fn aov_color(&self, aov: usize, x: usize, y: usize) -> Vec3 {
+        self.pixel(&self.aovs[aov], x, y)
+            .mean(self.samples_per_pixel)
+    }
+
+    fn pixel<'a>(&self, buffer: &'a SampleBuffer, x: usize, y: usize) -> &'a SplitSamples {
+        buffer
+            .get(x as isize, y as isize)
+            .expect("pixel is outside the film")
+    }
+}
+
+/// A contribution to a single pixel, which isn't filtered or averaged with samples.
+#[derive(Clone, Copy, Debug)]
+struct Splat {
+    x: isize,
+    y: isize,
+    sample_idx: u32,
+    color: Vec3,
+}
+
+/// The filter-weighted samples and the splats of a pixel, accumulated into two independent
+/// halves: even-numbered samples into "A" and odd-numbered samples into "B".
+#[derive(Clone, Copy)]
+pub(crate) struct SplitSamples {
+    sum_a: Vec3,
+    weight_a: f32,
+    splats_a: Vec3,
+    sum_b: Vec3,
+    weight_b: f32,
+    splats_b: Vec3,
+}
+
+impl SplitSamples {
+    pub fn new() -> SplitSamples {
+        SplitSamples {
+            sum_a: Vec3::ZERO,
+            weight_a: 0.0,
+            splats_a: Vec3::ZERO,
+            sum_b: Vec3::ZERO,
+            weight_b: 0.0,
+            splats_b: Vec3::ZERO,
+        }
+    }
+
+    pub fn add(&mut self, sample_idx: u32, color: Vec3, weight: f32) {
+        if sample_idx.is_multiple_of(2) {
+            self.sum_a += color * weight;
+            self.weight_a += weight;
+        } else {
+            self.sum_b += color * weight;
+            self.weight_b += weight;
+        }
+    }
+
+    pub fn add_splat(&mut self, sample_idx: u32, color: Vec3) {
+        if sample_idx.is_multiple_of(2) {
+            self.splats_a += color;
+        } else {
+            self.splats_b += color;
+        }
+    }
+
+    pub fn merge(&mut self, other: &SplitSamples) {
+        self.sum_a += other.sum_a;
+        self.weight_a += other.weight_a;
+        self.splats_a += other.splats_a;
+        self.sum_b += other.sum_b;
+        self.weight_b += other.weight_b;
+        self.splats_b += other.splats_b;
+    }
+
+    /// The weighted mean of all samples, plus the splats spread over the
+    /// `samples_per_pixel`.
+    pub fn mean(&self, samples_per_pixel: u32) -> Vec3 {
+        Self::weighted_mean(self.sum_a + self.sum_b, self.weight_a + self.weight_b)
+            + Self::splat_mean(self.splats_a + self.splats_b, samples_per_pixel)
+    }
+
+    /// The weighted mean of the even-numbered samples, plus their splats.
+    pub fn mean_a(&self, samples_per_pixel: u32) -> Vec3 {
+        Self::weighted_mean(self.sum_a, self.weight_a)
+            + Self::splat_mean(self.splats_a, samples_per_pixel.div_ceil(2))
+    }
+
+    /// The weighted mean of the odd-numbered samples, plus their splats.
+    pub fn mean_b(&self, samples_per_pixel: u32) -> Vec3 {
+        Self::weighted_mean(self.sum_b, self.weight_b)
+            + Self::splat_mean(self.splats_b, samples_per_pixel / 2)
+    }
+
+    fn weighted_mean(sum: Vec3, weight: f32) -> Vec3 {
+        // Filters with negative lobes can leave a pixel with no net weight.
+        if weight.abs() < f32::EPSILON {
+            return Vec3::ZERO;
+        }
+        (sum / weight).max(Vec3::ZERO)
+    }
+
+    fn splat_mean(splats: Vec3, samples: u32) -> Vec3 {
+        if samples == 0 {
+            return Vec3::ZERO;
+        }
+        splats / samples as f32
+    }
+}
+
+/// Accumulates filter-weighted samples for a rectangle of pixels, which may extend
+/// past the edges of the image.
+pub(crate) struct SampleBuffer {
+    /// The first pixel X coordinate of this buffer in the full image.
+    x_start: isize,
+    /// The first pixel Y coordinate of this buffer in the full image.
+    y_start: isize,
+    width: usize,
+    height: usize,
+    /// The samples of each pixel, flattened row-major.
+    pixels: Vec<SplitSamples>,
+}
+
+impl SampleBuffer {
+    pub fn new(x_start: isize, y_start: isize, width: usize, height: usize) -> SampleBuffer {
+        SampleBuffer {
+            x_start,
+            y_start,
+            width,
+            height,
+            pixels: vec![SplitSamples::new(); width * height],
+        }
+    }
+
+    /// Returns the samples of the pixel at full-image coordinates (`x`, `y`),
+    /// or `None` if the pixel lies outside this buffer.
+    pub fn get(&self, x: isize, y: isize) -> Option<&SplitSamples> {
+        self.get_idx(x, y).map(|idx| &self.pixels[idx])
+    }
+
+    /// Adds the sample at continuous full-image coordinates (`sample_x`, `sample_y`) to
+    /// every pixel in the buffer whose center is within the filter's radius.
+    pub fn splat(
+        &mut self,
+        filter: &Filter,
+        sample_x: f32,
+        sample_y: f32,
+        sample_idx: u32,
+        color: Vec3,
+    ) {
+        // Pixels whose centers lie in (sample - radius, sample + radius].
+        let radius = filter.radius();
+        let x_min = (sample_x - 0.5 - radius).floor() as isize + 1;
+        let x_max = (sample_x - 0.5 + radius).floor() as isize;
+        let y_min = (sample_y - 0.5 - radius).floor() as isize + 1;
+        let y_max = (sample_y - 0.5 + radius).floor() as isize;
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                if let Some(idx) = self.get_idx(x, y) {
+                    let weight =
+                        filter.evaluate(x as f32 + 0.5 - sample_x, y as f32 + 0.5 - sample_y);
+                    self.pixels[idx].add(sample_idx, color, weight);
+                }
+            }
+        }
+    }
+
+    /// Adds the `splat` to its pixel, returning false if the pixel lies outside this buffer.
+    fn add_splat(&mut self, splat: &Splat) -> bool {
+        match self.get_idx(splat.x, splat.y) {
+            Some(idx) => {
+                self.pixels[idx].add_splat(splat.sample_idx, splat.color);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds the samples of `other` to the overlapping pixels of this buffer.
+    pub fn merge(&mut self, other: &SampleBuffer) {
+        for y in 0..other.height {
+            for x in 0..other.width {
+                let full_x = other.x_start + x as isize;
+                let full_y = other.y_start + y as isize;
+                if let Some(idx) = self.get_idx(full_x, full_y) {
+                    self.pixels[idx].merge(&other.pixels[y * other.width + x]);
+                }
+            }
+        }
+    }
+
+    pub fn get_idx(&self, x: isize, y: isize) -> Option<usize> {
+        let x = x - self.x_start;
+        let y = y - self.y_start;
+        if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+}
+
+/// Adds per-pixel `other_counts`, laid out like `other_buffer`, to the overlapping pixels
+/// of `counts`, laid out like `buffer`.
+fn merge_counts<const N: usize>(
+    buffer: &SampleBuffer,
+    counts: &mut [[u32; N]],
+    other_buffer: &SampleBuffer,
+    other_counts: &[[u32; N]],
+) {
+    for y in 0..other_buffer.height {
+        for x in 0..other_buffer.width {
+            let full_x = other_buffer.x_start + x as isize;
+            let full_y = other_buffer.y_start + y as isize;
+            if let Some(idx) = buffer.get_idx(full_x, full_y) {
+                let other_pixel_counts = other_counts[y * other_buffer.width + x];
+                for (count, other_count) in counts[idx].iter_mut().zip(other_pixel_counts) {
+                    *count += other_count;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use crate::filter::Filter;
+
+    use super::{Film, FilmChannels};
+
+    #[test]
+    fn splats_outside_a_region_reach_the_image() {
+        let mut image = Film::new(0, 0, 8, 8, Filter::default(), FilmChannels::default());
+        let mut tile = image.region(0, 0, 2, 2);
+        tile.add_sample(0.5, 0.5, 0, Vec3::ONE);
+        tile.add_splat(6.5, 5.5, 0, Vec3::splat(4.0));
+        image.merge(&tile);
+        image.set_samples_per_pixel(2);
+
+        assert_eq!(image.color(0, 0), Vec3::ONE);
+        assert_eq!(image.color(6, 5), Vec3::splat(2.0));
+        assert_eq!(image.color(5, 6), Vec3::ZERO);
+    }
+}
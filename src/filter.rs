@@ -0,0 +1,116 @@
+//! Pixel reconstruction filters.
+//!
+//! Each sample contributes to every pixel whose center lies within the filter's radius,
+//! weighted by the filter evaluated at the offset from that pixel's center. The final
+//! pixel color is the weighted sum of its samples divided by the sum of the weights.
+
+use std::f32::consts::PI;
+
+/// A pixel reconstruction filter. Radii are in pixels.
+#[derive(Clone, Copy, Debug)]
+pub enum Filter {
+    /// Weights every sample within the radius equally. A radius of 0.5 gives each pixel
+    /// only its own samples, which is the simple average used before filters existed.
+    Box { radius: f32 },
+    /// Weight falls off linearly from the pixel center.
+    Tent { radius: f32 },
+    /// A Gaussian with falloff `alpha`, shifted down so it reaches zero at the radius.
+    Gaussian { radius: f32, alpha: f32 },
+    /// The Mitchell-Netravali cubic. B = C = 1/3 is the usual recommendation.
+    Mitchell { radius: f32, b: f32, c: f32 },
+    /// The four-term Blackman-Harris window.
+    BlackmanHarris { radius: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// The extent of the filter from the pixel center, in pixels, along each axis.
+    pub fn radius(&self) -> f32 {
+        match self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::BlackmanHarris { radius } => *radius,
+        }
+    }
+
+    /// Evaluates the filter at an offset of (`x`, `y`) pixels from a pixel center.
+    /// Filters are separable, so this is the product of the filter along each axis.
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, alpha } => {
+                f32::exp(-alpha * x * x) - f32::exp(-alpha * radius * radius)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                // The cubic is defined over [-2, 2].
+                let x = 2.0 * x / radius;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            Filter::BlackmanHarris { radius } => {
+                // The window spans [-radius, radius], peaking at the center.
+                let t = 2.0 * PI * (x + radius) / (2.0 * radius);
+                0.35875 - 0.48829 * f32::cos(t) + 0.14128 * f32::cos(2.0 * t)
+                    - 0.01168 * f32::cos(3.0 * t)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+
+    #[test]
+    fn filters_peak_at_center_and_vanish_outside_radius() {
+        let filters = [
+            Filter::Box { radius: 0.5 },
+            Filter::Tent { radius: 1.0 },
+            Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            },
+            Filter::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            Filter::BlackmanHarris { radius: 2.0 },
+        ];
+        for filter in filters {
+            let center = filter.evaluate(0.0, 0.0);
+            assert!(center > 0.0);
+            assert!(filter.evaluate(0.25, -0.25) <= center);
+            assert_eq!(filter.evaluate(filter.radius() + 0.01, 0.0), 0.0);
+            if !matches!(filter, Filter::Box { .. }) {
+                assert!(filter.evaluate(filter.radius(), 0.0).abs() < 1e-3);
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Caching what each camera ray hits first, so that re-renders with the same camera and
+//! geometry can skip finding it.
+//!
+//! Materials can't be saved to disk, so each hit is recorded as the index of the
+//! top-level object that was hit and the distance along the ray. When the cache is reused,
+//! rays which missed the scene go straight to the background, and the rest only intersect
+//! the object they hit, close to where they hit it, to find its current material. Changes
+//! to materials and lights are therefore picked up, but changes to the camera or to the
+//! scene's geometry are not, and the cache must then be deleted.
+//!
+//! Camera rays are only repeatable when each sample's random numbers are seeded from its
+//! pixel, so the cache requires a seed, and is only reused with the same seed, image size
+//! and number of samples per pixel.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use ahash::AHashMap;
+
+use crate::{
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable, HittableList},
+    hrpp::Predictor,
+    ray::Ray,
+};
+
+const MAGIC: &[u8; 4] = b"SFBC";
+
+/// The object index recorded for rays which miss the scene.
+const MISS: u32 = u32::MAX;
+
+/// The first hits of each sample of each pixel of an image.
+pub(crate) struct FirstBounceCache {
+    header: Header,
+    /// Whether the hits were loaded, to be reused, rather than being recorded.
+    loaded: bool,
+    /// The object index of each hit in the upper half, and the bits of its distance in
+    /// the lower half. Sample-major within each pixel.
+    hits: Vec<AtomicU64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub seed: u64,
+    /// The number of top-level objects in the scene.
+    pub object_count: u32,
+}
+
+impl FirstBounceCache {
+    /// Loads the cache at `path` if there is one matching `header`, and otherwise
+    /// creates an empty cache to record hits into.
+    pub fn load_or_record(path: &Path, header: Header) -> io::Result<FirstBounceCache> {
+        let len =
+            header.width as usize * header.height as usize * header.samples_per_pixel as usize;
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(FirstBounceCache::recording(header, len));
+            }
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC || Header::read(&mut reader)? != header {
+            eprintln!(
+                "First bounce cache {} doesn't match this render; recording a new one.",
+                path.display()
+            );
+            return Ok(FirstBounceCache::recording(header, len));
+        }
+        let mut hits = Vec::with_capacity(len);
+        let mut bytes = [0; 8];
+        for _ in 0..len {
+            reader.read_exact(&mut bytes)?;
+            hits.push(AtomicU64::new(u64::from_le_bytes(bytes)));
+        }
+        Ok(FirstBounceCache {
+            header,
+            loaded: true,
+            hits,
+        })
+    }
+
+    fn recording(header: Header, len: usize) -> FirstBounceCache {
+        FirstBounceCache {
+            header,
+            loaded: false,
+            hits: (0..len).map(|_| AtomicU64::new(pack(None))).collect(),
+        }
+    }
+
+    /// Whether the hits were loaded from disk rather than being recorded by this render.
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        self.header.write(&mut writer)?;
+        for hit in &self.hits {
+            writer.write_all(&hit.load(Ordering::Relaxed).to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Finds what the camera ray for the sample of the pixel hits first, from the cache
+    /// if it was loaded and otherwise by intersecting the world and recording the result.
+    pub fn first_hit(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        pixel_index: usize,
+        sample: u32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        let entry =
+            &self.hits[pixel_index * self.header.samples_per_pixel as usize + sample as usize];
+        if !self.loaded {
+            let hit = world.hit_object(ray, world.t_min(ray), f32::INFINITY, predictors);
+            entry.store(
+                pack(
+                    hit.as_ref()
+                        .map(|(object, hit_record)| (*object, hit_record.t)),
+                ),
+                Ordering::Relaxed,
+            );
+            return hit.map(|(_, hit_record)| hit_record);
+        }
+
+        let (object, t) = unpack(entry.load(Ordering::Relaxed))?;
+        // Allow for rounding in the intersection, falling back to the whole world if the
+        // object is no longer there.
+        let tolerance = 1e-4 * t.max(1.0);
+        world
+            .objects
+            .get(object)
+            .and_then(|object| {
+                object.hit(
+                    ray,
+                    (t - tolerance).max(world.t_min(ray)),
+                    t + tolerance,
+                    predictors,
+                )
+            })
+            .or_else(|| world.hit(ray, world.t_min(ray), f32::INFINITY, predictors))
+    }
+}
+
+impl Header {
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        writer.write_all(&self.samples_per_pixel.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.object_count.to_le_bytes())
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Header> {
+        let mut u32_bytes = [0; 4];
+        let mut read_u32 = |reader: &mut dyn Read| -> io::Result<u32> {
+            reader.read_exact(&mut u32_bytes)?;
+            Ok(u32::from_le_bytes(u32_bytes))
+        };
+        let width = read_u32(reader)?;
+        let height = read_u32(reader)?;
+        let samples_per_pixel = read_u32(reader)?;
+        let mut u64_bytes = [0; 8];
+        reader.read_exact(&mut u64_bytes)?;
+        let seed = u64::from_le_bytes(u64_bytes);
+        let object_count = read_u32(reader)?;
+        Ok(Header {
+            width,
+            height,
+            samples_per_pixel,
+            seed,
+            object_count,
+        })
+    }
+}
+
+fn pack(hit: Option<(usize, f32)>) -> u64 {
+    match hit {
+        Some((object, t)) => (object as u64) << 32 | t.to_bits() as u64,
+        None => (MISS as u64) << 32,
+    }
+}
+
+fn unpack(packed: u64) -> Option<(usize, f32)> {
+    let object = (packed >> 32) as u32;
+    if object == MISS {
+        return None;
+    }
+    Some((object as usize, f32::from_bits(packed as u32)))
+}
@@ -0,0 +1,77 @@
+//! White furnace tests, for checking that materials conserve energy.
+//!
+//! A sphere of the material is lit by a uniform white environment. The sphere is convex,
+//! so light leaving its surface escapes straight back to the environment, and the light
+//! reflected towards the viewer is the fraction of incoming light which the material
+//! reflects, averaged over the angles it's seen at. A material which neither absorbs nor
+//! loses light reflects exactly as much as its albedo, and anything else shows up as a
+//! deviation from it, e.g. from scattered directions being discarded as they go below
+//! the surface.
+
+use std::{f32::consts::PI, sync::Arc};
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    background::Background,
+    geometry::sphere::Sphere,
+    hittable::HittableList,
+    materials::material::Material,
+    random::{self, random},
+    ray::{Depth, Ray},
+};
+
+/// The maximum number of bounces along each path, enough that paths caught bouncing
+/// inside a dielectric lose very little light when they're cut off.
+const MAX_DEPTH: u32 = 64;
+
+/// Measures the average reflectance of the `material` in a white furnace, from
+/// `samples` rays spread evenly over a sphere of it as seen from afar.
+pub fn measure(material: Arc<dyn Material>, samples: u32) -> Vec3 {
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(Vec3::ZERO, 1.0, material)));
+    let background = Background::Solid(Vec3::ONE);
+    let predictors = Arc::new(None);
+
+    // Seeded, so measurements repeat exactly.
+    random::seeded(0, || {
+        let mut total = Vec3::ZERO;
+        for _ in 0..samples {
+            // Uniformly over the sphere's silhouette, just short of its grazing edge.
+            let r = 0.999 * random::<f32>().sqrt();
+            let angle = 2.0 * PI * random::<f32>();
+            let origin = vec3(r * angle.cos(), r * angle.sin(), 3.0);
+            let ray = Ray::new(origin, -Vec3::Z, 0.0);
+            total += ray.ray_color(
+                &world,
+                Depth::new(MAX_DEPTH, MAX_DEPTH, MAX_DEPTH),
+                &background,
+                &predictors,
+            );
+        }
+        total / samples as f32
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::Vec3;
+
+    use crate::materials::{dialectric::Dialectric, lambertian::Lambertian, metal::Metal};
+
+    use super::measure;
+
+    #[test]
+    fn materials_reflect_their_albedo() {
+        let lambertian = measure(Arc::new(Lambertian::from_color(Vec3::splat(0.5))), 4096);
+        assert!((lambertian - Vec3::splat(0.5)).abs().max_element() < 0.01);
+
+        let mirror = measure(Arc::new(Metal::new(Vec3::splat(0.8), 0.0)), 4096);
+        assert!((mirror - Vec3::splat(0.8)).abs().max_element() < 0.01);
+
+        let glass = measure(Arc::new(Dialectric::new(1.5)), 4096);
+        assert!((glass - Vec3::ONE).abs().max_element() < 0.01);
+    }
+}
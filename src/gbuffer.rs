@@ -0,0 +1,185 @@
+//! Writing a G-buffer: the position, normal, albedo and material of the surface seen
+//! through each pixel, as linear EXR images, for re-lighting and compositing the render
+//! in other tools.
+//!
+//! Each pixel holds the surface hit by a single ray through its center, from the center
+//! of the lens at the middle of the shutter interval, so the buffers are sharp and
+//! unfiltered, without antialiasing, depth of field or motion blur.
+
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use ahash::AHashMap;
+use glam::{vec2, Vec3};
+use image::{Rgb, Rgb32FImage};
+use rayon::prelude::*;
+
+use crate::{
+    background::Background,
+    bvh::BvhId,
+    camera::Camera,
+    hittable::{Hittable, HittableList},
+    hrpp::Predictor,
+};
+
+/// The surface seen through each pixel of an image, with rows stored bottom-up as the
+/// renderer stores them.
+pub(crate) struct GBuffer {
+    width: usize,
+    height: usize,
+    /// The point hit in world space, or zero where the ray escaped.
+    position: Vec<Vec3>,
+    /// The surface normal, facing the camera, or zero where the ray escaped.
+    normal: Vec<Vec3>,
+    /// The albedo of the surface, the emission of lights, or the background where the
+    /// ray escaped.
+    albedo: Vec<Vec3>,
+    /// The material of the surface, numbered from 1 in the order the materials are first
+    /// seen from the top left of the image, or 0 where the ray escaped.
+    material: Vec<u32>,
+}
+
+impl GBuffer {
+    /// Traces a ray through the center of each pixel of a `width` x `height` image.
+    pub(crate) fn trace(
+        camera: &Camera,
+        world: &HittableList,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        width: usize,
+        height: usize,
+    ) -> GBuffer {
+        // Each material is told apart by its address until they're numbered.
+        let pixels: Vec<(Vec3, Vec3, Vec3, usize)> = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..width).map(move |x| {
+                    let u = (x as f32 + 0.5) / (width - 1) as f32;
+                    let v = (y as f32 + 0.5) / (height - 1) as f32;
+                    let ray = camera.get_ray_at(u, v, vec2(0.5, 0.5), 0.5);
+                    let Some(hit_record) =
+                        world.hit(&ray, world.t_min(&ray), f32::INFINITY, predictors)
+                    else {
+                        return (Vec3::ZERO, Vec3::ZERO, background.value(ray.direction), 0);
+                    };
+                    let albedo = match hit_record.material.scatter(&ray, &hit_record) {
+                        Some(scatter_record) => scatter_record.attenuation,
+                        None => {
+                            hit_record
+                                .material
+                                .emit(hit_record.u, hit_record.v, &hit_record.point)
+                        }
+                    };
+                    let material = Arc::as_ptr(&hit_record.material) as *const () as usize;
+                    (hit_record.point, hit_record.normal, albedo, material)
+                })
+            })
+            .collect();
+
+        let mut ids = AHashMap::new();
+        let mut material = vec![0; pixels.len()];
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let index = y * width + x;
+                let address = pixels[index].3;
+                if address != 0 {
+                    let next_id = ids.len() as u32 + 1;
+                    material[index] = *ids.entry(address).or_insert(next_id);
+                }
+            }
+        }
+        GBuffer {
+            width,
+            height,
+            position: pixels.iter().map(|pixel| pixel.0).collect(),
+            normal: pixels.iter().map(|pixel| pixel.1).collect(),
+            albedo: pixels.iter().map(|pixel| pixel.2).collect(),
+            material,
+        }
+    }
+
+    /// Writes the buffers to `<prefix>_position.exr`, `<prefix>_normal.exr`,
+    /// `<prefix>_albedo.exr` and `<prefix>_material.exr`, the last of which holds each
+    /// pixel's material number in all three channels.
+    pub(crate) fn write(&self, prefix: &Path) -> io::Result<()> {
+        let prefix = prefix.display();
+        let material: Vec<Vec3> = self
+            .material
+            .iter()
+            .map(|id| Vec3::splat(*id as f32))
+            .collect();
+        for (name, values) in [
+            ("position", &self.position),
+            ("normal", &self.normal),
+            ("albedo", &self.albedo),
+            ("material", &material),
+        ] {
+            // Our rows are stored bottom-up; images are stored top-down.
+            let image = Rgb32FImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+                let value = values[(self.height - 1 - y as usize) * self.width + x as usize];
+                Rgb(value.to_array())
+            });
+            image
+                .save(format!("{}_{}.exr", prefix, name))
+                .map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        background::Background, camera::Camera, geometry::sphere::Sphere, hittable::HittableList,
+        materials::lambertian::Lambertian,
+    };
+
+    use super::GBuffer;
+
+    #[test]
+    fn materials_are_numbered_from_the_top_left() {
+        let red = Arc::new(Lambertian::from_color(vec3(0.8, 0.1, 0.1)));
+        let blue = Arc::new(Lambertian::from_color(vec3(0.1, 0.1, 0.8)));
+        let mut world = HittableList::new();
+        // Blue spheres in the top corners, a red one in the bottom right.
+        world.add(Arc::new(Sphere::new(
+            vec3(-2.0, 2.0, 0.0),
+            1.0,
+            blue.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(vec3(2.0, 2.0, 0.0), 1.0, blue)));
+        world.add(Arc::new(Sphere::new(vec3(2.0, -2.0, 0.0), 1.0, red)));
+        let camera = Camera::new(
+            vec3(0.0, 0.0, 10.0),
+            Vec3::ZERO,
+            Vec3::Y,
+            45.0,
+            1.0,
+            0.0,
+            10.0,
+            0.0,
+            1.0,
+        );
+        let background = Background::Solid(Vec3::ONE);
+        let gbuffer = GBuffer::trace(&camera, &world, &background, &Arc::new(None), 40, 40);
+
+        // Rows are bottom-up, so the top left is in the last row.
+        let material = |x: usize, y: usize| gbuffer.material[y * 40 + x];
+        assert_eq!(material(10, 30), 1);
+        assert_eq!(material(30, 30), 1);
+        assert_eq!(material(30, 10), 2);
+        assert_eq!(material(10, 10), 0);
+        assert_eq!(gbuffer.albedo[10 * 40 + 10], Vec3::ONE);
+        // Normals face the camera, and positions are on the spheres.
+        let center = 10 * 40 + 30;
+        assert!(gbuffer.normal[center].z > 0.9);
+        assert!((gbuffer.position[center].distance(vec3(2.0, -2.0, 0.0)) - 1.0).abs() < 1e-3);
+    }
+}
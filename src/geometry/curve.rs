@@ -0,0 +1,240 @@
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::{vec3, Vec3};
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable},
+    hrpp::Predictor,
+    materials::material::Material,
+    ray::Ray,
+};
+
+/// A strand of hair or fur, represented as a polyline of round segments.
+/// The radius tapers linearly from `root_radius` at the first point to `tip_radius` at the last.
+///
+/// Each segment is intersected as a capsule, so consecutive segments join smoothly at bends.
+/// Curves are treated as thin opaque geometry; rays starting inside a strand do not hit it.
+pub struct Curve {
+    points: Vec<Vec3>,
+    root_radius: f32,
+    tip_radius: f32,
+    material: Arc<dyn Material>,
+}
+
+impl Curve {
+    /// Creates a new curve through `points`, which must contain at least two points.
+    pub fn new(
+        points: Vec<Vec3>,
+        root_radius: f32,
+        tip_radius: f32,
+        material: Arc<dyn Material>,
+    ) -> Curve {
+        assert!(points.len() >= 2, "A curve requires at least two points");
+        Curve {
+            points,
+            root_radius,
+            tip_radius,
+            material,
+        }
+    }
+
+    /// The radius of the segment starting at `segment_idx`, taken at the segment's midpoint.
+    fn segment_radius(&self, segment_idx: usize) -> f32 {
+        let num_segments = (self.points.len() - 1) as f32;
+        let fraction = (segment_idx as f32 + 0.5) / num_segments;
+        self.root_radius + (self.tip_radius - self.root_radius) * fraction
+    }
+
+    /// Returns the distance along the normalized `direction` to the closest intersection
+    /// with the capsule from `a` to `b`, if any.
+    /// Follows Inigo Quilez's capsule intersection.
+    fn capsule_intersect(
+        origin: Vec3,
+        direction: Vec3,
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    ) -> Option<f32> {
+        let ba = b - a;
+        let oa = origin - a;
+        let baba = ba.dot(ba);
+        let bard = ba.dot(direction);
+        let baoa = ba.dot(oa);
+        let rdoa = direction.dot(oa);
+        let oaoa = oa.dot(oa);
+
+        let qa = baba - bard * bard;
+        // A ray parallel to the segment can only enter through one of the caps.
+        if qa.abs() < 1e-6 * baba {
+            let cap_a = Curve::sphere_intersect(origin, direction, a, radius);
+            let cap_b = Curve::sphere_intersect(origin, direction, b, radius);
+            return match (cap_a, cap_b) {
+                (Some(t_a), Some(t_b)) => Some(t_a.min(t_b)),
+                (t_a, t_b) => t_a.or(t_b),
+            };
+        }
+        let qb = baba * rdoa - baoa * bard;
+        let qc = baba * oaoa - baoa * baoa - radius * radius * baba;
+        let h = qb * qb - qa * qc;
+        if h < 0.0 {
+            return None;
+        }
+
+        // Body of the capsule
+        let t = (-qb - h.sqrt()) / qa;
+        let y = baoa + t * bard;
+        if y > 0.0 && y < baba {
+            return Some(t);
+        }
+
+        // Spherical caps
+        let cap = if y <= 0.0 { a } else { b };
+        Curve::sphere_intersect(origin, direction, cap, radius)
+    }
+
+    /// Returns the distance along the normalized `direction` to where the ray enters the
+    /// sphere of `radius` around `center`, if it does.
+    fn sphere_intersect(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+        let oc = origin - center;
+        let qb = direction.dot(oc);
+        let qc = oc.dot(oc) - radius * radius;
+        let h = qb * qb - qc;
+        if h > 0.0 {
+            Some(-qb - h.sqrt())
+        } else {
+            None
+        }
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        // The capsule test expects a normalized direction, so we convert distances
+        // back into the ray's parameterization afterwards.
+        let direction_length = ray.direction.length();
+        let direction = ray.direction / direction_length;
+
+        let mut closest: Option<(f32, usize)> = None;
+        for (segment_idx, segment) in self.points.windows(2).enumerate() {
+            let radius = self.segment_radius(segment_idx);
+            let distance =
+                Curve::capsule_intersect(ray.origin, direction, segment[0], segment[1], radius);
+            if let Some(distance) = distance {
+                let t = distance / direction_length;
+                let closest_t = closest.map_or(t_max, |(closest_t, _)| closest_t);
+                if t >= t_min && t <= closest_t {
+                    closest = Some((t, segment_idx));
+                }
+            }
+        }
+
+        let (t, segment_idx) = closest?;
+        let point = ray.at(t);
+        let a = self.points[segment_idx];
+        let b = self.points[segment_idx + 1];
+        let ba = b - a;
+        let along_segment = f32::clamp((point - a).dot(ba) / ba.dot(ba), 0.0, 1.0);
+        let outward_normal = (point - (a + ba * along_segment)).normalize();
+
+        // u runs from the root (0) to the tip (1) of the strand.
+        let u = (segment_idx as f32 + along_segment) / (self.points.len() - 1) as f32;
+        Some(HitRecord::new(
+            ray,
+            outward_normal,
+            t,
+            u,
+            0.0,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<Aabb> {
+        let radius = f32::max(self.root_radius, self.tip_radius);
+        let rad = vec3(radius, radius, radius);
+        let mut min = vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for point in self.points.iter() {
+            min = min.min(*point);
+            max = max.max(*point);
+        }
+        Some(Aabb::new(min - rad, max + rad))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::vec3;
+
+    use crate::{hittable::Hittable, materials::lambertian::Lambertian, ray::Ray};
+
+    use super::Curve;
+
+    fn curve() -> Curve {
+        Curve::new(
+            vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(0.0, 2.0, 0.0),
+            ],
+            0.2,
+            0.1,
+            Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_body() {
+        let ray = Ray::new(vec3(0.0, 0.5, -5.0), vec3(0.0, 0.0, 2.0), 0.0);
+        let hit = curve()
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+
+        // The first segment has a radius of 0.175 at its midpoint.
+        assert!((hit.point.z + 0.175).abs() < 1e-4);
+        assert!((hit.t - (5.0 - 0.175) / 2.0).abs() < 1e-4);
+        assert!((hit.normal - vec3(0.0, 0.0, -1.0)).length() < 1e-4);
+        assert!((hit.u - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hits_tip_cap() {
+        let ray = Ray::new(vec3(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = curve()
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+
+        assert!((hit.point.y - 2.125).abs() < 1e-4);
+        assert!((hit.u - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hits_root_cap_along_the_strand() {
+        let ray = Ray::new(vec3(0.0, -5.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        let hit = curve()
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+
+        // The first segment has a radius of 0.175, so its root cap reaches y = -0.175.
+        assert!((hit.point.y + 0.175).abs() < 1e-4);
+        assert!(hit.u.abs() < 1e-4);
+    }
+
+    #[test]
+    fn misses() {
+        let ray = Ray::new(vec3(1.0, 0.5, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(curve()
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .is_none());
+    }
+}
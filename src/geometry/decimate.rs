@@ -0,0 +1,147 @@
+//! Mesh simplification by vertex clustering.
+//!
+//! Space is divided into a uniform grid of cells, and every vertex in a cell is
+//! replaced by the average of those vertices. Triangles which collapse as a result
+//! are removed. This is fast and robust on triangle soups such as large scans,
+//! though it doesn't preserve features as well as edge-collapse methods.
+
+use ahash::{AHashMap, AHashSet};
+use glam::Vec3;
+
+type CellKey = (i64, i64, i64);
+
+/// How far `decimate()` simplifies a mesh.
+#[derive(Clone, Copy, Debug)]
+pub enum DecimationTarget {
+    /// Simplify until there are at most this many triangles.
+    TriangleCount(usize),
+    /// Simplify as far as possible without moving any vertex further than this distance.
+    MaxError(f32),
+}
+
+/// Simplifies the `triangles` to meet the `target`.
+pub fn decimate(triangles: &[[Vec3; 3]], target: DecimationTarget) -> Vec<[Vec3; 3]> {
+    if triangles.is_empty() {
+        return Vec::new();
+    }
+
+    let mut min = Vec3::splat(f32::INFINITY);
+    let mut max = Vec3::splat(f32::NEG_INFINITY);
+    for vertex in triangles.iter().flatten() {
+        min = min.min(*vertex);
+        max = max.max(*vertex);
+    }
+    let extent = (max - min).max_element();
+    if extent <= 0.0 {
+        return triangles.to_vec();
+    }
+
+    match target {
+        DecimationTarget::MaxError(max_error) => {
+            // Vertices move at most the diagonal of their cell.
+            cluster(triangles, min, max_error / f32::sqrt(3.0))
+        }
+        DecimationTarget::TriangleCount(count) => {
+            if triangles.len() <= count {
+                return triangles.to_vec();
+            }
+            // Coarser cells give fewer triangles, so search for the finest cells
+            // which meet the count.
+            let mut fine = extent / (1 << 16) as f32;
+            let mut coarse = extent;
+            let mut best = cluster(triangles, min, coarse);
+            for _ in 0..24 {
+                let cell_size = (fine * coarse).sqrt();
+                let decimated = cluster(triangles, min, cell_size);
+                if decimated.len() <= count {
+                    coarse = cell_size;
+                    best = decimated;
+                } else {
+                    fine = cell_size;
+                }
+            }
+            best
+        }
+    }
+}
+
+/// Clusters the vertices of the `triangles` into cubic cells of `cell_size`,
+/// with the grid's origin at `origin`.
+fn cluster(triangles: &[[Vec3; 3]], origin: Vec3, cell_size: f32) -> Vec<[Vec3; 3]> {
+    if cell_size <= 0.0 {
+        return triangles.to_vec();
+    }
+    let cell_of = |vertex: Vec3| -> CellKey {
+        let cell = ((vertex - origin) / cell_size).floor();
+        (cell.x as i64, cell.y as i64, cell.z as i64)
+    };
+
+    let mut clusters: AHashMap<CellKey, (Vec3, u32)> = AHashMap::new();
+    for vertex in triangles.iter().flatten() {
+        let cluster = clusters.entry(cell_of(*vertex)).or_insert((Vec3::ZERO, 0));
+        cluster.0 += *vertex;
+        cluster.1 += 1;
+    }
+
+    let mut seen: AHashSet<[CellKey; 3]> = AHashSet::new();
+    triangles
+        .iter()
+        .filter_map(|triangle| {
+            let cells = triangle.map(cell_of);
+            if cells[0] == cells[1] || cells[1] == cells[2] || cells[0] == cells[2] {
+                return None;
+            }
+            // Several triangles may collapse onto the same cells; keep only the first.
+            let mut sorted = cells;
+            sorted.sort_unstable();
+            if !seen.insert(sorted) {
+                return None;
+            }
+            Some(cells.map(|cell| {
+                let (sum, count) = clusters[&cell];
+                sum / count as f32
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::{decimate, DecimationTarget};
+
+    /// A square in the XZ plane, split into `n` x `n` quads of two triangles each.
+    fn grid(n: usize) -> Vec<[Vec3; 3]> {
+        let mut triangles = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                let p = |di: usize, dj: usize| vec3((i + di) as f32, 0.0, (j + dj) as f32);
+                triangles.push([p(0, 0), p(1, 0), p(1, 1)]);
+                triangles.push([p(0, 0), p(1, 1), p(0, 1)]);
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn decimate_to_triangle_count() {
+        let triangles = grid(64);
+        let decimated = decimate(&triangles, DecimationTarget::TriangleCount(1000));
+        assert!(decimated.len() <= 1000);
+        assert!(decimated.len() > 100);
+    }
+
+    #[test]
+    fn decimate_within_error() {
+        let triangles = grid(64);
+        let decimated = decimate(&triangles, DecimationTarget::MaxError(2.0));
+        assert!(decimated.len() < triangles.len());
+        // Every vertex stays within the square, and in the plane.
+        for vertex in decimated.iter().flatten() {
+            assert!(vertex.y.abs() < 1e-4);
+            assert!(vertex.x >= 0.0 && vertex.x <= 64.0);
+            assert!(vertex.z >= 0.0 && vertex.z <= 64.0);
+        }
+    }
+}
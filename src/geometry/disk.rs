@@ -0,0 +1,116 @@
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+use ahash::AHashMap;
+use glam::{vec3, Vec3};
+use rand::{Rng, RngCore};
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable},
+    hrpp::Predictor,
+    materials::material::Material,
+    precision,
+    ray::Ray,
+};
+
+use super::sampleable::{Sampleable, SurfacePoint};
+
+/// A flat disk facing along its `normal`.
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f32,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, material: Arc<dyn Material>) -> Disk {
+        Disk {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material,
+        }
+    }
+
+    /// Two unit vectors across the disk, perpendicular to each other and its normal.
+    fn tangents(&self) -> (Vec3, Vec3) {
+        self.normal.any_orthonormal_pair()
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        let denominator = self.normal.dot(ray.direction);
+        // Parallel to the plane.
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = self.normal.dot(self.center - ray.origin) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let offset = ray.at(t) - self.center;
+        let distance = offset.length();
+        if distance > self.radius {
+            return None;
+        }
+
+        // Around the disk, and out from its center.
+        let (tangent, bitangent) = self.tangents();
+        let angle = offset.dot(bitangent).atan2(offset.dot(tangent));
+        let u = (angle + PI) / (2.0 * PI);
+        let v = distance / self.radius;
+        Some(HitRecord::new(
+            ray,
+            self.normal,
+            t,
+            u,
+            v,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<Aabb> {
+        // How far the rim reaches along each axis.
+        let extent = (Vec3::ONE - self.normal * self.normal).max(Vec3::ZERO);
+        let extent = self.radius * vec3(extent.x.sqrt(), extent.y.sqrt(), extent.z.sqrt());
+        let (min, max) = (self.center - extent, self.center + extent);
+        // Pad to avoid infinitely-thin boxes for axis-aligned disks.
+        let padding = |v: Vec3| {
+            vec3(
+                precision::bounds_padding(v.x),
+                precision::bounds_padding(v.y),
+                precision::bounds_padding(v.z),
+            )
+        };
+        Some(Aabb::new(min - padding(min), max + padding(max)))
+    }
+}
+
+impl Sampleable for Disk {
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        // The square root spreads points evenly by area rather than by radius.
+        let r = self.radius * rng.gen::<f32>().sqrt();
+        let angle = 2.0 * PI * rng.gen::<f32>();
+        let (tangent, bitangent) = self.tangents();
+        SurfacePoint {
+            point: self.center + r * (angle.cos() * tangent + angle.sin() * bitangent),
+            normal: self.normal,
+        }
+    }
+}
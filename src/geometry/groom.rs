@@ -0,0 +1,274 @@
+//! Procedural hair and fur grown from triangle meshes.
+
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use glam::{vec3, IVec3, Vec3};
+use rand::Rng;
+
+use crate::{
+    hittable::HittableList,
+    materials::{material::Material, utils::random_in_unit_sphere_with},
+    random,
+};
+
+use super::{curve::Curve, sampleable::Sampleable, triangle::Tri};
+
+/// Controls how strands are grown by `grow()`.
+#[derive(Clone, Copy, Debug)]
+pub struct GroomParams {
+    /// Number of strands grown per unit of surface area.
+    pub density: f32,
+    /// Length of each strand, before variation.
+    pub length: f32,
+    /// Fraction of `length` by which each strand's length may randomly vary.
+    pub length_variation: f32,
+    /// Radius of each strand at the surface of the mesh.
+    pub root_radius: f32,
+    /// Radius of each strand at its tip.
+    pub tip_radius: f32,
+    /// Number of curve segments in each strand.
+    pub segments: usize,
+    /// Number of clumps per unit of surface area. Each clump grows a guide strand,
+    /// and every strand is pulled towards the guide of its nearest clump.
+    /// A density of 0.0 disables clumping.
+    pub clump_density: f32,
+    /// How strongly strands are pulled towards their clump's guide, in \[0, 1\].
+    /// The pull increases from nothing at the root to this value at the tip.
+    pub clump_strength: f32,
+    /// How far strands may deviate from the surface normal, in \[0, 1\].
+    pub randomness: f32,
+    /// How far the tip of each strand droops in -Y, as a fraction of its length.
+    pub gravity: f32,
+}
+
+impl Default for GroomParams {
+    fn default() -> Self {
+        GroomParams {
+            density: 1.0,
+            length: 1.0,
+            length_variation: 0.2,
+            root_radius: 0.02,
+            tip_radius: 0.005,
+            segments: 4,
+            clump_density: 0.0,
+            clump_strength: 0.5,
+            randomness: 0.2,
+            gravity: 0.1,
+        }
+    }
+}
+
+/// A strand before it is turned into a `Curve`.
+struct Strand {
+    root: Vec3,
+    points: Vec<Vec3>,
+}
+
+/// Grows strands from the surface of the mesh described by `triangles`, returning a list
+/// of `Curve`s. Strands grow along each triangle's geometric normal, so the mesh must be
+/// wound consistently for the fur to grow outwards.
+///
+/// Strands are placed using `crate::random`, so scenes grown within `random::seeded()`
+/// repeat. The returned list is typically large, and should be placed in a `Bvh`.
+pub fn grow(
+    triangles: &[[Vec3; 3]],
+    params: &GroomParams,
+    material: Arc<dyn Material>,
+) -> HittableList {
+    let mut rng = random::rng();
+    let triangles: Vec<Tri> = triangles
+        .iter()
+        .map(|[p0, p1, p2]| Tri::new(*p0, *p1, *p2, material.clone()))
+        .collect();
+
+    let guides: Vec<Strand> = if params.clump_density > 0.0 {
+        scatter_roots(&triangles, params.clump_density, &mut rng)
+            .into_iter()
+            .map(|(root, normal)| grow_strand(root, normal, params, &mut rng))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    // Guides are spread about one clump's width apart, so most lookups only search the
+    // few cells around the root.
+    let guide_grid = GuideGrid::new(&guides, params.clump_density.recip().sqrt());
+
+    let mut strands = HittableList::new();
+    for (root, normal) in scatter_roots(&triangles, params.density, &mut rng) {
+        let mut strand = grow_strand(root, normal, params, &mut rng);
+        if let Some(guide) = guide_grid.nearest(root) {
+            clump(&mut strand, guide, params.clump_strength);
+        }
+        strands.add(Arc::new(Curve::new(
+            strand.points,
+            params.root_radius,
+            params.tip_radius,
+            material.clone(),
+        )));
+    }
+    strands
+}
+
+/// Randomly distributes points over the triangles with the given `density` per unit area.
+/// Returns each point along with the normal of the triangle it lies on.
+fn scatter_roots(triangles: &[Tri], density: f32, rng: &mut impl Rng) -> Vec<(Vec3, Vec3)> {
+    let mut roots = Vec::new();
+    for triangle in triangles {
+        let area = triangle.area();
+        if area <= f32::EPSILON {
+            // Degenerate triangle; it has no well-defined normal to grow along.
+            continue;
+        }
+
+        // Round the expected count stochastically so small triangles still receive strands.
+        let expected = area * density;
+        let mut count = expected.floor() as usize;
+        if rng.gen::<f32>() < expected.fract() {
+            count += 1;
+        }
+
+        for _ in 0..count {
+            let sample = triangle.sample_point(rng);
+            roots.push((sample.point, sample.normal));
+        }
+    }
+    roots
+}
+
+fn grow_strand(root: Vec3, normal: Vec3, params: &GroomParams, rng: &mut impl Rng) -> Strand {
+    let direction =
+        (normal + params.randomness * random_in_unit_sphere_with(rng)).normalize_or_zero();
+    let direction = if direction == Vec3::ZERO {
+        normal
+    } else {
+        direction
+    };
+    let length = params.length * (1.0 + params.length_variation * rng.gen_range(-1.0..=1.0_f32));
+
+    let segments = usize::max(params.segments, 1);
+    let points = (0..=segments)
+        .map(|i| {
+            let fraction = i as f32 / segments as f32;
+            root + direction * length * fraction
+                + vec3(0.0, -params.gravity * length * fraction * fraction, 0.0)
+        })
+        .collect();
+
+    Strand { root, points }
+}
+
+/// The guide strands, bucketed by their roots into a uniform grid of cubic cells so that
+/// finding the nearest one only searches the cells around a point.
+struct GuideGrid<'a> {
+    guides: &'a [Strand],
+    cell_size: f32,
+    cells: AHashMap<IVec3, Vec<usize>>,
+    /// The smallest and largest occupied cells.
+    bounds: (IVec3, IVec3),
+}
+
+impl<'a> GuideGrid<'a> {
+    fn new(guides: &'a [Strand], cell_size: f32) -> GuideGrid<'a> {
+        let mut grid = GuideGrid {
+            guides,
+            cell_size,
+            cells: AHashMap::new(),
+            bounds: (IVec3::splat(i32::MAX), IVec3::splat(i32::MIN)),
+        };
+        for (i, guide) in guides.iter().enumerate() {
+            let cell = grid.cell(guide.root);
+            grid.cells.entry(cell).or_default().push(i);
+            grid.bounds = (grid.bounds.0.min(cell), grid.bounds.1.max(cell));
+        }
+        grid
+    }
+
+    fn cell(&self, point: Vec3) -> IVec3 {
+        (point / self.cell_size).floor().as_ivec3()
+    }
+
+    /// The guide whose root is nearest to `root`, or the first of them if several are.
+    fn nearest(&self, root: Vec3) -> Option<&'a Strand> {
+        if self.guides.is_empty() {
+            return None;
+        }
+        let center = self.cell(root);
+        // Past this many rings of cells around the center, every occupied cell is covered.
+        let last_ring = (center - self.bounds.0)
+            .abs()
+            .max((center - self.bounds.1).abs())
+            .max_element();
+
+        let mut best: Option<(f32, usize)> = None;
+        for ring in 0..=last_ring {
+            for x in -ring..=ring {
+                for y in -ring..=ring {
+                    for z in -ring..=ring {
+                        let offset = IVec3::new(x, y, z);
+                        if offset.abs().max_element() != ring {
+                            continue;
+                        }
+                        for &i in self.cells.get(&(center + offset)).into_iter().flatten() {
+                            let distance = self.guides[i].root.distance_squared(root);
+                            if best.is_none_or(|best| (distance, i) < best) {
+                                best = Some((distance, i));
+                            }
+                        }
+                    }
+                }
+            }
+            // Points in further rings are at least this far from the root.
+            let searched = ring as f32 * self.cell_size;
+            if best.is_some_and(|(distance, _)| distance <= searched * searched) {
+                break;
+            }
+        }
+        best.map(|(_, i)| &self.guides[i])
+    }
+}
+
+/// Pulls the `strand` towards the shape of the `guide`, more strongly towards the tip.
+fn clump(strand: &mut Strand, guide: &Strand, strength: f32) {
+    let num_points = strand.points.len();
+    for (i, point) in strand.points.iter_mut().enumerate() {
+        let fraction = i as f32 / (num_points - 1) as f32;
+        let target = guide.points[i];
+        *point = point.lerp(target, strength * fraction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::{GuideGrid, Strand};
+
+    #[test]
+    fn grid_finds_the_nearest_guide() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut point = || vec3(rng.gen(), rng.gen(), rng.gen()) * 10.0 - Vec3::splat(5.0);
+        let guides: Vec<Strand> = (0..50)
+            .map(|_| Strand {
+                root: point(),
+                points: Vec::new(),
+            })
+            .collect();
+        let grid = GuideGrid::new(&guides, 1.5);
+        for _ in 0..200 {
+            // Includes points well outside the grid.
+            let root = point() * 2.0;
+            let nearest = guides
+                .iter()
+                .min_by(|a, b| {
+                    a.root
+                        .distance_squared(root)
+                        .total_cmp(&b.root.distance_squared(root))
+                })
+                .unwrap();
+            assert_eq!(grid.nearest(root).unwrap().root, nearest.root);
+        }
+        assert!(GuideGrid::new(&[], 1.0).nearest(Vec3::ZERO).is_none());
+    }
+}
@@ -8,24 +8,75 @@ use glam::{vec3, Vec3};
 
 use crate::{
     aabb::Aabb,
+    bounding_sphere::BoundingSphere,
     bvh::BvhId,
-    hittable::{HitRecord, Hittable},
+    hittable::{HitRecord, Hittable, Volume},
     hrpp::Predictor,
-    ray::Ray,
+    ray::{Ray, RayMask},
 };
 
+/// Translates the wrapped hittable by a displacement.
+/// The displacement may change linearly over time, from `displacement_start` at `time_start`
+/// to `displacement_end` at `time_end`, giving the hittable motion blur.
 pub struct Translate {
     hittable: Arc<dyn Hittable>,
-    displacement: Vec3,
+    displacement_start: Vec3,
+    displacement_end: Vec3,
+    time_start: f32,
+    time_end: f32,
+    /// If the translation doesn't move, a sphere bounding the translated hittable, which
+    /// rays are tested against before being offset into it.
+    bounds: Option<BoundingSphere>,
 }
 
 impl Translate {
     pub fn new(hittable: Arc<dyn Hittable>, displacement: Vec3) -> Self {
+        Translate::moving(hittable, displacement, displacement, 0.0, 1.0)
+    }
+
+    /// Creates a translation which moves linearly from `displacement_start` at `time_start`
+    /// to `displacement_end` at `time_end`. As with `MovingSphere`, movement continues outside
+    /// those times. If `time_start` equals `time_end`, the translation holds still at
+    /// `displacement_start`.
+    pub fn moving(
+        hittable: Arc<dyn Hittable>,
+        displacement_start: Vec3,
+        displacement_end: Vec3,
+        time_start: f32,
+        time_end: f32,
+    ) -> Self {
+        let displacement_end = if time_start == time_end {
+            displacement_start
+        } else {
+            displacement_end
+        };
+        let bounds = if displacement_start == displacement_end {
+            hittable
+                .bounding_sphere(time_start, time_end)
+                .map(|sphere| {
+                    BoundingSphere::new(sphere.center + displacement_start, sphere.radius)
+                })
+        } else {
+            None
+        };
         Translate {
             hittable,
-            displacement,
+            displacement_start,
+            displacement_end,
+            time_start,
+            time_end,
+            bounds,
         }
     }
+
+    fn displacement(&self, time: f32) -> Vec3 {
+        if self.displacement_start == self.displacement_end {
+            return self.displacement_start;
+        }
+        self.displacement_start
+            + ((time - self.time_start) / (self.time_end - self.time_start))
+                * (self.displacement_end - self.displacement_start)
+    }
 }
 
 impl Hittable for Translate {
@@ -36,37 +87,95 @@ impl Hittable for Translate {
         t_max: f32,
         predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Option<HitRecord> {
-        let offset_ray = Ray::new(ray.origin - self.displacement, ray.direction, ray.time);
+        if let Some(bounds) = &self.bounds {
+            if !bounds.hit(ray, t_min, t_max) {
+                return None;
+            }
+        }
+        let displacement = self.displacement(ray.time);
+        let offset_ray = Ray::new(ray.origin - displacement, ray.direction, ray.time)
+            .with_kind(ray.kind)
+            .with_groups(ray.groups);
         let mut hit_record = self.hittable.hit(&offset_ray, t_min, t_max, predictors)?;
-        hit_record.point += self.displacement;
+        hit_record.point += displacement;
         Some(hit_record)
     }
 
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        self.hittable
+            .volumes_containing(point - self.displacement(time), time, volumes);
+    }
+
     fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<crate::aabb::Aabb> {
         let bbox = self.hittable.bounding_box(time_0, time_1)?;
 
-        Some(Aabb::new(
-            *bbox.min() + self.displacement,
-            *bbox.max() + self.displacement,
-        ))
+        // The displacement is linear in time, so the boxes at either end of the interval
+        // bound the full range of motion.
+        let start = self.displacement(time_0);
+        let end = self.displacement(time_1);
+        Aabb::union(
+            &Some(Aabb::new(*bbox.min() + start, *bbox.max() + start)),
+            &Some(Aabb::new(*bbox.min() + end, *bbox.max() + end)),
+        )
+    }
+
+    fn bounding_sphere(&self, time_0: f32, time_1: f32) -> Option<BoundingSphere> {
+        let sphere = self.hittable.bounding_sphere(time_0, time_1)?;
+        let start = BoundingSphere::new(sphere.center + self.displacement(time_0), sphere.radius);
+        let end = BoundingSphere::new(sphere.center + self.displacement(time_1), sphere.radius);
+        Some(start.union(&end))
+    }
+
+    fn mask(&self) -> RayMask {
+        self.hittable.mask()
     }
 }
 
+/// Rotates the wrapped hittable about the Y axis.
+/// The angle may change linearly over time, from `degrees_start` at `time_start`
+/// to `degrees_end` at `time_end`, giving the hittable motion blur.
 pub struct RotateY {
     hittable: Arc<dyn Hittable>,
+    degrees_start: f32,
+    degrees_end: f32,
+    time_start: f32,
+    time_end: f32,
+    /// Sine and cosine of `degrees_start`, cached for the common static case.
     sin_theta: f32,
     cos_theta: f32,
     bbox: Option<Aabb>,
+    /// A sphere bounding the rotated hittable, which rays are tested against before
+    /// being rotated into it.
+    bounds: Option<BoundingSphere>,
 }
 
 impl RotateY {
     pub fn new(hittable: Arc<dyn Hittable>, degrees: f32) -> Self {
-        let radians = f32::to_radians(degrees);
+        RotateY::moving(hittable, degrees, degrees, 0.0, 1.0)
+    }
+
+    /// Creates a rotation which turns linearly from `degrees_start` at `time_start`
+    /// to `degrees_end` at `time_end`. As with `MovingSphere`, movement continues outside
+    /// those times. If `time_start` equals `time_end`, the rotation holds still at
+    /// `degrees_start`.
+    pub fn moving(
+        hittable: Arc<dyn Hittable>,
+        degrees_start: f32,
+        degrees_end: f32,
+        time_start: f32,
+        time_end: f32,
+    ) -> Self {
+        let degrees_end = if time_start == time_end {
+            degrees_start
+        } else {
+            degrees_end
+        };
+        let radians = f32::to_radians(degrees_start);
 
         let sin_theta = f32::sin(radians);
         let cos_theta = f32::cos(radians);
 
-        let bbox = if let Some(bbox) = hittable.bounding_box(0.0, 1.0) {
+        let bbox = if let Some(bbox) = hittable.bounding_box(time_start, time_end) {
             let mut min = vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
             let mut max = vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
             for i in [0.0, 1.0] {
@@ -76,12 +185,20 @@ impl RotateY {
                         let y = j * bbox.max().y + (1.0 - j) * bbox.min().y;
                         let z = k * bbox.max().z + (1.0 - k) * bbox.min().z;
 
-                        let new_x = cos_theta * x + sin_theta * z;
-                        let new_z = sin_theta.neg() * x + cos_theta * z;
+                        let tester = if degrees_start == degrees_end {
+                            let new_x = cos_theta * x + sin_theta * z;
+                            let new_z = sin_theta.neg() * x + cos_theta * z;
+                            vec3(new_x, y, new_z)
+                        } else {
+                            // The corner sweeps around the Y axis over time; bound the full
+                            // circle it could trace rather than the arc it actually does.
+                            let radius = f32::sqrt(x * x + z * z);
+                            min.x = f32::min(min.x, -radius);
+                            min.z = f32::min(min.z, -radius);
+                            vec3(radius, y, radius)
+                        };
 
-                        let tester = vec3(new_x, y, new_z);
-
-                        for c in 0..2 {
+                        for c in 0..3 {
                             min[c] = f32::min(min[c], tester[c]);
                             max[c] = f32::max(max[c], tester[c]);
                         }
@@ -93,19 +210,49 @@ impl RotateY {
             None
         };
 
+        let bounds = hittable
+            .bounding_sphere(time_start, time_end)
+            .map(|sphere| {
+                let center = sphere.center;
+                if degrees_start == degrees_end {
+                    // Rotating the center the other way to how rays are rotated into the object.
+                    let center = RotateY::get_rotated_dvec(&center, -sin_theta, cos_theta);
+                    BoundingSphere::new(center, sphere.radius)
+                } else {
+                    // The center sweeps around the Y axis over time; bound the full circle.
+                    let sweep = vec3(center.x, 0.0, center.z).length();
+                    BoundingSphere::new(vec3(0.0, center.y, 0.0), sphere.radius + sweep)
+                }
+            });
+
         RotateY {
             hittable,
+            degrees_start,
+            degrees_end,
+            time_start,
+            time_end,
             sin_theta,
             cos_theta,
             bbox,
+            bounds,
+        }
+    }
+
+    fn sin_cos(&self, time: f32) -> (f32, f32) {
+        if self.degrees_start == self.degrees_end {
+            return (self.sin_theta, self.cos_theta);
         }
+        let degrees = self.degrees_start
+            + ((time - self.time_start) / (self.time_end - self.time_start))
+                * (self.degrees_end - self.degrees_start);
+        f32::sin_cos(f32::to_radians(degrees))
     }
 
-    fn get_rotated_dvec(&self, vec: &Vec3) -> Vec3 {
+    fn get_rotated_dvec(vec: &Vec3, sin_theta: f32, cos_theta: f32) -> Vec3 {
         Vec3::new(
-            self.cos_theta * vec[0] - self.sin_theta * vec[2],
+            cos_theta * vec[0] - sin_theta * vec[2],
             vec[1],
-            self.sin_theta * vec[0] + self.cos_theta * vec[2],
+            sin_theta * vec[0] + cos_theta * vec[2],
         )
     }
 }
@@ -118,22 +265,30 @@ impl Hittable for RotateY {
         t_max: f32,
         predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Option<crate::hittable::HitRecord> {
-        let origin = self.get_rotated_dvec(&ray.origin);
-        let direction = self.get_rotated_dvec(&ray.direction);
+        if let Some(bounds) = &self.bounds {
+            if !bounds.hit(ray, t_min, t_max) {
+                return None;
+            }
+        }
+        let (sin_theta, cos_theta) = self.sin_cos(ray.time);
+        let origin = RotateY::get_rotated_dvec(&ray.origin, sin_theta, cos_theta);
+        let direction = RotateY::get_rotated_dvec(&ray.direction, sin_theta, cos_theta);
 
-        let ray_rotated = Ray::new(origin, direction, ray.time);
+        let ray_rotated = Ray::new(origin, direction, ray.time)
+            .with_kind(ray.kind)
+            .with_groups(ray.groups);
 
         let mut hit_record = self.hittable.hit(&ray_rotated, t_min, t_max, predictors)?;
 
         let point = Vec3::new(
-            self.cos_theta * hit_record.point[0] + self.sin_theta * hit_record.point[2],
+            cos_theta * hit_record.point[0] + sin_theta * hit_record.point[2],
             hit_record.point[1],
-            -self.sin_theta * hit_record.point[0] + self.cos_theta * hit_record.point[2],
+            -sin_theta * hit_record.point[0] + cos_theta * hit_record.point[2],
         );
         let normal = Vec3::new(
-            self.cos_theta * hit_record.normal[0] + self.sin_theta * hit_record.normal[2],
+            cos_theta * hit_record.normal[0] + sin_theta * hit_record.normal[2],
             hit_record.normal[1],
-            -self.sin_theta * hit_record.normal[0] + self.cos_theta * hit_record.normal[2],
+            -sin_theta * hit_record.normal[0] + cos_theta * hit_record.normal[2],
         );
 
         hit_record.point = point;
@@ -145,4 +300,110 @@ impl Hittable for RotateY {
     fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<Aabb> {
         self.bbox
     }
+
+    fn bounding_sphere(&self, _time_0: f32, _time_1: f32) -> Option<BoundingSphere> {
+        self.bounds
+    }
+
+    fn mask(&self) -> RayMask {
+        self.hittable.mask()
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        let (sin_theta, cos_theta) = self.sin_cos(time);
+        let point = RotateY::get_rotated_dvec(&point, sin_theta, cos_theta);
+        self.hittable.volumes_containing(point, time, volumes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        bounding_sphere::BoundingSphere, geometry::sphere::Sphere, hittable::Hittable,
+        materials::lambertian::Lambertian, ray::Ray,
+    };
+
+    use super::{RotateY, Translate};
+
+    #[test]
+    fn moving_translate_follows_ray_time() {
+        let sphere = Arc::new(Sphere::new(
+            Vec3::ZERO,
+            1.0,
+            Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))),
+        ));
+        let moving = Translate::moving(sphere, Vec3::ZERO, vec3(10.0, 0.0, 0.0), 0.0, 1.0);
+
+        let ray_at_start = Ray::new(vec3(10.0, 0.0, -5.0), Vec3::Z, 0.0);
+        let ray_at_end = Ray::new(vec3(10.0, 0.0, -5.0), Vec3::Z, 1.0);
+        assert!(moving
+            .hit(&ray_at_start, 0.001, f32::INFINITY, &Arc::new(None))
+            .is_none());
+        let hit = moving
+            .hit(&ray_at_end, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+        assert!((hit.point - vec3(10.0, 0.0, -1.0)).length() < 1e-4);
+
+        let bbox = moving.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!(*bbox.min(), vec3(-1.0, -1.0, -1.0));
+        assert_eq!(*bbox.max(), vec3(11.0, 1.0, 1.0));
+        assert_eq!(
+            moving.bounding_sphere(0.0, 1.0),
+            Some(BoundingSphere::new(vec3(5.0, 0.0, 0.0), 6.0))
+        );
+    }
+
+    #[test]
+    fn moving_rotation_bounds_full_sweep() {
+        let sphere = Arc::new(Sphere::new(
+            vec3(5.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))),
+        ));
+        let rotating = RotateY::moving(sphere.clone(), 0.0, 180.0, 0.0, 1.0);
+
+        // At t = 1 the sphere has swung around to -X.
+        let ray = Ray::new(vec3(-5.0, 0.0, -5.0), Vec3::Z, 1.0);
+        let hit = rotating
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+        assert!((hit.point - vec3(-5.0, 0.0, -1.0)).length() < 1e-3);
+
+        let bbox = rotating.bounding_box(0.0, 1.0).unwrap();
+        assert!(bbox.min().x <= -6.0 && bbox.max().x >= 6.0);
+        assert!(bbox.min().z <= -6.0 && bbox.max().z >= 6.0);
+        let bounds = rotating.bounding_sphere(0.0, 1.0).unwrap();
+        assert!(bounds.center.length() < 1e-6 && bounds.radius >= 6.0);
+
+        // Without motion, the sphere turns with the object.
+        let rotated = RotateY::new(sphere, 90.0);
+        let bbox = rotated.bounding_box(0.0, 1.0).unwrap();
+        let bounds = rotated.bounding_sphere(0.0, 1.0).unwrap();
+        assert!(bounds.center.distance((*bbox.min() + *bbox.max()) / 2.0) < 1e-4);
+        assert_eq!(bounds.radius, 1.0);
+    }
+
+    #[test]
+    fn instants_hold_the_start_transform() {
+        let sphere = Arc::new(Sphere::new(
+            vec3(5.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))),
+        ));
+        let translated =
+            Translate::moving(sphere.clone(), Vec3::ZERO, vec3(10.0, 0.0, 0.0), 0.5, 0.5);
+        let rotated = RotateY::moving(sphere, 0.0, 180.0, 0.5, 0.5);
+
+        let ray = Ray::new(vec3(5.0, 0.0, -5.0), Vec3::Z, 0.5);
+        for instance in [&translated as &dyn Hittable, &rotated] {
+            let hit = instance
+                .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+                .unwrap();
+            assert!((hit.point - vec3(5.0, 0.0, -1.0)).length() < 1e-3);
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable, Volume},
+    hrpp::Predictor,
+    materials::material::Material,
+    ray::Ray,
+};
+
+/// Replaces the material of everything in the wrapped hittable, except for lights.
+/// For example, overriding a scene with a gray `Lambertian` gives a "clay" render for
+/// evaluating lighting and modeling independently of shading.
+pub struct MaterialOverride {
+    hittable: Arc<dyn Hittable>,
+    material: Arc<dyn Material>,
+}
+
+impl MaterialOverride {
+    pub fn new(hittable: Arc<dyn Hittable>, material: Arc<dyn Material>) -> MaterialOverride {
+        MaterialOverride { hittable, material }
+    }
+}
+
+impl Hittable for MaterialOverride {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        let mut hit_record = self.hittable.hit(ray, t_min, t_max, predictors)?;
+        if !hit_record.material.is_emissive() {
+            hit_record.material = self.material.clone();
+        }
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
+        self.hittable.bounding_box(time_0, time_1)
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        // Media and refractive surfaces alike take on the overriding material.
+        if self.material.is_refractive() {
+            let mut overridden = Vec::new();
+            self.hittable
+                .volumes_containing(point, time, &mut overridden);
+            volumes.extend(overridden.iter().map(|_| Volume::Refractive));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Loading triangle meshes from OBJ files.
+
+use std::{fmt, path::Path, sync::Arc};
+
+use glam::{vec3, Vec3};
+use tobj::{LoadError, LoadOptions};
+
+use crate::{
+    bvh::{Bvh, BvhBuild},
+    hittable::{Hittable, HittableList},
+    materials::material::Material,
+};
+
+use super::{
+    decimate::{self, DecimationTarget},
+    normals,
+    triangle::Tri,
+};
+
+/// Options for processing meshes as they're loaded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MeshImport {
+    /// If present, the mesh is simplified to meet the target.
+    pub decimation: Option<DecimationTarget>,
+    /// If present, normals are generated for meshes without them, with this smoothing angle.
+    /// Otherwise, meshes without normals are flat shaded.
+    pub smoothing_angle: Option<f32>,
+    /// How the BVH over the mesh's triangles is built.
+    pub bvh_build: BvhBuild,
+}
+
+/// The triangles of a mesh, the normal at each of their vertices if it's smooth shaded,
+/// and the color at each of their vertices if it has vertex colors.
+pub struct Mesh {
+    pub triangles: Vec<[Vec3; 3]>,
+    pub normals: Option<Vec<[Vec3; 3]>>,
+    pub colors: Option<Vec<[Vec3; 3]>>,
+}
+
+impl Mesh {
+    /// Loads the mesh in the OBJ file at `path` and builds a BVH over its triangles,
+    /// returning a single hittable ready to add to a scene.
+    pub fn from_obj<P>(path: P, material: Arc<dyn Material>) -> Result<Arc<dyn Hittable>, LoadError>
+    where
+        P: AsRef<Path> + fmt::Debug,
+    {
+        Mesh::from_obj_with_import(path, material, MeshImport::default())
+    }
+
+    /// As `from_obj()`, processing the mesh according to `import` as it's loaded.
+    pub fn from_obj_with_import<P>(
+        path: P,
+        material: Arc<dyn Material>,
+        import: MeshImport,
+    ) -> Result<Arc<dyn Hittable>, LoadError>
+    where
+        P: AsRef<Path> + fmt::Debug,
+    {
+        let tris = Mesh::load(path, import)?.to_tris(material);
+        Ok(Arc::new(Bvh::build(tris, 0.0, 1.0, import.bvh_build)))
+    }
+
+    /// Loads the first model in the OBJ file at `path`, using the file's normals and
+    /// vertex colors, given as `v x y z r g b`, if it has them and the mesh isn't decimated.
+    pub fn load<P>(path: P, import: MeshImport) -> Result<Mesh, LoadError>
+    where
+        P: AsRef<Path> + fmt::Debug,
+    {
+        let load_options = LoadOptions {
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, _) = tobj::load_obj(path, &load_options)?;
+
+        let mesh = &models.first().ok_or(LoadError::GenericFailure)?.mesh;
+        let indices = &mesh.indices;
+
+        let vertices: Vec<Vec3> = indices
+            .iter()
+            .map(|i| {
+                let x = mesh.positions[*i as usize * 3];
+                let y = mesh.positions[*i as usize * 3 + 1];
+                let z = mesh.positions[*i as usize * 3 + 2];
+                vec3(x, y, z)
+            })
+            .collect();
+
+        let triangles: Vec<[Vec3; 3]> = vertices
+            .chunks(3)
+            .map(|vertex_group| [vertex_group[0], vertex_group[1], vertex_group[2]])
+            .collect();
+
+        let file_normals = if !mesh.normals.is_empty() && mesh.normal_indices.len() == indices.len()
+        {
+            let normals: Vec<Vec3> = mesh
+                .normal_indices
+                .iter()
+                .map(|i| {
+                    let x = mesh.normals[*i as usize * 3];
+                    let y = mesh.normals[*i as usize * 3 + 1];
+                    let z = mesh.normals[*i as usize * 3 + 2];
+                    vec3(x, y, z).normalize()
+                })
+                .collect();
+            Some(
+                normals
+                    .chunks(3)
+                    .map(|normal_group| [normal_group[0], normal_group[1], normal_group[2]])
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        let colors = if mesh.vertex_color.len() == mesh.positions.len() {
+            let colors: Vec<Vec3> = indices
+                .iter()
+                .map(|i| {
+                    let r = mesh.vertex_color[*i as usize * 3];
+                    let g = mesh.vertex_color[*i as usize * 3 + 1];
+                    let b = mesh.vertex_color[*i as usize * 3 + 2];
+                    vec3(r, g, b)
+                })
+                .collect();
+            Some(
+                colors
+                    .chunks(3)
+                    .map(|color_group| [color_group[0], color_group[1], color_group[2]])
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        // Decimation moves vertices, so any normals or colors from the file no longer apply.
+        let (triangles, file_normals, colors) = match import.decimation {
+            Some(target) => {
+                let decimated = decimate::decimate(&triangles, target);
+                eprintln!(
+                    "Decimated {} triangles to {}",
+                    triangles.len(),
+                    decimated.len()
+                );
+                (decimated, None, None)
+            }
+            None => (triangles, file_normals, colors),
+        };
+
+        let normals = match (file_normals, import.smoothing_angle) {
+            (Some(normals), _) => Some(normals),
+            (None, Some(angle)) => Some(normals::smooth_normals(&triangles, angle)),
+            (None, None) => None,
+        };
+
+        Ok(Mesh {
+            triangles,
+            normals,
+            colors,
+        })
+    }
+
+    /// Creates a Tri for each of the triangles, smooth shaded if there are normals, and
+    /// carrying their vertex colors, if any, for a `VertexColor` texture.
+    pub fn to_tris(&self, material: Arc<dyn Material>) -> HittableList {
+        let mut tris = HittableList::new();
+        for (i, [p0, p1, p2]) in self.triangles.iter().enumerate() {
+            let mut tri = match &self.normals {
+                Some(normals) => Tri::with_normals(*p0, *p1, *p2, normals[i], material.clone()),
+                None => Tri::new(*p0, *p1, *p2, material.clone()),
+            };
+            if let Some(colors) = &self.colors {
+                tri = tri.with_colors(colors[i]);
+            }
+            tris.add(Arc::new(tri));
+        }
+        tris
+    }
+}
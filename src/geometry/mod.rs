@@ -1,6 +1,20 @@
 pub mod cube;
+pub mod curve;
+pub mod decimate;
+pub mod disk;
+pub mod groom;
 pub mod instance;
+pub mod material_override;
+pub mod mesh;
 pub mod moving_sphere;
+pub mod normals;
+pub mod ocean;
+pub mod particles;
+pub mod quad;
 pub mod rectangle;
+pub mod sampleable;
 pub mod sphere;
 pub mod triangle;
+pub mod visibility;
+pub mod vox;
+pub mod voxel_grid;
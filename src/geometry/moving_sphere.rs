@@ -5,10 +5,12 @@ use glam::{vec3, Vec3};
 
 use crate::{
     aabb::Aabb,
+    bounding_sphere::BoundingSphere,
     bvh::BvhId,
-    hittable::{HitRecord, Hittable},
+    hittable::{HitRecord, Hittable, Volume},
     hrpp::Predictor,
     materials::material::Material,
+    precision::{to_real, Real},
 };
 
 use super::sphere::Sphere;
@@ -45,6 +47,10 @@ impl MovingSphere {
     }
 
     fn center(&self, time: f32) -> Vec3 {
+        // With no time between its two points, the sphere has no velocity to move it.
+        if self.time_end == self.time_start {
+            return self.center_start;
+        }
         self.center_start
             + ((time - self.time_start) / (self.time_end - self.time_start))
                 * (self.center_end - self.center_start)
@@ -59,25 +65,26 @@ impl Hittable for MovingSphere {
         t_max: f32,
         _predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Option<HitRecord> {
-        let oc = ray.origin - self.center(ray.time);
-        let a = ray.direction.length_squared();
-        let half_b = oc.dot(ray.direction);
-        let c = oc.length_squared() - self.radius.powi(2);
+        let direction = to_real(ray.direction);
+        let oc = to_real(ray.origin) - to_real(self.center(ray.time));
+        let a = direction.length_squared();
+        let half_b = oc.dot(direction);
+        let c = oc.length_squared() - (self.radius as Real).powi(2);
         let discriminant = half_b.powi(2) - a * c;
         if discriminant.is_sign_negative() {
             return None;
         }
-        let sqrt_discriminant = f32::sqrt(discriminant);
+        let sqrt_discriminant = discriminant.sqrt();
         let mut root = (-half_b - sqrt_discriminant) / a;
-        if root < t_min || t_max < root {
+        if root < t_min as Real || (t_max as Real) < root {
             root = (-half_b + sqrt_discriminant) / a;
-            if root < t_min || t_max < root {
+            if root < t_min as Real || (t_max as Real) < root {
                 return None;
             }
         }
 
-        let t = root;
-        let point = ray.at(root);
+        let t = root as f32;
+        let point = ray.at(t);
         let normal = (point - self.center(ray.time)) / self.radius;
         let (u, v) = Sphere::get_uv(&normal);
         Some(HitRecord::new(&ray, normal, t, u, v, self.material.clone()))
@@ -88,7 +95,19 @@ impl Hittable for MovingSphere {
         // a parametric implementation wouldn't necessarily have its extent bounded like this.
         let rad = vec3(self.radius, self.radius, self.radius);
         let start_box = Aabb::new(self.center(time_0) - rad, self.center(time_0) + rad);
-        let end_box = Aabb::new(self.center(time_0) - rad, self.center(time_1) + rad);
+        let end_box = Aabb::new(self.center(time_1) - rad, self.center(time_1) + rad);
         Aabb::union(&Some(start_box), &Some(end_box))
     }
+
+    fn bounding_sphere(&self, time_0: f32, time_1: f32) -> Option<BoundingSphere> {
+        let start = BoundingSphere::new(self.center(time_0), self.radius);
+        let end = BoundingSphere::new(self.center(time_1), self.radius);
+        Some(start.union(&end))
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        if self.material.is_refractive() && point.distance(self.center(time)) < self.radius {
+            volumes.push(Volume::Refractive);
+        }
+    }
 }
@@ -0,0 +1,93 @@
+//! Generating smooth vertex normals for meshes which lack them.
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+/// Returns a normal for each vertex of each of the `triangles`, found by averaging the
+/// normals of the triangles sharing that vertex's position.
+///
+/// Only triangles whose normals are within `smoothing_angle` degrees of each other are
+/// averaged, so sharper edges remain hard. An angle of 0 gives flat shading, and 180
+/// smooths everything.
+///
+/// Triangles are weighted by their area, so slivers don't skew the result.
+pub fn smooth_normals(triangles: &[[Vec3; 3]], smoothing_angle: f32) -> Vec<[Vec3; 3]> {
+    // Area-weighted (unnormalized) and unit face normals.
+    let weighted_normals: Vec<Vec3> = triangles
+        .iter()
+        .map(|[p0, p1, p2]| (*p1 - *p0).cross(*p2 - *p0))
+        .collect();
+    let unit_normals: Vec<Vec3> = weighted_normals
+        .iter()
+        .map(|normal| normal.normalize_or_zero())
+        .collect();
+
+    // Triangles are often stored as a soup, so vertices are shared by exact position.
+    let key = |p: Vec3| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+    let mut incident: AHashMap<[u32; 3], Vec<usize>> = AHashMap::new();
+    for (triangle_idx, triangle) in triangles.iter().enumerate() {
+        for vertex in triangle {
+            incident.entry(key(*vertex)).or_default().push(triangle_idx);
+        }
+    }
+
+    let cos_threshold = f32::cos(smoothing_angle.clamp(0.0, 180.0).to_radians());
+    triangles
+        .iter()
+        .enumerate()
+        .map(|(triangle_idx, triangle)| {
+            let face_normal = unit_normals[triangle_idx];
+            triangle.map(|vertex| {
+                let normal: Vec3 = incident[&key(vertex)]
+                    .iter()
+                    .filter(|other| {
+                        **other == triangle_idx
+                            || unit_normals[**other].dot(face_normal) >= cos_threshold
+                    })
+                    .map(|other| weighted_normals[*other])
+                    .sum();
+                let normal = normal.normalize_or_zero();
+                if normal == Vec3::ZERO {
+                    face_normal
+                } else {
+                    normal
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::smooth_normals;
+
+    #[test]
+    fn smoothing_angle_preserves_hard_edges() {
+        // Two triangles meeting at a right angle along the edge from (0, 0, 0) to (0, 0, 1).
+        let triangles = [
+            [
+                vec3(0.0, 0.0, 0.0),
+                vec3(0.0, 0.0, 1.0),
+                vec3(1.0, 0.0, 0.0),
+            ],
+            [
+                vec3(0.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(0.0, 0.0, 1.0),
+            ],
+        ];
+
+        let hard = smooth_normals(&triangles, 30.0);
+        assert!((hard[0][0] - Vec3::Y).length() < 1e-5);
+        assert!((hard[1][0] - Vec3::X).length() < 1e-5);
+
+        let smooth = smooth_normals(&triangles, 120.0);
+        let expected = vec3(1.0, 1.0, 0.0).normalize();
+        assert!((smooth[0][0] - expected).length() < 1e-5);
+        assert!((smooth[1][0] - expected).length() < 1e-5);
+        // The vertices which aren't shared keep their face's normal.
+        assert!((smooth[0][2] - Vec3::Y).length() < 1e-5);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+use ahash::AHashMap;
+use glam::{vec2, vec3, Vec2, Vec3};
+
+use crate::{
+    aabb::Aabb,
+    bvh::{Bvh, BvhId},
+    hittable::{HitRecord, Hittable, HittableList},
+    hrpp::Predictor,
+    materials::material::Material,
+    ray::Ray,
+};
+
+use super::triangle::Tri;
+
+/// Gravitational acceleration, used to derive each wave's speed from its wavelength.
+const GRAVITY: f32 = 9.81;
+
+/// A single trochoidal (Gerstner) wave.
+#[derive(Clone, Copy, Debug)]
+pub struct GerstnerWave {
+    /// Direction of travel in the XZ plane. Need not be normalized.
+    pub direction: Vec2,
+    /// Height of the wave crest above the rest plane.
+    pub amplitude: f32,
+    /// Distance between successive crests.
+    pub wavelength: f32,
+    /// Sharpness of the crests in \[0, 1\]. 0 yields a sine wave; 1 yields cusped crests.
+    pub steepness: f32,
+}
+
+impl GerstnerWave {
+    pub fn new(direction: Vec2, amplitude: f32, wavelength: f32, steepness: f32) -> GerstnerWave {
+        GerstnerWave {
+            direction,
+            amplitude,
+            wavelength,
+            steepness,
+        }
+    }
+
+    /// Returns a set of `count` waves travelling roughly along `wind_direction`, with
+    /// wavelengths and amplitudes that shrink geometrically from the given largest wave.
+    /// This is a cheap stand-in for sampling a full ocean spectrum.
+    pub fn from_wind(
+        wind_direction: Vec2,
+        largest_wavelength: f32,
+        largest_amplitude: f32,
+        count: usize,
+    ) -> Vec<GerstnerWave> {
+        let wind_angle = f32::atan2(wind_direction.y, wind_direction.x);
+        (0..count)
+            .map(|i| {
+                // Fan the waves out around the wind direction so they don't all line up.
+                let spread = if i % 2 == 0 { 1.0 } else { -1.0 } * 0.35 * (i as f32).sqrt();
+                let angle = wind_angle + spread;
+                let falloff = 0.6_f32.powi(i as i32);
+                GerstnerWave::new(
+                    vec2(f32::cos(angle), f32::sin(angle)),
+                    largest_amplitude * falloff,
+                    largest_wavelength * falloff,
+                    0.5,
+                )
+            })
+            .collect()
+    }
+
+    /// Displacement of the rest position `position` (in the XZ plane) at `time`.
+    fn displacement(&self, position: Vec2, time: f32, num_waves: usize) -> Vec3 {
+        let direction = self.direction.normalize();
+        let k = 2.0 * PI / self.wavelength;
+        let omega = f32::sqrt(GRAVITY * k);
+        let phase = k * direction.dot(position) - omega * time;
+        // Divide the steepness among the waves so the sum can't loop over itself.
+        // This is Q * amplitude * cos(phase) with Q = steepness / (k * amplitude * n),
+        // written so that flat (zero amplitude) waves don't divide by zero.
+        let horizontal = self.steepness / (k * num_waves as f32) * f32::cos(phase);
+        vec3(
+            horizontal * direction.x,
+            self.amplitude * f32::sin(phase),
+            horizontal * direction.y,
+        )
+    }
+}
+
+/// A patch of ocean surface, displaced by a sum of Gerstner waves evaluated at a fixed time.
+/// The surface is tessellated into triangles and stored in its own BVH.
+///
+/// Pair with a `Dialectric` material (index of refraction 1.33) for water.
+pub struct Ocean {
+    surface: Bvh,
+}
+
+impl Ocean {
+    /// Creates a square patch of ocean.
+    ///
+    /// * `center` - Center of the patch; the rest plane of the water lies at `center.y`.
+    /// * `size` - Width and depth of the patch.
+    /// * `resolution` - Number of grid cells along each side of the patch.
+    /// * `waves` - The waves which displace the surface.
+    /// * `time` - The time at which the waves are evaluated.
+    pub fn new(
+        center: Vec3,
+        size: f32,
+        resolution: usize,
+        waves: &[GerstnerWave],
+        time: f32,
+        material: Arc<dyn Material>,
+    ) -> Ocean {
+        let resolution = usize::max(resolution, 1);
+        let grid = Ocean::surface_grid(center, size, resolution, waves, time);
+
+        let mut triangles = HittableList::new();
+        for i in 0..resolution {
+            for j in 0..resolution {
+                let p00 = grid[i][j];
+                let p10 = grid[i + 1][j];
+                let p01 = grid[i][j + 1];
+                let p11 = grid[i + 1][j + 1];
+                // Wound so that the geometric normals face +Y.
+                triangles.add(Arc::new(Tri::new(p00, p01, p10, material.clone())));
+                triangles.add(Arc::new(Tri::new(p10, p01, p11, material.clone())));
+            }
+        }
+
+        Ocean {
+            surface: Bvh::new(triangles, time, time),
+        }
+    }
+
+    /// The displaced vertices of the patch, indexed by their grid cell along X and then Z.
+    fn surface_grid(
+        center: Vec3,
+        size: f32,
+        resolution: usize,
+        waves: &[GerstnerWave],
+        time: f32,
+    ) -> Vec<Vec<Vec3>> {
+        let cell_size = size / resolution as f32;
+        let corner = vec2(center.x, center.z) - Vec2::splat(size / 2.0);
+
+        let vertex = |i: usize, j: usize| -> Vec3 {
+            let rest = corner + vec2(i as f32, j as f32) * cell_size;
+            let displacement: Vec3 = waves
+                .iter()
+                .map(|wave| wave.displacement(rest, time, waves.len()))
+                .sum();
+            vec3(rest.x, center.y, rest.y) + displacement
+        };
+
+        (0..=resolution)
+            .map(|i| (0..=resolution).map(|j| vertex(i, j)).collect())
+            .collect()
+    }
+}
+
+impl Hittable for Ocean {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        self.surface.hit(ray, t_min, t_max, predictors)
+    }
+
+    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
+        self.surface.bounding_box(time_0, time_1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec2, vec3, Vec3};
+
+    use crate::{hittable::Hittable, materials::lambertian::Lambertian, ray::Ray};
+
+    use super::{GerstnerWave, Ocean};
+
+    fn waves() -> Vec<GerstnerWave> {
+        vec![
+            GerstnerWave::new(vec2(1.0, 0.0), 0.0, 4.0, 0.5),
+            GerstnerWave::new(vec2(0.0, 1.0), 0.3, 6.0, 0.5),
+        ]
+    }
+
+    #[test]
+    fn flat_waves_keep_the_surface_finite() {
+        let grid = Ocean::surface_grid(Vec3::ZERO, 10.0, 8, &waves(), 1.5);
+        assert!(grid.iter().flatten().all(|vertex| vertex.is_finite()));
+    }
+
+    #[test]
+    fn hits_the_displaced_surface() {
+        let ocean = Ocean::new(
+            Vec3::ZERO,
+            10.0,
+            32,
+            &waves(),
+            1.5,
+            Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))),
+        );
+        let ray = Ray::new(vec3(0.3, 10.0, 0.7), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = ocean
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+
+        assert!(hit.point.is_finite());
+        assert!(hit.point.y.abs() <= 0.3 + 1e-4);
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+use crate::{
+    aabb::Aabb,
+    bvh::{Bvh, BvhId},
+    hittable::{HitRecord, Hittable, HittableList},
+    hrpp::Predictor,
+    materials::material::Material,
+    ray::Ray,
+};
+
+use super::{moving_sphere::MovingSphere, sphere::Sphere};
+
+/// A single particle, e.g. from the output of a simulation.
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    /// Position of the particle when the shutter opens.
+    pub position: Vec3,
+    pub radius: f32,
+    pub color: Vec3,
+    /// If present, the particle moves with this velocity while the shutter is open,
+    /// producing motion blur.
+    pub velocity: Option<Vec3>,
+}
+
+impl Particle {
+    pub fn new(position: Vec3, radius: f32, color: Vec3, velocity: Option<Vec3>) -> Particle {
+        Particle {
+            position,
+            radius,
+            color,
+            velocity,
+        }
+    }
+}
+
+/// A set of spherical particles, held in a dedicated BVH.
+pub struct ParticleSet {
+    particles: Bvh,
+}
+
+impl ParticleSet {
+    /// Creates a particle set.
+    ///
+    /// * `particles` - The particles in the set. Must not be empty.
+    /// * `time_0`, `time_1` - The shutter interval. Moving particles travel from their
+    ///   `position` at `time_0` along their `velocity` until `time_1`.
+    /// * `material` - Creates the material for a particle from its color; e.g. a
+    ///   `Lambertian` for spray, or a `DiffuseLight` for sparks.
+    pub fn new<F>(particles: &[Particle], time_0: f32, time_1: f32, material: F) -> ParticleSet
+    where
+        F: Fn(Vec3) -> Arc<dyn Material>,
+    {
+        let mut list = HittableList::new();
+        for particle in particles.iter() {
+            let particle_material = material(particle.color);
+            match particle.velocity {
+                // A zero-length shutter interval can't carry any motion.
+                Some(velocity) if time_1 > time_0 => list.add(Arc::new(MovingSphere::new(
+                    particle.position,
+                    particle.position + velocity * (time_1 - time_0),
+                    time_0,
+                    time_1,
+                    particle.radius,
+                    particle_material,
+                ))),
+                _ => list.add(Arc::new(Sphere::new(
+                    particle.position,
+                    particle.radius,
+                    particle_material,
+                ))),
+            }
+        }
+
+        ParticleSet {
+            particles: Bvh::new(list, time_0, time_1),
+        }
+    }
+}
+
+impl Hittable for ParticleSet {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        self.particles.hit(ray, t_min, t_max, predictors)
+    }
+
+    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
+        self.particles.bounding_box(time_0, time_1)
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::{vec3, Vec3};
+use rand::{Rng, RngCore};
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable},
+    hrpp::Predictor,
+    materials::material::Material,
+    precision,
+    ray::Ray,
+};
+
+use super::sampleable::{Sampleable, SurfacePoint};
+
+/// A parallelogram with a `corner` and the edges `u` and `v` leaving it, facing along
+/// `u x v`. Unlike the rectangles, it needn't be aligned with the axes.
+pub struct Quad {
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    /// The unit normal of the plane the quad lies in.
+    normal: Vec3,
+    /// `u x v` divided by its squared length, for finding the planar coordinates of hits.
+    w: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = u.cross(v);
+        Quad {
+            corner,
+            u,
+            v,
+            normal: n.normalize(),
+            w: n / n.length_squared(),
+            material,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        let denominator = self.normal.dot(ray.direction);
+        // Parallel to the plane.
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = self.normal.dot(self.corner - ray.origin) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // The coordinates of the hit along the edges, which are 0 to 1 within the quad.
+        let planar = ray.at(t) - self.corner;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(HitRecord::new(
+            ray,
+            self.normal,
+            t,
+            alpha,
+            beta,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<Aabb> {
+        let corners = [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ];
+        let min = corners.into_iter().reduce(Vec3::min).unwrap();
+        let max = corners.into_iter().reduce(Vec3::max).unwrap();
+        // Pad to avoid infinitely-thin boxes for axis-aligned quads.
+        let padding = |v: Vec3| {
+            vec3(
+                precision::bounds_padding(v.x),
+                precision::bounds_padding(v.y),
+                precision::bounds_padding(v.z),
+            )
+        };
+        Some(Aabb::new(min - padding(min), max + padding(max)))
+    }
+}
+
+impl Sampleable for Quad {
+    fn area(&self) -> f32 {
+        self.u.cross(self.v).length()
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        SurfacePoint {
+            point: self.corner + rng.gen::<f32>() * self.u + rng.gen::<f32>() * self.v,
+            normal: self.normal,
+        }
+    }
+}
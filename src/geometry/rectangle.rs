@@ -2,6 +2,7 @@ use std::sync::{Arc, Mutex};
 
 use ahash::AHashMap;
 use glam::{vec3, Vec3};
+use rand::{Rng, RngCore};
 
 use crate::{
     aabb::Aabb,
@@ -9,8 +10,11 @@ use crate::{
     hittable::{HitRecord, Hittable},
     hrpp::Predictor,
     materials::material::Material,
+    precision,
 };
 
+use super::sampleable::{Sampleable, SurfacePoint};
+
 pub struct XyRect {
     x0: f32,
     x1: f32,
@@ -65,14 +69,29 @@ impl Hittable for XyRect {
     }
 
     fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<crate::aabb::Aabb> {
-        // The bounding box cannot have an infinitely small side, so we add epsilon.
+        // The bounding box cannot have an infinitely small side, so we pad it.
         Some(Aabb::new(
-            vec3(self.x0, self.y0, self.z - f32::EPSILON),
-            vec3(self.x1, self.y1, self.z + f32::EPSILON),
+            vec3(self.x0, self.y0, self.z - precision::bounds_padding(self.z)),
+            vec3(self.x1, self.y1, self.z + precision::bounds_padding(self.z)),
         ))
     }
 }
 
+impl Sampleable for XyRect {
+    fn area(&self) -> f32 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        let a = rng.gen_range(self.x0..=self.x1);
+        let b = rng.gen_range(self.y0..=self.y1);
+        SurfacePoint {
+            point: vec3(a, b, self.z),
+            normal: Vec3::Z,
+        }
+    }
+}
+
 pub struct XzRect {
     x0: f32,
     x1: f32,
@@ -127,14 +146,29 @@ impl Hittable for XzRect {
     }
 
     fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<crate::aabb::Aabb> {
-        // The bounding box cannot have an infinitely small side, so we add epsilon.
+        // The bounding box cannot have an infinitely small side, so we pad it.
         Some(Aabb::new(
-            vec3(self.x0, self.y - f32::EPSILON, self.z0),
-            vec3(self.x1, self.y + f32::EPSILON, self.z1),
+            vec3(self.x0, self.y - precision::bounds_padding(self.y), self.z0),
+            vec3(self.x1, self.y + precision::bounds_padding(self.y), self.z1),
         ))
     }
 }
 
+impl Sampleable for XzRect {
+    fn area(&self) -> f32 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        let a = rng.gen_range(self.x0..=self.x1);
+        let b = rng.gen_range(self.z0..=self.z1);
+        SurfacePoint {
+            point: vec3(a, self.y, b),
+            normal: Vec3::Y,
+        }
+    }
+}
+
 pub struct YzRect {
     y0: f32,
     y1: f32,
@@ -189,10 +223,25 @@ impl Hittable for YzRect {
     }
 
     fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<crate::aabb::Aabb> {
-        // The bounding box cannot have an infinitely small side, so we add epsilon.
+        // The bounding box cannot have an infinitely small side, so we pad it.
         Some(Aabb::new(
-            vec3(self.x - f32::EPSILON, self.y0, self.z0),
-            vec3(self.x + f32::EPSILON, self.y1, self.z1),
+            vec3(self.x - precision::bounds_padding(self.x), self.y0, self.z0),
+            vec3(self.x + precision::bounds_padding(self.x), self.y1, self.z1),
         ))
     }
 }
+
+impl Sampleable for YzRect {
+    fn area(&self) -> f32 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        let a = rng.gen_range(self.y0..=self.y1);
+        let b = rng.gen_range(self.z0..=self.z1);
+        SurfacePoint {
+            point: vec3(self.x, a, b),
+            normal: Vec3::X,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Sampling points on the surfaces of primitives, uniformly by area.
+//!
+//! Lighting from an emitter's area, bidirectional path tracing and emitting photons all
+//! start from points chosen on a surface, and weight them by how likely they were to be
+//! chosen. Choosing uniformly by area makes that likelihood simply `1 / area()`.
+
+use glam::Vec3;
+use rand::RngCore;
+
+use crate::hittable::Hittable;
+
+/// A point on a surface, with the surface's outward normal there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfacePoint {
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+/// A surface which points can be chosen on.
+pub trait Sampleable: Send + Sync {
+    /// The area of the surface.
+    fn area(&self) -> f32;
+
+    /// Chooses a point on the surface uniformly by area, so with a probability density
+    /// of `1 / area()`.
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint;
+}
+
+/// An object with an emissive material which points can be chosen on, so that light
+/// (e.g. photons) can be sent out from it. See `HittableList::add_light()`.
+pub trait Light: Hittable + Sampleable {}
+
+impl<T: Hittable + Sampleable> Light for T {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        geometry::{
+            disk::Disk,
+            quad::Quad,
+            rectangle::{XyRect, XzRect, YzRect},
+            sphere::Sphere,
+            triangle::Tri,
+        },
+        hittable::Hittable,
+        materials::lambertian::Lambertian,
+        random,
+        ray::Ray,
+    };
+
+    use super::Sampleable;
+
+    /// Checks that the points sampled on the `surface` lie on it, facing outward.
+    fn assert_on_surface<S: Hittable + Sampleable>(surface: S) {
+        let predictors = Arc::new(None);
+        random::seeded(0, || {
+            for _ in 0..100 {
+                let sample = surface.sample_point(&mut random::rng());
+                assert!((sample.normal.length() - 1.0).abs() < 1e-4);
+                // Looking back at the point from just outside the surface finds it there.
+                let ray = Ray::new(sample.point + 0.01 * sample.normal, -sample.normal, 0.0);
+                let hit = surface
+                    .hit(&ray, 0.0, f32::INFINITY, &predictors)
+                    .expect("sampled point is not on the surface");
+                assert!((hit.t - 0.01).abs() < 1e-3);
+                assert!(hit.front_face);
+            }
+        });
+    }
+
+    #[test]
+    fn samples_lie_on_surfaces() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        assert_on_surface(Sphere::new(vec3(1.0, 2.0, 3.0), 2.0, material.clone()));
+        assert_on_surface(XyRect::new(0.0, 1.0, 2.0, 4.0, 1.0, material.clone()));
+        assert_on_surface(XzRect::new(0.0, 1.0, 2.0, 4.0, 1.0, material.clone()));
+        assert_on_surface(YzRect::new(0.0, 1.0, 2.0, 4.0, 1.0, material.clone()));
+        assert_on_surface(Tri::new(
+            Vec3::ZERO,
+            vec3(2.0, 0.0, 1.0),
+            vec3(0.0, 3.0, 1.0),
+            material.clone(),
+        ));
+        assert_on_surface(Quad::new(
+            vec3(1.0, 0.0, 0.0),
+            vec3(2.0, 0.0, 0.5),
+            vec3(0.0, 1.0, 1.0),
+            material.clone(),
+        ));
+        assert_on_surface(Disk::new(
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            1.5,
+            material,
+        ));
+    }
+
+    #[test]
+    fn areas_match_their_shapes() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let area = |surface: &dyn Sampleable| surface.area();
+        assert_eq!(
+            area(&XzRect::new(0.0, 2.0, 1.0, 4.0, 0.0, material.clone())),
+            6.0
+        );
+        assert_eq!(
+            area(&Tri::new(
+                Vec3::ZERO,
+                vec3(2.0, 0.0, 0.0),
+                vec3(0.0, 3.0, 0.0),
+                material.clone()
+            )),
+            3.0
+        );
+        assert_eq!(
+            area(&Quad::new(
+                Vec3::ZERO,
+                2.0 * Vec3::X,
+                3.0 * Vec3::Z,
+                material.clone()
+            )),
+            6.0
+        );
+        let disk = area(&Disk::new(Vec3::ZERO, Vec3::Y, 2.0, material.clone()));
+        assert!((disk - 4.0 * std::f32::consts::PI).abs() < 1e-4);
+        let sphere = area(&Sphere::new(Vec3::ZERO, 2.0, material));
+        assert!((sphere - 16.0 * std::f32::consts::PI).abs() < 1e-4);
+    }
+}
@@ -5,17 +5,22 @@ use std::{
 };
 
 use ahash::AHashMap;
-use glam::{vec3, DVec3, Vec3};
+use glam::{vec3, Vec3};
+use rand::{Rng, RngCore};
 
 use crate::{
     aabb::Aabb,
+    bounding_sphere::BoundingSphere,
     bvh::BvhId,
-    hittable::{HitRecord, Hittable},
+    hittable::{HitRecord, Hittable, Volume},
     hrpp::Predictor,
     materials::material::Material,
+    precision::{to_real, Real},
     ray::Ray,
 };
 
+use super::sampleable::{Sampleable, SurfacePoint};
+
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f32,
@@ -54,22 +59,10 @@ impl Hittable for Sphere {
         t_max: f32,
         _predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Option<HitRecord> {
-        let direction = DVec3::new(
-            ray.direction.x as f64,
-            ray.direction.y as f64,
-            ray.direction.z as f64,
-        );
-        let origin = DVec3::new(
-            ray.origin.x as f64,
-            ray.origin.y as f64,
-            ray.origin.z as f64,
-        );
-        let center = DVec3::new(
-            self.center.x as f64,
-            self.center.y as f64,
-            self.center.z as f64,
-        );
-        let radius = self.radius as f64;
+        let direction = to_real(ray.direction);
+        let origin = to_real(ray.origin);
+        let center = to_real(self.center);
+        let radius = self.radius as Real;
 
         let oc = origin - center;
         let a = direction.length_squared();
@@ -79,11 +72,11 @@ impl Hittable for Sphere {
         if discriminant.is_sign_negative() {
             return None;
         }
-        let sqrt_discriminant = f64::sqrt(discriminant);
+        let sqrt_discriminant = discriminant.sqrt();
         let mut root = (-half_b - sqrt_discriminant) / a;
-        if root < t_min as f64 || (t_max as f64) < root {
+        if root < t_min as Real || (t_max as Real) < root {
             root = (-half_b + sqrt_discriminant) / a;
-            if root < t_min as f64 || (t_max as f64) < root {
+            if root < t_min as Real || (t_max as Real) < root {
                 return None;
             }
         }
@@ -107,4 +100,32 @@ impl Hittable for Sphere {
         let bb = Aabb::new(self.center - rad, self.center + rad);
         Some(bb)
     }
+
+    fn bounding_sphere(&self, _time_0: f32, _time_1: f32) -> Option<BoundingSphere> {
+        Some(BoundingSphere::new(self.center, self.radius))
+    }
+
+    fn volumes_containing(&self, point: Vec3, _time: f32, volumes: &mut Vec<Volume>) {
+        if self.material.is_refractive() && point.distance(self.center) < self.radius {
+            volumes.push(Volume::Refractive);
+        }
+    }
+}
+
+impl Sampleable for Sphere {
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        // Uniform in height and angle around, which is uniform by area on a sphere.
+        let y = 1.0 - 2.0 * rng.gen::<f32>();
+        let r = (1.0 - y * y).max(0.0).sqrt();
+        let angle = 2.0 * PI * rng.gen::<f32>();
+        let normal = vec3(r * angle.cos(), y, r * angle.sin());
+        SurfacePoint {
+            point: self.center + self.radius * normal,
+            normal,
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use glam::{vec3, Vec3};
+use rand::{Rng, RngCore};
 
 use crate::{
     aabb::Aabb,
@@ -8,12 +9,20 @@ use crate::{
     hittable::{HitRecord, Hittable},
     hrpp::Predictor,
     materials::material::Material,
+    precision::{self, from_real, to_real, Real},
 };
 
+use super::sampleable::{Sampleable, SurfacePoint};
+
 pub struct Tri {
     p0: Vec3,
     p1: Vec3,
     p2: Vec3,
+    /// Per-vertex normals, interpolated across the triangle for smooth shading.
+    /// If absent, the triangle is flat shaded.
+    normals: Option<[Vec3; 3]>,
+    /// Per-vertex colors, interpolated across the triangle for the `VertexColor` texture.
+    colors: Option<[Vec3; 3]>,
     material: Arc<dyn Material>,
 }
 
@@ -23,9 +32,36 @@ impl Tri {
             p0,
             p1,
             p2,
+            normals: None,
+            colors: None,
+            material,
+        }
+    }
+
+    /// Creates a smooth shaded triangle, with the `normals` at the vertices
+    /// `p0`, `p1` and `p2` respectively.
+    pub fn with_normals(
+        p0: Vec3,
+        p1: Vec3,
+        p2: Vec3,
+        normals: [Vec3; 3],
+        material: Arc<dyn Material>,
+    ) -> Tri {
+        Tri {
+            p0,
+            p1,
+            p2,
+            normals: Some(normals),
+            colors: None,
             material,
         }
     }
+
+    /// Gives the triangle the `colors` at the vertices `p0`, `p1` and `p2` respectively.
+    pub fn with_colors(mut self, colors: [Vec3; 3]) -> Tri {
+        self.colors = Some(colors);
+        self
+    }
 }
 
 impl Hittable for Tri {
@@ -37,21 +73,23 @@ impl Hittable for Tri {
         _predictors: &Arc<Option<ahash::AHashMap<BvhId, std::sync::Mutex<Predictor>>>>,
     ) -> Option<crate::hittable::HitRecord> {
         // Moller-Trumbore intersection algorithm
-        let epsilon = 0.0000001;
-        let vertex0 = self.p0;
-        let vertex1 = self.p1;
-        let vertex2 = self.p2;
+        let epsilon: Real = 0.0000001;
+        let vertex0 = to_real(self.p0);
+        let vertex1 = to_real(self.p1);
+        let vertex2 = to_real(self.p2);
+        let direction = to_real(ray.direction);
         let edge1 = vertex1 - vertex0;
         let edge2 = vertex2 - vertex0;
-        let h = ray.direction.cross(edge2);
+        let h = direction.cross(edge2);
         let a = edge1.dot(h);
 
-        if a > -epsilon && a < epsilon {
+        // Relative to the triangle's size, so tiny triangles aren't all taken as parallel.
+        if a.abs() < epsilon * edge1.length() * h.length() {
             return None;
         }
 
         let f = 1.0 / a;
-        let s = ray.origin - vertex0;
+        let s = to_real(ray.origin) - vertex0;
         let u = f * s.dot(h);
 
         if u < 0.0 || u > 1.0 {
@@ -59,7 +97,7 @@ impl Hittable for Tri {
         }
 
         let q = s.cross(edge1);
-        let v = f * ray.direction.dot(q);
+        let v = f * direction.dot(q);
 
         if v < 0.0 || u + v > 1.0 {
             return None;
@@ -67,42 +105,65 @@ impl Hittable for Tri {
 
         let t = f * edge2.dot(q);
 
-        if t < t_min || t > t_max {
+        if t < t_min as Real || t > t_max as Real {
             return None;
         }
 
-        if t > epsilon {
-            // TODO We should use barycentric coordinates to get the uvs proper
-            //  for the triangle, but for now we'll just give 0,0 for UVs
-            //  since I just want to get it working with a solid color lambertian.
-            // let intersection_point = ray.origin + ray.direction * t;
-            let normal = edge1.cross(edge2).normalize();
-
-            Some(HitRecord::new(
-                ray,
-                normal,
-                t,
-                0.0,
-                0.0,
-                self.material.clone(),
-            ))
-        } else {
-            None
+        // TODO Meshes don't carry texture coordinates yet, so the barycentric
+        //  coordinates of p1 and p2 are given as the UVs. These are enough for
+        //  e.g. the Wireframe material to find the triangle's edges.
+        let normal = from_real(edge1.cross(edge2).normalize());
+        let (u, v) = (u as f32, v as f32);
+
+        let mut hit_record = HitRecord::new(ray, normal, t as f32, u, v, self.material.clone());
+        if let Some([n0, n1, n2]) = self.normals {
+            // Which side was hit is still decided by the geometric normal.
+            let shading_normal = ((1.0 - u - v) * n0 + u * n1 + v * n2).normalize();
+            hit_record.normal = if hit_record.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+        if let Some([c0, c1, c2]) = self.colors {
+            hit_record.color = Some((1.0 - u - v) * c0 + u * c1 + v * c2);
         }
+        Some(hit_record)
     }
 
     fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<Aabb> {
-        // Add/sub epsilon to avoid infinitely-thin boxes for axis-aligned tris.
-        let min_x = f32::min(self.p0.x, f32::min(self.p1.x, self.p2.x)) - f32::EPSILON;
-        let min_y = f32::min(self.p0.y, f32::min(self.p1.y, self.p2.y)) - f32::EPSILON;
-        let min_z = f32::min(self.p0.z, f32::min(self.p1.z, self.p2.z)) - f32::EPSILON;
-        let max_x = f32::max(self.p0.x, f32::max(self.p1.x, self.p2.x)) + f32::EPSILON;
-        let max_y = f32::max(self.p0.y, f32::max(self.p1.y, self.p2.y)) + f32::EPSILON;
-        let max_z = f32::max(self.p0.z, f32::max(self.p1.z, self.p2.z)) + f32::EPSILON;
-
-        Some(Aabb::new(
-            vec3(min_x, min_y, min_z),
-            vec3(max_x, max_y, max_z),
-        ))
+        // Pad to avoid infinitely-thin boxes for axis-aligned tris.
+        let min = self.p0.min(self.p1).min(self.p2);
+        let max = self.p0.max(self.p1).max(self.p2);
+        let padding = |v: Vec3| {
+            vec3(
+                precision::bounds_padding(v.x),
+                precision::bounds_padding(v.y),
+                precision::bounds_padding(v.z),
+            )
+        };
+
+        Some(Aabb::new(min - padding(min), max + padding(max)))
+    }
+}
+
+impl Sampleable for Tri {
+    fn area(&self) -> f32 {
+        (self.p1 - self.p0).cross(self.p2 - self.p0).length() / 2.0
+    }
+
+    fn sample_point(&self, rng: &mut dyn RngCore) -> SurfacePoint {
+        // Warping one coordinate by its square root maps the unit square onto the triangle
+        // uniformly. Folding the square along its diagonal is uniform too; the warp just
+        // keeps points which are close in the square close on the triangle.
+        let r = rng.gen::<f32>().sqrt();
+        let s = rng.gen::<f32>();
+        let (a, b) = (r * (1.0 - s), r * s);
+        let edge1 = self.p1 - self.p0;
+        let edge2 = self.p2 - self.p0;
+        SurfacePoint {
+            point: self.p0 + a * edge1 + b * edge2,
+            normal: edge1.cross(edge2).normalize(),
+        }
     }
 }
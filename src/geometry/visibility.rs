@@ -0,0 +1,220 @@
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable, Volume},
+    hrpp::Predictor,
+    ray::{Ray, RayKind, RayMask},
+};
+
+/// Which kinds of rays can see a hittable wrapped in `Visibility`.
+#[derive(Clone, Copy, Debug)]
+pub struct VisibilityFlags {
+    /// Whether the object is seen directly by the camera.
+    pub camera: bool,
+    /// Whether the object blocks diffusely scattered light, and so casts shadows.
+    /// Diffuse surfaces and volumes also see the object's own color and emission.
+    pub shadows: bool,
+    /// Whether the object is seen in specular reflections and refractions.
+    pub reflections: bool,
+}
+
+impl Default for VisibilityFlags {
+    fn default() -> Self {
+        VisibilityFlags {
+            camera: true,
+            shadows: true,
+            reflections: true,
+        }
+    }
+}
+
+/// Hides the wrapped hittable from some kinds of rays.
+/// For example, a light blocker can cast shadows without appearing in the image or in
+/// reflections with `VisibilityFlags { camera: false, reflections: false, ..Default::default() }`.
+pub struct Visibility {
+    hittable: Arc<dyn Hittable>,
+    flags: VisibilityFlags,
+}
+
+impl Visibility {
+    pub fn new(hittable: Arc<dyn Hittable>, flags: VisibilityFlags) -> Visibility {
+        Visibility { hittable, flags }
+    }
+}
+
+impl Hittable for Visibility {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        let visible = match ray.kind {
+            RayKind::Camera => self.flags.camera,
+            RayKind::Diffuse => self.flags.shadows,
+            RayKind::Specular => self.flags.reflections,
+        };
+        if !visible {
+            return None;
+        }
+        self.hittable.hit(ray, t_min, t_max, predictors)
+    }
+
+    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
+        self.hittable.bounding_box(time_0, time_1)
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        self.hittable.volumes_containing(point, time, volumes);
+    }
+
+    fn mask(&self) -> RayMask {
+        let kinds = [
+            (self.flags.camera, RayMask::CAMERA),
+            (self.flags.shadows, RayMask::DIFFUSE),
+            (self.flags.reflections, RayMask::SPECULAR),
+        ];
+        let mask = kinds
+            .into_iter()
+            .filter(|(visible, _)| *visible)
+            .fold(RayMask::GROUPS, |mask, (_, kind)| mask | kind);
+        mask & self.hittable.mask()
+    }
+}
+
+/// Gives the wrapped hittable a mask, so that only rays it admits can hit it (see
+/// `RayMask`). The mask holds both the kinds of rays and the groups, e.g.
+/// `RayMask::KINDS | RayMask::group(1)` for an object in group 1 which every kind of ray
+/// in that group can hit. Masks are checked as BVHs are traversed, so one scene can hold
+/// objects for different cameras or kinds of rays without building one for each.
+pub struct Masked {
+    hittable: Arc<dyn Hittable>,
+    mask: RayMask,
+}
+
+impl Masked {
+    pub fn new(hittable: Arc<dyn Hittable>, mask: RayMask) -> Masked {
+        Masked { hittable, mask }
+    }
+}
+
+impl Hittable for Masked {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        if !self.mask.admits(ray.mask()) {
+            return None;
+        }
+        self.hittable.hit(ray, t_min, t_max, predictors)
+    }
+
+    fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
+        self.hittable.bounding_box(time_0, time_1)
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        self.hittable.volumes_containing(point, time, volumes);
+    }
+
+    fn mask(&self) -> RayMask {
+        self.mask & self.hittable.mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        bvh::Bvh,
+        geometry::{instance::Translate, sphere::Sphere},
+        hittable::{Hittable, HittableList},
+        materials::lambertian::Lambertian,
+        ray::{Ray, RayKind, RayMask},
+    };
+
+    use super::{Masked, Visibility, VisibilityFlags};
+
+    #[test]
+    fn hidden_from_camera_still_casts_shadows() {
+        let sphere = Arc::new(Sphere::new(
+            Vec3::ZERO,
+            1.0,
+            Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))),
+        ));
+        let blocker = Visibility::new(
+            sphere,
+            VisibilityFlags {
+                camera: false,
+                reflections: false,
+                ..Default::default()
+            },
+        );
+
+        let camera_ray = Ray::new(vec3(0.0, 0.0, -5.0), Vec3::Z, 0.0);
+        let diffuse_ray = Ray::new(vec3(0.0, 0.0, -5.0), Vec3::Z, 0.0).with_kind(RayKind::Diffuse);
+        let specular_ray =
+            Ray::new(vec3(0.0, 0.0, -5.0), Vec3::Z, 0.0).with_kind(RayKind::Specular);
+        let predictors = Arc::new(None);
+        assert!(blocker
+            .hit(&camera_ray, 0.001, f32::INFINITY, &predictors)
+            .is_none());
+        assert!(blocker
+            .hit(&diffuse_ray, 0.001, f32::INFINITY, &predictors)
+            .is_some());
+        assert!(blocker
+            .hit(&specular_ray, 0.001, f32::INFINITY, &predictors)
+            .is_none());
+    }
+
+    #[test]
+    fn masked_objects_are_hit_only_by_rays_they_admit() {
+        let material = Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5)));
+        let sphere = |z: f32| Arc::new(Sphere::new(vec3(0.0, 0.0, z), 1.0, material.clone()));
+        let mut list = HittableList::new();
+        // The nearer sphere is seen only by camera rays in group 0, and the farther one by
+        // every kind of ray in group 1, behind a translation.
+        list.add(Arc::new(Masked::new(
+            sphere(0.0),
+            RayMask::CAMERA | RayMask::group(0),
+        )));
+        list.add(Arc::new(Translate::new(
+            Arc::new(Masked::new(sphere(0.0), RayMask::KINDS | RayMask::group(1))),
+            vec3(0.0, 0.0, 5.0),
+        )));
+        for i in 0..6 {
+            list.add(sphere(20.0 + i as f32 * 3.0));
+        }
+        let bvh = Bvh::new(list, 0.0, 1.0);
+        assert_eq!(bvh.mask(), RayMask::ALL);
+
+        let predictors = Arc::new(None);
+        let hit = |ray: Ray| {
+            bvh.hit(&ray, 0.001, f32::INFINITY, &predictors)
+                .map(|hit_record| hit_record.point.z)
+        };
+        let ray = || Ray::new(vec3(0.0, 0.0, -5.0), Vec3::Z, 0.0);
+        assert_eq!(hit(ray()), Some(-1.0));
+        assert_eq!(hit(ray().with_groups(RayMask::group(1))), Some(4.0));
+        // Unmasked objects are in every group, and every kind of ray hits them.
+        assert_eq!(
+            hit(ray()
+                .with_kind(RayKind::Diffuse)
+                .with_groups(RayMask::group(0))),
+            Some(19.0)
+        );
+        assert_eq!(hit(ray().with_kind(RayKind::Diffuse)), Some(4.0));
+    }
+}
@@ -0,0 +1,233 @@
+//! Loading MagicaVoxel (.vox) models into voxel grids.
+//!
+//! Each color of the model's palette becomes a material. Colors given a metal, glass or
+//! emissive material in MagicaVoxel become a `Metal`, `Dialectric` or `DiffuseLight`;
+//! all others are `Lambertian`.
+//!
+//! Only the first model in the file is loaded; the scene graph arranging several models
+//! is ignored. MagicaVoxel's Z-up coordinates are turned to be Y-up.
+
+use std::{fs, io, path::Path, sync::Arc};
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+use crate::materials::{
+    dialectric::Dialectric, diffuse_light::DiffuseLight, lambertian::Lambertian,
+    material::Material, metal::Metal,
+};
+
+use super::voxel_grid::VoxelGrid;
+
+/// Loads the model in the .vox file at `path` as a grid of voxels with sides of
+/// `voxel_size`, with its minimum corner at the origin.
+pub fn load(path: &Path, voxel_size: f32) -> io::Result<VoxelGrid> {
+    parse(&fs::read(path)?, voxel_size)
+}
+
+/// Parses the contents of a .vox file. See `load()`.
+pub fn parse(bytes: &[u8], voxel_size: f32) -> io::Result<VoxelGrid> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != b"VOX " {
+        return Err(invalid("not a .vox file"));
+    }
+    let _version = reader.i32()?;
+    let (id, main) = reader.chunk()?;
+    if id != b"MAIN" {
+        return Err(invalid("missing MAIN chunk"));
+    }
+
+    let mut size = None;
+    let mut voxels = None;
+    let mut palette = None;
+    let mut materials = AHashMap::new();
+    let mut children = Reader {
+        bytes: main.children,
+        pos: 0,
+    };
+    while children.pos < children.bytes.len() {
+        let (id, chunk) = children.chunk()?;
+        let mut content = Reader {
+            bytes: chunk.content,
+            pos: 0,
+        };
+        match id {
+            b"SIZE" if size.is_none() => {
+                size = Some([content.i32()?, content.i32()?, content.i32()?]);
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = content.i32()?;
+                let mut model = Vec::new();
+                for _ in 0..count {
+                    let voxel = content.take(4)?;
+                    model.push([voxel[0], voxel[1], voxel[2], voxel[3]]);
+                }
+                voxels = Some(model);
+            }
+            b"RGBA" => {
+                let mut colors = [Vec3::ZERO; 256];
+                for color in colors.iter_mut() {
+                    let rgba = content.take(4)?;
+                    *color = Vec3::new(rgba[0] as f32, rgba[1] as f32, rgba[2] as f32) / 255.0;
+                }
+                palette = Some(colors);
+            }
+            b"MATL" => {
+                let id = content.i32()?;
+                materials.insert(id, content.dict()?);
+            }
+            _ => (),
+        }
+    }
+
+    let [size_x, size_y, size_z] = size.ok_or_else(|| invalid("missing SIZE chunk"))?;
+    let voxels = voxels.ok_or_else(|| invalid("missing XYZI chunk"))?;
+    if size_x <= 0 || size_y <= 0 || size_z <= 0 {
+        return Err(invalid("invalid model size"));
+    }
+
+    // Color index i (1 to 255) is entry i - 1 of the palette. Files without a palette
+    // use MagicaVoxel's default one, which we don't have, so those are grey.
+    let palette = palette.unwrap_or([Vec3::splat(0.5); 256]);
+    let mut material_indices = [None; 256];
+    let mut grid_materials: Vec<Arc<dyn Material>> = Vec::new();
+
+    let dims = [size_x as usize, size_z as usize, size_y as usize];
+    let mut filled = Vec::with_capacity(voxels.len());
+    for [x, y, z, color_index] in voxels {
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        if x >= dims[0] || y >= dims[2] || z >= dims[1] || color_index == 0 {
+            continue;
+        }
+        let material = *material_indices[color_index as usize].get_or_insert_with(|| {
+            let color = palette[color_index as usize - 1];
+            grid_materials.push(to_material(color, materials.get(&(color_index as i32))));
+            grid_materials.len() - 1
+        });
+        // Z-up to Y-up, turning the model rather than mirroring it.
+        filled.push(([x, z, dims[2] - 1 - y], material));
+    }
+
+    let mut grid = VoxelGrid::new(dims, Vec3::ZERO, voxel_size, grid_materials);
+    for ([x, y, z], material) in filled {
+        grid.set(x, y, z, Some(material));
+    }
+    Ok(grid)
+}
+
+/// Converts a palette color and its MagicaVoxel material properties, if any.
+fn to_material(color: Vec3, properties: Option<&AHashMap<String, String>>) -> Arc<dyn Material> {
+    let property = |name: &str| -> Option<f32> {
+        properties
+            .and_then(|properties| properties.get(name))
+            .and_then(|value| value.parse().ok())
+    };
+    let material_type = properties
+        .and_then(|properties| properties.get("_type"))
+        .map(String::as_str);
+    match material_type {
+        Some("_metal") => Arc::new(Metal::new(color, property("_rough").unwrap_or(0.0))),
+        // MagicaVoxel stores the index of refraction less one.
+        Some("_glass") => Arc::new(Dialectric::new(1.0 + property("_ior").unwrap_or(0.5))),
+        Some("_emit") => Arc::new(DiffuseLight::from_color(
+            color * property("_emit").unwrap_or(1.0) * (1.0 + property("_flux").unwrap_or(0.0)),
+        )),
+        _ => Arc::new(Lambertian::from_color(color)),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct Chunk<'a> {
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Reads the little-endian values of a .vox file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| invalid("negative length"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.len()?;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn dict(&mut self) -> io::Result<AHashMap<String, String>> {
+        let len = self.len()?;
+        let mut dict = AHashMap::new();
+        for _ in 0..len {
+            let key = self.string()?;
+            dict.insert(key, self.string()?);
+        }
+        Ok(dict)
+    }
+
+    fn chunk(&mut self) -> io::Result<(&'a [u8], Chunk<'a>)> {
+        let id = self.take(4)?;
+        let content_len = self.len()?;
+        let children_len = self.len()?;
+        let content = self.take(content_len)?;
+        let children = self.take(children_len)?;
+        Ok((id, Chunk { content, children }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    fn chunk(id: &[u8], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as i32).to_le_bytes());
+        bytes.extend((children.len() as i32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    }
+
+    #[test]
+    fn parses_model_z_up() {
+        let size: Vec<u8> = [2i32, 3, 4].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut xyzi = 2i32.to_le_bytes().to_vec();
+        xyzi.extend([1, 0, 3, 7]);
+        xyzi.extend([0, 2, 0, 9]);
+        let mut children = chunk(b"SIZE", &size, &[]);
+        children.extend(chunk(b"XYZI", &xyzi, &[]));
+        let mut file = b"VOX ".to_vec();
+        file.extend(150i32.to_le_bytes());
+        file.extend(chunk(b"MAIN", &[], &children));
+
+        let grid = parse(&file, 1.0).unwrap();
+        assert_eq!(grid.dims(), [2, 4, 3]);
+        // (x, y, z) becomes (x, z, size_y - 1 - y).
+        assert_eq!(grid.get(1, 3, 2), Some(0));
+        assert_eq!(grid.get(0, 0, 0), Some(1));
+        assert_eq!(grid.get(0, 0, 2), None);
+        assert!(parse(b"VOX \x96\x00\x00\x00", 1.0).is_err());
+    }
+}
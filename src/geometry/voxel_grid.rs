@@ -0,0 +1,266 @@
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhId,
+    hittable::{HitRecord, Hittable},
+    hrpp::Predictor,
+    materials::material::Material,
+    ray::Ray,
+};
+
+/// A regular grid of axis-aligned cubes, each either empty or filled with one of the
+/// grid's materials.
+///
+/// Rays step through the grid cell by cell, so large grids don't need a BVH.
+/// Rays which start inside a filled voxel (e.g. refracted into glass) hit the boundary
+/// where the material changes on the way out.
+pub struct VoxelGrid {
+    /// The number of voxels along the X, Y and Z axes.
+    dims: [usize; 3],
+    /// The corner of the grid with the smallest coordinates.
+    min: Vec3,
+    voxel_size: f32,
+    /// The index into `materials` of each voxel, or `None` if empty.
+    /// Flattened with X varying fastest, then Y, then Z.
+    voxels: Vec<Option<usize>>,
+    materials: Vec<Arc<dyn Material>>,
+}
+
+impl VoxelGrid {
+    /// Creates an empty grid of `dims` voxels, each a cube with sides of `voxel_size`,
+    /// extending from `min` along the positive axes.
+    pub fn new(
+        dims: [usize; 3],
+        min: Vec3,
+        voxel_size: f32,
+        materials: Vec<Arc<dyn Material>>,
+    ) -> VoxelGrid {
+        VoxelGrid {
+            dims,
+            min,
+            voxel_size,
+            voxels: vec![None; dims[0] * dims[1] * dims[2]],
+            materials,
+        }
+    }
+
+    pub fn dims(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    pub fn voxel_size(&self) -> f32 {
+        self.voxel_size
+    }
+
+    /// Resizes the voxels to have sides of `voxel_size`, keeping the grid's minimum corner.
+    pub fn with_voxel_size(mut self, voxel_size: f32) -> VoxelGrid {
+        self.voxel_size = voxel_size;
+        self
+    }
+
+    /// Fills the voxel with the material at index `material`, or empties it if `None`.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, material: Option<usize>) {
+        assert!(material.is_none_or(|material| material < self.materials.len()));
+        let idx = self.idx([x, y, z]);
+        self.voxels[idx] = material;
+    }
+
+    /// Returns the index of the voxel's material, or `None` if it's empty.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        self.voxels[self.idx([x, y, z])]
+    }
+
+    fn idx(&self, cell: [usize; 3]) -> usize {
+        cell[0] + self.dims[0] * (cell[1] + self.dims[1] * cell[2])
+    }
+
+    fn max(&self) -> Vec3 {
+        self.min
+            + self.voxel_size
+                * Vec3::new(
+                    self.dims[0] as f32,
+                    self.dims[1] as f32,
+                    self.dims[2] as f32,
+                )
+    }
+
+    fn hit_record(
+        &self,
+        ray: &Ray,
+        t: f32,
+        cell: [isize; 3],
+        axis: usize,
+        outward_normal: Vec3,
+        material: usize,
+    ) -> HitRecord {
+        // UVs run across the face of the voxel which was hit.
+        let cell = Vec3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32);
+        let local = (ray.at(t) - self.min) / self.voxel_size - cell;
+        let u = local[(axis + 1) % 3].clamp(0.0, 1.0);
+        let v = local[(axis + 2) % 3].clamp(0.0, 1.0);
+        HitRecord::new(
+            ray,
+            outward_normal,
+            t,
+            u,
+            v,
+            self.materials[material].clone(),
+        )
+    }
+}
+
+impl Hittable for VoxelGrid {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        // Clip the ray to the grid's bounds, noting which face it enters through, if any.
+        let max = self.max();
+        let mut t_enter = t_min;
+        let mut t_exit = t_max;
+        let mut entry_axis = None;
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                t_enter = t0;
+                entry_axis = Some(axis);
+            }
+            t_exit = t_exit.min(t1);
+            if t_exit <= t_enter {
+                return None;
+            }
+        }
+
+        // Step through the voxels along the ray (Amanatides and Woo's traversal).
+        let start = (ray.at(t_enter) - self.min) / self.voxel_size;
+        let mut cell = [0isize; 3];
+        let mut step = [0isize; 3];
+        let mut t_next = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            cell[axis] = (start[axis].floor() as isize).clamp(0, self.dims[axis] as isize - 1);
+            let direction = ray.direction[axis];
+            if direction > 0.0 {
+                step[axis] = 1;
+                let boundary = self.min[axis] + (cell[axis] + 1) as f32 * self.voxel_size;
+                t_next[axis] = (boundary - ray.origin[axis]) / direction;
+                t_delta[axis] = self.voxel_size / direction;
+            } else if direction < 0.0 {
+                step[axis] = -1;
+                let boundary = self.min[axis] + cell[axis] as f32 * self.voxel_size;
+                t_next[axis] = (boundary - ray.origin[axis]) / direction;
+                t_delta[axis] = -self.voxel_size / direction;
+            }
+        }
+        let axis_normal = |axis: usize, sign: isize| {
+            let mut normal = Vec3::ZERO;
+            normal[axis] = sign as f32;
+            normal
+        };
+        let voxel = |cell: [isize; 3]| self.voxels[self.idx(cell.map(|c| c as usize))];
+
+        // A ray entering through a face starts outside; otherwise it starts within the
+        // voxel containing its origin.
+        let inside = match entry_axis {
+            Some(_) => None,
+            None => voxel(cell),
+        };
+        let mut t = t_enter;
+        let mut axis = entry_axis;
+        loop {
+            match (inside, voxel(cell)) {
+                (None, Some(material)) => {
+                    let axis = axis.unwrap();
+                    let normal = axis_normal(axis, -step[axis]);
+                    return Some(self.hit_record(ray, t, cell, axis, normal, material));
+                }
+                (Some(material), current) if current != Some(material) => {
+                    // Leaving the material; report the face of the voxel we were in.
+                    let axis = axis.unwrap();
+                    let normal = axis_normal(axis, step[axis]);
+                    let mut previous = cell;
+                    previous[axis] -= step[axis];
+                    return Some(self.hit_record(ray, t, previous, axis, normal, material));
+                }
+                _ => (),
+            }
+
+            let next_axis = (0..3)
+                .min_by(|a, b| t_next[*a].total_cmp(&t_next[*b]))
+                .unwrap();
+            t = t_next[next_axis];
+            if t > t_max {
+                return None;
+            }
+            cell[next_axis] += step[next_axis];
+            t_next[next_axis] += t_delta[next_axis];
+            axis = Some(next_axis);
+            if cell[next_axis] < 0 || cell[next_axis] >= self.dims[next_axis] as isize {
+                // Leaving the grid, which ends any material the ray is inside.
+                return inside.map(|material| {
+                    let normal = axis_normal(next_axis, step[next_axis]);
+                    let mut previous = cell;
+                    previous[next_axis] -= step[next_axis];
+                    self.hit_record(ray, t, previous, next_axis, normal, material)
+                });
+            }
+        }
+    }
+
+    fn bounding_box(&self, _time_0: f32, _time_1: f32) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{hittable::Hittable, materials::lambertian::Lambertian, ray::Ray};
+
+    use super::VoxelGrid;
+
+    #[test]
+    fn hits_first_filled_voxel() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let mut grid = VoxelGrid::new([4, 4, 4], Vec3::ZERO, 0.5, vec![material]);
+        grid.set(1, 2, 3, Some(0));
+        grid.set(3, 2, 3, Some(0));
+
+        // Along +X through the row y = 2, z = 3.
+        let ray = Ray::new(vec3(-1.0, 1.25, 1.75), Vec3::X, 0.0);
+        let hit = grid
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-5);
+        assert!((hit.normal - -Vec3::X).length() < 1e-5);
+
+        // From inside the first voxel, the ray leaves it through its +X face.
+        let ray = Ray::new(vec3(0.75, 1.25, 1.75), Vec3::X, 0.0);
+        let hit = grid
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .unwrap();
+        assert!((hit.t - 0.25).abs() < 1e-5);
+        assert!(!hit.front_face);
+
+        // Rows with no filled voxels are missed.
+        let ray = Ray::new(vec3(-1.0, 0.25, 1.75), Vec3::X, 0.0);
+        assert!(grid
+            .hit(&ray, 0.001, f32::INFINITY, &Arc::new(None))
+            .is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     ops::Neg,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, OnceLock},
 };
 
 use ahash::AHashMap;
@@ -8,8 +8,16 @@ use glam::Vec3;
 use rand::Rng;
 
 use crate::{
-    aabb::Aabb, bvh::BvhId, hrpp::Predictor, materials::isotropic::Isotropic,
-    materials::material::Material, ray::Ray, textures::texture::Texture,
+    aabb::Aabb,
+    bounding_sphere::BoundingSphere,
+    bvh::BvhId,
+    geometry::sampleable::Light,
+    hrpp::Predictor,
+    materials::isotropic::Isotropic,
+    materials::material::Material,
+    precision, random,
+    ray::{Ray, RayMask},
+    textures::texture::Texture,
 };
 
 pub struct HitRecord {
@@ -22,6 +30,8 @@ pub struct HitRecord {
     pub v: f32,
     pub front_face: bool,
     pub material: Arc<dyn Material>,
+    /// The color interpolated from the vertices of the surface, if it has vertex colors.
+    pub color: Option<Vec3>,
 }
 
 impl HitRecord {
@@ -48,6 +58,7 @@ impl HitRecord {
             v,
             front_face,
             material,
+            color: None,
         }
     }
 
@@ -79,42 +90,161 @@ pub trait Hittable: Send + Sync {
     /// full range of motion between `time_0` and `time_1`. If the object does not move,
     /// these values have no effect on the bounding box.
     fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb>;
+
+    /// Returns a sphere bounding the object over its motion between `time_0` and
+    /// `time_1`, as `bounding_box()` does, for cheaply rejecting rays which miss it and
+    /// judging how large it appears from a distance. By default, the sphere through the
+    /// corners of its bounding box; objects which can be bound more tightly override this.
+    fn bounding_sphere(&self, time_0: f32, time_1: f32) -> Option<BoundingSphere> {
+        self.bounding_box(time_0, time_1)
+            .map(|aabb| BoundingSphere::from_aabb(&aabb))
+    }
+
+    /// Adds each volume of the object which contains `point` at `time` to `volumes`, for
+    /// finding overlapping volumes, which don't render correctly.
+    /// Objects which don't enclose a medium or a refractive material add nothing.
+    fn volumes_containing(&self, _point: Vec3, _time: f32, _volumes: &mut Vec<Volume>) {}
+
+    /// The kinds of rays which can hit the object and the groups it's in, or for objects
+    /// holding others, the union of those of everything within (see `RayMask`), so that
+    /// BVHs can skip whatever a ray couldn't hit. Wrap an object in `Masked` to give it a
+    /// mask. By default, every kind of ray can hit it, and it's in every group.
+    fn mask(&self) -> RayMask {
+        RayMask::ALL
+    }
+}
+
+/// A kind of volume which light travels through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Volume {
+    /// A participating medium, such as smoke in a `ConstantMedium`.
+    Medium,
+    /// The inside of a surface with a refractive material, such as glass.
+    Refractive,
 }
 
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable>>,
+    /// The index in `objects` of each named object.
+    names: AHashMap<String, usize>,
+    /// The objects added as lights, which are also in `objects`.
+    lights: Vec<Arc<dyn Light>>,
+    /// The distance within which hits are ignored, scaled to the size of the scene.
+    /// Found on first use, once the objects are all added.
+    epsilon: OnceLock<f32>,
 }
 
 impl HittableList {
     pub fn new() -> HittableList {
         HittableList {
             objects: Vec::new(),
+            names: AHashMap::new(),
+            lights: Vec::new(),
+            epsilon: OnceLock::new(),
         }
     }
 
+    /// The smallest `t` at which the ray can hit the objects without risking hitting
+    /// the surface it's leaving, given the rounding errors in a scene of this size.
+    pub fn t_min(&self, ray: &Ray) -> f32 {
+        let epsilon = self
+            .epsilon
+            .get_or_init(|| precision::scene_epsilon(self.bounding_box(0.0, 1.0)));
+        precision::t_min(ray.origin, ray.direction, *epsilon)
+    }
+
     pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object);
     }
-}
 
-impl Hittable for HittableList {
-    fn hit(
+    /// Adds the object under `name`, by which it can be found with `get()` and
+    /// `get_mut()`. Adding another object with the same name takes the name from this one.
+    /// Names refer to positions in `objects`, so they're lost if `objects` is reordered.
+    pub fn add_named(&mut self, name: impl Into<String>, object: Arc<dyn Hittable>) {
+        self.names.insert(name.into(), self.objects.len());
+        self.add(object);
+    }
+
+    /// Adds the `light`, an object with an emissive material, which light can then be
+    /// sent out from, e.g. photons for caustics. Lights added with `add()` still light
+    /// the scene, but only by paths which happen to hit them.
+    pub fn add_light<L: Light + 'static>(&mut self, light: Arc<L>) {
+        self.lights.push(light.clone());
+        self.add(light);
+    }
+
+    /// The objects added with `add_light()`.
+    pub fn lights(&self) -> &[Arc<dyn Light>] {
+        &self.lights
+    }
+
+    /// Moves the lights to the `other` list, which holds the same objects (e.g. in a BVH).
+    pub(crate) fn move_lights_to(&mut self, other: &mut HittableList) {
+        other.lights.append(&mut self.lights);
+    }
+
+    /// Returns the object added under `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Hittable>> {
+        self.objects.get(*self.names.get(name)?)
+    }
+
+    /// Returns the object added under `name`, if there is one, so that it can be replaced
+    /// between frames (e.g. by the same object moved or with another material) without
+    /// rebuilding the rest of the scene.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Arc<dyn Hittable>> {
+        let index = *self.names.get(name)?;
+        // The object may change the size of the scene.
+        self.epsilon = OnceLock::new();
+        self.objects.get_mut(index)
+    }
+
+    /// The names of the named objects, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(String::as_str)
+    }
+
+    /// Removes the names of the objects, returning the index of each named object.
+    pub(crate) fn take_names(&mut self) -> AHashMap<String, usize> {
+        std::mem::take(&mut self.names)
+    }
+
+    /// As `hit()`, also returning the index of the object which was hit.
+    pub fn hit_object(
         &self,
         ray: &Ray,
         t_min: f32,
         t_max: f32,
         predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
-    ) -> Option<HitRecord> {
+    ) -> Option<(usize, HitRecord)> {
         let mut closest_so_far = t_max;
-        let mut out_hit_record: Option<HitRecord> = None;
-        for object in &self.objects {
+        let mut out_hit: Option<(usize, HitRecord)> = None;
+        for (index, object) in self.objects.iter().enumerate() {
             let hit_record = object.hit(ray, t_min, closest_so_far, predictors);
             if let Some(hit_record) = hit_record {
                 closest_so_far = hit_record.t;
-                out_hit_record = Some(hit_record);
+                out_hit = Some((index, hit_record));
             }
         }
-        out_hit_record
+        out_hit
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Option<HitRecord> {
+        self.hit_object(ray, t_min, t_max, predictors)
+            .map(|(_, hit_record)| hit_record)
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        for object in &self.objects {
+            object.volumes_containing(point, time, volumes);
+        }
     }
 
     /// Returns the bounding box encompassing all objects in the HittableList.
@@ -145,6 +275,9 @@ pub struct ConstantMedium {
     boundary: Arc<dyn Hittable>,
     phase_function: Arc<dyn Material>,
     neg_inv_density: f32,
+    /// A sphere bounding the boundary, which rays must pass through to scatter in the
+    /// medium. Checked first, as finding both crossings of the boundary is costly.
+    bounds: Option<BoundingSphere>,
 }
 
 impl ConstantMedium {
@@ -154,6 +287,7 @@ impl ConstantMedium {
         texture: Arc<dyn Texture>,
     ) -> ConstantMedium {
         ConstantMedium {
+            bounds: boundary.bounding_sphere(0.0, 1.0),
             boundary,
             phase_function: Arc::new(Isotropic::new(texture)),
             neg_inv_density: -1.0 / density,
@@ -166,6 +300,7 @@ impl ConstantMedium {
         color: Vec3,
     ) -> ConstantMedium {
         ConstantMedium {
+            bounds: boundary.bounding_sphere(0.0, 1.0),
             boundary,
             phase_function: Arc::new(Isotropic::from_color(color)),
             neg_inv_density: -1.0 / density,
@@ -181,12 +316,21 @@ impl Hittable for ConstantMedium {
         t_max: f32,
         predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Option<HitRecord> {
+        if let Some(bounds) = &self.bounds {
+            if !bounds.hit(ray, t_min, t_max) {
+                return None;
+            }
+        }
         let mut hit1 = self
             .boundary
             .hit(ray, f32::NEG_INFINITY, f32::INFINITY, &predictors)?;
-        let mut hit2 = self
-            .boundary
-            .hit(ray, hit1.t + 0.0001, f32::INFINITY, predictors)?;
+        // Offset the second hit by a distance of at least 0.0001 from the first.
+        let mut hit2 = self.boundary.hit(
+            ray,
+            hit1.t + precision::t_min(ray.at(hit1.t), ray.direction, 0.0001),
+            f32::INFINITY,
+            predictors,
+        )?;
 
         if hit1.t < t_min {
             hit1.t = t_min
@@ -205,7 +349,7 @@ impl Hittable for ConstantMedium {
 
         let ray_length = ray.direction.length();
         let distance_inside_boundary = (hit2.t - hit1.t) * ray_length;
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let hit_distance = self.neg_inv_density * f32::ln(rng.gen());
 
         if hit_distance > distance_inside_boundary {
@@ -227,6 +371,7 @@ impl Hittable for ConstantMedium {
             v: 0.0,
             front_face: true, // Arbitrary
             material: self.phase_function.clone(),
+            color: None,
         };
 
         Some(out_hit_record)
@@ -235,4 +380,73 @@ impl Hittable for ConstantMedium {
     fn bounding_box(&self, time_0: f32, time_1: f32) -> Option<Aabb> {
         self.boundary.bounding_box(time_0, time_1)
     }
+
+    fn bounding_sphere(&self, time_0: f32, time_1: f32) -> Option<BoundingSphere> {
+        self.boundary.bounding_sphere(time_0, time_1)
+    }
+
+    fn volumes_containing(&self, point: Vec3, time: f32, volumes: &mut Vec<Volume>) {
+        // The boundary is convex, so contains the point if a line through the point
+        // crosses the boundary on either side of it.
+        let ray = Ray::new(point, Vec3::Y, time);
+        let predictors = Arc::new(None);
+        let Some(entry) = self
+            .boundary
+            .hit(&ray, f32::NEG_INFINITY, f32::INFINITY, &predictors)
+        else {
+            return;
+        };
+        let exit_t_min = entry.t + precision::t_min(ray.at(entry.t), ray.direction, 0.0001);
+        if entry.t <= 0.0
+            && self
+                .boundary
+                .hit(&ray, exit_t_min.max(0.0), f32::INFINITY, &predictors)
+                .is_some()
+        {
+            volumes.push(Volume::Medium);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{bvh::Bvh, geometry::sphere::Sphere, materials::lambertian::Lambertian, ray::Ray};
+
+    use super::{Hittable, HittableList};
+
+    #[test]
+    fn named_objects_can_be_replaced() {
+        let material = Arc::new(Lambertian::from_color(Vec3::ONE));
+        let mut world = HittableList::new();
+        for x in [-4.0, 4.0] {
+            world.add(Arc::new(Sphere::new(
+                vec3(x, 0.0, 0.0),
+                1.0,
+                material.clone(),
+            )));
+        }
+        world.add_named(
+            "ball",
+            Arc::new(Sphere::new(Vec3::ZERO, 1.0, material.clone())),
+        );
+        let mut world = Bvh::top_level(world, 0.0, 1.0);
+        assert!(world.get("ball").is_some());
+
+        // Move the ball out of the way of a ray through the origin.
+        let ray = Ray::new(vec3(0.0, 0.0, -5.0), Vec3::Z, 0.0);
+        let t_min = world.t_min(&ray);
+        assert!(world
+            .hit(&ray, t_min, f32::INFINITY, &Arc::new(None))
+            .is_some());
+        *world.get_mut("ball").unwrap() = Arc::new(Sphere::new(vec3(0.0, 4.0, 0.0), 1.0, material));
+        let t_min = world.t_min(&ray);
+        assert!(world
+            .hit(&ray, t_min, f32::INFINITY, &Arc::new(None))
+            .is_none());
+        assert!(world.get("missing").is_none());
+    }
 }
@@ -2,10 +2,24 @@
 //! Based on a technique proposed by Francois Demoullin, Ayub Gubran, Tor Aamodt
 //! See https://arxiv.org/abs/1910.01304
 //! Hash-Based Ray Path Prediction: Skipping BVH Traversal Computation by Exploiting Ray Locality
+//!
+//! Predictions chain across nested BVHs: while a ray is in a BVH with a predictor, any
+//! BVH nested within it (e.g. a mesh in the scene's BVH) looks up and records its own
+//! predictions under the outer ray's hash rather than hashing the ray again. A ray whose
+//! prediction in the outer BVH is right thereby goes straight to the predicted node of
+//! the nested one as well, skipping traversal of both, and instances of a shared mesh
+//! keep predictions apart, as their rays differ in world space even where they're the
+//! same in the mesh's space.
+
+use std::cell::{Cell, RefCell};
+use std::sync::Mutex;
 
 use ahash::{AHashMap, AHashSet};
 
-use crate::{bvh::BvhId, ray::Ray};
+use crate::{
+    bvh::BvhId,
+    ray::{Ray, RayKind},
+};
 
 /// The number of bits extracted from float values'
 /// exponent and mantissa. So the total number of bits
@@ -22,6 +36,174 @@ enum BitPrecision {
     Seven,
 }
 
+/// The outcome of looking up a ray in a predictor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PredictionOutcome {
+    /// The ray hit something within the predicted node(s), skipping traversal.
+    TruePositive,
+    /// The ray missed the predicted node(s) and was traversed from the root.
+    FalsePositive,
+    /// There was no prediction for the ray.
+    NoPrediction,
+}
+
+/// How many lookups into a predictor had each outcome.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PredictionCounts {
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub no_predictions: u64,
+}
+
+impl PredictionCounts {
+    pub fn total(&self) -> u64 {
+        self.true_positives + self.false_positives + self.no_predictions
+    }
+
+    /// The fraction of lookups which were true positives, or 0 if there were none.
+    pub fn true_positive_ratio(&self) -> f32 {
+        self.true_positives as f32 / self.total().max(1) as f32
+    }
+
+    fn add(&mut self, outcome: PredictionOutcome) {
+        match outcome {
+            PredictionOutcome::TruePositive => self.true_positives += 1,
+            PredictionOutcome::FalsePositive => self.false_positives += 1,
+            PredictionOutcome::NoPrediction => self.no_predictions += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &PredictionCounts) {
+        self.true_positives += other.true_positives;
+        self.false_positives += other.false_positives;
+        self.no_predictions += other.no_predictions;
+    }
+}
+
+/// The prediction counts of each BVH, gathered by `count_outcomes()`.
+#[derive(Clone, Debug, Default)]
+pub struct PredictionStats {
+    counts: AHashMap<BvhId, PredictionCounts>,
+}
+
+impl PredictionStats {
+    /// The counts of the BVH with the `id`, which are zero if no rays entered it.
+    pub fn get(&self, id: &BvhId) -> PredictionCounts {
+        self.counts.get(id).copied().unwrap_or_default()
+    }
+
+    pub fn merge(&mut self, other: &PredictionStats) {
+        for (id, counts) in &other.counts {
+            self.counts.entry(*id).or_default().merge(counts);
+        }
+    }
+
+    /// Adds the counts to those kept by each predictor, for its statistics.
+    pub fn add_to(&self, predictors: &AHashMap<BvhId, Mutex<Predictor>>) {
+        for (id, counts) in &self.counts {
+            if let Some(predictor) = predictors.get(id) {
+                predictor.lock().unwrap().counts.merge(counts);
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Outcomes of camera rays on this thread, while they're being recorded.
+    static RECORDED_OUTCOMES: RefCell<Option<Vec<PredictionOutcome>>> = const { RefCell::new(None) };
+    /// Counts of the outcomes of all rays on this thread, while they're being counted.
+    static COUNTED_OUTCOMES: RefCell<Option<PredictionStats>> = const { RefCell::new(None) };
+    /// The hash of the ray in the outermost BVH with a predictor which this thread is
+    /// traversing, if any, under which nested BVHs key their predictions.
+    static CHAIN_KEY: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// The key under which predictions for the ray are looked up and recorded: the hash of
+/// the ray in the outermost BVH being traversed, or of the ray itself if there's none.
+pub(crate) fn key(ray: &Ray) -> u64 {
+    CHAIN_KEY
+        .with(|chain| chain.get())
+        .unwrap_or_else(|| hash(ray))
+}
+
+/// Calls `f`, in which BVHs nested within the one being traversed key their predictions
+/// by `key`, unless an outer BVH has already set one.
+pub(crate) fn chained<T>(key: u64, f: impl FnOnce() -> T) -> T {
+    // Clears the key even if `f` panics, so that a retried tile doesn't chain from it.
+    struct Clear;
+    impl Drop for Clear {
+        fn drop(&mut self) {
+            CHAIN_KEY.with(|chain| chain.set(None));
+        }
+    }
+
+    if CHAIN_KEY.with(|chain| chain.get()).is_some() {
+        return f();
+    }
+    CHAIN_KEY.with(|chain| chain.set(Some(key)));
+    let _clear = Clear;
+    f()
+}
+
+/// Calls `f`, returning its result along with the counts of prediction outcomes of
+/// every ray traced on this thread in the meantime. Counting on the thread rather than
+/// in the shared predictors means rays needn't lock a predictor to count its outcomes;
+/// the counts of each tile are added to the predictors once the tile is done.
+pub fn count_outcomes<T>(f: impl FnOnce() -> T) -> (T, PredictionStats) {
+    // Puts back the previous counts even if `f` panics, so a retried tile isn't counted
+    // into the stats of the one which panicked.
+    struct Restore(Option<PredictionStats>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            COUNTED_OUTCOMES.with(|counts| *counts.borrow_mut() = previous);
+        }
+    }
+
+    let _restore =
+        Restore(COUNTED_OUTCOMES.with(|counts| counts.replace(Some(PredictionStats::default()))));
+    let result = f();
+    let counts = COUNTED_OUTCOMES.with(|counts| counts.take());
+    (result, counts.unwrap_or_default())
+}
+
+/// Calls `f`, returning its result along with the prediction outcomes of the
+/// camera rays traced on this thread in the meantime.
+pub fn record_outcomes<T>(f: impl FnOnce() -> T) -> (T, Vec<PredictionOutcome>) {
+    // As in `count_outcomes()`, puts back the previous outcomes even if `f` panics.
+    struct Restore(Option<Vec<PredictionOutcome>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            RECORDED_OUTCOMES.with(|outcomes| *outcomes.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(RECORDED_OUTCOMES.with(|outcomes| outcomes.replace(Some(Vec::new()))));
+    let result = f();
+    let outcomes = RECORDED_OUTCOMES.with(|outcomes| outcomes.take());
+    (result, outcomes.unwrap_or_default())
+}
+
+/// Records the outcome of a prediction for the ray in the BVH with the `id`, counting it
+/// if outcomes are being counted, and recording it if it's a camera ray and outcomes
+/// are being recorded.
+pub(crate) fn record_outcome(ray: &Ray, id: BvhId, outcome: PredictionOutcome) {
+    COUNTED_OUTCOMES.with(|counts| {
+        if let Some(stats) = counts.borrow_mut().as_mut() {
+            stats.counts.entry(id).or_default().add(outcome);
+        }
+    });
+    if ray.kind != RayKind::Camera {
+        return;
+    }
+    RECORDED_OUTCOMES.with(|outcomes| {
+        if let Some(outcomes) = outcomes.borrow_mut().as_mut() {
+            outcomes.push(outcome);
+        }
+    });
+}
+
 // We define a predictor rather than using a has map directly because
 // 1. The predictor can convert Ray to a u64 for use as a key in the hash map.
 //    This is simpler than implementing Hash/Hasher for a Ray and using Ray as a key
@@ -34,12 +216,9 @@ pub struct Predictor {
     id: BvhId,
     // Maps the result of hash(ray) to the index of the predicted node for that hash.
     prediction_table: AHashMap<u64, AHashSet<usize>>,
-    // TODO it would be better to store statistics outside of the predictor, so we don't need
-    //  to lock access to the predictor just to increment these stats.
-    //  But we can just comment out stat collection if we want to test wall clock time etc...
-    pub true_positive_predictions: u32,
-    pub false_positive_predictions: u32,
-    pub no_predictions: u32,
+    // Rays count their outcomes on their own thread, which are added here after each tile
+    // (see `count_outcomes()`), so they needn't lock the predictor to count them.
+    counts: PredictionCounts,
 }
 
 impl Predictor {
@@ -48,24 +227,36 @@ impl Predictor {
         Predictor {
             id,
             prediction_table,
-            true_positive_predictions: 0,
-            false_positive_predictions: 0,
-            no_predictions: 0,
+            counts: PredictionCounts::default(),
         }
     }
 
+    /// How many lookups into this predictor have had each outcome, over the tiles
+    /// rendered so far.
+    pub fn counts(&self) -> PredictionCounts {
+        self.counts
+    }
+
     /// Returns the prediction if there is one.
     /// If there is no prediction for this ray, returns None.
     pub fn get_predictions(&self, ray: &Ray) -> Option<&AHashSet<usize>> {
-        let key = hash(ray);
+        self.get_predictions_by_key(hash(ray))
+    }
+
+    /// As `get_predictions()`, for the ray with the hash `key`.
+    pub fn get_predictions_by_key(&self, key: u64) -> Option<&AHashSet<usize>> {
         self.prediction_table.get(&key)
     }
 
     pub fn insert(&mut self, ray: &Ray, prediction: usize) {
+        self.insert_by_key(hash(ray), prediction);
+    }
+
+    /// As `insert()`, for the ray with the hash `key`.
+    pub fn insert_by_key(&mut self, key: u64, prediction: usize) {
         // TODO Likely limit size of set to 5, that's what original implementation does.
         // TODO I think that the cloning about this isn't great, but these should be small sets so, I'll accept it for now.
 
-        let key = hash(ray);
         let set_maybe = self.prediction_table.get(&key);
         if let Some(set) = set_maybe {
             // There was an entry for this hash;
@@ -84,30 +275,24 @@ impl Predictor {
 
 impl Drop for Predictor {
     fn drop(&mut self) {
-        let total =
-            self.true_positive_predictions + self.false_positive_predictions + self.no_predictions;
+        let counts = self.counts;
+        let total = counts.total();
         eprintln!("Statistics for BVH/Predictor {:?}", self.id);
         eprintln!("Total rays into BVH::hit(): {}", total);
-        eprintln!(
-            "True positive predictions:  {}",
-            self.true_positive_predictions
-        );
+        eprintln!("True positive predictions:  {}", counts.true_positives);
         eprintln!(
             "Ratio true positive:        {}",
-            self.true_positive_predictions as f32 / total as f32
-        );
-        eprintln!(
-            "False positive predictions: {}",
-            self.false_positive_predictions
+            counts.true_positives as f32 / total as f32
         );
+        eprintln!("False positive predictions: {}", counts.false_positives);
         eprintln!(
             "Ratio false positive:       {}",
-            self.false_positive_predictions as f32 / total as f32
+            counts.false_positives as f32 / total as f32
         );
-        eprintln!("No predictions:             {}", self.no_predictions);
+        eprintln!("No predictions:             {}", counts.no_predictions);
         eprintln!(
             "Ratio no predictions:       {}",
-            self.no_predictions as f32 / total as f32
+            counts.no_predictions as f32 / total as f32
         );
         eprintln!(
             "Table size (number entries): {}",
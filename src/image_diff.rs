@@ -0,0 +1,418 @@
+//! Measuring the differences between two renders of the same image, e.g. with and
+//! without an approximation such as hash-based ray path prediction.
+//!
+//! Two metrics are computed:
+//! * RMSE - The root mean squared error of the linear colors, which is simple and
+//!   comparable across renders, but says little about how visible the differences are.
+//! * FLIP - The perceptual difference metric of Andersson et al., "FLIP: A Difference
+//!   Evaluator for Alternating Images" (2020), which estimates how noticeable the
+//!   differences are when flipping between the images on a display. Each pixel's error
+//!   is between 0 (indistinguishable) and 1. This is LDR-FLIP, so colors are clamped to
+//!   [0, 1] first, as a display would.
+
+use std::{f32::consts::PI, io, path::Path};
+
+use glam::{Mat3, Vec3};
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// The viewing conditions FLIP assumes by default: a 0.7m wide 4K display viewed from
+/// 0.7m away.
+pub const DEFAULT_PIXELS_PER_DEGREE: f32 = 67.0;
+
+/// The linear colors of an image.
+pub struct LinearImage {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major from the top left.
+    pub pixels: Vec<Vec3>,
+}
+
+impl LinearImage {
+    /// Loads the image at `path`. Floating point images (e.g. EXR) are taken to be
+    /// linear, and others (e.g. PNG or PPM) to be sRGB encoded.
+    pub fn load(path: &Path) -> io::Result<LinearImage> {
+        let image = image::open(path).map_err(io::Error::other)?;
+        let linear = matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let image = image.to_rgb32f();
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let color = Vec3::from_array(pixel.0);
+                if linear {
+                    color
+                } else {
+                    srgb_to_linear(color)
+                }
+            })
+            .collect();
+        Ok(LinearImage {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels,
+        })
+    }
+}
+
+/// The result of comparing two images.
+pub struct Comparison {
+    pub rmse: f32,
+    /// The mean of `flip_errors`.
+    pub mean_flip: f32,
+    /// The FLIP error of each pixel, laid out like the images.
+    pub flip_errors: Vec<f32>,
+}
+
+/// Compares images of the same size, as seen with `pixels_per_degree` pixels across each
+/// degree of the viewer's field of view.
+pub fn compare(
+    reference: &LinearImage,
+    test: &LinearImage,
+    pixels_per_degree: f32,
+) -> Result<Comparison, String> {
+    if (reference.width, reference.height) != (test.width, test.height) {
+        return Err(format!(
+            "images differ in size: {}x{} and {}x{}",
+            reference.width, reference.height, test.width, test.height
+        ));
+    }
+    let squared_error: f32 = reference
+        .pixels
+        .iter()
+        .zip(&test.pixels)
+        .map(|(a, b)| (*a - *b).length_squared() / 3.0)
+        .sum();
+    let rmse = (squared_error / reference.pixels.len() as f32).sqrt();
+
+    let flip_errors = flip(reference, test, pixels_per_degree);
+    let mean_flip = flip_errors.iter().sum::<f32>() / flip_errors.len() as f32;
+    Ok(Comparison {
+        rmse,
+        mean_flip,
+        flip_errors,
+    })
+}
+
+/// Writes the per-pixel `errors`, between 0 and 1, as an image colored with the magma
+/// color map, from black for no error through purple and orange to pale yellow.
+pub fn write_heatmap(errors: &[f32], width: usize, height: usize, path: &Path) -> io::Result<()> {
+    const MAGMA: [[f32; 3]; 9] = [
+        [0.0, 0.0, 4.0],
+        [28.0, 16.0, 68.0],
+        [79.0, 18.0, 123.0],
+        [129.0, 37.0, 129.0],
+        [181.0, 54.0, 122.0],
+        [229.0, 80.0, 100.0],
+        [251.0, 135.0, 97.0],
+        [254.0, 194.0, 135.0],
+        [252.0, 253.0, 191.0],
+    ];
+    let image = RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        let error = errors[y as usize * width + x as usize].clamp(0.0, 1.0);
+        let position = error * (MAGMA.len() - 1) as f32;
+        let i = (position as usize).min(MAGMA.len() - 2);
+        let color =
+            Vec3::from_array(MAGMA[i]).lerp(Vec3::from_array(MAGMA[i + 1]), position - i as f32);
+        Rgb(color.round().to_array().map(|c| c as u8))
+    });
+    image.save(path).map_err(io::Error::other)
+}
+
+fn srgb_to_linear(color: Vec3) -> Vec3 {
+    Vec3::from_array(color.to_array().map(|c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }))
+}
+
+// Linear sRGB to CIE XYZ and back, and the D65 white point in XYZ.
+const RGB_TO_XYZ: Mat3 = Mat3::from_cols(
+    Vec3::new(0.412_456_4, 0.212_672_9, 0.019_333_9),
+    Vec3::new(0.357_576_1, 0.715_152_2, 0.119_192),
+    Vec3::new(0.180_437_5, 0.072_175, 0.950_304_1),
+);
+const XYZ_TO_RGB: Mat3 = Mat3::from_cols(
+    Vec3::new(3.240_454_2, -0.969_266, 0.055_643_4),
+    Vec3::new(-1.537_138_5, 1.876_010_8, -0.204_025_9),
+    Vec3::new(-0.498_531_4, 0.041_556, 1.057_225_2),
+);
+const WHITE: Vec3 = Vec3::new(0.950_428_5, 1.0, 1.088_900_4);
+
+/// Converts linear sRGB to YCxCz, an opponent color space in which FLIP models the
+/// eye's sensitivity to detail in luminance and in each pair of opposing colors.
+fn linear_to_ycxcz(color: Vec3) -> Vec3 {
+    let xyz = RGB_TO_XYZ * color / WHITE;
+    Vec3::new(
+        116.0 * xyz.y - 16.0,
+        500.0 * (xyz.x - xyz.y),
+        200.0 * (xyz.y - xyz.z),
+    )
+}
+
+fn ycxcz_to_linear(color: Vec3) -> Vec3 {
+    let y = (color.x + 16.0) / 116.0;
+    let xyz = Vec3::new(color.y / 500.0 + y, y, y - color.z / 200.0) * WHITE;
+    XYZ_TO_RGB * xyz
+}
+
+/// Converts linear sRGB to CIELAB, with FLIP's Hunt adjustment, which reduces the
+/// chroma of darker colors, as they're harder to tell apart.
+fn linear_to_hunt_lab(color: Vec3) -> Vec3 {
+    let f = |t: f32| {
+        let delta: f32 = 6.0 / 29.0;
+        if t > delta.powi(3) {
+            t.cbrt()
+        } else {
+            t / (3.0 * delta * delta) + 4.0 / 29.0
+        }
+    };
+    let xyz = RGB_TO_XYZ * color / WHITE;
+    let l = 116.0 * f(xyz.y) - 16.0;
+    let a = 500.0 * (f(xyz.x) - f(xyz.y));
+    let b = 200.0 * (f(xyz.y) - f(xyz.z));
+    Vec3::new(l, 0.01 * l * a, 0.01 * l * b)
+}
+
+/// The HyAB distance between colors in CIELAB, which suits large color differences.
+fn hyab(a: Vec3, b: Vec3) -> f32 {
+    (a.x - b.x).abs() + ((a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+/// Computes the FLIP error of each pixel of `test` against `reference`.
+fn flip(reference: &LinearImage, test: &LinearImage, pixels_per_degree: f32) -> Vec<f32> {
+    let (width, height) = (reference.width, reference.height);
+    let prepare = |image: &LinearImage| -> Vec<Vec3> {
+        image
+            .pixels
+            .iter()
+            .map(|color| color.clamp(Vec3::ZERO, Vec3::ONE))
+            .collect()
+    };
+    let (reference, test) = (prepare(reference), prepare(test));
+
+    // The color pipeline: blur away the detail the eye can't resolve, then compare.
+    let filter_colors = |image: &[Vec3]| -> Vec<Vec3> {
+        let ycxcz: Vec<Vec3> = image.iter().map(|c| linear_to_ycxcz(*c)).collect();
+        let filtered = csf_filter(&ycxcz, width, height, pixels_per_degree);
+        filtered
+            .iter()
+            .map(|c| linear_to_hunt_lab(ycxcz_to_linear(*c).clamp(Vec3::ZERO, Vec3::ONE)))
+            .collect()
+    };
+    let (reference_lab, test_lab) = (filter_colors(&reference), filter_colors(&test));
+
+    // The feature pipeline: find edges and points in the luminance of each image.
+    let features = |image: &[Vec3]| -> (Vec<f32>, Vec<f32>) {
+        let luminance: Vec<f32> = image.iter().map(|c| (RGB_TO_XYZ * *c).y).collect();
+        detect_features(&luminance, width, height, pixels_per_degree)
+    };
+    let (reference_edges, reference_points) = features(&reference);
+    let (test_edges, test_points) = features(&test);
+
+    const QC: f32 = 0.7;
+    const PC: f32 = 0.4;
+    const PT: f32 = 0.95;
+    const QF: f32 = 0.5;
+    // The largest color difference, between green and blue.
+    let max_difference = hyab(linear_to_hunt_lab(Vec3::Y), linear_to_hunt_lab(Vec3::Z)).powf(QC);
+
+    (0..width * height)
+        .map(|i| {
+            let difference = hyab(reference_lab[i], test_lab[i]).powf(QC);
+            // Compress large differences, which are all very visible, into the top of
+            // the range.
+            let color_error = if difference < PC * max_difference {
+                PT / PC * difference / max_difference
+            } else {
+                PT + (difference - PC * max_difference) / (max_difference - PC * max_difference)
+                    * (1.0 - PT)
+            };
+            let feature_difference = f32::max(
+                (reference_edges[i] - test_edges[i]).abs(),
+                (reference_points[i] - test_points[i]).abs(),
+            );
+            let feature_error = (feature_difference / 2.0f32.sqrt()).powf(QF);
+            color_error.powf(1.0 - feature_error)
+        })
+        .collect()
+}
+
+/// Filters each channel of an image in YCxCz by the eye's contrast sensitivity to it.
+fn csf_filter(image: &[Vec3], width: usize, height: usize, pixels_per_degree: f32) -> Vec<Vec3> {
+    // Each channel's sensitivity is a sum of up to two Gaussians, with weights `a` and
+    // widths `b` in degrees squared.
+    const CHANNELS: [[(f32, f32); 2]; 3] = [
+        [(1.0, 0.0047), (0.0, 1e-5)],
+        [(1.0, 0.0053), (0.0, 1e-5)],
+        [(34.1, 0.04), (13.5, 0.025)],
+    ];
+    let radius = (3.0 * (0.04 / (2.0 * PI * PI)).sqrt() * pixels_per_degree).ceil() as isize;
+
+    let mut filtered = vec![Vec3::ZERO; image.len()];
+    for (channel, gaussians) in CHANNELS.iter().enumerate() {
+        let plane: Vec<f32> = image.iter().map(|c| c[channel]).collect();
+        let mut sum = vec![0.0; image.len()];
+        let mut total_weight = 0.0;
+        for &(a, b) in gaussians {
+            if a == 0.0 {
+                continue;
+            }
+            let kernel: Vec<f32> = (-radius..=radius)
+                .map(|x| {
+                    let x = x as f32 / pixels_per_degree;
+                    (PI / b).sqrt() * (-PI * PI * x * x / b).exp()
+                })
+                .collect();
+            let kernel_sum: f32 = kernel.iter().sum();
+            total_weight += a * kernel_sum * kernel_sum;
+            let blurred = convolve(&plane, width, height, &kernel, &kernel);
+            for (sum, blurred) in sum.iter_mut().zip(blurred) {
+                *sum += a * blurred;
+            }
+        }
+        for (filtered, sum) in filtered.iter_mut().zip(sum) {
+            filtered[channel] = sum / total_weight;
+        }
+    }
+    filtered
+}
+
+/// Finds the strength of edges and of points at each pixel of the `luminance`, at the
+/// scale the eye is most sensitive to them.
+fn detect_features(
+    luminance: &[f32],
+    width: usize,
+    height: usize,
+    pixels_per_degree: f32,
+) -> (Vec<f32>, Vec<f32>) {
+    let sigma = 0.5 * 0.082 * pixels_per_degree;
+    let radius = (3.0 * sigma).ceil() as isize;
+    let offsets = || (-radius..=radius).map(|x| x as f32);
+    let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+
+    let mut blur: Vec<f32> = offsets().map(gaussian).collect();
+    let blur_sum: f32 = blur.iter().sum();
+    blur.iter_mut().for_each(|w| *w /= blur_sum);
+    let edge = balanced(offsets().map(|x| -x * gaussian(x)).collect());
+    let point = balanced(
+        offsets()
+            .map(|x| (x * x / (sigma * sigma) - 1.0) * gaussian(x))
+            .collect(),
+    );
+
+    let magnitude = |kernel: &[f32]| -> Vec<f32> {
+        let along_x = convolve(luminance, width, height, kernel, &blur);
+        let along_y = convolve(luminance, width, height, &blur, kernel);
+        along_x
+            .iter()
+            .zip(along_y)
+            .map(|(x, y)| (x * x + y * y).sqrt())
+            .collect()
+    };
+    (magnitude(&edge), magnitude(&point))
+}
+
+/// Scales the positive weights of the `kernel` to sum to 1 and its negative weights to
+/// sum to -1, so it responds equally to features of either sign.
+fn balanced(mut kernel: Vec<f32>) -> Vec<f32> {
+    let positive: f32 = kernel.iter().filter(|w| **w > 0.0).sum();
+    let negative: f32 = -kernel.iter().filter(|w| **w < 0.0).sum::<f32>();
+    for w in kernel.iter_mut() {
+        *w /= if *w > 0.0 { positive } else { negative };
+    }
+    kernel
+}
+
+/// Convolves the `plane` with the separable kernel given by `kernel_x` and `kernel_y`,
+/// extending its edges outward.
+fn convolve(
+    plane: &[f32],
+    width: usize,
+    height: usize,
+    kernel_x: &[f32],
+    kernel_y: &[f32],
+) -> Vec<f32> {
+    let pass = |input: &[f32], kernel: &[f32], horizontal: bool| -> Vec<f32> {
+        let radius = (kernel.len() / 2) as isize;
+        let mut output = vec![0.0; input.len()];
+        for y in 0..height {
+            for x in 0..width {
+                output[y * width + x] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| {
+                        let offset = k as isize - radius;
+                        let (sx, sy) = if horizontal {
+                            (
+                                (x as isize + offset).clamp(0, width as isize - 1),
+                                y as isize,
+                            )
+                        } else {
+                            (
+                                x as isize,
+                                (y as isize + offset).clamp(0, height as isize - 1),
+                            )
+                        };
+                        weight * input[sy as usize * width + sx as usize]
+                    })
+                    .sum();
+            }
+        }
+        output
+    };
+    let horizontal = pass(plane, kernel_x, true);
+    pass(&horizontal, kernel_y, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{compare, LinearImage, DEFAULT_PIXELS_PER_DEGREE};
+
+    fn image(width: usize, height: usize, color: impl Fn(usize, usize) -> Vec3) -> LinearImage {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| color(x, y))
+            .collect();
+        LinearImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn differences_are_measured() {
+        let gray = image(32, 32, |_, _| Vec3::splat(0.5));
+        let same = compare(&gray, &gray, DEFAULT_PIXELS_PER_DEGREE).unwrap();
+        assert_eq!(same.rmse, 0.0);
+        assert!(same.mean_flip < 1e-4);
+
+        // Blotches are far more visible than a slight change of brightness, but
+        // alternating pixels are too fine to see, and blur together.
+        let brighter = image(32, 32, |_, _| Vec3::splat(0.52));
+        let blotchy = image(32, 32, |x, y| {
+            Vec3::splat(if (x / 4 + y / 4) % 2 == 0 { 0.3 } else { 0.7 })
+        });
+        let fine = image(32, 32, |x, y| {
+            Vec3::splat(if (x + y) % 2 == 0 { 0.3 } else { 0.7 })
+        });
+        let brighter = compare(&gray, &brighter, DEFAULT_PIXELS_PER_DEGREE).unwrap();
+        let blotchy = compare(&gray, &blotchy, DEFAULT_PIXELS_PER_DEGREE).unwrap();
+        let fine = compare(&gray, &fine, DEFAULT_PIXELS_PER_DEGREE).unwrap();
+        assert!((brighter.rmse - 0.02).abs() < 1e-4);
+        assert_eq!(blotchy.rmse, fine.rmse);
+        assert!(brighter.mean_flip < blotchy.mean_flip);
+        assert!(fine.mean_flip < brighter.mean_flip);
+        assert!(blotchy.flip_errors.iter().all(|e| (0.0..=1.0).contains(e)));
+
+        let smaller = image(16, 32, |_, _| Vec3::ZERO);
+        assert!(compare(&gray, &smaller, DEFAULT_PIXELS_PER_DEGREE).is_err());
+    }
+}
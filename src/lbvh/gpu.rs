@@ -0,0 +1,268 @@
+//! Building the hierarchy of a linear BVH with wgpu compute shaders.
+//!
+//! The Morton codes are computed on the GPU and read back to be sorted with the CPU's
+//! parallel sort, then uploaded again for the nodes of the hierarchy to be found.
+
+use std::sync::mpsc;
+
+use glam::Vec3;
+use rayon::prelude::*;
+use wgpu::util::DeviceExt;
+
+use super::{bounds, Hierarchy, LEAF};
+
+const SHADER: &str = include_str!("lbvh.wgsl");
+
+/// The number of invocations in each workgroup, which must match `WORKGROUP_SIZE` in
+/// lbvh.wgsl.
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Builds the hierarchy over objects with the `centroids`, of which there must be at
+/// least two.
+pub(super) fn hierarchy(centroids: &[Vec3]) -> Result<Hierarchy, String> {
+    let gpu = Gpu::new()?;
+    let count = u32::try_from(centroids.len())
+        .ok()
+        .filter(|&count| count < LEAF)
+        .ok_or_else(|| format!("Too many objects: {}", centroids.len()))?;
+
+    let (min, extent) = bounds(centroids);
+    let mut params = Vec::with_capacity(32);
+    params.extend(min.to_array().iter().flat_map(|v| v.to_ne_bytes()));
+    params.extend(count.to_ne_bytes());
+    params.extend(extent.to_array().iter().flat_map(|v| v.to_ne_bytes()));
+    params.extend(0u32.to_ne_bytes());
+    let params = gpu.buffer("params", &params, wgpu::BufferUsages::UNIFORM);
+
+    let centroid_bytes: Vec<u8> = centroids
+        .par_iter()
+        .flat_map_iter(|centroid| centroid.to_array().into_iter().flat_map(f32::to_ne_bytes))
+        .collect();
+    let centroids = gpu.buffer("centroids", &centroid_bytes, wgpu::BufferUsages::STORAGE);
+    drop(centroid_bytes);
+    let codes = gpu.output("codes", count as u64 * 4);
+    let codes = gpu.run(
+        "morton",
+        count,
+        &[(0, &params), (1, &centroids), (2, &codes)],
+        &codes,
+    )?;
+    let codes: Vec<u32> = codes
+        .chunks_exact(4)
+        .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+        .collect();
+
+    let (order, sorted) = super::sort(codes);
+    let sorted: Vec<u8> = sorted.iter().flat_map(|code| code.to_ne_bytes()).collect();
+    let sorted = gpu.buffer("sorted codes", &sorted, wgpu::BufferUsages::STORAGE);
+    let children = gpu.output("children", (count as u64 - 1) * 8);
+    let children = gpu.run(
+        "hierarchy",
+        count - 1,
+        &[(0, &params), (3, &sorted), (4, &children)],
+        &children,
+    )?;
+    let children = children
+        .chunks_exact(8)
+        .map(|bytes| {
+            [
+                u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
+                u32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
+            ]
+        })
+        .collect();
+
+    Ok(Hierarchy { order, children })
+}
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    shader: wgpu::ShaderModule,
+    limits: wgpu::Limits,
+}
+
+impl Gpu {
+    fn new() -> Result<Gpu, String> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or("No GPU adapter found")?;
+        // Ask for as much as the adapter allows, since large scenes need large buffers.
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("BVH builder"),
+                required_features: wgpu::Features::empty(),
+                required_limits: limits.clone(),
+            },
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("lbvh.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        Ok(Gpu {
+            device,
+            queue,
+            shader,
+            limits,
+        })
+    }
+
+    /// A buffer holding the `contents`.
+    fn buffer(&self, label: &str, contents: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage,
+            })
+    }
+
+    /// A buffer of `size` bytes for a shader to write to.
+    fn output(&self, label: &str, size: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Runs the `entry_point` of the shader over `count` invocations with the buffers
+    /// bound at their bindings, and reads back the contents of the `output` buffer.
+    fn run(
+        &self,
+        entry_point: &str,
+        count: u32,
+        bindings: &[(u32, &wgpu::Buffer)],
+        output: &wgpu::Buffer,
+    ) -> Result<Vec<u8>, String> {
+        for (_, buffer) in bindings {
+            let limit = if buffer.usage().contains(wgpu::BufferUsages::UNIFORM) {
+                self.limits.max_uniform_buffer_binding_size
+            } else {
+                self.limits.max_storage_buffer_binding_size
+            };
+            if buffer.size() > limit as u64 {
+                return Err(format!(
+                    "The {} bytes needed for {} exceed the GPU's limit of {}",
+                    buffer.size(),
+                    entry_point,
+                    limit
+                ));
+            }
+        }
+
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &self.shader,
+                entry_point,
+            });
+        let entries: Vec<wgpu::BindGroupEntry> = bindings
+            .iter()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(entry_point),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: output.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Spread the workgroups over rows, as each dimension has a limited number of them.
+        let workgroups = count.div_ceil(WORKGROUP_SIZE);
+        let max_row = self.limits.max_compute_workgroups_per_dimension;
+        let (columns, rows) = if workgroups <= max_row {
+            (workgroups, 1)
+        } else {
+            (max_row, workgroups.div_ceil(max_row))
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(entry_point),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(entry_point),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(columns, rows, 1);
+        }
+        encoder.copy_buffer_to_buffer(output, 0, &readback, 0, output.size());
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                sender.send(result).unwrap()
+            });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        let bytes = readback.slice(..).get_mapped_range().to_vec();
+        readback.unmap();
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+    use wgpu::naga;
+
+    use super::SHADER;
+
+    #[test]
+    fn shader_is_valid() {
+        let module = naga::front::wgsl::parse_str(SHADER).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn matches_cpu_build() {
+        // Within the unit cube and away from the edges of its cells, so that any rounding
+        // of division on the GPU doesn't change which cell they're in.
+        let cell = |i: u32| (i as f32 + 0.5) / 1024.0;
+        let mut centroids: Vec<_> = (0..5000)
+            .map(|i| vec3(cell(i * 37 % 1024), cell(i * 11 % 1024), cell(i % 7)))
+            .collect();
+        centroids.push(Vec3::ZERO);
+        centroids.push(Vec3::ONE);
+        // There may not be a GPU to test with.
+        let Ok(gpu) = super::hierarchy(&centroids) else {
+            return;
+        };
+        let cpu = super::super::hierarchy(&centroids);
+        assert_eq!(gpu.order, cpu.order);
+        assert_eq!(gpu.children, cpu.children);
+    }
+}
@@ -0,0 +1,109 @@
+// Building the hierarchy of a linear BVH on the GPU, in two passes with a sort of the
+// Morton codes between them. These match the CPU build in lbvh/mod.rs.
+
+const WORKGROUP_SIZE: u32 = 256u;
+const LEAF: u32 = 0x80000000u;
+
+struct Params {
+    // The corner of the box around the centroids, and its size along each axis.
+    min: vec3<f32>,
+    // The number of objects.
+    count: u32,
+    extent: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// The centroid of each object, as consecutive x, y and z coordinates.
+@group(0) @binding(1) var<storage, read> centroids: array<f32>;
+@group(0) @binding(2) var<storage, read_write> codes: array<u32>;
+@group(0) @binding(3) var<storage, read> sorted_codes: array<u32>;
+@group(0) @binding(4) var<storage, read_write> children: array<vec2<u32>>;
+
+// The index of an invocation, in dispatches spread over rows of workgroups so that
+// they're not limited to the number of workgroups per dimension.
+fn invocation(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * WORKGROUP_SIZE;
+}
+
+fn spread_bits(value: u32) -> u32 {
+    var v = (value | (value << 16u)) & 0x030000FFu;
+    v = (v | (v << 8u)) & 0x0300F00Fu;
+    v = (v | (v << 4u)) & 0x030C30C3u;
+    return (v | (v << 2u)) & 0x09249249u;
+}
+
+@compute @workgroup_size(256)
+fn morton(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = invocation(id, groups);
+    if index >= params.count {
+        return;
+    }
+    let centroid = vec3(centroids[3u * index], centroids[3u * index + 1u], centroids[3u * index + 2u]);
+    let position = (centroid - params.min) / params.extent;
+    let quantized = clamp(position * 1024.0, vec3(0.0), vec3(1023.0));
+    codes[index] = (spread_bits(u32(quantized.x)) << 2u)
+        | (spread_bits(u32(quantized.y)) << 1u)
+        | spread_bits(u32(quantized.z));
+}
+
+fn common_prefix(i: i32, j: i32) -> i32 {
+    if j < 0 || j >= i32(params.count) {
+        return -1;
+    }
+    let a = sorted_codes[i];
+    let b = sorted_codes[j];
+    if a == b {
+        return 32 + i32(countLeadingZeros(u32(i) ^ u32(j)));
+    }
+    return i32(countLeadingZeros(a ^ b));
+}
+
+@compute @workgroup_size(256)
+fn hierarchy(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = invocation(id, groups);
+    if index + 1u >= params.count {
+        return;
+    }
+    let i = i32(index);
+    let d = sign(common_prefix(i, i + 1) - common_prefix(i, i - 1));
+
+    let min_prefix = common_prefix(i, i - d);
+    var max_length = 2;
+    while common_prefix(i, i + max_length * d) > min_prefix {
+        max_length *= 2;
+    }
+    var length = 0;
+    var step = max_length / 2;
+    while step >= 1 {
+        if common_prefix(i, i + (length + step) * d) > min_prefix {
+            length += step;
+        }
+        step /= 2;
+    }
+    let j = i + length * d;
+
+    let node_prefix = common_prefix(i, j);
+    var split = 0;
+    var divisor = 2;
+    loop {
+        let split_step = (length + divisor - 1) / divisor;
+        if common_prefix(i, i + (split + split_step) * d) > node_prefix {
+            split += split_step;
+        }
+        if split_step == 1 {
+            break;
+        }
+        divisor *= 2;
+    }
+    let gamma = i + split * d + min(d, 0);
+
+    var left = u32(gamma);
+    if min(i, j) == gamma {
+        left |= LEAF;
+    }
+    var right = u32(gamma + 1);
+    if max(i, j) == gamma + 1 {
+        right |= LEAF;
+    }
+    children[index] = vec2(left, right);
+}
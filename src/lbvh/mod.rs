@@ -0,0 +1,215 @@
+//! Building the hierarchy of a BVH from Morton codes, as a linear BVH (LBVH).
+//!
+//! Objects are sorted along a Z-order curve through their centroids, and each internal
+//! node is then found from the sorted codes independently of every other node, following
+//! Karras, "Maximizing Parallelism in the Construction of BVHs, Octrees, and k-d Trees"
+//! (2012). The hierarchy is less tuned to the objects than one built by splitting them
+//! recursively, but every step of building it is parallel, so it builds quickly across
+//! cores, or on the GPU with the `gpu` feature.
+
+#[cfg(feature = "gpu")]
+mod gpu;
+
+use glam::Vec3;
+use rayon::prelude::*;
+
+/// Set on a child of an internal node if it's a leaf, i.e. an object rather than another
+/// internal node.
+pub(crate) const LEAF: u32 = 1 << 31;
+
+/// The shape of a BVH over `n` objects.
+pub(crate) struct Hierarchy {
+    /// The objects in the order of their Morton codes, as indices of the original objects.
+    pub order: Vec<usize>,
+    /// The left and right children of each of the `n - 1` internal nodes, the first of
+    /// which is the root. Leaf children are indices into `order`, marked with `LEAF`.
+    pub children: Vec<[u32; 2]>,
+}
+
+/// Builds the hierarchy over objects with the `centroids`, of which there must be at
+/// least two, on the CPU.
+pub(crate) fn hierarchy(centroids: &[Vec3]) -> Hierarchy {
+    let (min, extent) = bounds(centroids);
+    let codes: Vec<u32> = centroids
+        .par_iter()
+        .map(|centroid| morton_code((*centroid - min) / extent))
+        .collect();
+    let (order, codes) = sort(codes);
+    let children = (0..codes.len() - 1)
+        .into_par_iter()
+        .map(|node| internal_node(&codes, node))
+        .collect();
+    Hierarchy { order, children }
+}
+
+/// Builds the hierarchy over objects with the `centroids` on the GPU, or returns `None`
+/// if there's no GPU to build it on.
+pub(crate) fn hierarchy_on_gpu(centroids: &[Vec3]) -> Option<Hierarchy> {
+    #[cfg(feature = "gpu")]
+    match gpu::hierarchy(centroids) {
+        Ok(hierarchy) => Some(hierarchy),
+        Err(e) => {
+            eprintln!("Couldn't build the BVH on the GPU: {}", e);
+            None
+        }
+    }
+    #[cfg(not(feature = "gpu"))]
+    {
+        let _ = centroids;
+        eprintln!("Building BVHs on the GPU requires the gpu feature.");
+        None
+    }
+}
+
+/// The corner of the box around the `centroids`, and the size of the box along each axis,
+/// kept above zero so that positions within it can be divided by it.
+fn bounds(centroids: &[Vec3]) -> (Vec3, Vec3) {
+    let (min, max) = centroids
+        .par_iter()
+        .fold(
+            || (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), centroid| (min.min(*centroid), max.max(*centroid)),
+        )
+        .reduce(
+            || (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)),
+        );
+    (min, (max - min).max(Vec3::splat(f32::MIN_POSITIVE)))
+}
+
+/// Sorts the `codes`, returning the original index of each sorted code and the codes.
+fn sort(codes: Vec<u32>) -> (Vec<usize>, Vec<u32>) {
+    let mut order: Vec<usize> = (0..codes.len()).collect();
+    order.par_sort_unstable_by_key(|&index| (codes[index], index));
+    let sorted = order.iter().map(|&index| codes[index]).collect();
+    (order, sorted)
+}
+
+/// The 30-bit Morton code of a `position` within the unit cube, interleaving 10 bits
+/// of each of its coordinates.
+fn morton_code(position: Vec3) -> u32 {
+    let quantized = (position * 1024.0).clamp(Vec3::ZERO, Vec3::splat(1023.0));
+    (spread_bits(quantized.x as u32) << 2)
+        | (spread_bits(quantized.y as u32) << 1)
+        | spread_bits(quantized.z as u32)
+}
+
+/// Spreads the lower 10 bits of `v` out to every third bit.
+fn spread_bits(v: u32) -> u32 {
+    let v = (v | (v << 16)) & 0x030000FF;
+    let v = (v | (v << 8)) & 0x0300F00F;
+    let v = (v | (v << 4)) & 0x030C30C3;
+    (v | (v << 2)) & 0x09249249
+}
+
+/// The length of the prefix which the `i`th and `j`th sorted `codes` share, or -1 if `j`
+/// is out of range. Equal codes are told apart by their indices, as if the indices were
+/// appended to them.
+fn common_prefix(codes: &[u32], i: i64, j: i64) -> i64 {
+    if j < 0 || j >= codes.len() as i64 {
+        return -1;
+    }
+    let (a, b) = (codes[i as usize], codes[j as usize]);
+    if a == b {
+        32 + (i as u32 ^ j as u32).leading_zeros() as i64
+    } else {
+        (a ^ b).leading_zeros() as i64
+    }
+}
+
+/// The children of the `node`th internal node, over the sorted `codes`.
+///
+/// This must match the `hierarchy` entry point in lbvh.wgsl.
+fn internal_node(codes: &[u32], node: usize) -> [u32; 2] {
+    let i = node as i64;
+    // Which way the node's range of codes extends from i.
+    let d = (common_prefix(codes, i, i + 1) - common_prefix(codes, i, i - 1)).signum();
+
+    // Find the other end of the range: a power of two past it, then search back to it.
+    let min_prefix = common_prefix(codes, i, i - d);
+    let mut max_length = 2;
+    while common_prefix(codes, i, i + max_length * d) > min_prefix {
+        max_length *= 2;
+    }
+    let mut length = 0;
+    let mut step = max_length / 2;
+    while step >= 1 {
+        if common_prefix(codes, i, i + (length + step) * d) > min_prefix {
+            length += step;
+        }
+        step /= 2;
+    }
+    let j = i + length * d;
+
+    // Split where the range's codes first differ in the bit after their common prefix.
+    let node_prefix = common_prefix(codes, i, j);
+    let mut split = 0;
+    let mut divisor = 2;
+    loop {
+        let step = (length + divisor - 1) / divisor;
+        if common_prefix(codes, i, i + (split + step) * d) > node_prefix {
+            split += step;
+        }
+        if step == 1 {
+            break;
+        }
+        divisor *= 2;
+    }
+    let gamma = i + split * d + d.min(0);
+
+    let left = if i.min(j) == gamma {
+        gamma as u32 | LEAF
+    } else {
+        gamma as u32
+    };
+    let right = if i.max(j) == gamma + 1 {
+        (gamma + 1) as u32 | LEAF
+    } else {
+        (gamma + 1) as u32
+    };
+    [left, right]
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::{hierarchy, Hierarchy, LEAF};
+
+    /// Visits the subtree under the `child`, counting how often each leaf is reached and
+    /// returning the range of leaves it covers.
+    fn visit(hierarchy: &Hierarchy, child: u32, reached: &mut [u32]) -> (usize, usize) {
+        if child & LEAF != 0 {
+            let leaf = (child & !LEAF) as usize;
+            reached[leaf] += 1;
+            return (leaf, leaf);
+        }
+        let [left, right] = hierarchy.children[child as usize];
+        let (first, left_last) = visit(hierarchy, left, reached);
+        let (right_first, last) = visit(hierarchy, right, reached);
+        // Each node covers a contiguous range of the sorted objects.
+        assert_eq!(left_last + 1, right_first);
+        (first, last)
+    }
+
+    #[test]
+    fn hierarchy_reaches_each_object_once() {
+        // A grid of points, with some repeated so that their codes are equal.
+        let mut centroids = Vec::new();
+        for i in 0..300 {
+            centroids.push(vec3((i % 7) as f32, (i % 11) as f32, (i / 77) as f32));
+        }
+        centroids.push(Vec3::ZERO);
+        centroids.push(Vec3::ZERO);
+
+        let hierarchy = hierarchy(&centroids);
+        assert_eq!(hierarchy.children.len(), centroids.len() - 1);
+        let mut reached = vec![0; centroids.len()];
+        assert_eq!(visit(&hierarchy, 0, &mut reached), (0, centroids.len() - 1));
+        assert!(reached.iter().all(|&count| count == 1));
+
+        let mut order = hierarchy.order.clone();
+        order.sort();
+        assert!(order.into_iter().eq(0..centroids.len()));
+    }
+}
@@ -1,11 +1,38 @@
 mod aabb;
+pub mod background;
+pub mod batch;
+pub mod bounding_sphere;
 pub mod bvh;
 pub mod camera;
+pub mod caustics;
+mod denoise;
+pub mod depth_map;
+pub mod film;
+pub mod filter;
+mod first_bounce;
+pub mod furnace;
+mod gbuffer;
 pub mod geometry;
 pub mod hittable;
 pub mod hrpp;
+pub mod image_diff;
+mod lbvh;
+pub mod loading;
+pub mod lpe;
 pub mod materials;
+pub mod monitor;
+pub mod path_export;
+mod precision;
+pub mod primaries;
+pub mod random;
 mod ray;
 pub mod renderer;
+pub mod sampler;
+pub mod scenes;
+pub mod slice;
+pub mod sun;
 pub mod textures;
+mod tile_costs;
+pub mod tile_scheduler;
+pub mod units;
 mod utils;
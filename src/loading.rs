@@ -0,0 +1,151 @@
+//! Loading a scene's files concurrently, so that large meshes and images load alongside
+//! each other and alongside building the rest of the scene, rather than one after another
+//! before the render can start.
+//!
+//! Each load runs on its own thread from when it's started until it's waited on, and
+//! reports when it finishes, along with how many of the loads started so far are done.
+//!
+//! When several scenes are built over a run, e.g. by the jobs of a batch, what's loaded
+//! can be cached and shared between them rather than loaded again. See `enable_cache()`.
+
+use std::{
+    any::Any,
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use ahash::AHashMap;
+
+/// The number of loads started, and finished, over the run.
+static STARTED: AtomicUsize = AtomicUsize::new(0);
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+/// What's been loaded by `Pending::spawn_cached()`, by key, if caching is enabled.
+type Cache = AHashMap<String, Arc<dyn Any + Send + Sync>>;
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+/// Keeps what's loaded with `Pending::spawn_cached()` for the rest of the run, so that
+/// later loads with the same key reuse it. This is off by default, as a single scene
+/// would only hold on to memory it never uses again.
+pub fn enable_cache() {
+    CACHE.lock().unwrap().get_or_insert_with(AHashMap::new);
+}
+
+/// A file being loaded in the background.
+pub struct Pending<T> {
+    state: State<T>,
+}
+
+enum State<T> {
+    Loading(JoinHandle<T>),
+    /// Taken from the cache, so there's nothing to wait for.
+    Cached(T),
+}
+
+impl<T: Send + 'static> Pending<T> {
+    /// Starts loading `name` with `load` on a thread of its own. Loads shouldn't draw
+    /// random numbers, as they don't share the seeded random stream of the thread which
+    /// started them.
+    pub fn spawn(name: impl Into<String>, load: impl FnOnce() -> T + Send + 'static) -> Pending<T> {
+        let name = name.into();
+        STARTED.fetch_add(1, Ordering::Relaxed);
+        let handle = thread::Builder::new()
+            .name(format!("load {}", name))
+            .spawn(move || {
+                let start = Instant::now();
+                let loaded = load();
+                let finished = FINISHED.fetch_add(1, Ordering::Relaxed) + 1;
+                eprintln!(
+                    "Loaded {} in {:.2?} ({}/{} loads done)",
+                    name,
+                    start.elapsed(),
+                    finished,
+                    STARTED.load(Ordering::Relaxed)
+                );
+                loaded
+            })
+            .expect("Failed to start a loading thread");
+        Pending {
+            state: State::Loading(handle),
+        }
+    }
+
+    /// Waits for the load to finish and returns what it loaded. If the load panicked,
+    /// e.g. because the file was missing, the panic carries on here.
+    pub fn wait(self) -> T {
+        match self.state {
+            State::Loading(handle) => handle
+                .join()
+                .unwrap_or_else(|panic| panic::resume_unwind(panic)),
+            State::Cached(loaded) => loaded,
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Pending<T> {
+    /// As `spawn()`, but if caching is enabled, reuses whatever was loaded earlier in the
+    /// run with the same `key`, which should be the file's name and any settings which
+    /// change what's loaded from it.
+    pub fn spawn_cached(
+        name: impl Into<String>,
+        key: impl Into<String>,
+        load: impl FnOnce() -> T + Send + 'static,
+    ) -> Pending<T> {
+        let key = key.into();
+        let cached = match CACHE.lock().unwrap().as_ref() {
+            Some(cache) => cache.get(&key).cloned(),
+            None => return Pending::spawn(name, load),
+        };
+        if let Some(loaded) = cached.as_deref().and_then(|any| any.downcast_ref::<T>()) {
+            eprintln!("Reusing {}", name.into());
+            return Pending {
+                state: State::Cached(loaded.clone()),
+            };
+        }
+        Pending::spawn(name, move || {
+            let loaded = load();
+            if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+                cache.insert(key, Arc::new(loaded.clone()));
+            }
+            loaded
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::{enable_cache, Pending};
+
+    #[test]
+    fn loads_run_at_the_same_time() {
+        // Each load waits to hear from the other, which it only can if both are running.
+        let (to_first, from_second) = mpsc::channel();
+        let (to_second, from_first) = mpsc::channel();
+        let first = Pending::spawn("first", move || {
+            to_second.send(()).unwrap();
+            from_second.recv_timeout(Duration::from_secs(10)).is_ok()
+        });
+        let second = Pending::spawn("second", move || {
+            to_first.send(()).unwrap();
+            from_first.recv_timeout(Duration::from_secs(10)).is_ok()
+        });
+        assert!(second.wait());
+        assert!(first.wait());
+    }
+
+    #[test]
+    fn cached_loads_are_reused() {
+        enable_cache();
+        let first = Pending::spawn_cached("first", "loading test", || 1).wait();
+        // The key was loaded already, so this load never runs.
+        let second = Pending::spawn_cached("second", "loading test", || 2).wait();
+        assert_eq!((first, second), (1, 1));
+    }
+}
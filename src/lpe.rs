@@ -0,0 +1,242 @@
+//! Light path expressions (LPEs), for splitting the light arriving at the camera into
+//! separate passes by the path it took.
+//!
+//! Each path is described by a string of events, starting at the camera:
+//! * `C` - The camera.
+//! * `D` - A diffuse scattering event, at a rough surface or in a volume.
+//! * `S` - A specular scattering event, i.e. a reflection or refraction.
+//! * `L` - Emission from a light, which ends the path.
+//! * `B` - Light from the background, which ends the path.
+//!
+//! An expression is a regular expression over these events, which may use `.` for any
+//! event, `[...]` and `[^...]` for sets of events, `(...)` for grouping, `|` for
+//! alternatives, and the `*`, `+` and `?` quantifiers. For example:
+//! * `CL` - Lights seen directly by the camera.
+//! * `CDL` - Direct lighting on diffuse surfaces.
+//! * `CD.+[LB]` - Indirect lighting on diffuse surfaces.
+//! * `CDS+[LB]` - Caustics: light focused by specular surfaces onto diffuse ones.
+//! * `CS.*[LB]` - Everything seen in reflections and refractions.
+
+use std::{iter::Peekable, str::Chars};
+
+const EVENTS: [char; 5] = ['C', 'D', 'S', 'L', 'B'];
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// Matches a single event from the set, given as a mask over `EVENTS`.
+    Event([bool; 5]),
+    Sequence(Vec<Node>),
+    Alternatives(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// A parsed light path expression.
+#[derive(Clone, Debug)]
+pub struct LightPathExpression {
+    root: Node,
+}
+
+impl LightPathExpression {
+    /// Parses an expression such as "CD.+L". Whitespace is ignored.
+    pub fn parse(expression: &str) -> Result<LightPathExpression, String> {
+        let mut chars = expression.chars().peekable();
+        let root = parse_alternatives(&mut chars)?;
+        if let Some(c) = chars.next() {
+            return Err(format!("unexpected '{}' in '{}'", c, expression));
+        }
+        Ok(LightPathExpression { root })
+    }
+
+    /// Whether the expression matches the whole of the path described by `events`.
+    pub fn matches(&self, events: &str) -> bool {
+        let events: Vec<usize> = events
+            .chars()
+            .filter_map(|c| EVENTS.iter().position(|e| *e == c))
+            .collect();
+        match_node(&self.root, &events, 0, &mut |end| end == events.len())
+    }
+}
+
+fn parse_alternatives(chars: &mut Peekable<Chars>) -> Result<Node, String> {
+    let mut alternatives = vec![parse_sequence(chars)?];
+    while chars.peek() == Some(&'|') {
+        chars.next();
+        alternatives.push(parse_sequence(chars)?);
+    }
+    if alternatives.len() == 1 {
+        Ok(alternatives.pop().unwrap())
+    } else {
+        Ok(Node::Alternatives(alternatives))
+    }
+}
+
+fn parse_sequence(chars: &mut Peekable<Chars>) -> Result<Node, String> {
+    let mut sequence = Vec::new();
+    while let Some(c) = chars.peek().copied() {
+        if c == '|' || c == ')' {
+            break;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let atom = parse_atom(chars)?;
+        let quantifier = match chars.peek() {
+            Some('*') => Some((0, None)),
+            Some('+') => Some((1, None)),
+            Some('?') => Some((0, Some(1))),
+            _ => None,
+        };
+        match quantifier {
+            Some((min, max)) => {
+                chars.next();
+                sequence.push(Node::Repeat {
+                    node: Box::new(atom),
+                    min,
+                    max,
+                });
+            }
+            None => sequence.push(atom),
+        }
+    }
+    Ok(Node::Sequence(sequence))
+}
+
+fn parse_atom(chars: &mut Peekable<Chars>) -> Result<Node, String> {
+    match chars.next() {
+        Some('.') => Ok(Node::Event([true; 5])),
+        Some('(') => {
+            let node = parse_alternatives(chars)?;
+            match chars.next() {
+                Some(')') => Ok(node),
+                _ => Err(String::from("missing ')'")),
+            }
+        }
+        Some('[') => {
+            let negated = chars.peek() == Some(&'^');
+            if negated {
+                chars.next();
+            }
+            let mut set = [false; 5];
+            loop {
+                match chars.next() {
+                    Some(']') => break,
+                    Some(c) => set[event_index(c)?] = true,
+                    None => return Err(String::from("missing ']'")),
+                }
+            }
+            if negated {
+                set = set.map(|in_set| !in_set);
+            }
+            Ok(Node::Event(set))
+        }
+        Some(c) => {
+            let mut set = [false; 5];
+            set[event_index(c)?] = true;
+            Ok(Node::Event(set))
+        }
+        None => Err(String::from("unexpected end of expression")),
+    }
+}
+
+fn event_index(c: char) -> Result<usize, String> {
+    EVENTS
+        .iter()
+        .position(|e| *e == c)
+        .ok_or_else(|| format!("unknown event '{}'; expected one of C, D, S, L, B", c))
+}
+
+/// Matches `node` against `events` starting at `pos`, calling `rest` with each position
+/// at which the match could end until it returns true.
+fn match_node(
+    node: &Node,
+    events: &[usize],
+    pos: usize,
+    rest: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    match node {
+        Node::Event(set) => pos < events.len() && set[events[pos]] && rest(pos + 1),
+        Node::Sequence(nodes) => match_sequence(nodes, events, pos, rest),
+        Node::Alternatives(nodes) => nodes
+            .iter()
+            .any(|node| match_node(node, events, pos, &mut *rest)),
+        Node::Repeat { node, min, max } => match_repeat(node, *min, *max, events, pos, rest),
+    }
+}
+
+fn match_sequence(
+    nodes: &[Node],
+    events: &[usize],
+    pos: usize,
+    rest: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    match nodes.split_first() {
+        None => rest(pos),
+        Some((first, others)) => match_node(first, events, pos, &mut |next| {
+            match_sequence(others, events, next, &mut *rest)
+        }),
+    }
+}
+
+fn match_repeat(
+    node: &Node,
+    min: usize,
+    max: Option<usize>,
+    events: &[usize],
+    pos: usize,
+    rest: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    // Greedily take another repetition if allowed, requiring progress to avoid looping.
+    if max != Some(0)
+        && match_node(node, events, pos, &mut |next| {
+            next > pos
+                && match_repeat(
+                    node,
+                    min.saturating_sub(1),
+                    max.map(|max| max - 1),
+                    events,
+                    next,
+                    &mut *rest,
+                )
+        })
+    {
+        return true;
+    }
+    min == 0 && rest(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LightPathExpression;
+
+    #[test]
+    fn matches_whole_paths() {
+        let direct_diffuse = LightPathExpression::parse("CDL").unwrap();
+        assert!(direct_diffuse.matches("CDL"));
+        assert!(!direct_diffuse.matches("CDDL"));
+
+        let indirect = LightPathExpression::parse("CD.+[LB]").unwrap();
+        assert!(indirect.matches("CDDL"));
+        assert!(indirect.matches("CDSSB"));
+        assert!(!indirect.matches("CDL"));
+
+        let caustics = LightPathExpression::parse("C D S+ (L|B)").unwrap();
+        assert!(caustics.matches("CDSSL"));
+        assert!(!caustics.matches("CDSDL"));
+
+        let not_specular = LightPathExpression::parse("C[^S]*L").unwrap();
+        assert!(not_specular.matches("CL"));
+        assert!(not_specular.matches("CDDL"));
+        assert!(!not_specular.matches("CDSL"));
+    }
+
+    #[test]
+    fn rejects_unknown_events() {
+        assert!(LightPathExpression::parse("CXL").is_err());
+        assert!(LightPathExpression::parse("C(DL").is_err());
+    }
+}
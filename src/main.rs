@@ -1,36 +1,44 @@
-use ahash::AHashMap;
-use shimmer::bvh::{Bvh, BvhId};
-use shimmer::camera::Camera;
-use shimmer::geometry::cube::Cube;
-use shimmer::geometry::instance::{RotateY, Translate};
-use shimmer::geometry::moving_sphere::MovingSphere;
-use shimmer::geometry::rectangle::{XyRect, XzRect, YzRect};
-use shimmer::geometry::sphere::Sphere;
-use shimmer::geometry::triangle::Tri;
-use shimmer::hittable::{ConstantMedium, HittableList};
-use shimmer::hrpp::Predictor;
-use shimmer::materials::diffuse_light::DiffuseLight;
+use shimmer::background::Background;
+use shimmer::batch;
+use shimmer::bvh::{self, Bvh, BvhBuild, Traversal};
+use shimmer::camera::{Camera, MotionBlur};
+use shimmer::caustics::CausticParams;
+use shimmer::depth_map::{DepthMap, OrthographicView};
+use shimmer::filter::Filter;
+use shimmer::furnace;
+use shimmer::geometry::decimate::DecimationTarget;
+use shimmer::geometry::material_override::MaterialOverride;
+use shimmer::geometry::mesh::MeshImport;
+use shimmer::hittable::HittableList;
+use shimmer::image_diff::{self, LinearImage};
+use shimmer::loading::{self, Pending};
+use shimmer::lpe::LightPathExpression;
 use shimmer::materials::{
-    dialectric::Dialectric,
-    lambertian::Lambertian,
-    material::Material,
-    metal::Metal,
-    utils::{random_color, random_color_range},
+    dialectric::Dialectric, lambertian::Lambertian, material::Material, metal::Metal,
+    reloadable::Reloadable, wireframe::Wireframe,
 };
-use shimmer::renderer::Renderer;
-use shimmer::textures::checker::Checker;
+use shimmer::monitor::RenderMonitor;
+use shimmer::path_export::{self, PixelRegion};
+use shimmer::primaries::Primaries;
+use shimmer::renderer::{RenderSettings, Renderer};
+use shimmer::sampler::Sampler;
+use shimmer::scenes::{self, RandomSpheresParams, ShowcaseParams, SparksParams};
+use shimmer::slice::{self, Slice};
+use shimmer::textures::graph::TextureGraph;
 use shimmer::textures::image_texture::ImageTexture;
+use shimmer::textures::panorama::PanoramaLayout;
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use glam::{vec3, Vec3};
-use tobj::LoadOptions;
 
-use rand::{random, Rng};
-use shimmer::textures::marble::Marble;
-use std::fmt;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::fs::{self, File};
+use std::io;
+use std::iter;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(ValueEnum, Clone)]
 enum Scene {
@@ -44,15 +52,160 @@ enum Scene {
     CornellSmoke,
     Showcase,
     Bunny,
+    FurryBunny,
     Gargoyle,
     IgeaHrpp,
+    Ocean,
+    Sparks,
+    /// A MagicaVoxel model given by --vox-file, on a ground plane.
+    Voxels,
+}
+
+#[derive(ValueEnum, Clone)]
+enum OverrideMaterial {
+    /// A neutral gray Lambertian.
+    Clay,
+    /// Unlit triangle edges over a flat base, for inspecting mesh topology.
+    Wireframe,
+}
+
+#[derive(ValueEnum, Clone)]
+enum PixelFilter {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    BlackmanHarris,
+}
+
+#[derive(ValueEnum, Clone)]
+enum PixelSampler {
+    /// Each sample's position in the pixel, on the lens and in time is drawn independently.
+    Independent,
+    /// A pixel's samples are spread evenly over the pixel, the lens and the shutter
+    /// interval together, which reduces noise in depth of field and motion blur.
+    Stratified,
+}
+
+#[derive(ValueEnum, Clone)]
+enum BvhBuilder {
+    /// Recursive median splits along random axes.
+    Median,
+    /// Recursive median splits along the axis the objects are spread farthest over.
+    LongestAxis,
+    /// A linear BVH over Morton codes, built in parallel.
+    Morton,
+    /// A linear BVH over Morton codes, built on the GPU.
+    MortonGpu,
+}
+
+#[derive(ValueEnum, Clone)]
+enum BvhTraversal {
+    /// Recursing into the children of each node.
+    Recursive,
+    /// Visiting nodes from a stack, skipping those beyond the closest hit so far.
+    Stack,
+    /// As stack, visiting the nearer child of each node first.
+    Ordered,
+    /// Using hash-based ray path prediction where a BVH has a predictor.
+    Hrpp,
+}
+
+#[derive(ValueEnum, Clone)]
+enum BackgroundLayout {
+    /// Longitude across and latitude down.
+    Equirectangular,
+    /// A light probe's angular map, looking forward at its center.
+    AngularMap,
+    /// A horizontal cross of cube faces, four wide and three tall.
+    Cubemap,
+}
+
+#[derive(ValueEnum, Clone)]
+enum FurnaceMaterial {
+    Lambertian,
+    /// Swept over fuzz values from 0 to 1.
+    Metal,
+    Dielectric,
+}
+
+#[derive(ValueEnum, Clone)]
+enum OutputPrimaries {
+    Srgb,
+    DisplayP3,
+    Rec2020,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compares two renders of the same image, printing their RMSE and mean FLIP error.
+    /// EXR images are read as linear colors and other formats as sRGB.
+    Diff {
+        /// The image to compare against, e.g. a render with many samples or exact settings.
+        reference: PathBuf,
+        /// The image with approximations to measure.
+        test: PathBuf,
+        /// If set, writes an image of the FLIP error of each pixel to this file.
+        #[arg(long)]
+        heatmap: Option<PathBuf>,
+        /// Pixels across each degree of the viewer's field of view, which determines how
+        /// fine a difference FLIP considers visible.
+        #[arg(long, default_value_t = image_diff::DEFAULT_PIXELS_PER_DEGREE)]
+        pixels_per_degree: f32,
+    },
+    /// Puts the slices of an image rendered with --slice back together, writing the whole
+    /// image as a PPM.
+    Assemble {
+        /// The PPM images of every slice, in any order.
+        #[arg(required = true)]
+        slices: Vec<PathBuf>,
+        /// Writes the image to this PPM file instead of stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Renders the jobs listed in a TOML manifest one after another, e.g. as an overnight
+    /// queue. Meshes and images loaded by one job are reused by later ones.
+    ///
+    /// Each [[job]] table gives a scene and the options to render it with, named as on
+    /// the command line, and a [defaults] table gives options for every job, e.g.
+    ///
+    ///   [defaults]
+    ///   samples-per-pixel = 500
+    ///   image-width = 800
+    ///
+    ///   [[job]]
+    ///   scene = "cornell"
+    ///   camera = ["front", "top"]
+    ///   output = "cornell.ppm"
+    ///
+    ///   [[job]]
+    ///   scene = "bunny"
+    ///   cam-look-from = [278, 278, -800]
+    ///   cam-look-at = [278, 278, 0]
+    ///   output = "bunny.ppm"
+    ///
+    /// Flags are set with true. Arrays of numbers give options which take several values,
+    /// and arrays of strings repeat the option once for each. A job which fails is
+    /// reported, and the rest still run.
+    Batch {
+        /// The manifest listing the jobs.
+        manifest: PathBuf,
+    },
 }
 
 #[derive(Parser)]
-#[clap(author, version, about)]
+#[clap(
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    #[clap(value_enum)]
-    scene: Scene,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[clap(value_enum, required_unless_present = "furnace")]
+    scene: Option<Scene>,
     /// Image width; image height is determined by this value and the aspect ratio.
     #[arg(short = 'w', long, default_value = "1080")]
     image_width: usize,
@@ -100,12 +253,384 @@ struct Cli {
     /// Camera shutter close time.
     #[arg(long, default_value = "0.0")]
     cam_end_time: f32,
+    /// If set, turns motion blur off and renders a sharp still of the scene at this time,
+    /// whatever the shutter interval. Moving objects are only found between 0 and 1.
+    #[arg(long)]
+    still_time: Option<f32>,
+    /// If set, serves render progress over HTTP on this port (e.g. view http://localhost:8080).
+    #[arg(long)]
+    monitor_port: Option<u16>,
+    /// The address the render monitor listens on. Only this machine can view it by default;
+    /// use 0.0.0.0 to serve it to the network.
+    #[arg(long, default_value = "127.0.0.1", requires = "monitor_port")]
+    monitor_host: String,
+    /// Shows quick previews at 1/8, 1/4 and 1/2 resolution in the monitor before rendering.
+    #[arg(long, requires = "monitor_port")]
+    progressive_preview: bool,
+    /// Denoises the tiles shown in the monitor, so they look clean after few samples.
+    /// The written image is not denoised.
+    #[arg(long, requires = "monitor_port")]
+    denoise_preview: bool,
+    /// Quickly previews the scene by shading camera rays with only the emission and albedo
+    /// of what they hit, without bouncing.
+    #[arg(long)]
+    emission_albedo_only: bool,
+    /// Colors the image by the media (green) and refractive volumes (blue) that camera
+    /// rays enter, with red where they overlap, to debug overlapping volume boundaries.
+    #[arg(long)]
+    volume_debug: bool,
+    /// If set, renders and writes the image in bands of this many rows, so that images
+    /// larger than memory allows can be rendered. Only the PPM image is written.
+    #[arg(long, value_name = "ROWS")]
+    band_height: Option<usize>,
+    /// If set, renders only the Ith of N horizontal slices of the image, numbered from 1 at
+    /// the top, and writes just its rows. Render each slice on its own machine with the
+    /// same options and --seed, then put them together with the assemble subcommand.
+    #[arg(long, value_name = "I/N", value_parser = Slice::parse, conflicts_with = "band_height")]
+    slice: Option<Slice>,
+    /// If set, seeds the random numbers of each sample from this and the sample's pixel, so
+    /// renders are reproducible regardless of tile size or core count.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Caches what each camera ray hits first in this file, and reuses it on later renders
+    /// with the same size, samples and seed. Delete it after moving the camera or geometry.
+    #[arg(long, value_name = "PATH", requires = "seed")]
+    first_bounce_cache: Option<PathBuf>,
+    /// Starts the tiles which took longest in the render which saved this file first, e.g.
+    /// the previous frame of an animation, and saves this render's tile costs to it.
+    /// Without it, tile costs are estimated with a quick low resolution probe.
+    #[arg(long, value_name = "PATH")]
+    tile_costs: Option<PathBuf>,
+    /// If set, sends this many photons out from the scene's lights to light caustics,
+    /// e.g. under glass spheres, which the first diffuse surface seen along each path
+    /// looks up rather than waiting for paths to find the lights through the glass.
+    #[arg(long, value_name = "PHOTONS", conflicts_with = "first_bounce_cache")]
+    caustic_photons: Option<usize>,
+    /// The farthest from a point that caustic photons are looked up, in world units.
+    /// Defaults to 1% of the size of the area the photons land in.
+    #[arg(long, value_name = "RADIUS", requires = "caustic_photons")]
+    caustic_radius: Option<f32>,
+    /// Use the classic blue-to-white gradient sky as the background instead of the scene's.
+    #[arg(long)]
+    gradient_background: bool,
+    /// Path to an image to use as the background instead of the scene's.
+    #[arg(long, conflicts_with = "gradient_background")]
+    background_image: Option<PathBuf>,
+    /// How directions are laid out in the background image. Other layouts are converted
+    /// to equirectangular as the image is loaded.
+    #[arg(
+        long,
+        value_enum,
+        default_value = "equirectangular",
+        requires = "background_image"
+    )]
+    background_layout: BackgroundLayout,
+    /// Degrees to turn the background image about the vertical axis.
+    #[arg(
+        long,
+        default_value = "0.0",
+        allow_negative_numbers = true,
+        requires = "background_image"
+    )]
+    background_yaw: f32,
+    /// Degrees to tilt the background image up or down, after turning it.
+    #[arg(
+        long,
+        default_value = "0.0",
+        allow_negative_numbers = true,
+        requires = "background_image"
+    )]
+    background_pitch: f32,
+    /// If set, also writes the even- and odd-sample half-buffers and their difference
+    /// as EXR images named <PREFIX>_a.exr, <PREFIX>_b.exr and <PREFIX>_diff.exr.
+    #[arg(long, value_name = "PREFIX")]
+    split_buffer_output: Option<PathBuf>,
+    /// If set, also writes the position, normal, albedo and material number of the
+    /// surface seen through each pixel as EXR images named <PREFIX>_position.exr,
+    /// <PREFIX>_normal.exr, <PREFIX>_albedo.exr and <PREFIX>_material.exr.
+    #[arg(long, value_name = "PREFIX")]
+    gbuffer_output: Option<PathBuf>,
+    /// Filter used to reconstruct pixels from samples.
+    #[arg(long, value_enum, default_value = "box")]
+    filter: PixelFilter,
+    /// Radius of the pixel filter, in pixels. Defaults to a typical radius for the filter.
+    #[arg(long)]
+    filter_radius: Option<f32>,
+    /// How the camera rays of each pixel are spread over the pixel, lens and shutter.
+    #[arg(long, value_enum, default_value = "stratified")]
+    sampler: PixelSampler,
+    /// Color primaries of the output images, for displays with a wider gamut than sRGB.
+    #[arg(long, value_enum, default_value = "srgb")]
+    primaries: OutputPrimaries,
+    /// If set, exposes the image at this exposure value at ISO 100 (EV100), as a camera
+    /// would, for scenes whose lights are in physical units. Otherwise colors are
+    /// written unscaled.
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f32>,
+    /// If set, renders one sample per pixel at a time until this much time has passed
+    /// (e.g. "90s", "10m", "2h"), then outputs the image. Samples stop early if
+    /// samples_per_pixel is reached.
+    #[arg(long, value_parser = parse_duration)]
+    max_time: Option<Duration>,
+    /// Also writes the image as it is after each of these numbers of samples per pixel,
+    /// e.g. "16,64,256,1024", to <MILESTONE_OUTPUT>_<N>spp.ppm, so that the quality
+    /// reached at each can be compared from a single render.
+    #[arg(long, value_delimiter = ',', value_name = "SPP")]
+    spp_milestones: Vec<u32>,
+    /// File name prefix for images written by --spp-milestones.
+    #[arg(long, default_value = "milestone")]
+    milestone_output: PathBuf,
+    /// If set, simplifies loaded meshes to at most this many triangles.
+    #[arg(long)]
+    decimate_triangles: Option<usize>,
+    /// If set, simplifies loaded meshes as far as possible without moving any vertex
+    /// further than this distance, in the model's units.
+    #[arg(long, conflicts_with = "decimate_triangles")]
+    decimate_error: Option<f32>,
+    /// If set, loaded meshes without normals are smooth shaded, with normals averaged across
+    /// edges sharper than this angle in degrees left hard. Otherwise they're flat shaded.
+    #[arg(long)]
+    smoothing_angle: Option<f32>,
+    /// How the BVHs over loaded meshes and the showcase's ground of boxes are built. The
+    /// Morton builds are much faster for large meshes, though the resulting BVHs may be
+    /// slower to render.
+    #[arg(long, value_enum, default_value = "median")]
+    bvh_build: BvhBuilder,
+    /// How rays traverse the scene's BVHs, to compare traversal strategies.
+    #[arg(long, value_enum, default_value = "hrpp")]
+    bvh_traversal: BvhTraversal,
+    /// If set, replaces the materials of everything in the scene except lights.
+    #[arg(long, value_enum)]
+    override_material: Option<OverrideMaterial>,
+    /// If set, replaces the materials of everything in the scene except lights with a
+    /// diffuse material colored by the texture graph in this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "override_material")]
+    material_graph: Option<PathBuf>,
+    /// Keeps previewing in the monitor after rendering: whenever the --material-graph file
+    /// changes, its material is swapped in without rebuilding the scene's BVHs, and the
+    /// image is rendered again from the first sample. Runs until interrupted.
+    #[arg(long, requires_all = ["material_graph", "monitor_port"])]
+    watch_material_graph: bool,
+    /// Renders the scene's top-level objects as a plain list, testing each ray against
+    /// every one of them, instead of putting them in a BVH. Always the case with
+    /// --first-bounce-cache.
+    #[arg(long)]
+    no_top_level_bvh: bool,
+    /// x, y, width, height
+    /// Instead of rendering, records the path of every sample through this region of pixels
+    /// (with 0, 0 at the top left) and writes them to <PATH_OUTPUT>.obj and <PATH_OUTPUT>.json.
+    /// Paths are traced from each selected --camera, whose name then suffixes <PATH_OUTPUT>
+    /// if there are several.
+    #[arg(long, num_args = 4, value_names = ["X", "Y", "WIDTH", "HEIGHT"])]
+    export_paths: Option<Vec<usize>>,
+    /// File name prefix for paths recorded by --export-paths.
+    #[arg(long, default_value = "paths")]
+    path_output: PathBuf,
+    /// Instead of rendering, writes the linear depth seen by an orthographic light camera
+    /// to this EXR file, e.g. as a ground-truth shadow map of a directional light or, looking
+    /// down, a height map. Its width in pixels is --image-width.
+    #[arg(long, value_name = "PATH", requires_all = ["depth_map_from", "depth_map_extent"])]
+    depth_map: Option<PathBuf>,
+    /// x, y, z
+    /// The center of the light camera's view, from which depth is measured.
+    #[arg(long, num_args = 3, allow_negative_numbers = true, value_names = ["X", "Y", "Z"])]
+    depth_map_from: Option<Vec<f32>>,
+    /// x, y, z
+    /// A point the light camera looks towards.
+    #[arg(long, num_args = 3, allow_negative_numbers = true, default_values = vec!["0.0", "0.0", "0.0"])]
+    depth_map_at: Vec<f32>,
+    /// x, y, z
+    /// Orients the light camera, as --cam-view-up does the camera.
+    #[arg(long, num_args = 3, allow_negative_numbers = true, default_values = vec!["0.0", "0.0", "-1.0"])]
+    depth_map_up: Vec<f32>,
+    /// The width and height of the area seen by the light camera, in world units.
+    #[arg(long, num_args = 2, value_names = ["WIDTH", "HEIGHT"], value_parser = parse_positive)]
+    depth_map_extent: Option<Vec<f32>>,
+    /// If set, surfaces farther than this from the light camera are ignored, and read as
+    /// this depth. Otherwise, pixels seeing nothing read as infinity.
+    #[arg(long)]
+    depth_map_far: Option<f32>,
+    /// Renders the scene's named camera with this name instead of the camera given by the
+    /// cam_* options. May be repeated to render several cameras, reusing the loaded scene.
+    #[arg(long = "camera", value_name = "NAME")]
+    cameras: Vec<String>,
+    /// Renders all of the scene's named cameras.
+    #[arg(long, conflicts_with = "cameras")]
+    all_cameras: bool,
+    /// Writes the image to this PPM file instead of stdout. When rendering several cameras,
+    /// each is written to <OUTPUT>_<NAME>.ppm.
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// NAME=EXPRESSION
+    /// Also renders the light along paths matching the light path expression to a pass
+    /// written to <LPE_OUTPUT>_<NAME>.exr, e.g. "direct=CDL" or "caustics=CDS+[LB]".
+    /// Events are C (camera), D (diffuse), S (specular), L (light) and B (background).
+    /// May be repeated.
+    #[arg(long = "lpe", value_name = "NAME=EXPRESSION", value_parser = parse_light_path)]
+    light_paths: Vec<(String, LightPathExpression)>,
+    /// File name prefix for passes rendered by --lpe.
+    #[arg(long, default_value = "lpe")]
+    lpe_output: PathBuf,
+    /// If set, writes an image of hash-based ray path prediction outcomes for camera rays
+    /// to this file (e.g. a PNG): green for true positives, red for false positives and
+    /// blue for no prediction. Only scenes using predictors (e.g. showcase) record these.
+    #[arg(long)]
+    prediction_heatmap: Option<PathBuf>,
+    /// If set, probes the scene with a sparse set of paths before rendering, and disables
+    /// the hash-based ray path predictor of each BVH whose ratio of true positive
+    /// predictions over the probe is below this threshold (between 0 and 1).
+    #[arg(long, value_name = "THRESHOLD")]
+    hrpp_probe: Option<f32>,
+    /// Also predicts the hits of the top-level BVH, so that predictions chain from it into
+    /// the scene's nested BVHs. Like every prediction, this may return a hit which isn't
+    /// the closest. Without it, the top level is traversed exactly.
+    #[arg(long, conflicts_with = "no_top_level_bvh")]
+    hrpp_chain: bool,
+    /// Instead of rendering a scene, runs a white furnace test of the material: measures
+    /// how much light a sphere of it reflects under a uniform white environment, and
+    /// reports how far that deviates from its albedo.
+    #[arg(long, value_enum)]
+    furnace: Option<FurnaceMaterial>,
+    /// If set, paths are cut short after this many diffuse bounces (e.g. off Lambertians),
+    /// as well as after --depth bounces overall.
+    #[arg(long)]
+    max_diffuse_depth: Option<u32>,
+    /// If set, paths are cut short after this many specular bounces (off metals and
+    /// dielectrics), as well as after --depth bounces overall.
+    #[arg(long)]
+    max_specular_depth: Option<u32>,
+    /// If set, writes the average number of bounces of paths through each pixel to
+    /// <PREFIX>_length.exr, and the fractions which escaped, were absorbed, or were cut
+    /// short by the maximum depth to the channels of <PREFIX>_termination.exr.
+    #[arg(long, value_name = "PREFIX")]
+    path_statistics: Option<PathBuf>,
+    /// MagicaVoxel (.vox) model to render in the voxels scene.
+    #[arg(long)]
+    vox_file: Option<PathBuf>,
+}
+
+/// Returns `path` with `_<suffix>` appended to its file stem.
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or(String::from("ppm"), |ext| ext.to_string_lossy().to_string());
+    path.with_file_name(format!("{}_{}.{}", stem, suffix, extension))
+}
+
+/// Parses a duration such as "30", "30s", "10m" or "1.5h". Plain numbers are seconds.
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let arg = arg.trim();
+    let (value, unit_seconds) = match arg.chars().last() {
+        Some('s') => (&arg[..arg.len() - 1], 1.0),
+        Some('m') => (&arg[..arg.len() - 1], 60.0),
+        Some('h') => (&arg[..arg.len() - 1], 3600.0),
+        _ => (arg, 1.0),
+    };
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'; expected e.g. 90s, 10m or 2h", arg))?;
+    if value < 0.0 {
+        return Err(String::from("duration must not be negative"));
+    }
+    Ok(Duration::from_secs_f64(value * unit_seconds))
+}
+
+/// How often a watched file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A diffuse material colored by the texture graph in the file at `path`.
+fn load_graph_material(path: &Path) -> Result<Arc<dyn Material>, String> {
+    let graph = TextureGraph::load(path)
+        .map_err(|e| format!("Can't load the material graph {}: {}", path.display(), e))?;
+    Ok(Arc::new(Lambertian::new(Arc::new(graph))))
+}
+
+/// When the file at `path` was last modified, if it can be told.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Waits until the file at `path` has been modified at a time other than `last_modified`,
+/// returning the new time.
+fn wait_for_change(path: &Path, last_modified: Option<SystemTime>) -> SystemTime {
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        if let Some(modified) = modified_time(path).filter(|&m| Some(m) != last_modified) {
+            return modified;
+        }
+    }
+}
+
+/// Parses a number which must be greater than zero.
+fn parse_positive(arg: &str) -> Result<f32, String> {
+    let value: f32 = arg
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", arg))?;
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(String::from("must be greater than zero"))
+    }
+}
+
+/// Parses a named light path expression such as "direct=CDL".
+fn parse_light_path(arg: &str) -> Result<(String, LightPathExpression), String> {
+    let (name, expression) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=EXPRESSION, got '{}'", arg))?;
+    Ok((name.to_string(), LightPathExpression::parse(expression)?))
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let aspect_ratio = cli.aspect_ratio;
+    if let Some(Command::Diff {
+        reference,
+        test,
+        heatmap,
+        pixels_per_degree,
+    }) = &cli.command
+    {
+        diff(reference, test, heatmap.as_deref(), *pixels_per_degree);
+        return;
+    }
+    if let Some(Command::Assemble { slices, output }) = &cli.command {
+        let result = match output {
+            Some(path) => File::create(path)
+                .and_then(|file| slice::assemble(slices, &mut io::BufWriter::new(file))),
+            None => slice::assemble(slices, &mut io::BufWriter::new(io::stdout())),
+        };
+        if let Err(e) = result {
+            eprintln!("Can't assemble the slices: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Batch { manifest }) = &cli.command {
+        batch(manifest);
+        return;
+    }
+    if let Some(material) = &cli.furnace {
+        furnace_test(material, cli.samples_per_pixel);
+        return;
+    }
+    if let Err(e) = render(&cli) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Builds the scene given by the `cli` and renders it, or does whatever it asks for
+/// instead of rendering. Returns why not if it can't.
+fn render(cli: &Cli) -> Result<(), String> {
+    let Some(scene) = cli.scene.clone() else {
+        return Err(String::from("No scene was given to render."));
+    };
+
+    let aspect_ratio = &cli.aspect_ratio;
     let aspect_ratio = aspect_ratio[0] / aspect_ratio[1];
     let look_from = vec3(
         cli.cam_look_from[0],
@@ -119,6 +644,10 @@ fn main() {
     let focus_dist = cli.cam_focus_dist;
     let cam_start_time = cli.cam_start_time;
     let cam_end_time = cli.cam_end_time;
+    let motion_blur = match cli.still_time {
+        Some(time) => MotionBlur::Off { time },
+        None => MotionBlur::On,
+    };
 
     let camera = Camera::new(
         look_from,
@@ -130,700 +659,589 @@ fn main() {
         focus_dist,
         cam_start_time,
         cam_end_time,
-    );
+    )
+    .with_motion_blur(motion_blur);
 
     let image_width = cli.image_width;
-    let renderer = Renderer::from_aspect_ratio(image_width, aspect_ratio);
+    let mut renderer = Renderer::from_aspect_ratio(image_width, aspect_ratio);
+    if let Some(port) = cli.monitor_port {
+        let monitor = RenderMonitor::serve((cli.monitor_host.as_str(), port))
+            .map_err(|e| format!("Can't start the render monitor: {}", e))?;
+        eprintln!(
+            "Serving render progress at http://{}:{}",
+            cli.monitor_host, port
+        );
+        renderer = renderer.with_monitor(monitor);
+        if cli.progressive_preview {
+            renderer = renderer.with_progressive_preview();
+        }
+        if cli.denoise_preview {
+            renderer = renderer.with_preview_denoising();
+        }
+    }
+    if let Some(prefix) = &cli.split_buffer_output {
+        renderer = renderer.with_split_buffer_output(prefix.clone());
+    }
+    if let Some(prefix) = &cli.gbuffer_output {
+        renderer = renderer.with_gbuffer_output(prefix.clone());
+    }
+    let filter = match cli.filter {
+        PixelFilter::Box => Filter::Box {
+            radius: cli.filter_radius.unwrap_or(0.5),
+        },
+        PixelFilter::Tent => Filter::Tent {
+            radius: cli.filter_radius.unwrap_or(1.0),
+        },
+        PixelFilter::Gaussian => Filter::Gaussian {
+            radius: cli.filter_radius.unwrap_or(1.5),
+            alpha: 2.0,
+        },
+        PixelFilter::Mitchell => Filter::Mitchell {
+            radius: cli.filter_radius.unwrap_or(2.0),
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        },
+        PixelFilter::BlackmanHarris => Filter::BlackmanHarris {
+            radius: cli.filter_radius.unwrap_or(2.0),
+        },
+    };
+    renderer = renderer.with_filter(filter);
+    renderer = renderer.with_sampler(match cli.sampler {
+        PixelSampler::Independent => Sampler::Independent,
+        PixelSampler::Stratified => Sampler::Stratified,
+    });
+    renderer = renderer.with_primaries(match cli.primaries {
+        OutputPrimaries::Srgb => Primaries::Srgb,
+        OutputPrimaries::DisplayP3 => Primaries::DisplayP3,
+        OutputPrimaries::Rec2020 => Primaries::Rec2020,
+    });
+    if let Some(ev100) = cli.exposure {
+        renderer = renderer.with_exposure(ev100);
+    }
+    if let Some(max_time) = cli.max_time {
+        renderer = renderer.with_max_time(max_time);
+    }
+    if !cli.spp_milestones.is_empty() {
+        renderer =
+            renderer.with_spp_milestones(cli.spp_milestones.clone(), cli.milestone_output.clone());
+    }
+    if let Some(band_height) = cli.band_height {
+        renderer = renderer.with_band_height(band_height);
+    }
+    if let Some(slice) = cli.slice {
+        renderer = renderer.with_slice(slice);
+    }
+    if let Some(seed) = cli.seed {
+        renderer = renderer.with_seed(seed);
+    }
+    if let Some(path) = &cli.first_bounce_cache {
+        renderer = renderer.with_first_bounce_cache(path.clone());
+    }
+    if let Some(path) = &cli.tile_costs {
+        renderer = renderer.with_tile_costs(path.clone());
+    }
+    if let Some(photons) = cli.caustic_photons {
+        renderer = renderer.with_caustics(CausticParams {
+            photons,
+            max_radius: cli.caustic_radius,
+            ..CausticParams::default()
+        });
+    }
+    if cli.emission_albedo_only {
+        renderer = renderer.with_emission_albedo_only();
+    }
+    if cli.volume_debug {
+        renderer = renderer.with_volume_debug();
+    }
+    if let Some(max_depth) = cli.max_diffuse_depth {
+        renderer = renderer.with_max_diffuse_depth(max_depth);
+    }
+    if let Some(max_depth) = cli.max_specular_depth {
+        renderer = renderer.with_max_specular_depth(max_depth);
+    }
+    if let Some(prefix) = &cli.path_statistics {
+        renderer = renderer.with_path_statistics(prefix.clone());
+    }
+    if let Some(path) = &cli.prediction_heatmap {
+        renderer = renderer.with_prediction_heatmap(path.clone());
+    }
+    if !cli.light_paths.is_empty() {
+        renderer = renderer.with_light_path_passes(cli.light_paths.clone(), cli.lpe_output.clone());
+    }
+
+    let decimation = if let Some(count) = cli.decimate_triangles {
+        Some(DecimationTarget::TriangleCount(count))
+    } else {
+        cli.decimate_error.map(DecimationTarget::MaxError)
+    };
+    let import = MeshImport {
+        decimation,
+        smoothing_angle: cli.smoothing_angle,
+        bvh_build: match cli.bvh_build {
+            BvhBuilder::Median => BvhBuild::Median,
+            BvhBuilder::LongestAxis => BvhBuild::LongestAxis,
+            BvhBuilder::Morton => BvhBuild::Morton,
+            BvhBuilder::MortonGpu => BvhBuild::MortonGpu,
+        },
+    };
+
+    bvh::set_default_traversal(match cli.bvh_traversal {
+        BvhTraversal::Recursive => Traversal::Recursive,
+        BvhTraversal::Stack => Traversal::Stack,
+        BvhTraversal::Ordered => Traversal::Ordered,
+        BvhTraversal::Hrpp => Traversal::Hrpp,
+    });
 
     let start = Instant::now();
 
-    let (world, predictors) = match cli.scene {
-        Scene::RandomSpheres => random_spheres(),
-        Scene::RandomMovingSpheres => random_moving_spheres(),
-        Scene::TwoSpheres => two_spheres(),
-        Scene::Marble => two_marble_spheres(),
-        Scene::Earth => earth(),
-        Scene::SimpleLights => simple_lights(),
-        Scene::Cornell => cornell_box(),
-        Scene::CornellSmoke => cornell_smoke(),
-        Scene::Showcase => showcase(),
-        Scene::Bunny => bunny(),
-        Scene::Gargoyle => gargoyle(),
-        Scene::IgeaHrpp => igea_hrpp(),
+    // The background image loads while the scene is built.
+    let background_image = cli.background_image.clone().map(|path| {
+        let layout = match cli.background_layout {
+            BackgroundLayout::Equirectangular => PanoramaLayout::Equirectangular,
+            BackgroundLayout::AngularMap => PanoramaLayout::AngularMap,
+            BackgroundLayout::Cubemap => PanoramaLayout::Cubemap,
+        };
+        Pending::spawn_cached(
+            path.display().to_string(),
+            format!("{} {:?}", path.display(), layout),
+            move || Arc::new(ImageTexture::panorama(&path, layout)),
+        )
+    });
+
+    let (world, mut predictors, named_cameras) = match scene {
+        Scene::RandomSpheres => scenes::random_spheres(&RandomSpheresParams::default()),
+        Scene::RandomMovingSpheres => {
+            scenes::random_moving_spheres(&RandomSpheresParams::default())
+        }
+        Scene::TwoSpheres => scenes::two_spheres(),
+        Scene::Marble => scenes::two_marble_spheres(),
+        Scene::Earth => scenes::earth(),
+        Scene::SimpleLights => scenes::simple_lights(),
+        Scene::Cornell => scenes::cornell_box(),
+        Scene::CornellSmoke => scenes::cornell_smoke(),
+        Scene::Showcase => scenes::showcase(&ShowcaseParams {
+            bvh_build: import.bvh_build,
+            ..ShowcaseParams::default()
+        }),
+        Scene::Bunny => scenes::bunny(import),
+        Scene::FurryBunny => scenes::furry_bunny(import),
+        Scene::Gargoyle => scenes::gargoyle(import),
+        Scene::IgeaHrpp => scenes::igea_hrpp(import),
+        Scene::Ocean => scenes::ocean(),
+        Scene::Sparks => scenes::sparks(&SparksParams::default()),
+        Scene::Voxels => match &cli.vox_file {
+            Some(path) => scenes::voxels(path),
+            None => return Err(String::from("--vox-file is required for the voxels scene.")),
+        },
     };
 
-    let background = match cli.scene {
-        Scene::SimpleLights => Vec3::ZERO,
-        Scene::Cornell => Vec3::ZERO,
-        Scene::CornellSmoke => Vec3::ZERO,
-        Scene::Showcase => Vec3::ZERO,
-        Scene::Bunny => Vec3::ZERO,
-        Scene::Gargoyle => Vec3::ZERO,
-        Scene::IgeaHrpp => Vec3::ZERO,
-        _ => vec3(0.70, 0.80, 1.00),
+    // The first bounce cache records which top-level object each camera ray hits, so it
+    // needs them in a list rather than all in one BVH.
+    let (time_0, time_1) = match motion_blur {
+        MotionBlur::On => (cam_start_time, cam_end_time),
+        MotionBlur::Off { time } => (time, time),
+    };
+    let world = if cli.no_top_level_bvh || cli.first_bounce_cache.is_some() {
+        world
+    } else if let (true, Some(predictors)) = (cli.hrpp_chain, predictors.as_mut()) {
+        Bvh::top_level_with_predictor(world, time_0, time_1, predictors)
+    } else {
+        Bvh::top_level(world, time_0, time_1)
+    };
+
+    // The material of --material-graph, which is swapped for the file's new one if it's
+    // watched and changes.
+    let graph_material = match &cli.material_graph {
+        Some(path) => Some(Arc::new(Reloadable::new(load_graph_material(path)?))),
+        None => None,
+    };
+    let override_material: Option<Arc<dyn Material>> =
+        match (&cli.override_material, &graph_material) {
+            (Some(OverrideMaterial::Clay), _) => {
+                Some(Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5))))
+            }
+            (Some(OverrideMaterial::Wireframe), _) => Some(Arc::new(Wireframe::new(
+                vec3(0.2, 0.2, 0.2),
+                vec3(0.9, 0.9, 0.9),
+                0.03,
+            ))),
+            (None, Some(graph_material)) => Some(graph_material.clone()),
+            (None, None) => None,
+        };
+    let world = match override_material {
+        Some(material) => {
+            let mut overridden = HittableList::new();
+            overridden.add(Arc::new(MaterialOverride::new(Arc::new(world), material)));
+            overridden
+        }
+        None => world,
+    };
+
+    let background = if let Some(texture) = background_image {
+        Background::Image {
+            texture: texture.wait(),
+            yaw: cli.background_yaw,
+            pitch: cli.background_pitch,
+        }
+    } else if cli.gradient_background {
+        Background::sky()
+    } else {
+        match scene {
+            Scene::SimpleLights => Background::Solid(Vec3::ZERO),
+            Scene::Cornell => Background::Solid(Vec3::ZERO),
+            Scene::CornellSmoke => Background::Solid(Vec3::ZERO),
+            Scene::Showcase => Background::Solid(Vec3::ZERO),
+            Scene::Bunny => Background::Solid(Vec3::ZERO),
+            Scene::FurryBunny => Background::Solid(Vec3::ZERO),
+            Scene::Gargoyle => Background::Solid(Vec3::ZERO),
+            Scene::IgeaHrpp => Background::Solid(Vec3::ZERO),
+            Scene::Sparks => Background::Solid(vec3(0.02, 0.02, 0.03)),
+            Scene::Ocean => Background::sky(),
+            _ => Background::Solid(vec3(0.70, 0.80, 1.00)),
+        }
     };
 
     let samples_per_pixel = cli.samples_per_pixel;
     let max_depth = cli.depth;
-    renderer
-        .render(
-            &camera,
-            &world,
-            background,
-            samples_per_pixel,
-            max_depth,
-            cli.tile_width,
-            cli.tile_height,
-            predictors,
-        )
-        .unwrap();
-
-    let duration = start.elapsed();
-    eprintln!("Render time: {:?}", duration);
-}
 
-fn random_spheres() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-
-    let material_ground = Arc::new(Lambertian::new(Arc::new(Checker::from_color(
-        10.0,
-        vec3(0.2, 0.3, 0.1),
-        vec3(0.9, 0.9, 0.9),
-    ))));
-    world.add(Arc::new(Sphere::new(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        material_ground,
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random::<f32>();
-            let center = vec3(
-                a as f32 + 0.9 * random::<f32>(),
-                0.2,
-                b as f32 + 0.9 * random::<f32>(),
-            );
+    let predictors = Arc::new(predictors);
+
+    if let Some(path) = &cli.depth_map {
+        let (from, extent) = (
+            cli.depth_map_from.as_ref().unwrap(),
+            cli.depth_map_extent.as_ref().unwrap(),
+        );
+        let mut view = OrthographicView::new(
+            vec3(from[0], from[1], from[2]),
+            vec3(
+                cli.depth_map_at[0],
+                cli.depth_map_at[1],
+                cli.depth_map_at[2],
+            ),
+            vec3(
+                cli.depth_map_up[0],
+                cli.depth_map_up[1],
+                cli.depth_map_up[2],
+            ),
+            extent[0],
+            extent[1],
+        )?;
+        if let Some(far) = cli.depth_map_far {
+            view = view.with_far(far);
+        }
+        let width = cli.image_width;
+        let height = ((width as f32 * extent[1] / extent[0]).round() as usize).max(1);
+        let depth_map = DepthMap::render(&view, &world, &predictors, width, height);
+        depth_map
+            .write(path)
+            .map_err(|e| format!("Can't write the depth map: {}", e))?;
+        eprintln!(
+            "Wrote a {}x{} depth map to {}",
+            width,
+            height,
+            path.display()
+        );
+        return Ok(());
+    }
 
-            if (center - vec3(4.0, 0.2, 0.0)).length() > 0.9 {
-                let material: Arc<dyn Material> = if choose_mat < 0.8 {
-                    let albedo = random_color() * random_color();
-                    Arc::new(Lambertian::from_color(albedo))
-                } else if choose_mat < 0.95 {
-                    let albedo = random_color_range(0.5, 1.0);
-                    let fuzz = random::<f32>() * 0.5;
-                    Arc::new(Metal::new(albedo, fuzz))
-                } else {
-                    Arc::new(Dialectric::new(1.5))
-                };
-                world.add(Arc::new(Sphere::new(center, 0.2, material)));
+    // Render the camera given by the cam_* options, or else the selected named cameras.
+    let views: Vec<(Option<&str>, Camera)> = if cli.all_cameras || !cli.cameras.is_empty() {
+        if named_cameras.is_empty() {
+            return Err(String::from("This scene has no named cameras."));
+        }
+        for name in cli.cameras.iter() {
+            if !named_cameras.iter().any(|camera| camera.name == name) {
+                let names: Vec<&str> = named_cameras.iter().map(|camera| camera.name).collect();
+                return Err(format!(
+                    "No camera named '{}'; this scene has: {}",
+                    name,
+                    names.join(", ")
+                ));
             }
         }
+        named_cameras
+            .iter()
+            .filter(|camera| cli.all_cameras || cli.cameras.iter().any(|c| c == camera.name))
+            .map(|camera| {
+                let view = camera
+                    .camera(aspect_ratio, cam_start_time, cam_end_time)
+                    .with_motion_blur(motion_blur);
+                (Some(camera.name), view)
+            })
+            .collect()
+    } else {
+        vec![(None, camera)]
+    };
+    if let Some(region) = &cli.export_paths {
+        let region = PixelRegion::new(region[0], region[1], region[2], region[3]);
+        for (name, camera) in views.iter() {
+            let settings = RenderSettings {
+                camera,
+                world: &world,
+                background: &background,
+                max_depth,
+                predictors: &predictors,
+            };
+            let paths = renderer.trace_paths(&settings, region, samples_per_pixel);
+            // Escaping paths are drawn out to about the distance of the subject.
+            let escape_length = named_cameras
+                .iter()
+                .find(|named| Some(named.name) == *name)
+                .map_or(look_from.distance(look_at), |named| {
+                    named.look_from.distance(named.look_at)
+                });
+            let path_output = match name {
+                Some(name) if views.len() > 1 => suffixed_path(&cli.path_output, name),
+                _ => cli.path_output.clone(),
+            };
+            let obj_path = path_output.with_extension("obj");
+            let json_path = path_output.with_extension("json");
+            path_export::write_obj(&paths, &obj_path, escape_length)
+                .map_err(|e| format!("Can't write the paths: {}", e))?;
+            path_export::write_json(&paths, &json_path)
+                .map_err(|e| format!("Can't write the paths: {}", e))?;
+            eprintln!(
+                "Wrote {} paths to {} and {}",
+                paths.len(),
+                obj_path.display(),
+                json_path.display()
+            );
+        }
+        return Ok(());
     }
 
-    let large_sphere_radius = 1.0;
-    let glass_material = Arc::new(Dialectric::new(1.5));
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, 1.0, 0.0),
-        large_sphere_radius,
-        glass_material,
-    )));
-
-    let diffuse_material = Arc::new(Lambertian::from_color(vec3(0.4, 0.2, 0.1)));
-    world.add(Arc::new(Sphere::new(
-        vec3(-4.0, 1.0, 0.0),
-        large_sphere_radius,
-        diffuse_material,
-    )));
-
-    let metal_material = Arc::new(Metal::new(vec3(0.7, 0.6, 0.5), 0.0));
-    world.add(Arc::new(Sphere::new(
-        vec3(4.0, 1.0, 0.0),
-        large_sphere_radius,
-        metal_material,
-    )));
-
-    let bvh = Arc::new(Bvh::new(world, 0.0, 1.0));
-    let mut world = HittableList::new();
-    world.add(bvh);
-
-    (world, None)
-}
+    if views.len() > 1 && cli.output.is_none() {
+        return Err(String::from(
+            "--output is required when rendering several cameras.",
+        ));
+    }
 
-fn random_moving_spheres() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-
-    let material_ground = Arc::new(Lambertian::new(Arc::new(Checker::from_color(
-        10.0,
-        vec3(0.2, 0.3, 0.1),
-        vec3(0.9, 0.9, 0.9),
-    ))));
-    world.add(Arc::new(Sphere::new(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        material_ground,
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random::<f32>();
-            let center = vec3(
-                a as f32 + 0.9 * random::<f32>(),
-                0.2,
-                b as f32 + 0.9 * random::<f32>(),
-            );
+    let predictors = match cli.hrpp_probe {
+        Some(threshold) => renderer.probe_predictors(
+            &views[0].1,
+            &world,
+            &background,
+            max_depth,
+            predictors,
+            threshold,
+        ),
+        None => predictors,
+    };
 
-            if (center - vec3(4.0, 0.2, 0.0)).length() > 0.9 {
-                let material: Arc<dyn Material> = if choose_mat < 0.8 {
-                    let albedo = random_color() * random_color();
-                    Arc::new(Lambertian::from_color(albedo))
-                } else if choose_mat < 0.95 {
-                    let albedo = random_color_range(0.5, 1.0);
-                    let fuzz = random::<f32>() * 0.5;
-                    Arc::new(Metal::new(albedo, fuzz))
-                } else {
-                    Arc::new(Dialectric::new(1.5))
-                };
-                let center_end = center + vec3(0.0, random::<f32>() * 0.5, 0.0);
-                world.add(Arc::new(MovingSphere::new(
-                    center, center_end, 0.0, 1.0, 0.2, material,
+    let mut graph_modified = cli.material_graph.as_deref().and_then(modified_time);
+    loop {
+        for (name, camera) in views.iter() {
+            let mut view_renderer = renderer.clone();
+            match (name, &cli.output) {
+                (Some(name), Some(output)) if views.len() > 1 => {
+                    view_renderer = view_renderer.with_output(suffixed_path(output, name));
+                }
+                (_, Some(output)) => view_renderer = view_renderer.with_output(output.clone()),
+                (_, None) => (),
+            }
+            if let (Some(name), Some(prefix)) = (name, &cli.split_buffer_output) {
+                view_renderer = view_renderer.with_split_buffer_output(PathBuf::from(format!(
+                    "{}_{}",
+                    prefix.display(),
+                    name
+                )));
+            }
+            if let (Some(name), Some(prefix)) = (name, &cli.gbuffer_output) {
+                view_renderer = view_renderer.with_gbuffer_output(PathBuf::from(format!(
+                    "{}_{}",
+                    prefix.display(),
+                    name
+                )));
+            }
+            if let (Some(name), Some(prefix)) = (name, &cli.path_statistics) {
+                view_renderer = view_renderer.with_path_statistics(PathBuf::from(format!(
+                    "{}_{}",
+                    prefix.display(),
+                    name
                 )));
             }
+            if let (Some(name), Some(path)) = (name, &cli.prediction_heatmap) {
+                view_renderer = view_renderer.with_prediction_heatmap(suffixed_path(path, name));
+            }
+            if let (Some(name), Some(path)) = (name, &cli.first_bounce_cache) {
+                view_renderer = view_renderer.with_first_bounce_cache(suffixed_path(path, name));
+            }
+            if let (Some(name), Some(path)) = (name, &cli.tile_costs) {
+                view_renderer = view_renderer.with_tile_costs(suffixed_path(path, name));
+            }
+            if let (Some(name), false) = (name, cli.spp_milestones.is_empty()) {
+                view_renderer = view_renderer.with_spp_milestones(
+                    cli.spp_milestones.clone(),
+                    PathBuf::from(format!("{}_{}", cli.milestone_output.display(), name)),
+                );
+            }
+            if let (Some(name), false) = (name, cli.light_paths.is_empty()) {
+                view_renderer = view_renderer.with_light_path_passes(
+                    cli.light_paths.clone(),
+                    PathBuf::from(format!("{}_{}", cli.lpe_output.display(), name)),
+                );
+            }
+            if let Some(name) = name {
+                eprintln!("Rendering camera '{}'", name);
+            }
+            let settings = RenderSettings {
+                camera,
+                world: &world,
+                background: &background,
+                max_depth,
+                predictors: &predictors,
+            };
+            view_renderer
+                .render(
+                    &settings,
+                    samples_per_pixel,
+                    cli.tile_width,
+                    cli.tile_height,
+                )
+                .map_err(|e| format!("Can't render: {}", e))?;
         }
-    }
-
-    let large_sphere_radius = 1.0;
-    let glass_material = Arc::new(Dialectric::new(1.5));
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, 1.0, 0.0),
-        large_sphere_radius,
-        glass_material,
-    )));
-
-    let diffuse_material = Arc::new(Lambertian::from_color(vec3(0.4, 0.2, 0.1)));
-    world.add(Arc::new(Sphere::new(
-        vec3(-4.0, 1.0, 0.0),
-        large_sphere_radius,
-        diffuse_material,
-    )));
-
-    let metal_material = Arc::new(Metal::new(vec3(0.7, 0.6, 0.5), 0.0));
-    world.add(Arc::new(Sphere::new(
-        vec3(4.0, 1.0, 0.0),
-        large_sphere_radius,
-        metal_material,
-    )));
-
-    let bvh = Arc::new(Bvh::new(world, 0.0, 1.0));
-    let mut world = HittableList::new();
-    world.add(bvh);
-    (world, None)
-}
-
-fn two_spheres() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-    let checkerboard = Arc::new(Lambertian::new(Arc::new(Checker::from_color(
-        10.0,
-        vec3(0.2, 0.3, 0.1),
-        vec3(0.9, 0.9, 0.9),
-    ))));
-
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, -10.0, 0.0),
-        10.0,
-        checkerboard.clone(),
-    )));
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, 10.0, 0.0),
-        10.0,
-        checkerboard.clone(),
-    )));
-
-    (world, None)
-}
-
-fn two_marble_spheres() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-
-    let marble_texture = Arc::new(Marble::new(4.0));
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, -1000.0, 0.0),
-        1000.0,
-        Arc::new(Lambertian::new(marble_texture.clone())),
-    )));
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, 2.0, 0.0),
-        2.0,
-        Arc::new(Lambertian::new(marble_texture)),
-    )));
-    (world, None)
-}
-
-// The relative filepath of the image texture means this works if running from the top level of the git repository,
-// but not from other working directories (such as if the built app is run elsewhere).
-// This is sufficient for now as this executable is just to demo the library for developers.
-// Ideally, the image file (and other file resources) would be specified by a scene defined in some file (in JSON, maybe)
-// and we wouldn't be defining sample scenes via code like this at all (we would provide sample scenes as separate files
-// and would just use Shimmer to parse and render the provided scene).
-fn earth() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let earth_texture = Arc::new(ImageTexture::new(Path::new("images/earthmap.jpg")));
-    let earth_surface = Arc::new(Lambertian::new(earth_texture));
-    let globe = Arc::new(Sphere::new(vec3(0.0, 0.0, 0.0), 2.0, earth_surface));
-    let mut world = HittableList::new();
-    world.add(globe);
-    (world, None)
-}
-
-fn simple_lights() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-    let marble_texture = Arc::new(Marble::new(4.0));
-    let ground = Arc::new(Sphere::new(
-        vec3(0.0, -1000.0, 0.0),
-        1000.0,
-        Arc::new(Lambertian::new(marble_texture.clone())),
-    ));
-    world.add(ground);
-    let sphere = Arc::new(Sphere::new(
-        vec3(0.0, 2.0, 0.0),
-        2.0,
-        Arc::new(Lambertian::new(marble_texture)),
-    ));
-    world.add(sphere);
-
-    let light_mat = Arc::new(DiffuseLight::from_color(vec3(4.0, 4.0, 4.0)));
-    let light = Arc::new(XyRect::new(3.0, 5.0, 1.0, 3.0, -2.0, light_mat.clone()));
-    world.add(light);
-
-    let sphere_light = Arc::new(Sphere::new(vec3(0.0, 7.0, 0.0), 2.0, light_mat));
-    world.add(sphere_light);
-
-    (world, None)
-}
 
-fn cornell_box() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-
-    let red = Arc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
-    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let green = Arc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
-    let light = Arc::new(DiffuseLight::from_color(vec3(15.0, 15.0, 15.0)));
-
-    world.add(Arc::new(YzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        green.clone(),
-    )));
-    world.add(Arc::new(YzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        red.clone(),
-    )));
-    world.add(Arc::new(XzRect::new(
-        213.0, 343.0, 227.0, 332.0, 554.0, light,
-    )));
-    world.add(Arc::new(XzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        white.clone(),
-    )));
-    world.add(Arc::new(XzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-    world.add(Arc::new(XyRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-
-    let box1 = Arc::new(Cube::new(
-        Vec3::ZERO,
-        vec3(165.0, 330.0, 165.0),
-        white.clone(),
-    ));
-    let box1 = Arc::new(RotateY::new(box1, 15.0));
-    let box1 = Arc::new(Translate::new(box1, vec3(265.0, 0.0, 295.0)));
-
-    let box2 = Arc::new(Cube::new(
-        Vec3::ZERO,
-        vec3(165.0, 165.0, 165.0),
-        white.clone(),
-    ));
-    let box2 = Arc::new(RotateY::new(box2, -18.0));
-    let box2 = Arc::new(Translate::new(box2, vec3(130.0, 0.0, 65.0)));
-
-    world.add(box1);
-    world.add(box2);
-
-    (world, None)
-}
-
-fn cornell_smoke() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = HittableList::new();
-
-    let red = Arc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
-    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let green = Arc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
-    let light = Arc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
-
-    world.add(Arc::new(YzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        green.clone(),
-    )));
-    world.add(Arc::new(YzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        red.clone(),
-    )));
-    world.add(Arc::new(XzRect::new(
-        113.0, 443.0, 127.0, 432.0, 554.0, light,
-    )));
-    world.add(Arc::new(XzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        white.clone(),
-    )));
-    world.add(Arc::new(XzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-    world.add(Arc::new(XyRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-
-    let box1 = Arc::new(Cube::new(
-        Vec3::ZERO,
-        vec3(165.0, 330.0, 165.0),
-        white.clone(),
-    ));
-    let box1 = Arc::new(RotateY::new(box1, 15.0));
-    let box1 = Arc::new(Translate::new(box1, vec3(265.0, 0.0, 295.0)));
-
-    let box2 = Arc::new(Cube::new(
-        Vec3::ZERO,
-        vec3(165.0, 165.0, 165.0),
-        white.clone(),
-    ));
-    let box2 = Arc::new(RotateY::new(box2, -18.0));
-    let box2 = Arc::new(Translate::new(box2, vec3(130.0, 0.0, 65.0)));
-
-    world.add(Arc::new(ConstantMedium::new_with_color(
-        box1,
-        0.01,
-        Vec3::new(0.0, 0.0, 0.0),
-    )));
-    world.add(Arc::new(ConstantMedium::new_with_color(
-        box2,
-        0.01,
-        Vec3::new(1.0, 1.0, 1.0),
-    )));
-
-    (world, None)
-}
-
-fn showcase() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut rng = rand::thread_rng();
-
-    let mut predictors = AHashMap::<BvhId, Mutex<Predictor>>::new();
-
-    let mut boxes = HittableList::new();
-    let ground_mat = Arc::new(Lambertian::from_color(vec3(0.48, 0.83, 0.53)));
-    let boxes_per_side = 20;
-    for i in 0..boxes_per_side {
-        for j in 0..boxes_per_side {
-            let w = 100.0;
-            let x0 = -1000.0 + i as f32 * w;
-            let z0 = -1000.0 + j as f32 * w;
-            let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = rng.gen_range(1.0..101.0);
-            let z1 = z0 + w;
-
-            boxes.add(Arc::new(Cube::new(
-                vec3(x0, y0, z0),
-                vec3(x1, y1, z1),
-                ground_mat.clone(),
-            )));
+        let (true, Some(path), Some(graph_material)) = (
+            cli.watch_material_graph,
+            &cli.material_graph,
+            &graph_material,
+        ) else {
+            break;
+        };
+        eprintln!("Watching {} for changes...", path.display());
+        loop {
+            graph_modified = Some(wait_for_change(path, graph_modified));
+            match load_graph_material(path) {
+                Ok(material) => {
+                    graph_material.set(material);
+                    break;
+                }
+                Err(e) => eprintln!("{}", e),
+            }
         }
     }
 
-    let mut world = HittableList::new();
-    world.add(Arc::new(Bvh::with_predictor(
-        boxes,
-        0.0,
-        1.0,
-        &mut predictors,
-    )));
-
-    let light_mat = Arc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
-    world.add(Arc::new(XzRect::new(
-        123.0, 423.0, 147.0, 412.0, 554.0, light_mat,
-    )));
-
-    let center1 = vec3(400.0, 400.0, 200.0);
-    let center2 = center1 + vec3(30.0, 0.0, 0.0);
-
-    let moving_sphere_mat = Arc::new(Lambertian::from_color(vec3(0.7, 0.3, 0.1)));
-    world.add(Arc::new(MovingSphere::new(
-        center1,
-        center2,
-        0.0,
-        1.0,
-        50.0,
-        moving_sphere_mat,
-    )));
-
-    world.add(Arc::new(Sphere::new(
-        vec3(260.0, 150.0, 45.0),
-        50.0,
-        Arc::new(Dialectric::new(1.5)),
-    )));
-
-    world.add(Arc::new(Sphere::new(
-        vec3(0.0, 150.0, 145.0),
-        50.0,
-        Arc::new(Metal::new(vec3(0.8, 0.8, 0.9), 1.0)),
-    )));
-
-    let boundary = Arc::new(Sphere::new(
-        vec3(360.0, 150.0, 145.0),
-        70.0,
-        Arc::new(Dialectric::new(1.5)),
-    ));
-    world.add(boundary.clone());
-    world.add(Arc::new(ConstantMedium::new_with_color(
-        boundary,
-        0.2,
-        vec3(0.2, 0.4, 0.9),
-    )));
-
-    let boundary = Arc::new(Sphere::new(
-        vec3(0.0, 0.0, 0.0),
-        5000.0,
-        Arc::new(Dialectric::new(1.5)),
-    ));
-    world.add(Arc::new(ConstantMedium::new_with_color(
-        boundary,
-        0.0001,
-        vec3(1.0, 1.0, 1.0),
-    )));
-
-    let earth_mat = Arc::new(Lambertian::new(Arc::new(ImageTexture::new(Path::new(
-        "images/earthmap.jpg",
-    )))));
-    world.add(Arc::new(Sphere::new(
-        vec3(400.0, 200.0, 400.0),
-        100.0,
-        earth_mat,
-    )));
-
-    let perlin_texture = Arc::new(Marble::new(0.1));
-    world.add(Arc::new(Sphere::new(
-        vec3(220.0, 280.0, 300.0),
-        80.0,
-        Arc::new(Lambertian::new(perlin_texture)),
-    )));
-
-    let mut spheres = HittableList::new();
-    let white_mat = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let num_spheres = 1000;
-    for _ in 0..num_spheres {
-        let max_val = 165.0;
-        let random_x = rng.gen_range(0.0..max_val);
-        let random_y = rng.gen_range(0.0..max_val);
-        let random_z = rng.gen_range(0.0..max_val);
-        spheres.add(Arc::new(Sphere::new(
-            vec3(random_x, random_y, random_z),
-            10.0,
-            white_mat.clone(),
-        )));
-    }
-
-    world.add(Arc::new(Translate::new(
-        Arc::new(RotateY::new(
-            Arc::new(Bvh::with_predictor(spheres, 0.0, 1.0, &mut predictors)),
-            15.0,
-        )),
-        vec3(-100.0, 270.0, 395.0),
-    )));
-
-    (world, Some(predictors))
-}
-
-fn cornell_boundaries() -> HittableList {
-    let mut world = HittableList::new();
-
-    let red = Arc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
-    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let green = Arc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
-    let light = Arc::new(DiffuseLight::from_color(vec3(15.0, 15.0, 15.0)));
-
-    world.add(Arc::new(XzRect::new(
-        200.0, 356.0, 200.0, 359.0, 554.0, light,
-    )));
-
-    world.add(Arc::new(YzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        green.clone(),
-    )));
-    world.add(Arc::new(YzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        red.clone(),
-    )));
-
-    world.add(Arc::new(XzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        white.clone(),
-    )));
-    world.add(Arc::new(XzRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-    world.add(Arc::new(XyRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-
-    world
+    let duration = start.elapsed();
+    eprintln!("Render time: {:?}", duration);
+    Ok(())
 }
 
-fn load_to_tris<P>(file: P, material: Arc<dyn Material>) -> HittableList
-where
-    P: AsRef<Path> + fmt::Debug,
-{
-    let load_options = LoadOptions {
-        triangulate: true,
-        ..Default::default()
+/// Renders each job of the manifest at `path` in turn, sharing what they load. Exits
+/// with an error if any of them failed.
+fn batch(path: &Path) {
+    let jobs = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| batch::parse_manifest(&text))
+    {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Can't read the manifest {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
     };
-    let (models, _) = tobj::load_obj(file, &load_options).expect("Failed to OBJ load file");
-
-    let model = &models[0];
-    let mesh = &model.mesh;
-    let indices = &mesh.indices;
-
-    let vertices: Vec<Vec3> = indices
-        .into_iter()
-        .map(|i| {
-            let x = mesh.positions[*i as usize * 3];
-            let y = mesh.positions[*i as usize * 3 + 1];
-            let z = mesh.positions[*i as usize * 3 + 2];
-            vec3(x, y, z)
-        })
-        .collect();
-
-    let tris: Vec<Tri> = vertices
-        .as_slice()
-        .chunks(3)
-        .into_iter()
-        .map(|vertex_group| {
-            Tri::new(
-                vertex_group[0],
-                vertex_group[1],
-                vertex_group[2],
-                material.clone(),
-            )
-        })
-        .collect();
-
-    let mut bunny = HittableList::new();
-    for tri in tris {
-        bunny.add(Arc::new(tri));
+    loading::enable_cache();
+    let mut failed = Vec::new();
+    for (index, args) in jobs.iter().enumerate() {
+        let number = index + 1;
+        eprintln!("Job {}/{}: {}", number, jobs.len(), args.join(" "));
+        let start = Instant::now();
+        let result =
+            Cli::try_parse_from(iter::once("shimmer").chain(args.iter().map(|a| a.as_str())))
+                // Only the first line of clap's error, without the usage.
+                .map_err(|e| e.to_string().lines().next().unwrap_or_default().to_string())
+                .and_then(|cli| {
+                    if cli.scene.is_none() || cli.furnace.is_some() {
+                        return Err(String::from("jobs can only render scenes"));
+                    }
+                    // A panicking job, e.g. one missing a model, shouldn't stop the others.
+                    panic::catch_unwind(AssertUnwindSafe(|| render(&cli)))
+                        .unwrap_or_else(|_| Err(String::from("the render panicked")))
+                });
+        match result {
+            Ok(()) => eprintln!("Job {} finished in {:.1?}", number, start.elapsed()),
+            Err(e) => {
+                eprintln!("Job {} failed: {}", number, e.trim_end());
+                failed.push(number);
+            }
+        }
+    }
+    if !failed.is_empty() {
+        eprintln!(
+            "{} of {} jobs failed: {:?}",
+            failed.len(),
+            jobs.len(),
+            failed
+        );
+        std::process::exit(1);
     }
-
-    bunny
-}
-
-fn bunny() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = cornell_boundaries();
-
-    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let bunny = load_to_tris("models/bunny_2000_scale.obj", white);
-
-    let bunny = Bvh::new(bunny, 0.0, 1.0);
-    let bunny = Arc::new(Translate::new(Arc::new(bunny), vec3(325.0, 0.0, 200.0)));
-    world.add(bunny);
-
-    (world, None)
 }
 
-fn gargoyle() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = cornell_boundaries();
-
-    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let garg = load_to_tris("models/gargoyle.obj", white);
-
-    let garg = Bvh::new(garg, 0.0, 1.0);
-    let garg = Arc::new(Translate::new(Arc::new(garg), vec3(275.0, 0.0, 200.0)));
-    world.add(garg);
-
-    (world, None)
+/// Prints the differences between the images at `reference` and `test`.
+fn diff(reference: &Path, test: &Path, heatmap: Option<&Path>, pixels_per_degree: f32) {
+    let load = |path: &Path| {
+        LinearImage::load(path).unwrap_or_else(|e| {
+            eprintln!("Couldn't read {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    };
+    let (reference, test) = (load(reference), load(test));
+    let comparison =
+        image_diff::compare(&reference, &test, pixels_per_degree).unwrap_or_else(|e| {
+            eprintln!("Can't compare the images: {}", e);
+            std::process::exit(1);
+        });
+    println!("RMSE: {}", comparison.rmse);
+    println!("FLIP: {}", comparison.mean_flip);
+    if let Some(path) = heatmap {
+        image_diff::write_heatmap(
+            &comparison.flip_errors,
+            reference.width,
+            reference.height,
+            path,
+        )
+        .unwrap();
+    }
 }
 
-fn igea_hrpp() -> (HittableList, Option<AHashMap<BvhId, Mutex<Predictor>>>) {
-    let mut world = cornell_boundaries();
-
-    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
-    let igea = load_to_tris("models/igea.obj", white);
-
-    let mut predictors = AHashMap::<BvhId, Mutex<Predictor>>::new();
-    let igea = Bvh::with_predictor(igea, 0.0, 1.0, &mut predictors);
-    let igea = Arc::new(Translate::new(Arc::new(igea), vec3(275.0, 0.0, 200.0)));
-    world.add(igea);
-
-    (world, Some(predictors))
+/// Prints the reflectance of spheres of the `material` in a white furnace, measured with
+/// `samples` rays each, against their albedo.
+fn furnace_test(material: &FurnaceMaterial, samples: u32) {
+    let albedo = vec3(0.8, 0.8, 0.8);
+    let materials: Vec<(String, Arc<dyn Material>, Vec3)> = match material {
+        FurnaceMaterial::Lambertian => vec![(
+            String::from("lambertian"),
+            Arc::new(Lambertian::from_color(albedo)),
+            albedo,
+        )],
+        FurnaceMaterial::Metal => [0.0, 0.25, 0.5, 0.75, 1.0]
+            .into_iter()
+            .map(|fuzz| {
+                let material: Arc<dyn Material> = Arc::new(Metal::new(albedo, fuzz));
+                (format!("metal, fuzz {:.2}", fuzz), material, albedo)
+            })
+            .collect(),
+        FurnaceMaterial::Dielectric => vec![(
+            String::from("dielectric"),
+            Arc::new(Dialectric::new(1.5)),
+            Vec3::ONE,
+        )],
+    };
+    // A few samples per pixel would be far too noisy to measure with.
+    let samples = samples.max(1) * 1024;
+    eprintln!(
+        "{:<20} {:>9} {:>9} {:>10}",
+        "material", "expected", "measured", "deviation"
+    );
+    for (name, material, expected) in materials {
+        let measured = furnace::measure(material, samples);
+        let expected = expected.dot(Vec3::ONE) / 3.0;
+        let measured = measured.dot(Vec3::ONE) / 3.0;
+        eprintln!(
+            "{:<20} {:>9.4} {:>9.4} {:>+9.2}%",
+            name,
+            expected,
+            measured,
+            100.0 * (measured - expected) / expected
+        );
+    }
 }
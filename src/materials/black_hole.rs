@@ -0,0 +1,20 @@
+use crate::{hittable::HitRecord, ray::Ray};
+
+use super::material::{Material, ScatterRecord};
+
+/// Ends every path which hits it, contributing no light, e.g. to cut a perfectly black
+/// hole into a scene or to cull paths which stray into part of it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlackHole;
+
+impl BlackHole {
+    pub fn new() -> BlackHole {
+        BlackHole
+    }
+}
+
+impl Material for BlackHole {
+    fn scatter(&self, _ray: &Ray, _hit_record: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+}
@@ -1,9 +1,12 @@
 use std::ops::Neg;
 
 use glam::vec3;
-use rand::random;
 
-use crate::{hittable::HitRecord, ray::Ray};
+use crate::{
+    hittable::HitRecord,
+    random::random,
+    ray::{Ray, RayKind},
+};
 
 use super::{
     material::{Material, ScatterRecord},
@@ -52,10 +55,12 @@ impl Material for Dialectric {
             utils::refract(unit_direction, hit_record.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(hit_record.point, direction, ray.time);
-        Some(ScatterRecord {
-            attenuation,
-            ray: scattered,
-        })
+        let scattered =
+            Ray::new(hit_record.point, direction, ray.time).with_kind(RayKind::Specular);
+        Some(ScatterRecord::new(attenuation, scattered))
+    }
+
+    fn is_refractive(&self) -> bool {
+        true
     }
 }
@@ -2,9 +2,12 @@ use std::sync::Arc;
 
 use glam::Vec3;
 
-use crate::textures::{solid_color::SolidColor, texture::Texture};
+use crate::{
+    textures::{solid_color::SolidColor, texture::Texture},
+    units::{self, LightPower},
+};
 
-use super::material::Material;
+use super::{material::Material, utils::blackbody_color};
 
 pub struct DiffuseLight {
     emission_texture: Arc<dyn Texture>,
@@ -20,6 +23,19 @@ impl DiffuseLight {
             emission_texture: Arc::new(SolidColor::new(color)),
         }
     }
+
+    /// Creates a light emitting the color of a blackbody at `temperature` Kelvin,
+    /// e.g. 1900 for candlelight, 2700 for a tungsten bulb, or 5800 for the sun.
+    /// The emitted color has a luminance of `intensity`.
+    pub fn blackbody(temperature: f32, intensity: f32) -> DiffuseLight {
+        DiffuseLight::from_color(blackbody_color(temperature) * intensity)
+    }
+
+    /// Creates a light with the hue of `color` which sends out `power` in total over a
+    /// surface of `area` square meters, for scenes in physical units (see `units`).
+    pub fn from_power(color: Vec3, power: LightPower, area: f32) -> DiffuseLight {
+        DiffuseLight::from_color(units::area_light_radiance(color, power, area))
+    }
 }
 
 impl Material for DiffuseLight {
@@ -34,4 +50,8 @@ impl Material for DiffuseLight {
     fn emit(&self, u: f32, v: f32, point: &Vec3) -> Vec3 {
         self.emission_texture.value(u, v, point)
     }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
 }
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use glam::Vec3;
 
 use crate::{
-    ray::Ray,
+    ray::{Ray, RayKind},
     textures::{solid_color::SolidColor, texture::Texture},
 };
 
@@ -34,10 +34,9 @@ impl Material for Isotropic {
         ray: &crate::ray::Ray,
         hit_record: &crate::hittable::HitRecord,
     ) -> Option<super::material::ScatterRecord> {
-        let scattered = Ray::new(hit_record.point, random_in_unit_sphere(), ray.time);
-        let attenuation = self
-            .albedo
-            .value(hit_record.u, hit_record.v, &hit_record.point);
+        let scattered = Ray::new(hit_record.point, random_in_unit_sphere(), ray.time)
+            .with_kind(RayKind::Diffuse);
+        let attenuation = self.albedo.value_at(hit_record);
         Some(ScatterRecord::new(attenuation, scattered))
     }
 }
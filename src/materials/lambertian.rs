@@ -1,17 +1,17 @@
-use std::sync::Arc;
+use std::{f32::consts::PI, sync::Arc};
 
 use glam::Vec3;
 
 use crate::{
     hittable::HitRecord,
-    ray::Ray,
+    ray::{Ray, RayKind},
     textures::{solid_color::SolidColor, texture::Texture},
-    utils,
+    utils::Onb,
 };
 
 use super::{
     material::{Material, ScatterRecord},
-    utils::random_unit_vector,
+    utils::random_cosine_direction,
 };
 
 #[derive(Clone)]
@@ -33,21 +33,15 @@ impl Lambertian {
 
 impl Material for Lambertian {
     fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let scatter_direction = hit_record.normal + random_unit_vector();
-        // Catch degenerate scatter directions
-        let scatter_direction = if utils::near_zero(&scatter_direction) {
-            hit_record.normal
-        } else {
-            scatter_direction
-        };
-        let scattered = Ray::new(hit_record.point, scatter_direction, ray.time);
-
-        let attenuation = self
-            .albedo
-            .value(hit_record.u, hit_record.v, &hit_record.point);
-        Some(ScatterRecord {
-            ray: scattered,
-            attenuation,
-        })
+        // Scattering in proportion to the cosine of the angle from the normal matches the
+        // cosine factor of the rendering equation, leaving the albedo as the attenuation.
+        let onb = Onb::from_w(hit_record.normal);
+        let scatter_direction = onb.local(random_cosine_direction());
+        let pdf = onb.w().dot(scatter_direction) / PI;
+        let scattered =
+            Ray::new(hit_record.point, scatter_direction, ray.time).with_kind(RayKind::Diffuse);
+
+        let attenuation = self.albedo.value_at(hit_record);
+        Some(ScatterRecord::new(attenuation, scattered).with_pdf(pdf))
     }
 }
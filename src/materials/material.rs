@@ -1,15 +1,28 @@
-use glam::{Vec3, vec3};
+use glam::{vec3, Vec3};
 
 use crate::{hittable::HitRecord, ray::Ray};
 
 pub struct ScatterRecord {
     pub attenuation: Vec3,
     pub ray: Ray,
+    /// The probability density with which the scattered direction was chosen, per unit
+    /// solid angle, if it was drawn from a continuous distribution. None for directions
+    /// which are certain given the incoming ray, e.g. perfect reflections.
+    pub pdf: Option<f32>,
 }
 
 impl ScatterRecord {
     pub fn new(attenuation: Vec3, ray: Ray) -> ScatterRecord {
-        ScatterRecord { attenuation, ray }
+        ScatterRecord {
+            attenuation,
+            ray,
+            pdf: None,
+        }
+    }
+
+    pub fn with_pdf(mut self, pdf: f32) -> ScatterRecord {
+        self.pdf = Some(pdf);
+        self
     }
 }
 
@@ -20,4 +33,14 @@ pub trait Material: Send + Sync {
     fn emit(&self, _u: f32, _v: f32, _point: &Vec3) -> Vec3 {
         vec3(0.0, 0.0, 0.0)
     }
+
+    /// Whether the material is a light source. Lights are kept when materials are overridden.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Whether light refracts into the volume which the material bounds, e.g. glass.
+    fn is_refractive(&self) -> bool {
+        false
+    }
 }
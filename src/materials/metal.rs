@@ -1,6 +1,9 @@
 use glam::Vec3;
 
-use crate::{hittable::HitRecord, ray::Ray};
+use crate::{
+    hittable::HitRecord,
+    ray::{Ray, RayKind},
+};
 
 use super::{
     material::{Material, ScatterRecord},
@@ -29,15 +32,13 @@ impl Material for Metal {
             hit_record.point,
             reflected + self.fuzz * utils::random_in_unit_sphere(),
             ray.time,
-        );
+        )
+        .with_kind(RayKind::Specular);
         let attenuation = self.albedo;
         if scattered.direction.dot(hit_record.normal) > 0.0 {
-            return Some(ScatterRecord {
-                attenuation,
-                ray: scattered,
-            });
+            Some(ScatterRecord::new(attenuation, scattered))
         } else {
-            return None;
+            None
         }
     }
 }
@@ -1,7 +1,11 @@
+pub mod black_hole;
 pub mod dialectric;
 pub mod diffuse_light;
 pub mod isotropic;
 pub mod lambertian;
 pub mod material;
 pub mod metal;
+pub mod portal;
+pub mod reloadable;
 pub mod utils;
+pub mod wireframe;
@@ -0,0 +1,80 @@
+use glam::{Affine3A, Vec3};
+
+use crate::{
+    hittable::HitRecord,
+    ray::{Ray, RayKind},
+};
+
+use super::material::{Material, ScatterRecord};
+
+/// Teleports rays which hit it to another place in the scene, where they carry on as if
+/// they had passed through, unchanged other than being tinted.
+///
+/// The portal is given the transforms of its entrance and its exit, and a ray leaves the
+/// exit at the same position and direction relative to it as it hit the entrance with.
+/// An exit can be a portal too, or empty space. Teleports count as specular bounces, so
+/// rays caught between facing portals stop at the specular depth limit.
+pub struct Portal {
+    /// Maps from around the entrance to around the exit.
+    transform: Affine3A,
+    tint: Vec3,
+}
+
+impl Portal {
+    pub fn new(entrance: Affine3A, exit: Affine3A) -> Portal {
+        Portal {
+            transform: exit * entrance.inverse(),
+            tint: Vec3::ONE,
+        }
+    }
+
+    /// Tints the light passing through the portal, e.g. to make it visible.
+    pub fn with_tint(mut self, tint: Vec3) -> Portal {
+        self.tint = tint;
+        self
+    }
+}
+
+impl Material for Portal {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        let teleported = Ray::new(
+            self.transform.transform_point3(hit_record.point),
+            self.transform.transform_vector3(ray.direction),
+            ray.time,
+        )
+        .with_kind(RayKind::Specular);
+        Some(ScatterRecord::new(self.tint, teleported))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Affine3A, Vec3};
+
+    use crate::{
+        hittable::HitRecord,
+        materials::material::Material,
+        ray::{Ray, RayKind},
+    };
+
+    use super::Portal;
+
+    #[test]
+    fn teleports_relative_to_exit() {
+        // An exit 10 units along +X, turned a quarter turn about Y.
+        let exit = Affine3A::from_translation(vec3(10.0, 0.0, 0.0))
+            * Affine3A::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let portal = Arc::new(Portal::new(Affine3A::IDENTITY, exit));
+
+        let ray = Ray::new(vec3(0.5, 0.0, -1.0), Vec3::Z, 0.0);
+        let hit_record = HitRecord::new(&ray, -Vec3::Z, 1.0, 0.0, 0.0, portal.clone());
+        let scattered = portal.scatter(&ray, &hit_record).unwrap();
+
+        assert!((scattered.ray.origin - vec3(10.0, 0.0, -0.5)).length() < 1e-5);
+        assert!((scattered.ray.direction - Vec3::X).length() < 1e-5);
+        assert!(matches!(scattered.ray.kind, RayKind::Specular));
+        assert_eq!(scattered.attenuation, Vec3::ONE);
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::{Arc, RwLock};
+
+use glam::Vec3;
+
+use crate::{hittable::HitRecord, ray::Ray};
+
+use super::material::{Material, ScatterRecord};
+
+/// A material which can be swapped for another while the scene is in use, e.g. to see an
+/// edited material without rebuilding the BVHs of the objects which use it.
+pub struct Reloadable {
+    material: RwLock<Arc<dyn Material>>,
+}
+
+impl Reloadable {
+    pub fn new(material: Arc<dyn Material>) -> Reloadable {
+        Reloadable {
+            material: RwLock::new(material),
+        }
+    }
+
+    /// Replaces the material. Rays scattered afterwards see the new one.
+    pub fn set(&self, material: Arc<dyn Material>) {
+        *self.material.write().unwrap() = material;
+    }
+
+    fn current(&self) -> Arc<dyn Material> {
+        self.material.read().unwrap().clone()
+    }
+}
+
+impl Material for Reloadable {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        self.current().scatter(ray, hit_record)
+    }
+
+    fn emit(&self, u: f32, v: f32, point: &Vec3) -> Vec3 {
+        self.current().emit(u, v, point)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.current().is_emissive()
+    }
+
+    fn is_refractive(&self) -> bool {
+        self.current().is_refractive()
+    }
+}
@@ -1,11 +1,16 @@
-use std::ops::Neg;
+use std::{f32::consts::PI, ops::Neg};
 
 use glam::Vec3;
-use rand::{random, Rng};
+use rand::Rng;
+
+use crate::random::{self, random};
 
 pub fn random_in_unit_sphere() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    random_in_unit_sphere_with(&mut random::rng())
+}
 
+/// A random point in the unit sphere, drawn from `rng`.
+pub fn random_in_unit_sphere_with(rng: &mut impl Rng) -> Vec3 {
     loop {
         let vec = Vec3::new(
             rng.gen_range(-1.0..1.0),
@@ -23,6 +28,20 @@ pub fn random_unit_vector() -> Vec3 {
     random_in_unit_sphere().normalize()
 }
 
+/// A random direction in the hemisphere about +Z, distributed in proportion to the
+/// cosine of its angle from +Z, so with a density of `z / PI`.
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = random::<f32>();
+    let r2 = random::<f32>();
+    let phi = 2.0 * PI * r1;
+    let sin_theta = r2.sqrt();
+    Vec3::new(
+        phi.cos() * sin_theta,
+        phi.sin() * sin_theta,
+        (1.0 - r2).sqrt(),
+    )
+}
+
 /// Useful as an alternative diffuse shading approach compared to random_on_unit_sphere()
 #[allow(dead_code)]
 pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
@@ -54,10 +73,84 @@ pub fn random_color_range(min: f32, max: f32) -> Vec3 {
     let min = f32::max(min, 0.0);
     let max = f32::min(1.0, max);
 
-    let mut rng = rand::thread_rng();
+    let mut rng = random::rng();
     Vec3::new(
         rng.gen_range(min..max),
         rng.gen_range(min..max),
         rng.gen_range(min..max),
     )
 }
+
+/// Returns the linear sRGB color of a blackbody radiator at `temperature` Kelvin,
+/// normalized to a luminance of 1.0.
+///
+/// Integrates Planck's law against an analytic fit of the CIE 1931 color matching functions
+/// (Wyman, Sloan and Shirley, 2013). Components which fall outside the sRGB gamut
+/// (e.g. blue at candle temperatures) are clamped to zero.
+pub fn blackbody_color(temperature: f32) -> Vec3 {
+    // Planck's constant, speed of light, Boltzmann's constant
+    const H: f64 = 6.62607015e-34;
+    const C: f64 = 2.99792458e8;
+    const K: f64 = 1.380649e-23;
+
+    let temperature = f64::max(temperature as f64, 1.0);
+
+    // Piecewise gaussian, with different widths either side of the peak
+    let g = |x: f64, mu: f64, sigma_1: f64, sigma_2: f64| {
+        let sigma = if x < mu { sigma_1 } else { sigma_2 };
+        f64::exp(-0.5 * ((x - mu) / sigma).powi(2))
+    };
+
+    let mut xyz = [0.0_f64; 3];
+    for wavelength_nm in (380..=780).step_by(5) {
+        let lambda = wavelength_nm as f64;
+        let x_bar = 1.056 * g(lambda, 599.8, 37.9, 31.0) + 0.362 * g(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * g(lambda, 501.1, 20.4, 26.2);
+        let y_bar = 0.821 * g(lambda, 568.8, 46.9, 40.5) + 0.286 * g(lambda, 530.9, 16.3, 31.1);
+        let z_bar = 1.217 * g(lambda, 437.0, 11.8, 36.0) + 0.681 * g(lambda, 459.0, 26.0, 13.8);
+
+        let lambda_m = lambda * 1e-9;
+        let radiance = 2.0 * H * C * C
+            / (lambda_m.powi(5) * (f64::exp(H * C / (lambda_m * K * temperature)) - 1.0));
+
+        xyz[0] += radiance * x_bar;
+        xyz[1] += radiance * y_bar;
+        xyz[2] += radiance * z_bar;
+    }
+
+    if xyz[1] <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let (x, y, z) = (xyz[0] / xyz[1], 1.0, xyz[2] / xyz[1]);
+
+    // CIE XYZ to linear sRGB (D65)
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+
+    Vec3::new(
+        f64::max(r, 0.0) as f32,
+        f64::max(g, 0.0) as f32,
+        f64::max(b, 0.0) as f32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::blackbody_color;
+
+    #[test]
+    fn blackbody_daylight_is_near_white() {
+        let color = blackbody_color(6500.0);
+        assert!((color.x - 1.0).abs() < 0.1);
+        assert!((color.y - 1.0).abs() < 0.1);
+        assert!((color.z - 1.0).abs() < 0.15);
+    }
+
+    #[test]
+    fn blackbody_candle_is_orange() {
+        let color = blackbody_color(1900.0);
+        assert!(color.x > color.y);
+        assert!(color.y > color.z);
+    }
+}
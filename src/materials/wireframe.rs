@@ -0,0 +1,42 @@
+use glam::Vec3;
+
+use crate::{hittable::HitRecord, ray::Ray};
+
+use super::material::{Material, ScatterRecord};
+
+/// Shades triangle edges in a flat `edge` color over a flat `base` color, without lighting,
+/// for inspecting the topology and tessellation density of meshes.
+///
+/// Edges are found from the barycentric coordinates which triangles give as their UVs.
+/// Other geometry has no edges, and only shows lines along its UV seams.
+pub struct Wireframe {
+    base: Vec3,
+    edge: Vec3,
+    /// Width of the edges, as a fraction of the distance from each edge to the opposite vertex.
+    edge_width: f32,
+}
+
+impl Wireframe {
+    pub fn new(base: Vec3, edge: Vec3, edge_width: f32) -> Wireframe {
+        Wireframe {
+            base,
+            edge,
+            edge_width,
+        }
+    }
+}
+
+impl Material for Wireframe {
+    fn scatter(&self, _ray: &Ray, _hit_record: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emit(&self, u: f32, v: f32, _point: &Vec3) -> Vec3 {
+        let w = 1.0 - u - v;
+        if f32::min(w, f32::min(u, v)) < self.edge_width {
+            self.edge
+        } else {
+            self.base
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! A small HTTP endpoint for watching renders on headless machines.
+//!
+//! While a render is running, the monitor serves:
+//! * `/` - A page which polls the endpoints below and shows the render as it progresses.
+//! * `/stats` - Render progress as JSON.
+//! * `/image` - The image so far as a PNG. Tiles which have not completed are black.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>shimmer render monitor</title></head>
+<body style="background:#222;color:#ddd;font-family:monospace">
+<pre id="stats">Waiting for render...</pre>
+<img id="image" src="/image" style="image-rendering:pixelated;max-width:100%">
+<script>
+setInterval(async () => {
+    const stats = await (await fetch('/stats')).json();
+    document.getElementById('stats').textContent = JSON.stringify(stats, null, 2);
+    document.getElementById('image').src = '/image?' + Date.now();
+}, 1000);
+</script>
+</body>
+</html>
+"#;
+
+struct MonitorState {
+    image_width: usize,
+    image_height: usize,
+    /// 8-bit RGB, flattened row-major with the bottom row first (matching `ImageColors`).
+    pixels: Vec<u8>,
+    tiles_completed: usize,
+    tiles_total: usize,
+    start: Option<Instant>,
+    done: bool,
+}
+
+/// Serves the progress of renders over HTTP from a background thread.
+#[derive(Clone)]
+pub struct RenderMonitor {
+    state: Arc<Mutex<MonitorState>>,
+    address: SocketAddr,
+    /// Kept only to stop the server when the last clone is dropped.
+    _server: Arc<Server>,
+}
+
+/// The thread accepting connections, which stops and releases its address once every
+/// handle to the monitor is dropped, e.g. for the next job of a batch to serve on it.
+struct Server {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// How long a client may take to send its request or receive the response.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the server checks whether it should stop while waiting for connections.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+impl RenderMonitor {
+    /// Starts serving on `address` (e.g. "127.0.0.1:8080") in a background thread.
+    /// The server runs until the last clone of the monitor is dropped.
+    pub fn serve<A: ToSocketAddrs>(address: A) -> io::Result<RenderMonitor> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        // Accepting without blocking lets the thread notice when to stop.
+        listener.set_nonblocking(true)?;
+        let state = Arc::new(Mutex::new(MonitorState {
+            image_width: 0,
+            image_height: 0,
+            pixels: Vec::new(),
+            tiles_completed: 0,
+            tiles_total: 0,
+            start: None,
+            done: false,
+        }));
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_state = state.clone();
+        let server_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            while !server_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        // A misbehaving client shouldn't take the monitor down, nor hold
+                        // up other clients by sending nothing.
+                        let state = server_state.clone();
+                        thread::spawn(move || {
+                            let _ = stream
+                                .set_nonblocking(false)
+                                .and_then(|()| stream.set_read_timeout(Some(CLIENT_TIMEOUT)))
+                                .and_then(|()| stream.set_write_timeout(Some(CLIENT_TIMEOUT)))
+                                .and_then(|()| respond(&state, stream));
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL)
+                    }
+                    Err(_) => (),
+                }
+            }
+        });
+
+        Ok(RenderMonitor {
+            state,
+            address,
+            _server: Arc::new(Server {
+                stop,
+                thread: Some(thread),
+            }),
+        })
+    }
+
+    /// The address being served on, e.g. to find the port chosen when serving on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Resets the monitor for a new render of the given size.
+    pub(crate) fn begin(&self, image_width: usize, image_height: usize, tiles_total: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.image_width = image_width;
+        state.image_height = image_height;
+        state.pixels = vec![0; image_width * image_height * 3];
+        state.tiles_completed = 0;
+        state.tiles_total = tiles_total;
+        state.start = Some(Instant::now());
+        state.done = false;
+    }
+
+    /// Starts another pass over all tiles of the current render, keeping the image so far.
+    pub(crate) fn begin_pass(&self) {
+        self.state.lock().unwrap().tiles_completed = 0;
+    }
+
+    /// Records a completed tile. `pixel` returns the 8-bit color of the tile's pixel at
+    /// the given full-image coordinates.
+    pub(crate) fn tile_completed<F>(
+        &self,
+        x_start: usize,
+        y_start: usize,
+        width: usize,
+        height: usize,
+        pixel: F,
+    ) where
+        F: Fn(usize, usize) -> [u8; 3],
+    {
+        let mut state = self.state.lock().unwrap();
+        let image_width = state.image_width;
+        for y in y_start..y_start + height {
+            for x in x_start..x_start + width {
+                let idx = (y * image_width + x) * 3;
+                state.pixels[idx..idx + 3].copy_from_slice(&pixel(x, y));
+            }
+        }
+        state.tiles_completed += 1;
+    }
+
+    pub(crate) fn finish(&self) {
+        self.state.lock().unwrap().done = true;
+    }
+}
+
+fn respond(state: &Mutex<MonitorState>, mut stream: TcpStream) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or("/");
+
+    let (content_type, body) = match path {
+        "/" => ("text/html", INDEX_HTML.as_bytes().to_vec()),
+        "/stats" => ("application/json", stats_json(state).into_bytes()),
+        "/image" => ("image/png", png(state)?),
+        _ => {
+            return write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+        }
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+fn stats_json(state: &Mutex<MonitorState>) -> String {
+    let state = state.lock().unwrap();
+    let elapsed = state
+        .start
+        .map_or(0.0, |start| start.elapsed().as_secs_f32());
+    format!(
+        "{{\"image_width\":{},\"image_height\":{},\"tiles_completed\":{},\"tiles_total\":{},\"elapsed_seconds\":{},\"done\":{}}}",
+        state.image_width,
+        state.image_height,
+        state.tiles_completed,
+        state.tiles_total,
+        elapsed,
+        state.done
+    )
+}
+
+fn png(state: &Mutex<MonitorState>) -> io::Result<Vec<u8>> {
+    // Copy the pixels and encode them after unlocking, so as not to hold up the tiles
+    // being rendered.
+    let (flipped, image_width, image_height) = {
+        let state = state.lock().unwrap();
+        if state.image_width == 0 || state.image_height == 0 {
+            return Ok(Vec::new());
+        }
+        // Our rows are stored bottom-up; images are encoded top-down.
+        let row_len = state.image_width * 3;
+        let flipped: Vec<u8> = state
+            .pixels
+            .chunks(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        (flipped, state.image_width, state.image_height)
+    };
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            &flipped,
+            image_width as u32,
+            image_height as u32,
+            ColorType::Rgb8,
+        )
+        .map_err(io::Error::other)?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::RenderMonitor;
+
+    #[test]
+    fn dropped_monitors_release_their_port() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        // As for two jobs of a batch serving on the same port, one after the other.
+        let first = RenderMonitor::serve(("127.0.0.1", port)).unwrap();
+        let clone = first.clone();
+        first.begin(4, 4, 1);
+        drop(first);
+        assert!(RenderMonitor::serve(("127.0.0.1", port)).is_err());
+        drop(clone);
+        let second = RenderMonitor::serve(("127.0.0.1", port)).unwrap();
+        second.begin(4, 4, 1);
+    }
+
+    #[test]
+    fn idle_clients_dont_block_others() {
+        let monitor = RenderMonitor::serve("127.0.0.1:0").unwrap();
+        let address = monitor.local_addr();
+        monitor.begin(2, 2, 1);
+        // Connects and never sends a request.
+        let _idle = TcpStream::connect(address).unwrap();
+        let mut client = TcpStream::connect(address).unwrap();
+        write!(client, "GET /stats HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"tiles_total\":1"));
+    }
+}
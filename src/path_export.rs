@@ -0,0 +1,149 @@
+//! Recording and exporting the paths taken by camera rays, for debugging.
+//!
+//! Paths can be written as OBJ polylines, which can be imported into e.g. Blender
+//! alongside the scene, or as JSON for custom tooling.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use glam::Vec3;
+
+/// A rectangle of pixels in image coordinates, with (0, 0) at the top left.
+#[derive(Clone, Copy, Debug)]
+pub struct PixelRegion {
+    pub x_start: usize,
+    pub y_start: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PixelRegion {
+    pub fn new(x_start: usize, y_start: usize, width: usize, height: usize) -> PixelRegion {
+        PixelRegion {
+            x_start,
+            y_start,
+            width,
+            height,
+        }
+    }
+}
+
+/// The path of a single camera ray sample through the scene.
+#[derive(Clone, Debug)]
+pub struct RayPath {
+    /// The pixel the sample belongs to, in image coordinates.
+    pub pixel: (usize, usize),
+    /// The index of the sample within its pixel.
+    pub sample: u32,
+    /// The camera ray's origin, followed by each surface hit in order.
+    pub vertices: Vec<Vec3>,
+    /// If the path escaped the scene, the direction in which it left.
+    pub escape_direction: Option<Vec3>,
+    /// The radiance carried back to the camera along the path.
+    pub radiance: Vec3,
+}
+
+impl RayPath {
+    pub fn new(pixel: (usize, usize), sample: u32, origin: Vec3) -> RayPath {
+        RayPath {
+            pixel,
+            sample,
+            vertices: vec![origin],
+            escape_direction: None,
+            radiance: Vec3::ZERO,
+        }
+    }
+}
+
+/// Writes the `paths` as OBJ polylines, one object per path. Escaping paths end with a
+/// segment of `escape_length` in the direction they left the scene.
+pub fn write_obj(paths: &[RayPath], file: &Path, escape_length: f32) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    let mut vertex_count = 0;
+    for path in paths {
+        writeln!(
+            writer,
+            "o path_{}_{}_{}",
+            path.pixel.0, path.pixel.1, path.sample
+        )?;
+        let mut vertices = path.vertices.clone();
+        if let (Some(direction), Some(last)) = (path.escape_direction, vertices.last()) {
+            vertices.push(*last + direction * escape_length);
+        }
+        for vertex in vertices.iter() {
+            writeln!(writer, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+        }
+        // OBJ indices start at 1.
+        let indices: Vec<String> = (vertex_count + 1..=vertex_count + vertices.len())
+            .map(|i| i.to_string())
+            .collect();
+        if indices.len() > 1 {
+            writeln!(writer, "l {}", indices.join(" "))?;
+        }
+        vertex_count += vertices.len();
+    }
+    writer.flush()
+}
+
+/// Writes the `paths` as a JSON array.
+pub fn write_json(paths: &[RayPath], file: &Path) -> io::Result<()> {
+    // JSON has no infinities or NaNs, so those are written as null.
+    let number_json = |x: f32| {
+        if x.is_finite() {
+            x.to_string()
+        } else {
+            String::from("null")
+        }
+    };
+    let vec3_json = |v: Vec3| {
+        format!(
+            "[{},{},{}]",
+            number_json(v.x),
+            number_json(v.y),
+            number_json(v.z)
+        )
+    };
+    let mut writer = BufWriter::new(File::create(file)?);
+    writeln!(writer, "[")?;
+    for (i, path) in paths.iter().enumerate() {
+        let vertices: Vec<String> = path.vertices.iter().map(|v| vec3_json(*v)).collect();
+        let escape_direction = path
+            .escape_direction
+            .map_or(String::from("null"), vec3_json);
+        write!(
+            writer,
+            "  {{\"pixel\":[{},{}],\"sample\":{},\"vertices\":[{}],\"escape_direction\":{},\"radiance\":{}}}",
+            path.pixel.0,
+            path.pixel.1,
+            path.sample,
+            vertices.join(","),
+            escape_direction,
+            vec3_json(path.radiance)
+        )?;
+        writeln!(writer, "{}", if i + 1 < paths.len() { "," } else { "" })?;
+    }
+    writeln!(writer, "]")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::{write_json, RayPath};
+
+    #[test]
+    fn non_finite_numbers_are_written_as_null() {
+        let mut path = RayPath::new((0, 0), 0, Vec3::ZERO);
+        path.radiance = vec3(f32::NAN, f32::INFINITY, 1.0);
+        let file = std::env::temp_dir().join("shimmer_paths.json");
+        write_json(&[path], &file).unwrap();
+        let json = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert!(json.contains("\"radiance\":[null,null,1]"));
+    }
+}
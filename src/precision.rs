@@ -0,0 +1,85 @@
+//! The floating point precision used for ray-geometry intersection math.
+//!
+//! Intersections are computed in `f32` by default. Enabling the `double-precision`
+//! feature computes those of `Sphere`, `MovingSphere` and `Tri` in `f64` instead, which
+//! avoids self-intersection and missed hits on them in very large (e.g. planet-scale)
+//! scenes, at some cost to speed. Other primitives, and BVH traversal through `Aabb`,
+//! stay in `f32`.
+
+use glam::Vec3;
+
+use crate::aabb::Aabb;
+
+#[cfg(not(feature = "double-precision"))]
+pub type Real = f32;
+#[cfg(not(feature = "double-precision"))]
+pub type RealVec3 = Vec3;
+
+#[cfg(feature = "double-precision")]
+pub type Real = f64;
+#[cfg(feature = "double-precision")]
+pub type RealVec3 = glam::DVec3;
+
+/// Converts a vector to the intersection precision.
+#[cfg(not(feature = "double-precision"))]
+pub fn to_real(v: Vec3) -> RealVec3 {
+    v
+}
+
+/// Converts a vector to the intersection precision.
+#[cfg(feature = "double-precision")]
+pub fn to_real(v: Vec3) -> RealVec3 {
+    v.as_dvec3()
+}
+
+/// Converts a vector from the intersection precision.
+#[cfg(not(feature = "double-precision"))]
+pub fn from_real(v: RealVec3) -> Vec3 {
+    v
+}
+
+/// Converts a vector from the intersection precision.
+#[cfg(feature = "double-precision")]
+pub fn from_real(v: RealVec3) -> Vec3 {
+    v.as_vec3()
+}
+
+/// Rounding errors in hit points grow with the magnitude of their coordinates. Hits closer
+/// to a ray's origin than this fraction of its coordinates' magnitude are taken to be the
+/// surface the ray is leaving.
+const RELATIVE_EPSILON: f32 = 2e-6;
+
+/// Intersection math on large objects loses precision even near the origin (e.g. near a
+/// large sphere's surface), so hits are also ignored within this fraction of the size of
+/// the scene.
+const SCENE_EPSILON: f32 = 1e-6;
+
+/// The distance within which a ray's hits are always ignored, for a scene with `bounds`.
+pub fn scene_epsilon(bounds: Option<Aabb>) -> f32 {
+    match bounds {
+        Some(bounds) => {
+            SCENE_EPSILON
+                * f32::max(
+                    bounds.min().abs().max_element(),
+                    bounds.max().abs().max_element(),
+                )
+        }
+        // Unbounded scenes, e.g. with infinite planes, rely on the relative epsilon alone.
+        None => 0.0,
+    }
+}
+
+/// The smallest `t` at which a ray from `origin` along `direction` hits anything, so that
+/// rays leaving a surface don't hit it again due to rounding. Hits within `scene_epsilon`
+/// or within the relative error of the origin's coordinates, whichever is larger, are
+/// ignored.
+pub fn t_min(origin: Vec3, direction: Vec3, scene_epsilon: f32) -> f32 {
+    let distance = f32::max(RELATIVE_EPSILON * origin.abs().max_element(), scene_epsilon);
+    distance / direction.length()
+}
+
+/// How far to pad a bounding box from the `coordinate` of a flat object, so that the box
+/// has some thickness even where `coordinate` is too large for `f32::EPSILON` to change.
+pub fn bounds_padding(coordinate: f32) -> f32 {
+    f32::EPSILON * coordinate.abs().max(1.0)
+}
@@ -0,0 +1,65 @@
+//! The color primaries of output images.
+//!
+//! Rendering works in linear RGB with sRGB (Rec. 709) primaries, which is also what
+//! images are written in by default. Wider gamut displays can show more saturated colors,
+//! but they interpret pixel values relative to their own primaries, so images meant for
+//! them must be converted. Colors within the sRGB gamut stay within these wider gamuts,
+//! so the conversion never needs to clip.
+
+use glam::{Mat3, Vec3};
+
+/// The primaries which an output image's RGB values are relative to. All use the D65
+/// white point, so white stays white.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Primaries {
+    /// The primaries of sRGB and Rec. 709, used for rendering.
+    #[default]
+    Srgb,
+    /// The primaries of DCI-P3 with a D65 white point, as used by Apple's displays.
+    DisplayP3,
+    /// The primaries of Rec. 2020, for HDR and UHD video.
+    Rec2020,
+}
+
+impl Primaries {
+    /// Converts a linear color with sRGB primaries to these primaries.
+    pub fn from_linear_srgb(&self, color: Vec3) -> Vec3 {
+        match self {
+            Primaries::Srgb => color,
+            // Via CIE XYZ. Matrices are given column by column.
+            Primaries::DisplayP3 => {
+                Mat3::from_cols_array(&[
+                    0.822_462, 0.033_194, 0.017_083, //
+                    0.177_538, 0.966_806, 0.072_397, //
+                    0.0, 0.0, 0.910_520,
+                ]) * color
+            }
+            Primaries::Rec2020 => {
+                Mat3::from_cols_array(&[
+                    0.627_404, 0.069_097, 0.016_392, //
+                    0.329_283, 0.919_540, 0.088_013, //
+                    0.043_313, 0.011_362, 0.895_595,
+                ]) * color
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::Primaries;
+
+    #[test]
+    fn white_and_primaries_convert() {
+        for primaries in [Primaries::DisplayP3, Primaries::Rec2020] {
+            let white = primaries.from_linear_srgb(Vec3::ONE);
+            assert!((white - Vec3::ONE).abs().max_element() < 1e-4);
+
+            // sRGB's red is within the wider gamut, so less saturated there.
+            let red = primaries.from_linear_srgb(Vec3::X);
+            assert!(red.x < 1.0 && red.y > 0.0 && red.z > 0.0);
+        }
+    }
+}
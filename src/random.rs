@@ -0,0 +1,154 @@
+//! The random numbers used while rendering.
+//!
+//! By default these are drawn from the thread's RNG, so the noise in an image depends on
+//! which thread rendered which pixel. Within `seeded()` they are instead drawn from a
+//! stream determined only by the seed, so seeding each sample from its pixel and sample
+//! index (see `sample_seed()`) makes renders reproducible regardless of tiling, thread
+//! scheduling or core count.
+//!
+//! Materials, textures and geometry which sample randomly should use `rng()` or
+//! `random()` rather than `rand::thread_rng()` so that they're seeded too.
+
+use std::cell::RefCell;
+use std::thread;
+
+use rand::{
+    distributions::{Distribution, Standard},
+    rngs::SmallRng,
+    Rng, RngCore, SeedableRng,
+};
+
+thread_local! {
+    static SEEDED: RefCell<Option<SmallRng>> = const { RefCell::new(None) };
+}
+
+/// Draws from the seeded stream if within `seeded()`, and the thread's RNG otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SampleRng;
+
+impl RngCore for SampleRng {
+    fn next_u32(&mut self) -> u32 {
+        SEEDED.with(|seeded| match seeded.borrow_mut().as_mut() {
+            Some(rng) => rng.next_u32(),
+            None => rand::thread_rng().next_u32(),
+        })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        SEEDED.with(|seeded| match seeded.borrow_mut().as_mut() {
+            Some(rng) => rng.next_u64(),
+            None => rand::thread_rng().next_u64(),
+        })
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        SEEDED.with(|seeded| match seeded.borrow_mut().as_mut() {
+            Some(rng) => rng.fill_bytes(dest),
+            None => rand::thread_rng().fill_bytes(dest),
+        })
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+pub fn rng() -> SampleRng {
+    SampleRng
+}
+
+/// A random value from the current stream, as `rand::random()`.
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    rng().gen()
+}
+
+/// Runs `f` with random numbers on this thread drawn from the stream given by `seed`.
+pub fn seeded<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    Stream::new(seed).run(f)
+}
+
+/// A seeded stream of random numbers which can be drawn from in several runs, e.g. to
+/// generate a sample's camera ray and later trace it, as if they were drawn in one.
+#[derive(Clone, Debug)]
+pub struct Stream(SmallRng);
+
+impl Stream {
+    pub fn new(seed: u64) -> Stream {
+        Stream(SmallRng::seed_from_u64(seed))
+    }
+
+    /// Runs `f` with random numbers on this thread drawn from where the stream left off.
+    pub fn run<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let mut previous = Reinstall(SEEDED.with(|seeded| seeded.replace(Some(self.0.clone()))));
+        let result = f();
+        let rng = SEEDED.with(|seeded| seeded.replace(previous.0.take()));
+        self.0 = rng.expect("the stream is installed while running");
+        result
+    }
+}
+
+/// Puts back the stream which a run replaced if the run panics, so that a caught panic
+/// doesn't leave the thread drawing from the run's stream.
+struct Reinstall(Option<SmallRng>);
+
+impl Drop for Reinstall {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            SEEDED.with(|seeded| seeded.replace(self.0.take()));
+        }
+    }
+}
+
+/// Derives the seed for one sample of a pixel from a seed for the whole render.
+pub fn sample_seed(seed: u64, pixel_index: u64, sample_index: u32) -> u64 {
+    let mut hash = seed;
+    for value in [pixel_index, sample_index as u64] {
+        hash = splitmix64(hash ^ splitmix64(value));
+    }
+    hash
+}
+
+/// The SplitMix64 finalizer, which scrambles nearby inputs into unrelated outputs.
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::{random, sample_seed, seeded, Stream, SEEDED};
+
+    #[test]
+    fn seeded_streams_repeat() {
+        let draw = || (0..4).map(|_| random::<u32>()).collect::<Vec<_>>();
+        let seed = sample_seed(7, 12, 3);
+        assert_eq!(seeded(seed, draw), seeded(seed, draw));
+        assert_ne!(seeded(seed, draw), seeded(sample_seed(7, 12, 4), draw));
+    }
+
+    #[test]
+    fn streams_continue_across_runs() {
+        let mut stream = Stream::new(3);
+        let first = stream.run(random::<u64>);
+        let second = stream.run(random::<u64>);
+        assert_eq!(
+            seeded(3, || (random::<u64>(), random::<u64>())),
+            (first, second)
+        );
+    }
+
+    #[test]
+    fn panicking_runs_put_back_the_previous_stream() {
+        let result = panic::catch_unwind(|| seeded(1, || panic!("the run fails")));
+        assert!(result.is_err());
+        assert!(SEEDED.with(|seeded| seeded.borrow().is_none()));
+    }
+}
@@ -1,19 +1,311 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    ops::{BitAnd, BitOr},
+    sync::{Arc, Mutex},
+};
 
 use ahash::AHashMap;
 use glam::Vec3;
 
 use crate::{
+    background::Background,
     bvh::BvhId,
-    hittable::{Hittable, HittableList},
+    caustics::CausticMap,
+    denoise::Guide,
+    hittable::{HitRecord, Hittable, HittableList, Volume},
     hrpp::Predictor,
+    lpe::LightPathExpression,
+    materials::material::ScatterRecord,
+    path_export::RayPath,
 };
 
+/// What produced a ray, which determines which objects it can see.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RayKind {
+    /// A primary ray from the camera.
+    Camera,
+    /// A ray scattered diffusely, e.g. by a Lambertian surface or a volume.
+    /// Shadows form where these rays are blocked from reaching lights.
+    Diffuse,
+    /// A ray reflected or refracted specularly, e.g. by metal or glass.
+    Specular,
+}
+
+/// Bits deciding which rays can hit which objects, without building separate scenes for
+/// each kind of ray. The lowest bits stand for the kinds of rays, and the rest for groups
+/// of objects which a scene can assign as it likes, e.g. for render layers.
+///
+/// An object's mask holds the kinds of rays which can hit it and the groups it's in (see
+/// `Hittable::mask()`), and a ray's holds its kind and the groups it can see (see
+/// `Ray::mask()`). A ray hits an object only if the object's mask has the ray's kind and
+/// they share a group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RayMask(pub u32);
+
+impl RayMask {
+    /// Every kind of ray, and every group.
+    pub const ALL: RayMask = RayMask(u32::MAX);
+    pub const CAMERA: RayMask = RayMask(1 << 0);
+    pub const DIFFUSE: RayMask = RayMask(1 << 1);
+    pub const SPECULAR: RayMask = RayMask(1 << 2);
+    /// Every kind of ray, in no group.
+    pub const KINDS: RayMask = RayMask(0b111);
+    /// Every group, for no kind of ray.
+    pub const GROUPS: RayMask = RayMask(!0b111);
+    /// The number of groups there are bits for.
+    pub const GROUP_COUNT: u32 = 29;
+
+    /// The bit of the `index`th group, from 0 to `GROUP_COUNT - 1`.
+    pub const fn group(index: u32) -> RayMask {
+        assert!(index < RayMask::GROUP_COUNT, "There are only 29 ray groups");
+        RayMask(1 << (index + 3))
+    }
+
+    /// The bit of the kind of ray.
+    pub const fn kind(kind: RayKind) -> RayMask {
+        match kind {
+            RayKind::Camera => RayMask::CAMERA,
+            RayKind::Diffuse => RayMask::DIFFUSE,
+            RayKind::Specular => RayMask::SPECULAR,
+        }
+    }
+
+    /// Whether an object with this mask can be hit by a ray with the `ray` mask: the
+    /// object admits the ray's kind, and they share a group.
+    pub const fn admits(&self, ray: RayMask) -> bool {
+        let shared = self.0 & ray.0;
+        shared & RayMask::KINDS.0 != 0 && shared & RayMask::GROUPS.0 != 0
+    }
+}
+
+impl BitOr for RayMask {
+    type Output = RayMask;
+
+    fn bitor(self, other: RayMask) -> RayMask {
+        RayMask(self.0 | other.0)
+    }
+}
+
+impl BitAnd for RayMask {
+    type Output = RayMask;
+
+    fn bitand(self, other: RayMask) -> RayMask {
+        RayMask(self.0 & other.0)
+    }
+}
+
+/// The number of further bounces a path may take, overall and by the kind of scattering.
+#[derive(Clone, Copy, Debug)]
+pub struct Depth {
+    total: u32,
+    diffuse: u32,
+    specular: u32,
+}
+
+impl Depth {
+    pub fn new(total: u32, diffuse: u32, specular: u32) -> Depth {
+        Depth {
+            total,
+            diffuse,
+            specular,
+        }
+    }
+
+    /// Whether the bounce limit has been reached, so no further rays are traced.
+    fn is_exhausted(&self) -> bool {
+        self.total == 0
+    }
+
+    /// The depth left for a ray of the given kind scattered at the end of this one,
+    /// or `None` if the path may not bounce that way again.
+    fn after_scatter(&self, kind: RayKind) -> Option<Depth> {
+        let mut depth = *self;
+        depth.total = depth.total.checked_sub(1)?;
+        match kind {
+            RayKind::Camera => (),
+            RayKind::Diffuse => depth.diffuse = depth.diffuse.checked_sub(1)?,
+            RayKind::Specular => depth.specular = depth.specular.checked_sub(1)?,
+        }
+        Some(depth)
+    }
+}
+
+/// Why a path stopped bouncing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Termination {
+    /// The path left the scene, picking up light from the background.
+    #[default]
+    Escaped,
+    /// The path was absorbed, e.g. by a light or a material which doesn't scatter.
+    Absorbed,
+    /// The path was cut short by the maximum depth.
+    MaxDepth,
+}
+
+/// How far along a path traced with a caustic map is, which determines whether light is
+/// taken from the map or from the path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CausticState {
+    /// Only specular bounces since the camera; the next diffuse surface looks up caustics.
+    BeforeDiffuse,
+    /// Just scattered from the surface which looked up caustics.
+    AfterDiffuse,
+    /// Specularly bounced since that surface, so a light reached now is part of its
+    /// caustics, which were already looked up.
+    Caustic,
+}
+
+/// Statistics about a traced path.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathStats {
+    /// The number of times the path scattered.
+    pub bounces: u32,
+    pub termination: Termination,
+}
+
+/// Light path expressions, and the light arriving along paths which match each of them.
+pub struct LightPathPasses<'a> {
+    pub expressions: &'a [LightPathExpression],
+    /// The light for each expression, in the same order.
+    pub colors: &'a mut [Vec3],
+}
+
+/// What a path is traced through.
+struct Scene<'a> {
+    world: &'a HittableList,
+    background: &'a Background,
+    predictors: &'a Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+}
+
+/// Follows a path as it's traced by `Ray::walk()`, to record it or change the light
+/// gathered along it. Each method is called at most once per vertex, in the order the
+/// path reaches them.
+trait PathVisitor {
+    /// The path left the scene, picking up `light` from the background.
+    fn escaped(&mut self, _ray: &Ray, _light: Vec3) {}
+
+    /// The path hit a surface, which emits `emitted`. Returns the emitted light to gather.
+    fn hit(&mut self, _hit_record: &HitRecord, emitted: Vec3) -> Vec3 {
+        emitted
+    }
+
+    /// The path scattered from the surface it hit, and continues. Returns any further
+    /// light to gather at the surface.
+    fn scattered(&mut self, _hit_record: &HitRecord, _scatter_record: &ScatterRecord) -> Vec3 {
+        Vec3::ZERO
+    }
+}
+
+/// Gathers the light along the path, and nothing more.
+impl PathVisitor for () {}
+
+/// Records each surface hit along the path, and the direction in which it escapes.
+impl PathVisitor for RayPath {
+    fn escaped(&mut self, ray: &Ray, _light: Vec3) {
+        self.escape_direction = Some(ray.direction.normalize());
+    }
+
+    fn hit(&mut self, hit_record: &HitRecord, emitted: Vec3) -> Vec3 {
+        self.vertices.push(hit_record.point);
+        emitted
+    }
+}
+
+/// Adds the light reaching the camera along the path to the passes whose expressions
+/// match the events along it.
+struct PassVisitor<'p, 'a> {
+    passes: &'p mut LightPathPasses<'a>,
+    /// The events along the path so far.
+    events: String,
+    /// The fraction of the light at the current vertex which reaches the camera.
+    throughput: Vec3,
+}
+
+impl PassVisitor<'_, '_> {
+    fn contribute(&mut self, event: char, light: Vec3) {
+        self.events.push(event);
+        let passes = &mut *self.passes;
+        for (expression, pass) in passes.expressions.iter().zip(passes.colors.iter_mut()) {
+            if expression.matches(&self.events) {
+                *pass += self.throughput * light;
+            }
+        }
+        self.events.pop();
+    }
+}
+
+impl PathVisitor for PassVisitor<'_, '_> {
+    fn escaped(&mut self, _ray: &Ray, light: Vec3) {
+        self.contribute('B', light);
+    }
+
+    fn hit(&mut self, _hit_record: &HitRecord, emitted: Vec3) -> Vec3 {
+        if emitted != Vec3::ZERO {
+            self.contribute('L', emitted);
+        }
+        emitted
+    }
+
+    fn scattered(&mut self, _hit_record: &HitRecord, scatter_record: &ScatterRecord) -> Vec3 {
+        self.events.push(match scatter_record.ray.kind {
+            RayKind::Specular => 'S',
+            RayKind::Camera | RayKind::Diffuse => 'D',
+        });
+        self.throughput *= scatter_record.attenuation;
+        Vec3::ZERO
+    }
+}
+
+/// Lights the first diffuse surface along the path from a caustic map.
+struct CausticVisitor<'a> {
+    caustics: &'a CausticMap,
+    /// How far along the path is, or `None` once it's past a second diffuse surface and
+    /// traced as usual.
+    state: Option<CausticState>,
+}
+
+impl PathVisitor for CausticVisitor<'_> {
+    fn hit(&mut self, _hit_record: &HitRecord, emitted: Vec3) -> Vec3 {
+        if self.state == Some(CausticState::Caustic) {
+            Vec3::ZERO
+        } else {
+            emitted
+        }
+    }
+
+    fn scattered(&mut self, hit_record: &HitRecord, scatter_record: &ScatterRecord) -> Vec3 {
+        let Some(state) = self.state else {
+            return Vec3::ZERO;
+        };
+        let specular = scatter_record.ray.kind == RayKind::Specular;
+        let (caustic, next_state) = match (state, specular) {
+            (CausticState::BeforeDiffuse, true) => (Vec3::ZERO, Some(CausticState::BeforeDiffuse)),
+            (CausticState::BeforeDiffuse, false) => (
+                self.caustics.radiance(
+                    hit_record.point,
+                    hit_record.normal,
+                    scatter_record.attenuation,
+                ),
+                Some(CausticState::AfterDiffuse),
+            ),
+            (CausticState::AfterDiffuse | CausticState::Caustic, true) => {
+                (Vec3::ZERO, Some(CausticState::Caustic))
+            }
+            // Past a second diffuse surface, the path is traced as usual.
+            (CausticState::AfterDiffuse | CausticState::Caustic, false) => (Vec3::ZERO, None),
+        };
+        self.state = next_state;
+        caustic
+    }
+}
+
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     /// The time at which the ray exists
     pub time: f32,
+    pub kind: RayKind,
+    /// The groups of objects the ray can hit, which rays scattered from it inherit.
+    pub groups: RayMask,
 }
 
 impl Ray {
@@ -22,9 +314,28 @@ impl Ray {
             origin,
             direction,
             time,
+            kind: RayKind::Camera,
+            groups: RayMask::GROUPS,
         }
     }
 
+    pub fn with_kind(mut self, kind: RayKind) -> Ray {
+        self.kind = kind;
+        self
+    }
+
+    /// Lets the ray hit only objects in the `groups`, ignoring any kinds of rays they
+    /// hold.
+    pub fn with_groups(mut self, groups: RayMask) -> Ray {
+        self.groups = groups & RayMask::GROUPS;
+        self
+    }
+
+    /// The ray's kind and groups, to be matched against the masks of objects.
+    pub fn mask(&self) -> RayMask {
+        RayMask::kind(self.kind) | self.groups
+    }
+
     pub fn at(&self, t: f32) -> Vec3 {
         self.origin + t * self.direction
     }
@@ -32,32 +343,264 @@ impl Ray {
     pub fn ray_color(
         &self,
         world: &HittableList,
-        depth: u32,
-        background: Vec3,
+        depth: Depth,
+        background: &Background,
         predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
     ) -> Vec3 {
-        // Ray bounce limit reached; accumulate no further light.
-        if depth <= 0 {
-            return Vec3::ZERO;
+        let scene = Scene {
+            world,
+            background,
+            predictors,
+        };
+        self.walk(&scene, depth, &mut (), &mut PathStats::default())
+    }
+
+    /// A quick approximation of `ray_color()` which doesn't bounce: the light emitted
+    /// by the surface the ray hits plus that surface's albedo, as though it were lit by
+    /// uniform white light, or the background if the ray misses.
+    pub fn emission_albedo(
+        &self,
+        world: &HittableList,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Vec3 {
+        let Some(hit_record) = world.hit(self, world.t_min(self), f32::INFINITY, predictors) else {
+            return background.value(self.direction);
+        };
+        let emitted = hit_record
+            .material
+            .emit(hit_record.u, hit_record.v, &hit_record.point);
+        match hit_record.material.scatter(self, &hit_record) {
+            Some(scatter_record) => emitted + scatter_record.attenuation,
+            None => emitted,
         }
+    }
 
-        let hit_record = world.hit(&self, 0.001, f32::INFINITY, &predictors);
-        if let Some(hit_record) = hit_record {
-            let emitted = hit_record
+    /// Finds the normal and albedo of the first surface the ray hits, to guide denoising.
+    pub(crate) fn guide(
+        &self,
+        world: &HittableList,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Guide {
+        let Some(hit_record) = world.hit(self, world.t_min(self), f32::INFINITY, predictors) else {
+            return Guide {
+                normal: Vec3::ZERO,
+                albedo: background.value(self.direction),
+            };
+        };
+        let albedo = match hit_record.material.scatter(self, &hit_record) {
+            Some(scatter_record) => scatter_record.attenuation,
+            None => hit_record
                 .material
-                .emit(hit_record.u, hit_record.v, &hit_record.point);
-
-            if let Some(scatter_record) = hit_record.material.scatter(&self, &hit_record) {
-                emitted
-                    + scatter_record.attenuation
-                        * scatter_record
-                            .ray
-                            .ray_color(world, depth - 1, background, &predictors)
-            } else {
-                emitted
-            }
+                .emit(hit_record.u, hit_record.v, &hit_record.point),
+        };
+        Guide {
+            normal: hit_record.normal,
+            albedo,
+        }
+    }
+
+    /// Colors the ray by the volumes it's inside just past the first surface it hits:
+    /// green for each medium and blue for each refractive volume, with red added where
+    /// volumes overlap, which they shouldn't. Surfaces outside any volume are grey and
+    /// misses are black.
+    pub fn volume_debug(
+        &self,
+        world: &HittableList,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+    ) -> Vec3 {
+        let Some(hit_record) = world.hit(self, world.t_min(self), f32::INFINITY, predictors) else {
+            return Vec3::ZERO;
+        };
+        let past_hit = Ray::new(hit_record.point, self.direction, self.time);
+        let point = past_hit.at(world.t_min(&past_hit));
+        let mut volumes = Vec::new();
+        world.volumes_containing(point, self.time, &mut volumes);
+
+        let media = volumes.iter().filter(|v| **v == Volume::Medium).count();
+        let refractive = volumes.len() - media;
+        let color = if volumes.is_empty() {
+            Vec3::splat(0.2)
         } else {
-            background
+            Vec3::new(
+                if volumes.len() > 1 { 1.0 } else { 0.0 },
+                media as f32 / 2.0,
+                refractive as f32 / 2.0,
+            )
+            .min(Vec3::ONE)
+        };
+        // Shade by the angle to the surface, to show its shape.
+        let facing = hit_record.normal.dot(self.direction.normalize()).abs();
+        color * (0.5 + 0.5 * facing)
+    }
+
+    /// As `ray_color()`, additionally recording statistics about the path in `stats`.
+    pub fn ray_color_with_stats(
+        &self,
+        world: &HittableList,
+        depth: Depth,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        stats: &mut PathStats,
+    ) -> Vec3 {
+        *stats = PathStats::default();
+        let scene = Scene {
+            world,
+            background,
+            predictors,
+        };
+        self.walk(&scene, depth, &mut (), stats)
+    }
+
+    /// As `ray_color_with_stats()`, lighting the first diffuse surface along the path with
+    /// the photons of the `caustics` map, rather than with paths which reach a light
+    /// through specular bounces from there.
+    pub fn ray_color_with_caustics(
+        &self,
+        world: &HittableList,
+        depth: Depth,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        caustics: &CausticMap,
+        stats: &mut PathStats,
+    ) -> Vec3 {
+        *stats = PathStats::default();
+        let scene = Scene {
+            world,
+            background,
+            predictors,
+        };
+        let mut visitor = CausticVisitor {
+            caustics,
+            state: Some(CausticState::BeforeDiffuse),
+        };
+        self.walk(&scene, depth, &mut visitor, stats)
+    }
+
+    /// As `ray_color_with_stats()`, given the closest thing the ray hits (e.g. from a
+    /// cache) rather than finding it.
+    pub fn ray_color_from_hit(
+        &self,
+        hit_record: Option<HitRecord>,
+        world: &HittableList,
+        depth: Depth,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        stats: &mut PathStats,
+    ) -> Vec3 {
+        *stats = PathStats::default();
+        if depth.is_exhausted() {
+            stats.termination = Termination::MaxDepth;
+            return Vec3::ZERO;
+        }
+        let scene = Scene {
+            world,
+            background,
+            predictors,
+        };
+        self.shade(hit_record, &scene, depth, &mut (), stats)
+    }
+
+    /// As `ray_color()`, additionally recording each surface hit along the path,
+    /// and the direction in which it escapes the scene, if it does.
+    pub fn ray_color_recording(
+        &self,
+        world: &HittableList,
+        depth: Depth,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        path: &mut RayPath,
+    ) -> Vec3 {
+        let scene = Scene {
+            world,
+            background,
+            predictors,
+        };
+        self.walk(&scene, depth, path, &mut PathStats::default())
+    }
+
+    /// As `ray_color()`, additionally adding the light arriving along paths which match
+    /// each of the expressions of the `passes` to the corresponding color, and recording
+    /// statistics about the path in `stats`.
+    pub fn ray_color_with_passes(
+        &self,
+        world: &HittableList,
+        depth: Depth,
+        background: &Background,
+        predictors: &Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        passes: &mut LightPathPasses,
+        stats: &mut PathStats,
+    ) -> Vec3 {
+        *stats = PathStats::default();
+        let scene = Scene {
+            world,
+            background,
+            predictors,
+        };
+        let mut visitor = PassVisitor {
+            passes,
+            events: String::from("C"),
+            throughput: Vec3::ONE,
+        };
+        self.walk(&scene, depth, &mut visitor, stats)
+    }
+
+    /// Traces the path starting with the ray through the `scene`, returning the light
+    /// gathered along it. The `visitor` follows each vertex of the path.
+    fn walk(
+        &self,
+        scene: &Scene,
+        depth: Depth,
+        visitor: &mut impl PathVisitor,
+        stats: &mut PathStats,
+    ) -> Vec3 {
+        // Ray bounce limit reached; accumulate no further light.
+        if depth.is_exhausted() {
+            stats.termination = Termination::MaxDepth;
+            return Vec3::ZERO;
         }
+
+        let world = scene.world;
+        let hit_record = world.hit(self, world.t_min(self), f32::INFINITY, scene.predictors);
+        self.shade(hit_record, scene, depth, visitor, stats)
+    }
+
+    /// Shades the ray given the closest thing it hits, if anything, tracing the rest of
+    /// its path from there.
+    fn shade(
+        &self,
+        hit_record: Option<HitRecord>,
+        scene: &Scene,
+        depth: Depth,
+        visitor: &mut impl PathVisitor,
+        stats: &mut PathStats,
+    ) -> Vec3 {
+        let Some(hit_record) = hit_record else {
+            stats.termination = Termination::Escaped;
+            let light = scene.background.value(self.direction);
+            visitor.escaped(self, light);
+            return light;
+        };
+        let emitted = hit_record
+            .material
+            .emit(hit_record.u, hit_record.v, &hit_record.point);
+        let emitted = visitor.hit(&hit_record, emitted);
+
+        let Some(scatter_record) = hit_record.material.scatter(self, &hit_record) else {
+            stats.termination = Termination::Absorbed;
+            return emitted;
+        };
+        let Some(next_depth) = depth.after_scatter(scatter_record.ray.kind) else {
+            stats.termination = Termination::MaxDepth;
+            return emitted;
+        };
+        stats.bounces += 1;
+        let gathered = visitor.scattered(&hit_record, &scatter_record);
+        let incoming = scatter_record
+            .ray
+            .with_groups(self.groups)
+            .walk(scene, next_depth, visitor, stats);
+        emitted + gathered + scatter_record.attenuation * incoming
     }
 }
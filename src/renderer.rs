@@ -1,119 +1,1348 @@
+use std::fs::File;
 use std::io;
 use std::io::Write;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use ahash::AHashMap;
 use glam::Vec3;
-use indicatif::ParallelProgressIterator;
+use image::{Rgb, Rgb32FImage, RgbImage};
+use indicatif::{ParallelProgressIterator, ProgressBar};
 use palette::Pixel;
 use palette::Srgb;
-use rand::random;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
 
+use crate::background::Background;
 use crate::bvh::BvhId;
 use crate::camera::Camera;
+use crate::caustics::{CausticMap, CausticParams};
+use crate::denoise::{self, Guide};
+use crate::film::{Film, FilmChannels};
+use crate::filter::Filter;
+use crate::first_bounce::{FirstBounceCache, Header};
+use crate::gbuffer::GBuffer;
 use crate::hittable::HittableList;
-use crate::hrpp::Predictor;
+use crate::hrpp::{self, PredictionOutcome, PredictionStats, Predictor};
+use crate::lpe::LightPathExpression;
+use crate::monitor::RenderMonitor;
+use crate::path_export::{PixelRegion, RayPath};
+use crate::primaries::Primaries;
+use crate::random::{self, random};
+use crate::ray::{Depth, LightPathPasses, PathStats, Ray, Termination};
+use crate::sampler::Sampler;
+use crate::slice::Slice;
+use crate::tile_costs::TileCosts;
+use crate::tile_scheduler::{TileResult, TileScheduler, TileTask};
+use crate::units;
 use crate::utils::srgb_from_vec3;
 
+/// What a render traces: the `world` seen through the `camera`, against the `background`,
+/// with paths bouncing up to `max_depth` times.
+#[derive(Clone, Copy)]
+pub struct RenderSettings<'a> {
+    pub camera: &'a Camera,
+    pub world: &'a HittableList,
+    pub background: &'a Background,
+    pub max_depth: u32,
+    pub predictors: &'a Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+}
+
+#[derive(Clone)]
 pub struct Renderer {
     image_width: usize,
     image_height: usize,
+    /// If present, progress is reported to the monitor as tiles complete.
+    monitor: Option<RenderMonitor>,
+    /// If present, the even- and odd-numbered samples of each pixel are also accumulated
+    /// separately and written to EXR files starting with this prefix.
+    split_buffer_output: Option<PathBuf>,
+    /// If present, the G-buffer is written as EXR images with this file name prefix.
+    gbuffer_output: Option<PathBuf>,
+    /// Reconstructs pixels from their surrounding samples.
+    filter: Filter,
+    /// If present, samples are rendered in passes until this much time has elapsed.
+    max_time: Option<Duration>,
+    /// If present, the image is written to this PPM file rather than to stdout.
+    output: Option<PathBuf>,
+    /// Sample counts at which the image so far is written, in ascending order.
+    spp_milestones: Vec<u32>,
+    /// File name prefix for the images written at `spp_milestones`.
+    milestone_output: PathBuf,
+    /// The names of the light path passes, by which their images are written.
+    light_path_names: Vec<String>,
+    /// Light path expressions, each of which is rendered to its own pass.
+    light_path_expressions: Vec<LightPathExpression>,
+    /// File name prefix for the light path passes.
+    light_path_output: PathBuf,
+    /// If present, an image of the prediction outcomes of camera rays is written here.
+    prediction_heatmap: Option<PathBuf>,
+    /// If present, the maximum number of diffuse bounces along a path.
+    max_diffuse_depth: Option<u32>,
+    /// If present, the maximum number of specular bounces along a path.
+    max_specular_depth: Option<u32>,
+    /// Whether to send low resolution previews to the monitor before rendering.
+    progressive_preview: bool,
+    /// If present, images of path statistics are written with this file name prefix.
+    path_statistics_output: Option<PathBuf>,
+    /// Whether to shade camera rays with only emission and albedo, without bouncing.
+    emission_albedo_only: bool,
+    /// If present, the image is rendered and written in bands of this many rows.
+    band_height: Option<usize>,
+    /// If present, only this slice of the image is rendered and written.
+    slice: Option<Slice>,
+    /// If present, each sample's random numbers are seeded from this, its pixel and its
+    /// index, rather than drawn from the rendering thread's RNG.
+    seed: Option<u64>,
+    /// If present, the first hit of each camera ray is cached in this file.
+    first_bounce_cache: Option<PathBuf>,
+    /// If present, caustics are lit by a photon map built with these settings.
+    caustics: Option<CausticParams>,
+    /// How the camera rays of each pixel are spread over the pixel, lens and shutter.
+    sampler: Sampler,
+    /// The primaries of the written images' colors.
+    primaries: Primaries,
+    /// If present, the exposure value at ISO 100 which colors are exposed at.
+    exposure: Option<f32>,
+    /// Whether to color camera rays by the volumes they enter, rather than shading them.
+    volume_debug: bool,
+    /// Whether to denoise the tiles shown by the monitor.
+    preview_denoising: bool,
+    /// If present, the costs of the tiles are loaded from and saved to this file.
+    tile_costs: Option<PathBuf>,
 }
 
 impl Renderer {
-    #[allow(dead_code)]
     pub fn new(image_width: usize, image_height: usize) -> Renderer {
         Renderer {
             image_width,
             image_height,
+            monitor: None,
+            split_buffer_output: None,
+            gbuffer_output: None,
+            filter: Filter::default(),
+            max_time: None,
+            output: None,
+            spp_milestones: Vec::new(),
+            milestone_output: PathBuf::new(),
+            light_path_names: Vec::new(),
+            light_path_expressions: Vec::new(),
+            light_path_output: PathBuf::new(),
+            prediction_heatmap: None,
+            max_diffuse_depth: None,
+            max_specular_depth: None,
+            progressive_preview: false,
+            path_statistics_output: None,
+            emission_albedo_only: false,
+            band_height: None,
+            slice: None,
+            seed: None,
+            first_bounce_cache: None,
+            caustics: None,
+            sampler: Sampler::default(),
+            primaries: Primaries::default(),
+            exposure: None,
+            volume_debug: false,
+            preview_denoising: false,
+            tile_costs: None,
         }
     }
 
     pub fn from_aspect_ratio(image_width: usize, aspect_ratio: f32) -> Renderer {
-        Renderer {
-            image_width,
-            image_height: (image_width as f32 / aspect_ratio) as usize,
-        }
+        Renderer::new(image_width, (image_width as f32 / aspect_ratio) as usize)
+    }
+
+    /// Reports render progress to the `monitor` as tiles complete.
+    pub fn with_monitor(mut self, monitor: RenderMonitor) -> Renderer {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// Additionally accumulates the even- and odd-numbered samples of each pixel into two
+    /// independent half-buffers, "A" and "B". After rendering, both are written as linear
+    /// EXR images alongside their signed difference (A - B), which gives an unbiased
+    /// estimate of the noise remaining in the render.
+    ///
+    /// The images are written to `<prefix>_a.exr`, `<prefix>_b.exr` and `<prefix>_diff.exr`.
+    pub fn with_split_buffer_output(mut self, prefix: PathBuf) -> Renderer {
+        self.split_buffer_output = Some(prefix);
+        self
+    }
+
+    /// Additionally writes the position, normal, albedo and material number of the
+    /// surface seen through the center of each pixel as linear EXR images, for re-lighting
+    /// and compositing in other tools. See the `gbuffer` module.
+    ///
+    /// The images are written to `<prefix>_position.exr`, `<prefix>_normal.exr`,
+    /// `<prefix>_albedo.exr` and `<prefix>_material.exr`.
+    pub fn with_gbuffer_output(mut self, prefix: PathBuf) -> Renderer {
+        self.gbuffer_output = Some(prefix);
+        self
+    }
+
+    /// Reconstructs pixels with the `filter` rather than the default box filter,
+    /// which simply averages the samples within each pixel.
+    pub fn with_filter(mut self, filter: Filter) -> Renderer {
+        self.filter = filter;
+        self
+    }
+
+    /// Renders one sample per pixel at a time, over the whole image, until the `max_time`
+    /// budget would be exceeded by another pass or the requested samples per pixel are
+    /// reached. The image is then output as usual.
+    pub fn with_max_time(mut self, max_time: Duration) -> Renderer {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    /// Writes the image to the PPM file at `path` rather than to stdout.
+    pub fn with_output(mut self, path: PathBuf) -> Renderer {
+        self.output = Some(path);
+        self
+    }
+
+    /// Additionally writes the image as it is after each of the `milestones` samples per
+    /// pixel to `<prefix>_<milestone>spp.ppm`, so that the quality reached at each sample
+    /// count, and the time taken to reach it, can be compared from a single render. The
+    /// time at which each is written is printed to stderr. Milestones beyond the samples
+    /// per pixel, or the samples reached within a time budget, aren't written.
+    pub fn with_spp_milestones(mut self, milestones: Vec<u32>, prefix: PathBuf) -> Renderer {
+        self.spp_milestones = milestones;
+        self.spp_milestones.sort_unstable();
+        self.spp_milestones.dedup();
+        self.milestone_output = prefix;
+        self
+    }
+
+    /// Additionally renders the light arriving along paths matching each of the named
+    /// light path expressions to its own pass, written as a linear EXR image to
+    /// `<prefix>_<name>.exr`. See the `lpe` module for the syntax of expressions.
+    ///
+    /// Passes for expressions which together cover every path sum to the beauty image.
+    pub fn with_light_path_passes(
+        mut self,
+        passes: Vec<(String, LightPathExpression)>,
+        prefix: PathBuf,
+    ) -> Renderer {
+        (self.light_path_names, self.light_path_expressions) = passes.into_iter().unzip();
+        self.light_path_output = prefix;
+        self
+    }
+
+    /// Renders the image in horizontal bands of `rows` rows from the top down, writing
+    /// each to the PPM output as soon as it's done. Only a couple of bands are held in
+    /// memory at once, so images far larger than memory allows can be rendered.
+    ///
+    /// Only the PPM image is written in this mode; it can't be combined with a monitor,
+    /// a time budget, or other outputs such as split buffers and light path passes.
+    pub fn with_band_height(mut self, rows: usize) -> Renderer {
+        self.band_height = Some(rows.max(1));
+        self
+    }
+
+    /// Renders and writes only the rows of the `slice` of the image, so that the slices
+    /// of a render can be rendered on separate machines and put back together with
+    /// `slice::assemble()`. Seed the renderer for the slices to join without seams.
+    ///
+    /// Only the PPM image, and any milestones of it, are written in this mode; it can't
+    /// be combined with a time budget, which would leave slices with different sample
+    /// counts, or other outputs such as split buffers and light path passes.
+    pub fn with_slice(mut self, slice: Slice) -> Renderer {
+        self.slice = Some(slice);
+        self
+    }
+
+    /// Seeds the random numbers of each sample from `seed` and the sample's pixel and
+    /// index, so the same seed gives the same image regardless of the tile size, the
+    /// number of threads, or which thread renders which tile.
+    pub fn with_seed(mut self, seed: u64) -> Renderer {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Writes the image, split buffers and light path passes with colors relative to
+    /// `primaries` rather than sRGB's, for wide gamut displays. The monitor still shows
+    /// sRGB, as browsers expect.
+    pub fn with_primaries(mut self, primaries: Primaries) -> Renderer {
+        self.primaries = primaries;
+        self
+    }
+
+    /// Exposes the image as a camera would at the exposure value `ev100` (at ISO 100),
+    /// for scenes whose lights are in physical units (see `units`), rather than writing
+    /// colors unscaled. This applies to every image written and to the monitor.
+    pub fn with_exposure(mut self, ev100: f32) -> Renderer {
+        self.exposure = Some(ev100);
+        self
+    }
+
+    /// Caches what each camera ray hits first in the file at `path`. If the file already
+    /// holds the hits of a render of the same size, samples and seed, they're reused
+    /// rather than tracing camera rays through the whole scene, which speeds up
+    /// re-rendering after changing only materials or lights. The file must be deleted
+    /// if the camera or the scene's geometry change.
+    ///
+    /// Requires `with_seed()`, so that camera rays are the same from one render to the
+    /// next. Only the image, split buffers and path statistics can be written in this
+    /// mode, without a time budget.
+    pub fn with_first_bounce_cache(mut self, path: PathBuf) -> Renderer {
+        self.first_bounce_cache = Some(path);
+        self
+    }
+
+    /// Starts the tiles of the first pass in order of their costs in the file at `path`,
+    /// from a previous render of the same size and tiles such as the last frame of an
+    /// animation, and saves this render's costs there for the next. Otherwise the costs
+    /// are estimated with a quick low resolution probe of each tile. Either way, later
+    /// passes start with the tiles which took longest in the pass before.
+    pub fn with_tile_costs(mut self, path: PathBuf) -> Renderer {
+        self.tile_costs = Some(path);
+        self
+    }
+
+    /// Spreads the camera rays of each pixel over the pixel, the lens and the shutter
+    /// interval with the `sampler`, which is stratified by default.
+    pub fn with_sampler(mut self, sampler: Sampler) -> Renderer {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Lights the first diffuse surface seen along each path with caustics from a photon
+    /// map, built with the `params` before rendering, so that light focused by glass and
+    /// mirrors shows up without many samples. Only the scene's lights send out photons.
+    ///
+    /// This can't be combined with the first bounce cache or light path passes.
+    pub fn with_caustics(mut self, params: CausticParams) -> Renderer {
+        self.caustics = Some(params);
+        self
+    }
+
+    /// Colors camera rays by which media and refractive volumes they're inside just past
+    /// the first surface they hit, to find volumes whose boundaries overlap. Each medium
+    /// adds green and each refractive volume adds blue, with red where more than one
+    /// overlap. Surfaces outside any volume are grey.
+    pub fn with_volume_debug(mut self) -> Renderer {
+        self.volume_debug = true;
+        self
     }
 
-    /// Outputs an image to stdout
+    /// Traces only camera rays, shading them with the light emitted by the surface they
+    /// hit plus its albedo, rather than bouncing. This renders almost instantly, to check
+    /// the placement of lights and the mapping of textures before a full render.
+    pub fn with_emission_albedo_only(mut self) -> Renderer {
+        self.emission_albedo_only = true;
+        self
+    }
+
+    /// Before rendering, quickly renders previews at 1/8, 1/4 and 1/2 resolution with
+    /// one sample per block of pixels, so the composition of the image can be seen almost
+    /// immediately. Previews are only shown by the monitor, so this has no effect without one.
+    pub fn with_progressive_preview(mut self) -> Renderer {
+        self.progressive_preview = true;
+        self
+    }
+
+    /// Denoises each tile shown by the monitor with a fast edge-aware filter, guided by
+    /// the normal and albedo of the surface seen through each pixel, so that previews
+    /// look clean after few samples. The written images are not filtered. This has no
+    /// effect without a monitor.
+    pub fn with_preview_denoising(mut self) -> Renderer {
+        self.preview_denoising = true;
+        self
+    }
+
+    /// Limits paths to at most `max_depth` diffuse bounces, in addition to the overall
+    /// limit given to `render()`. Diffuse light contributes little after a few bounces,
+    /// so this can save time without cutting short chains of specular bounces.
+    pub fn with_max_diffuse_depth(mut self, max_depth: u32) -> Renderer {
+        self.max_diffuse_depth = Some(max_depth);
+        self
+    }
+
+    /// Limits paths to at most `max_depth` specular bounces, in addition to the overall
+    /// limit given to `render()`.
+    pub fn with_max_specular_depth(mut self, max_depth: u32) -> Renderer {
+        self.max_specular_depth = Some(max_depth);
+        self
+    }
+
+    /// Additionally records how long paths are and why they end, to help tune the
+    /// maximum depth. Writes the average number of bounces per path through each pixel to
+    /// `<prefix>_length.exr`, and the fractions of paths which escaped the scene, were
+    /// absorbed, or were cut short by the maximum depth to the red, green and blue
+    /// channels of `<prefix>_termination.exr`. A summary is also printed to stderr.
+    pub fn with_path_statistics(mut self, prefix: PathBuf) -> Renderer {
+        self.path_statistics_output = Some(prefix);
+        self
+    }
+
+    /// Additionally writes an image of how hash-based ray path prediction fared for the
+    /// camera rays through each pixel, to `path` (e.g. a PNG). Each pixel is a mix of
+    /// green for true positive predictions, red for false positives and blue for rays
+    /// with no prediction, in proportion to how often each occurred. Pixels whose rays
+    /// didn't enter a BVH with a predictor are black.
+    pub fn with_prediction_heatmap(mut self, path: PathBuf) -> Renderer {
+        self.prediction_heatmap = Some(path);
+        self
+    }
+
+    /// Outputs an image to stdout, or to the file given by `with_output()`
     pub fn render(
         &self,
-        camera: &Camera,
-        world: &HittableList,
-        background: Vec3,
+        settings: &RenderSettings,
         samples_per_pixel: u32,
-        max_depth: u32,
         tile_width: usize,
         tile_height: usize,
-        predictors: Option<AHashMap<BvhId, Mutex<Predictor>>>,
     ) -> std::io::Result<()> {
+        if let Some(band_height) = self.band_height {
+            return self.render_banded(
+                settings,
+                samples_per_pixel,
+                tile_width,
+                tile_height,
+                band_height,
+            );
+        }
+
+        if self.slice.is_some()
+            && (self.max_time.is_some()
+                || self.split_buffer_output.is_some()
+                || self.gbuffer_output.is_some()
+                || !self.light_path_expressions.is_empty()
+                || self.prediction_heatmap.is_some()
+                || self.path_statistics_output.is_some()
+                || self.first_bounce_cache.is_some())
+        {
+            return Err(io::Error::other(
+                "slices can't be rendered with a time budget, and only write the PPM image",
+            ));
+        }
+
+        if self.caustics.is_some()
+            && (self.first_bounce_cache.is_some() || !self.light_path_expressions.is_empty())
+        {
+            return Err(io::Error::other(
+                "caustics can't be used with the first bounce cache or light path passes",
+            ));
+        }
+
         let stderr = io::stderr();
         let mut stderr_buf_writer = io::BufWriter::new(stderr);
 
-        let tiles = Tile::tile(self.image_width, self.image_height, tile_width, tile_height);
-        let mut colors = ImageColors::new(self.image_width, self.image_height);
+        let first_bounce = match &self.first_bounce_cache {
+            Some(path) => {
+                let Some(seed) = self.seed else {
+                    return Err(io::Error::other("the first bounce cache requires a seed"));
+                };
+                if self.max_time.is_some()
+                    || !self.light_path_expressions.is_empty()
+                    || self.prediction_heatmap.is_some()
+                    || self.emission_albedo_only
+                {
+                    return Err(io::Error::other(
+                        "the first bounce cache can't be used with a time budget, light path passes, prediction heatmaps or emission and albedo previews",
+                    ));
+                }
+                let header = Header {
+                    width: self.image_width as u32,
+                    height: self.image_height as u32,
+                    samples_per_pixel,
+                    seed,
+                    object_count: settings.world.objects.len() as u32,
+                };
+                let cache = FirstBounceCache::load_or_record(path, header)?;
+                if cache.is_loaded() {
+                    writeln!(
+                        stderr_buf_writer,
+                        "Reusing first bounces from {}",
+                        path.display()
+                    )?;
+                }
+                Some(cache)
+            }
+            None => None,
+        };
+
+        let caustics = self.build_caustics(settings, &mut stderr_buf_writer)?;
+
+        // Samples splat onto neighboring pixels within the filter's radius, so each tile
+        // accumulates into a buffer padded by enough pixels to hold them.
+        let padding = Film::padding(&self.filter);
 
-        let predictors = Arc::new(predictors);
+        let tiles = match self.slice {
+            // The slice's rows, and those whose samples may splat into them.
+            Some(slice) => {
+                let rows = slice.rows(self.image_height);
+                let start = rows.start.saturating_sub(padding as usize);
+                let end = (rows.end + padding as usize).min(self.image_height);
+                Tile::tile(self.image_width, end - start, tile_width, tile_height)
+                    .into_iter()
+                    .map(|tile| {
+                        Tile::new(
+                            tile.width,
+                            tile.height,
+                            tile.x_coord_start,
+                            tile.y_coord_start + start,
+                        )
+                    })
+                    .collect()
+            }
+            None => Tile::tile(self.image_width, self.image_height, tile_width, tile_height),
+        };
+        let mut film = self.film(0, 0, self.image_width, self.image_height);
+
+        if let Some(monitor) = &self.monitor {
+            monitor.begin(self.image_width, self.image_height, tiles.len());
+        }
 
-        write!(stderr_buf_writer, "Rendering tiles...\n")?;
+        if let (true, Some(monitor)) = (self.progressive_preview, &self.monitor) {
+            for scale in [8, 4, 2] {
+                monitor.begin_pass();
+                self.render_preview(monitor, &tiles, scale, settings);
+            }
+        }
+
+        let preview_guides = match (self.preview_denoising, &self.monitor) {
+            (true, Some(_)) => Some(self.preview_guides(settings)),
+            _ => None,
+        };
+
+        let mut costs = self.initial_tile_costs(&tiles, settings, &mut stderr_buf_writer)?;
+
+        // Without a time budget, all samples are rendered in a single pass.
+        let samples_per_pass = if self.max_time.is_some() {
+            1
+        } else {
+            samples_per_pixel
+        };
+        let pass_progress_bar = if self.max_time.is_some() {
+            writeln!(stderr_buf_writer, "Rendering sample passes...")?;
+            ProgressBar::new(samples_per_pixel as u64)
+        } else {
+            writeln!(stderr_buf_writer, "Rendering tiles...")?;
+            ProgressBar::hidden()
+        };
         stderr_buf_writer.flush().unwrap();
-        let rendered_tiles: Vec<RenderedTile> = tiles
-            .par_iter()
-            .progress()
-            .map(|tile| {
-                let mut tile_colors = ImageColors::new(tile.width, tile.height);
-                for y in 0..tile.height {
-                    for x in 0..tile.width {
-                        let pixel_coords = tile.get_full_image_pixel_coordinates(x, y);
-                        let color = self.get_color(
-                            &pixel_coords,
-                            samples_per_pixel,
-                            world,
-                            max_depth,
-                            camera,
-                            background,
-                            predictors.clone(),
+
+        let start = Instant::now();
+        let mut samples_rendered = 0;
+        // The indices of tiles which panicked, and are missing samples.
+        let mut failed_tiles = Vec::new();
+        // Whether each tile rendered in any pass, and so has samples.
+        let mut rendered_tiles = vec![false; tiles.len()];
+        while samples_rendered < samples_per_pixel {
+            let pass_start = Instant::now();
+            let mut pass_samples = samples_per_pass.min(samples_per_pixel - samples_rendered);
+            // End the pass at the next milestone, so that the image can be written there.
+            if let Some(milestone) = self
+                .spp_milestones
+                .iter()
+                .find(|&&milestone| milestone > samples_rendered)
+            {
+                pass_samples = pass_samples.min(milestone - samples_rendered);
+            }
+            let tile_progress_bar = if self.max_time.is_some() {
+                ProgressBar::hidden()
+            } else {
+                ProgressBar::new(tiles.len() as u64)
+            };
+            if let Some(monitor) = &self.monitor {
+                monitor.begin_pass();
+            }
+
+            // Threads take the most expensive tiles first, and the next as they finish.
+            let tile_results: Vec<(usize, Option<Film>, f32)> = costs
+                .order()
+                .into_iter()
+                .par_bridge()
+                .progress_with(tile_progress_bar)
+                .map(|index| {
+                    let tile = &tiles[index];
+                    let tile_start = Instant::now();
+                    let tile_film = catch_tile_panics(|| {
+                        self.sample_tile(
+                            tile,
+                            padding,
+                            samples_rendered..samples_rendered + pass_samples,
+                            settings,
+                            first_bounce.as_ref(),
+                            caustics.as_ref(),
+                        )
+                    });
+                    let seconds = tile_start.elapsed().as_secs_f32();
+                    if let (Some(tile_film), Some(monitor)) = (&tile_film, &self.monitor) {
+                        // Show the tile's samples together with those of previous passes.
+                        let mut colors = Vec::with_capacity(tile.width * tile.height);
+                        for y in 0..tile.height {
+                            for x in 0..tile.width {
+                                let pixel_coords = tile.get_full_image_pixel_coordinates(x, y);
+                                let (x, y) = (pixel_coords.x as isize, pixel_coords.y as isize);
+                                let mut pixel_samples = *film.beauty.get(x, y).unwrap();
+                                if let Some(tile_pixel_samples) = tile_film.beauty.get(x, y) {
+                                    pixel_samples.merge(tile_pixel_samples);
+                                }
+                                colors.push(pixel_samples.mean(samples_rendered + pass_samples));
+                            }
+                        }
+                        if let Some(guides) = &preview_guides {
+                            let tile_guides: Vec<Guide> = (0..tile.height)
+                                .flat_map(|y| {
+                                    let pixel_coords = tile.get_full_image_pixel_coordinates(0, y);
+                                    let start = pixel_coords.y * self.image_width + pixel_coords.x;
+                                    guides[start..start + tile.width].iter().copied()
+                                })
+                                .collect();
+                            colors =
+                                denoise::a_trous(&colors, &tile_guides, tile.width, tile.height);
+                        }
+                        monitor.tile_completed(
+                            tile.x_coord_start,
+                            tile.y_coord_start,
+                            tile.width,
+                            tile.height,
+                            |x, y| {
+                                let idx = (y - tile.y_coord_start) * tile.width
+                                    + (x - tile.x_coord_start);
+                                let color = srgb_from_vec3(self.expose(colors[idx]));
+                                Srgb::into_raw(color.into_format())
+                            },
                         );
-                        tile_colors.set_color(&PixelCoordinates::new(x, y), color);
                     }
+                    (index, tile_film, seconds)
+                })
+                .collect();
+            // Tiles are merged in order, whichever order they finished in.
+            let mut tile_films: Vec<Option<Film>> = tiles.iter().map(|_| None).collect();
+            for (index, tile_film, seconds) in tile_results {
+                costs.set(index, seconds);
+                tile_films[index] = tile_film;
+            }
+            for (index, tile_film) in tile_films.iter().enumerate() {
+                match tile_film {
+                    Some(tile_film) => {
+                        film.merge(tile_film);
+                        rendered_tiles[index] = true;
+                    }
+                    None => failed_tiles.push(index),
                 }
-                RenderedTile::new(*tile, tile_colors)
-            })
-            .collect();
-        rendered_tiles.iter().for_each(|rendered_tile| {
-            for x in 0..rendered_tile.tile.width {
-                for y in 0..rendered_tile.tile.height {
-                    let full_image_pixel_coords =
-                        rendered_tile.tile.get_full_image_pixel_coordinates(x, y);
-                    let color = rendered_tile.colors.get_color(x, y);
-                    colors.set_color(&full_image_pixel_coords, *color);
+            }
+            samples_rendered += pass_samples;
+            pass_progress_bar.set_position(samples_rendered as u64);
+            if self.spp_milestones.contains(&samples_rendered) {
+                film.set_samples_per_pixel(samples_rendered);
+                let path = format!(
+                    "{}_{}spp.ppm",
+                    self.milestone_output.display(),
+                    samples_rendered
+                );
+                self.write_milestone(&film, Path::new(&path))?;
+                writeln!(
+                    stderr_buf_writer,
+                    "Wrote {} samples per pixel to {} after {:.1}s.",
+                    samples_rendered,
+                    path,
+                    start.elapsed().as_secs_f32()
+                )?;
+                stderr_buf_writer.flush()?;
+            }
+
+            if let Some(max_time) = self.max_time {
+                // Stop if another pass like this one would run over the budget.
+                if start.elapsed() + pass_start.elapsed() > max_time {
+                    break;
+                }
+            }
+        }
+        pass_progress_bar.finish_and_clear();
+        if self.max_time.is_some() {
+            writeln!(
+                stderr_buf_writer,
+                "Rendered {} samples per pixel in {:.1}s.",
+                samples_rendered,
+                start.elapsed().as_secs_f32()
+            )?;
+        }
+
+        film.set_samples_per_pixel(samples_rendered);
+        // Tiles which failed in every pass have no samples, so rather than leaving a black
+        // block, their pixels are filled in from the pixels around them.
+        let mut missing = vec![false; self.image_width * self.image_height];
+        for (tile, _) in tiles
+            .iter()
+            .zip(&rendered_tiles)
+            .filter(|(_, &rendered)| !rendered)
+        {
+            for y in 0..tile.height {
+                for x in 0..tile.width {
+                    let pixel_coords = tile.get_full_image_pixel_coordinates(x, y);
+                    missing[pixel_coords.y * self.image_width + pixel_coords.x] = true;
                 }
             }
+        }
+        let image_colors = |color: &dyn Fn(usize, usize) -> Vec3| {
+            let mut linear: Vec<Vec3> = (0..self.image_height)
+                .flat_map(|y| (0..self.image_width).map(move |x| color(x, y)))
+                .collect();
+            fill_from_neighbors(&mut linear, &missing, self.image_width);
+            let mut colors = ImageColors::new(self.image_width, self.image_height);
+            for (output, color) in colors.colors.iter_mut().zip(linear) {
+                *output = self.output_color(color);
+            }
+            colors
+        };
+        let colors = image_colors(&|x, y| film.color(x, y));
+        let halves = self.split_buffer_output.as_ref().map(|_| {
+            (
+                image_colors(&|x, y| film.color_a(x, y)),
+                image_colors(&|x, y| film.color_b(x, y)),
+            )
         });
 
         write!(stderr_buf_writer, "\nDone tracing.\n")?;
+        failed_tiles.sort_unstable();
+        failed_tiles.dedup();
+        let failed_tiles: Vec<(Tile, &str)> = failed_tiles
+            .into_iter()
+            .map(|i| {
+                let outcome = if rendered_tiles[i] {
+                    "missing the samples of the passes it failed in"
+                } else {
+                    "filled in from the pixels around it"
+                };
+                (tiles[i], outcome)
+            })
+            .collect();
+        report_failed_tiles(&mut stderr_buf_writer, &failed_tiles)?;
+        if let Some(path) = &self.tile_costs {
+            costs.save(path, &tiles)?;
+        }
+        if let Some(monitor) = &self.monitor {
+            monitor.finish();
+        }
+        if let (Some(path), Some(cache)) = (&self.first_bounce_cache, &first_bounce) {
+            if !cache.is_loaded() {
+                cache.save(path)?;
+                writeln!(
+                    stderr_buf_writer,
+                    "Wrote first bounces to {}",
+                    path.display()
+                )?;
+            }
+        }
 
         write!(stderr_buf_writer, "Writing to file...\n")?;
-        self.write_ppm(&colors).unwrap();
+        self.write_ppm(&colors)?;
+        if let (Some(prefix), Some((a, b))) = (&self.split_buffer_output, &halves) {
+            let prefix = prefix.display();
+            self.write_exr(a, Path::new(&format!("{}_a.exr", prefix)))?;
+            self.write_exr(b, Path::new(&format!("{}_b.exr", prefix)))?;
+            let mut diff = ImageColors::new(self.image_width, self.image_height);
+            for (diff, (a, b)) in diff.colors.iter_mut().zip(a.colors.iter().zip(&b.colors)) {
+                *diff = Srgb::new(a.red - b.red, a.green - b.green, a.blue - b.blue);
+            }
+            self.write_exr(&diff, Path::new(&format!("{}_diff.exr", prefix)))?;
+        }
+        if let Some(prefix) = &self.gbuffer_output {
+            GBuffer::trace(
+                settings.camera,
+                settings.world,
+                settings.background,
+                settings.predictors,
+                self.image_width,
+                self.image_height,
+            )
+            .write(prefix)?;
+        }
+        for (aov, name) in self.light_path_names.iter().enumerate() {
+            let mut pass = ImageColors::new(self.image_width, self.image_height);
+            for y in 0..self.image_height {
+                for x in 0..self.image_width {
+                    pass.set_color(
+                        &PixelCoordinates::new(x, y),
+                        self.output_color(film.aov_color(aov, x, y)),
+                    );
+                }
+            }
+            let path = format!("{}_{}.exr", self.light_path_output.display(), name);
+            self.write_exr(&pass, Path::new(&path))?;
+        }
+        if let (Some(path), Some(outcomes)) = (&self.prediction_heatmap, &film.prediction_outcomes)
+        {
+            let mut heatmap = ImageColors::new(self.image_width, self.image_height);
+            for y in 0..self.image_height {
+                for x in 0..self.image_width {
+                    let [true_positives, false_positives, no_predictions] =
+                        outcomes[y * self.image_width + x];
+                    let total = (true_positives + false_positives + no_predictions).max(1) as f32;
+                    heatmap.set_color(
+                        &PixelCoordinates::new(x, y),
+                        Srgb::new(
+                            false_positives as f32 / total,
+                            true_positives as f32 / total,
+                            no_predictions as f32 / total,
+                        ),
+                    );
+                }
+            }
+            self.write_image(&heatmap, path)?;
+        }
+        if let (Some(prefix), Some(stats)) = (&self.path_statistics_output, &film.path_stats) {
+            let mut length = ImageColors::new(self.image_width, self.image_height);
+            let mut termination = ImageColors::new(self.image_width, self.image_height);
+            for y in 0..self.image_height {
+                for x in 0..self.image_width {
+                    let [paths, bounces, escaped, absorbed, max_depth] =
+                        stats[y * self.image_width + x];
+                    let paths = paths.max(1) as f32;
+                    let average_length = bounces as f32 / paths;
+                    let pixel_coords = PixelCoordinates::new(x, y);
+                    length.set_color(
+                        &pixel_coords,
+                        Srgb::new(average_length, average_length, average_length),
+                    );
+                    termination.set_color(
+                        &pixel_coords,
+                        Srgb::new(
+                            escaped as f32 / paths,
+                            absorbed as f32 / paths,
+                            max_depth as f32 / paths,
+                        ),
+                    );
+                }
+            }
+            let prefix = prefix.display();
+            self.write_exr(&length, Path::new(&format!("{}_length.exr", prefix)))?;
+            self.write_exr(
+                &termination,
+                Path::new(&format!("{}_termination.exr", prefix)),
+            )?;
+
+            let mut totals = [0u64; 5];
+            for pixel_stats in stats {
+                for (total, count) in totals.iter_mut().zip(pixel_stats) {
+                    *total += *count as u64;
+                }
+            }
+            let [paths, bounces, escaped, absorbed, max_depth] = totals.map(|total| total as f64);
+            let paths = paths.max(1.0);
+            writeln!(
+                stderr_buf_writer,
+                "Average path length: {:.2} bounces",
+                bounces / paths
+            )?;
+            writeln!(
+                stderr_buf_writer,
+                "Paths escaped: {:.1}%, absorbed: {:.1}%, cut short at max depth: {:.1}%",
+                100.0 * escaped / paths,
+                100.0 * absorbed / paths,
+                100.0 * max_depth / paths
+            )?;
+        }
         write!(stderr_buf_writer, "Done writing to file.\n")?;
 
         stderr_buf_writer.flush().unwrap();
         Ok(())
     }
 
-    fn write_ppm(&self, colors: &ImageColors) -> std::io::Result<()> {
-        let stdout = io::stdout();
-        let mut buf_writer = io::BufWriter::new(stdout);
+    /// Schedules the tiles of the image, of `tile_width` x `tile_height` pixels, to be
+    /// rendered one at a time with `render_tile()` by a frontend of its own, rather than
+    /// all together by `render()`. See the `tile_scheduler` module.
+    pub fn tile_scheduler(
+        &self,
+        samples_per_pixel: u32,
+        tile_width: usize,
+        tile_height: usize,
+    ) -> TileScheduler {
+        let tiles = Tile::tile(self.image_width, self.image_height, tile_width, tile_height);
+        let film = self.film(0, 0, self.image_width, self.image_height);
+        TileScheduler::new(tiles, samples_per_pixel, film)
+    }
+
+    /// Renders the tile of a task from a `TileScheduler`, to submit to it. Tiles are
+    /// rendered as `render()` renders them, without caustics or a first bounce cache, and
+    /// the same seed gives the same samples whichever order they're rendered in.
+    pub fn render_tile(&self, task: &TileTask, settings: &RenderSettings) -> TileResult {
+        let film = self.sample_tile(
+            &task.tile,
+            Film::padding(&self.filter),
+            task.samples.clone(),
+            settings,
+            None,
+            None,
+        );
+        TileResult {
+            index: task.index,
+            film,
+        }
+    }
+
+    /// Writes the image in the `film`, e.g. from `TileScheduler::into_film()`, as a PPM
+    /// image to stdout, or to the file given by `with_output()`.
+    pub fn write_film(&self, film: &Film) -> std::io::Result<()> {
+        let mut buf_writer = self.begin_ppm()?;
+        self.write_ppm_rows(&mut buf_writer, self.output_rows(), film)?;
+        buf_writer.flush()
+    }
+
+    /// Renders the image in bands of `band_height` rows, writing each band as soon as the
+    /// samples of the band below, which may splat into it, have been traced.
+    fn render_banded(
+        &self,
+        settings: &RenderSettings,
+        samples_per_pixel: u32,
+        tile_width: usize,
+        tile_height: usize,
+        band_height: usize,
+    ) -> std::io::Result<()> {
+        if self.monitor.is_some()
+            || self.max_time.is_some()
+            || !self.spp_milestones.is_empty()
+            || self.split_buffer_output.is_some()
+            || self.gbuffer_output.is_some()
+            || !self.light_path_expressions.is_empty()
+            || self.prediction_heatmap.is_some()
+            || self.path_statistics_output.is_some()
+            || self.first_bounce_cache.is_some()
+            || self.slice.is_some()
+        {
+            return Err(io::Error::other(
+                "banded rendering only supports writing the PPM image",
+            ));
+        }
+
+        let stderr = io::stderr();
+        let mut stderr_buf_writer = io::BufWriter::new(stderr);
+        let caustics = self.build_caustics(settings, &mut stderr_buf_writer)?;
+        writeln!(stderr_buf_writer, "Rendering bands...")?;
+        stderr_buf_writer.flush()?;
+
+        let padding = Film::padding(&self.filter);
+        // Samples must only splat into the bands directly above and below them.
+        let band_height = band_height.max(2 * padding as usize);
+        let band_count = self.image_height.div_ceil(band_height);
+        let progress_bar = ProgressBar::new(band_count as u64);
+
+        let mut ppm = self.begin_ppm()?;
+        let mut failed_tiles = Vec::new();
+        // The previous band, which is waiting on samples from the current band.
+        let mut pending: Option<(Range<usize>, Film)> = None;
+        let mut band_end = self.image_height;
+        while band_end > 0 {
+            // Our rows are stored bottom-up, and written top-down.
+            let band_start = band_end.saturating_sub(band_height);
+            let mut band = self.film(
+                0,
+                band_start as isize - padding,
+                self.image_width,
+                band_end - band_start + 2 * padding as usize,
+            );
+            band.set_samples_per_pixel(samples_per_pixel);
+            if let Some((_, pending_film)) = &pending {
+                band.merge(pending_film);
+            }
+
+            let tiles: Vec<Tile> = Tile::tile(
+                self.image_width,
+                band_end - band_start,
+                tile_width,
+                tile_height,
+            )
+            .into_iter()
+            .map(|tile| {
+                Tile::new(
+                    tile.width,
+                    tile.height,
+                    tile.x_coord_start,
+                    tile.y_coord_start + band_start,
+                )
+            })
+            .collect();
+            let tile_films: Vec<Option<Film>> = tiles
+                .par_iter()
+                .map(|tile| {
+                    catch_tile_panics(|| {
+                        self.sample_tile(
+                            tile,
+                            padding,
+                            0..samples_per_pixel,
+                            settings,
+                            None,
+                            caustics.as_ref(),
+                        )
+                    })
+                })
+                .collect();
+            for (tile, tile_film) in tiles.iter().zip(&tile_films) {
+                let Some(tile_film) = tile_film else {
+                    failed_tiles.push((*tile, "left black"));
+                    continue;
+                };
+                band.merge(tile_film);
+                if let Some((_, pending_film)) = &mut pending {
+                    pending_film.merge(tile_film);
+                }
+            }
+
+            if let Some((rows, pending_film)) = pending.take() {
+                self.write_ppm_rows(&mut ppm, rows, &pending_film)?;
+            }
+            pending = Some((band_start..band_end, band));
+            band_end = band_start;
+            progress_bar.inc(1);
+        }
+        if let Some((rows, pending_film)) = pending {
+            self.write_ppm_rows(&mut ppm, rows, &pending_film)?;
+        }
+        ppm.flush()?;
+        progress_bar.finish_and_clear();
+
+        report_failed_tiles(&mut stderr_buf_writer, &failed_tiles)?;
+        writeln!(stderr_buf_writer, "Done writing to file.")?;
+        stderr_buf_writer.flush()?;
+        Ok(())
+    }
+
+    /// The costs of the `tiles` before the first pass, loaded from the tile costs file if
+    /// it has them, and otherwise estimated by timing a path through every
+    /// `PROBE_STRIDE`th pixel of each tile along each axis.
+    fn initial_tile_costs(
+        &self,
+        tiles: &[Tile],
+        settings: &RenderSettings,
+        stderr_buf_writer: &mut impl Write,
+    ) -> io::Result<TileCosts> {
+        if let Some(path) = &self.tile_costs {
+            if let Some(costs) = TileCosts::load(path, tiles)? {
+                writeln!(
+                    stderr_buf_writer,
+                    "Ordering tiles by their costs in {}",
+                    path.display()
+                )?;
+                return Ok(costs);
+            }
+        }
+        let mut costs = TileCosts::new(tiles.len());
+        // With no more tiles than threads, every tile starts at once anyway.
+        if tiles.len() <= rayon::current_num_threads() {
+            return Ok(costs);
+        }
+        // The probe leaves the predictors alone, so they learn only from the render.
+        let predictors = Arc::new(None);
+        let seconds: Vec<f32> = tiles
+            .par_iter()
+            .map(|tile| {
+                let start = Instant::now();
+                for y in (0..tile.height).step_by(PROBE_STRIDE) {
+                    for x in (0..tile.width).step_by(PROBE_STRIDE) {
+                        let PixelCoordinates { x, y } = tile.get_full_image_pixel_coordinates(x, y);
+                        // As for the predictor probe, a sample index which is never
+                        // rendered keeps the probe's random numbers apart from the image's.
+                        self.with_sample_rng(x, y, u32::MAX, || {
+                            let u = (x as f32 + random::<f32>()) / (self.image_width - 1) as f32;
+                            let v = (y as f32 + random::<f32>()) / (self.image_height - 1) as f32;
+                            settings.camera.get_ray(u, v).ray_color(
+                                settings.world,
+                                self.depth(settings.max_depth),
+                                settings.background,
+                                &predictors,
+                            )
+                        });
+                    }
+                }
+                start.elapsed().as_secs_f32()
+            })
+            .collect();
+        for (index, seconds) in seconds.into_iter().enumerate() {
+            costs.set(index, seconds);
+        }
+        Ok(costs)
+    }
+
+    /// Renders the image at 1/`scale` resolution, tracing one sample through the center
+    /// of each `scale` x `scale` block of pixels, and sends each tile to the monitor.
+    fn render_preview(
+        &self,
+        monitor: &RenderMonitor,
+        tiles: &[Tile],
+        scale: usize,
+        settings: &RenderSettings,
+    ) {
+        let RenderSettings {
+            camera,
+            world,
+            background,
+            max_depth,
+            predictors,
+        } = *settings;
+        tiles.par_iter().for_each(|tile| {
+            // The blocks overlapping the tile, which may extend into neighboring tiles.
+            let blocks_x_start = tile.x_coord_start / scale;
+            let blocks_y_start = tile.y_coord_start / scale;
+            let blocks_width = (tile.x_coord_start + tile.width - 1) / scale - blocks_x_start + 1;
+            let blocks_height = (tile.y_coord_start + tile.height - 1) / scale - blocks_y_start + 1;
+            let mut block_colors = Vec::with_capacity(blocks_width * blocks_height);
+            for block_y in blocks_y_start..blocks_y_start + blocks_height {
+                for block_x in blocks_x_start..blocks_x_start + blocks_width {
+                    let center_x = ((block_x * scale) as f32 + scale as f32 / 2.0)
+                        .min(self.image_width as f32);
+                    let center_y = ((block_y * scale) as f32 + scale as f32 / 2.0)
+                        .min(self.image_height as f32);
+                    let u = center_x / (self.image_width - 1) as f32;
+                    let v = center_y / (self.image_height - 1) as f32;
+                    let ray = camera.get_ray(u, v);
+                    let color = ray.ray_color(world, self.depth(max_depth), background, predictors);
+                    let color = srgb_from_vec3(self.expose(color));
+                    block_colors.push(Srgb::into_raw(color.into_format()));
+                }
+            }
+            monitor.tile_completed(
+                tile.x_coord_start,
+                tile.y_coord_start,
+                tile.width,
+                tile.height,
+                |x, y| {
+                    let block_x = x / scale - blocks_x_start;
+                    let block_y = y / scale - blocks_y_start;
+                    block_colors[block_y * blocks_width + block_x]
+                },
+            );
+        });
+    }
+
+    /// Traces `samples_per_pixel` paths through each pixel of the `region`, recording
+    /// where they bounce, rather than rendering an image.
+    pub fn trace_paths(
+        &self,
+        settings: &RenderSettings,
+        region: PixelRegion,
+        samples_per_pixel: u32,
+    ) -> Vec<RayPath> {
+        let RenderSettings {
+            camera,
+            world,
+            background,
+            max_depth,
+            predictors,
+        } = *settings;
+        let mut paths = Vec::new();
+        let x_end = (region.x_start + region.width).min(self.image_width);
+        let y_end = (region.y_start + region.height).min(self.image_height);
+        for y in region.y_start..y_end {
+            for x in region.x_start..x_end {
+                // Our rows are stored bottom-up; the region is given top-down.
+                let row = self.image_height - 1 - y;
+                for sample in 0..samples_per_pixel {
+                    let path = self.with_sample_rng(x, row, sample, || {
+                        let u = (x as f32 + random::<f32>()) / (self.image_width - 1) as f32;
+                        let v = (row as f32 + random::<f32>()) / (self.image_height - 1) as f32;
+                        let ray = camera.get_ray(u, v);
+
+                        let mut path = RayPath::new((x, y), sample, ray.origin);
+                        path.radiance = ray.ray_color_recording(
+                            world,
+                            self.depth(max_depth),
+                            background,
+                            predictors,
+                            &mut path,
+                        );
+                        path
+                    });
+                    paths.push(path);
+                }
+            }
+        }
+        paths
+    }
+
+    /// Finds the surface seen through the center of each pixel, to guide denoising.
+    fn preview_guides(&self, settings: &RenderSettings) -> Vec<Guide> {
+        let RenderSettings {
+            camera,
+            world,
+            background,
+            predictors,
+            ..
+        } = *settings;
+        let rows: Vec<usize> = (0..self.image_height).collect();
+        rows.par_iter()
+            .map(|&y| {
+                (0..self.image_width)
+                    .map(|x| {
+                        let u = (x as f32 + 0.5) / (self.image_width - 1) as f32;
+                        let v = (y as f32 + 0.5) / (self.image_height - 1) as f32;
+                        camera.get_ray(u, v).guide(world, background, predictors)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    /// Traces a sparse probe of camera paths through the scene with every predictor
+    /// enabled, then keeps only the predictors whose true positive ratio over the probe
+    /// is at least `threshold`. The rest are dropped, so that their BVHs are traversed
+    /// without the hashing and locking of a predictor which rarely helps them.
+    ///
+    /// The probe traces one path through every `PROBE_STRIDE`th pixel along each axis,
+    /// which warms up the predictors which are kept.
+    pub fn probe_predictors(
+        &self,
+        camera: &Camera,
+        world: &HittableList,
+        background: &Background,
+        max_depth: u32,
+        predictors: Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
+        threshold: f32,
+    ) -> Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>> {
+        if predictors.is_none() {
+            return predictors;
+        }
+        if Arc::strong_count(&predictors) > 1 {
+            eprintln!("Predictors are shared, so they can't be disabled; skipping the probe.");
+            return predictors;
+        }
+
+        let stats = (0..self.image_height)
+            .step_by(PROBE_STRIDE)
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|&y| {
+                let ((), stats) = hrpp::count_outcomes(|| {
+                    for x in (0..self.image_width).step_by(PROBE_STRIDE) {
+                        // A sample index which is never rendered, so the probe's random
+                        // numbers are independent of the image's.
+                        self.with_sample_rng(x, y, u32::MAX, || {
+                            let u = (x as f32 + random::<f32>()) / (self.image_width - 1) as f32;
+                            let v = (y as f32 + random::<f32>()) / (self.image_height - 1) as f32;
+                            camera.get_ray(u, v).ray_color(
+                                world,
+                                self.depth(max_depth),
+                                background,
+                                &predictors,
+                            )
+                        });
+                    }
+                });
+                stats
+            })
+            .reduce(PredictionStats::default, |mut a, b| {
+                a.merge(&b);
+                a
+            });
+
+        let Ok(Some(mut predictors)) = Arc::try_unwrap(predictors) else {
+            unreachable!("the predictors are present and not shared");
+        };
+        stats.add_to(&predictors);
+        predictors.retain(|id, _| {
+            let counts = stats.get(id);
+            let ratio = counts.true_positive_ratio();
+            let keep = ratio >= threshold;
+            eprintln!(
+                "Predictor for BVH {:?}: true positive ratio {} over {} probe rays; {}",
+                id,
+                ratio,
+                counts.total(),
+                if keep { "enabled" } else { "disabled" }
+            );
+            keep
+        });
+        Arc::new(Some(predictors))
+    }
+
+    /// Builds the photon map of caustics, if the renderer has one.
+    fn build_caustics(
+        &self,
+        settings: &RenderSettings,
+        stderr_buf_writer: &mut impl Write,
+    ) -> std::io::Result<Option<CausticMap>> {
+        let Some(params) = &self.caustics else {
+            return Ok(None);
+        };
+        if settings.world.lights().is_empty() {
+            writeln!(
+                stderr_buf_writer,
+                "The scene has no lights to send caustic photons out from."
+            )?;
+        }
+        let start = Instant::now();
+        let caustics =
+            CausticMap::build(settings.world, params, settings.camera.shutter(), self.seed);
+        writeln!(
+            stderr_buf_writer,
+            "Kept {} of {} photons for caustics in {:.1}s.",
+            caustics.len(),
+            params.photons,
+            start.elapsed().as_secs_f32()
+        )?;
+        stderr_buf_writer.flush()?;
+        Ok(Some(caustics))
+    }
+
+    /// Opens the output for writing a PPM image, writing its header.
+    fn begin_ppm(&self) -> std::io::Result<io::BufWriter<Box<dyn Write>>> {
+        let writer: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+        let mut buf_writer = io::BufWriter::new(writer);
+        self.write_ppm_header(&mut buf_writer)?;
+        Ok(buf_writer)
+    }
+
+    fn write_ppm_header(&self, buf_writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(buf_writer, "P3")?;
+        if let Some(slice) = self.slice {
+            writeln!(buf_writer, "# slice {}", slice)?;
+        }
         write!(
             buf_writer,
-            "P3\n{} {}\n255\n",
-            self.image_width, self.image_height
-        )?;
+            "{} {}\n255\n",
+            self.image_width,
+            self.output_rows().len()
+        )
+    }
+
+    /// The rows of the image which are written: those of its slice, if it has one.
+    fn output_rows(&self) -> Range<usize> {
+        match self.slice {
+            Some(slice) => slice.rows(self.image_height),
+            None => 0..self.image_height,
+        }
+    }
+
+    /// Writes the image so far, from the `film`, to a PPM file at `path`.
+    fn write_milestone(&self, film: &Film, path: &Path) -> std::io::Result<()> {
+        let mut buf_writer = io::BufWriter::new(File::create(path)?);
+        self.write_ppm_header(&mut buf_writer)?;
+        self.write_ppm_rows(&mut buf_writer, self.output_rows(), film)?;
+        buf_writer.flush()
+    }
 
-        for y in (0..self.image_height).rev() {
+    /// Writes the `rows` of the image, top-down, from the `film`.
+    fn write_ppm_rows(
+        &self,
+        buf_writer: &mut impl Write,
+        rows: Range<usize>,
+        film: &Film,
+    ) -> std::io::Result<()> {
+        for y in rows.rev() {
+            for x in 0..self.image_width {
+                let color = self.output_color(film.color(x, y));
+                let raw: [u8; 3] = Srgb::into_raw(color.into_format());
+                writeln!(buf_writer, "{} {} {}", raw[0], raw[1], raw[2])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_ppm(&self, colors: &ImageColors) -> std::io::Result<()> {
+        let mut buf_writer = self.begin_ppm()?;
+
+        for y in self.output_rows().rev() {
             for x in 0..self.image_width {
                 let color = colors.get_color(x, y);
                 let raw: [u8; 3] = Srgb::into_raw(color.into_format());
@@ -126,44 +1355,394 @@ impl Renderer {
         Ok(())
     }
 
-    fn get_color(
+    /// An empty film over `width` x `height` pixels from (`x_start`, `y_start`), with the
+    /// channels which this renderer writes.
+    fn film(&self, x_start: isize, y_start: isize, width: usize, height: usize) -> Film {
+        let channels = FilmChannels {
+            aovs: self.light_path_expressions.len(),
+            prediction_outcomes: self.prediction_heatmap.is_some(),
+            path_stats: self.path_statistics_output.is_some(),
+        };
+        Film::new(x_start, y_start, width, height, self.filter, channels)
+    }
+
+    /// Converts a linear color from the renderer's working space to that of the output.
+    fn output_color(&self, color: Vec3) -> Srgb {
+        srgb_from_vec3(self.primaries.from_linear_srgb(self.expose(color)))
+    }
+
+    /// Scales a color by the renderer's exposure, if it has one.
+    fn expose(&self, color: Vec3) -> Vec3 {
+        match self.exposure {
+            Some(ev100) => color * units::exposure_scale(ev100),
+            None => color,
+        }
+    }
+
+    /// The bounce limits of paths, given the overall `max_depth`.
+    fn depth(&self, max_depth: u32) -> Depth {
+        Depth::new(
+            max_depth,
+            self.max_diffuse_depth.unwrap_or(max_depth),
+            self.max_specular_depth.unwrap_or(max_depth),
+        )
+    }
+
+    /// Runs `f` to take a sample of the pixel at (x, y), with its random numbers seeded
+    /// from the renderer's seed if it has one.
+    fn with_sample_rng<T>(&self, x: usize, y: usize, sample: u32, f: impl FnOnce() -> T) -> T {
+        match self.seed {
+            Some(seed) => {
+                let pixel_index = (y * self.image_width + x) as u64;
+                random::seeded(random::sample_seed(seed, pixel_index, sample), f)
+            }
+            None => f(),
+        }
+    }
+
+    /// Writes the colors to an 8-bit image at `path`, in the format given by its extension.
+    fn write_image(&self, colors: &ImageColors, path: &Path) -> std::io::Result<()> {
+        // Our rows are stored bottom-up; images are stored top-down.
+        let image = RgbImage::from_fn(self.image_width as u32, self.image_height as u32, |x, y| {
+            let color = colors.get_color(x as usize, self.image_height - 1 - y as usize);
+            Rgb(Srgb::into_raw(color.into_format()))
+        });
+        image.save(path).map_err(io::Error::other)
+    }
+
+    /// Writes the linear colors to an EXR image at `path`.
+    fn write_exr(&self, colors: &ImageColors, path: &Path) -> std::io::Result<()> {
+        // Our rows are stored bottom-up; images are stored top-down.
+        let image =
+            Rgb32FImage::from_fn(self.image_width as u32, self.image_height as u32, |x, y| {
+                let color = colors.get_color(x as usize, self.image_height - 1 - y as usize);
+                Rgb([color.red, color.green, color.blue])
+            });
+        image.save(path).map_err(io::Error::other)
+    }
+
+    /// Traces the samples with the given indices for each pixel of the `tile`, into a
+    /// buffer padded by `padding` pixels to hold samples splatted past its edges.
+    fn sample_tile(
         &self,
-        pixel_coords: &PixelCoordinates,
-        samples_per_pixel: u32,
-        world: &HittableList,
-        max_depth: u32,
+        tile: &Tile,
+        padding: isize,
+        sample_indices: Range<u32>,
+        settings: &RenderSettings,
+        first_bounce: Option<&FirstBounceCache>,
+        caustics: Option<&CausticMap>,
+    ) -> Film {
+        let mut tile_film = self.film(
+            tile.x_coord_start as isize - padding,
+            tile.y_coord_start as isize - padding,
+            tile.width + 2 * padding as usize,
+            tile.height + 2 * padding as usize,
+        );
+        // Generate every camera ray of the tile up front, and trace them grouped by the
+        // octant of their direction and then by nearby pixels, so that consecutive rays
+        // visit the same BVH nodes while they're still in cache.
+        let mut camera_samples =
+            Vec::with_capacity(tile.width * tile.height * sample_indices.len());
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let pixel_coords = tile.get_full_image_pixel_coordinates(x, y);
+                for sample in sample_indices.clone() {
+                    camera_samples.push(self.camera_sample(pixel_coords, sample, settings.camera));
+                }
+            }
+        }
+        camera_samples.sort_by_key(|camera_sample| {
+            let local_x = camera_sample.pixel_coords.x - tile.x_coord_start;
+            let local_y = camera_sample.pixel_coords.y - tile.y_coord_start;
+            (
+                octant(camera_sample.ray.direction),
+                morton_code(local_x as u32, local_y as u32),
+            )
+        });
+        let ((), stats) = hrpp::count_outcomes(|| {
+            for batch in camera_samples.chunks_mut(BATCH_SIZE) {
+                self.trace_batch(batch, settings, first_bounce, caustics, &mut tile_film);
+            }
+        });
+        if let Some(predictors) = settings.predictors.as_ref() {
+            stats.add_to(predictors);
+        }
+        tile_film
+    }
+
+    /// Generates the camera ray for a sample of the pixel at `pixel_coords`.
+    fn camera_sample(
+        &self,
+        pixel_coords: PixelCoordinates,
+        sample: u32,
         camera: &Camera,
-        background: Vec3,
-        predictors: Arc<Option<AHashMap<BvhId, Mutex<Predictor>>>>,
-    ) -> Srgb {
-        let mut color_accumulator = Vec3::ZERO;
-        for _ in 0..samples_per_pixel {
-            let u = (pixel_coords.x as f32 + random::<f32>()) / (self.image_width - 1) as f32;
-            let v = (pixel_coords.y as f32 + random::<f32>()) / (self.image_height - 1) as f32;
-            let ray = camera.get_ray(u, v);
+    ) -> CameraSample {
+        let mut stream = self.seed.map(|seed| {
+            let pixel_index = (pixel_coords.y * self.image_width + pixel_coords.x) as u64;
+            random::Stream::new(random::sample_seed(seed, pixel_index, sample))
+        });
+        let (sample_x, sample_y, ray) = run_in(&mut stream, || {
+            // The pixel's samples are spread from the same shifted sequence, whether or
+            // not the render is seeded.
+            let pixel_index = (pixel_coords.y * self.image_width + pixel_coords.x) as u64;
+            let pixel_seed = random::sample_seed(self.seed.unwrap_or(0), pixel_index, SHIFT_SAMPLE);
+            let point = self.sampler.point(sample, pixel_seed);
+            // Position of the sample in continuous pixel coordinates, where pixel (x, y)
+            // covers [x, x + 1) x [y, y + 1) and has its center at (x + 0.5, y + 0.5).
+            let sample_x = pixel_coords.x as f32 + point.pixel.x;
+            let sample_y = pixel_coords.y as f32 + point.pixel.y;
+            let u = sample_x / (self.image_width - 1) as f32;
+            let v = sample_y / (self.image_height - 1) as f32;
+            (
+                sample_x,
+                sample_y,
+                camera.get_ray_at(u, v, point.lens, point.time),
+            )
+        });
+        CameraSample {
+            pixel_coords,
+            sample,
+            sample_x,
+            sample_y,
+            ray,
+            stream,
+        }
+    }
 
-            color_accumulator += ray.ray_color(&world, max_depth, background, &predictors);
+    /// Traces a batch of camera samples, adding each to the pixels of the `film` within
+    /// the filter's radius. The light along paths matching each light path expression is
+    /// added to the corresponding AOV.
+    ///
+    /// Batches are sorted to be coherent, so this is where they could be traced as packets.
+    fn trace_batch(
+        &self,
+        batch: &mut [CameraSample],
+        settings: &RenderSettings,
+        first_bounce: Option<&FirstBounceCache>,
+        caustics: Option<&CausticMap>,
+        film: &mut Film,
+    ) {
+        let RenderSettings {
+            world,
+            background,
+            max_depth,
+            predictors,
+            ..
+        } = *settings;
+        let expressions = &self.light_path_expressions;
+        let mut pass_colors = vec![Vec3::ZERO; expressions.len()];
+        for camera_sample in batch {
+            let CameraSample {
+                pixel_coords,
+                sample,
+                sample_x,
+                sample_y,
+                ref ray,
+                ref mut stream,
+            } = *camera_sample;
+            run_in(stream, || {
+                let mut path_stats = PathStats::default();
+                let mut trace = || {
+                    if self.emission_albedo_only {
+                        ray.emission_albedo(world, background, predictors)
+                    } else if self.volume_debug {
+                        ray.volume_debug(world, predictors)
+                    } else if let Some(first_bounce) = first_bounce {
+                        let pixel_index = pixel_coords.y * self.image_width + pixel_coords.x;
+                        let hit_record =
+                            first_bounce.first_hit(ray, world, pixel_index, sample, predictors);
+                        ray.ray_color_from_hit(
+                            hit_record,
+                            world,
+                            self.depth(max_depth),
+                            background,
+                            predictors,
+                            &mut path_stats,
+                        )
+                    } else if let Some(caustics) = caustics {
+                        ray.ray_color_with_caustics(
+                            world,
+                            self.depth(max_depth),
+                            background,
+                            predictors,
+                            caustics,
+                            &mut path_stats,
+                        )
+                    } else if expressions.is_empty() {
+                        ray.ray_color_with_stats(
+                            world,
+                            self.depth(max_depth),
+                            background,
+                            predictors,
+                            &mut path_stats,
+                        )
+                    } else {
+                        pass_colors.fill(Vec3::ZERO);
+                        ray.ray_color_with_passes(
+                            world,
+                            self.depth(max_depth),
+                            background,
+                            predictors,
+                            &mut LightPathPasses {
+                                expressions,
+                                colors: &mut pass_colors,
+                            },
+                            &mut path_stats,
+                        )
+                    }
+                };
+                let color = match &mut film.prediction_outcomes {
+                    Some(pixel_outcomes) => {
+                        let (color, outcomes) = hrpp::record_outcomes(trace);
+                        let idx = film
+                            .beauty
+                            .get_idx(pixel_coords.x as isize, pixel_coords.y as isize)
+                            .unwrap();
+                        for outcome in outcomes {
+                            let counter = match outcome {
+                                PredictionOutcome::TruePositive => 0,
+                                PredictionOutcome::FalsePositive => 1,
+                                PredictionOutcome::NoPrediction => 2,
+                            };
+                            pixel_outcomes[idx][counter] += 1;
+                        }
+                        color
+                    }
+                    None => trace(),
+                };
+                if let Some(pixel_stats) = &mut film.path_stats {
+                    let idx = film
+                        .beauty
+                        .get_idx(pixel_coords.x as isize, pixel_coords.y as isize)
+                        .unwrap();
+                    let termination = match path_stats.termination {
+                        Termination::Escaped => 2,
+                        Termination::Absorbed => 3,
+                        Termination::MaxDepth => 4,
+                    };
+                    pixel_stats[idx][0] += 1;
+                    pixel_stats[idx][1] += path_stats.bounces;
+                    pixel_stats[idx][termination] += 1;
+                }
+                film.add_sample(sample_x, sample_y, sample, color);
+                for (aov, pass_color) in pass_colors.iter().enumerate() {
+                    film.add_aov_sample(aov, sample_x, sample_y, sample, *pass_color);
+                }
+            });
         }
-        let color = color_accumulator / samples_per_pixel as f32;
-        srgb_from_vec3(color)
     }
 }
 
-/// Carries this tile's render in `colors`, while `tile` carries
-/// the information needed to update the full image's colors from
-/// this tile.
-struct RenderedTile {
-    tile: Tile,
-    /// The colors for this tile (where this tile is the "Image")
-    colors: ImageColors,
+/// The spacing, in pixels, between the pixels sampled to probe predictors and tile costs.
+const PROBE_STRIDE: usize = 4;
+
+/// The number of camera samples traced together in a batch.
+const BATCH_SIZE: usize = 64;
+
+/// A sample index which is never rendered (nor probed), from which the random shift of
+/// each pixel's stratified samples is seeded.
+const SHIFT_SAMPLE: u32 = u32::MAX - 1;
+
+/// A camera ray waiting to be traced, along with the sample it belongs to.
+struct CameraSample {
+    pixel_coords: PixelCoordinates,
+    sample: u32,
+    /// The position of the sample in continuous pixel coordinates.
+    sample_x: f32,
+    sample_y: f32,
+    ray: Ray,
+    /// If seeded, the sample's random numbers, continuing from generating its ray.
+    stream: Option<random::Stream>,
 }
 
-impl RenderedTile {
-    pub fn new(tile: Tile, colors: ImageColors) -> RenderedTile {
-        RenderedTile { tile, colors }
+/// Runs `f` with random numbers drawn from the `stream` if there is one.
+fn run_in<T>(stream: &mut Option<random::Stream>, f: impl FnOnce() -> T) -> T {
+    match stream {
+        Some(stream) => stream.run(f),
+        None => f(),
     }
 }
 
+/// Runs `sample` to render a tile, retrying it once if it panics, so that a panic in one
+/// tile, e.g. from a degenerate primitive, doesn't bring down the whole render. Returns
+/// `None` if the tile panicked both times.
+fn catch_tile_panics(sample: impl Fn() -> Film) -> Option<Film> {
+    (0..2).find_map(|_| panic::catch_unwind(AssertUnwindSafe(&sample)).ok())
+}
+
+/// Lists the tiles which panicked on stderr, each with what became of its pixels.
+fn report_failed_tiles(writer: &mut impl Write, tiles: &[(Tile, &str)]) -> io::Result<()> {
+    if tiles.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "{} tiles failed to render:", tiles.len())?;
+    for (tile, outcome) in tiles {
+        writeln!(
+            writer,
+            "  {}x{} pixels from ({}, {}), {}",
+            tile.width, tile.height, tile.x_coord_start, tile.y_coord_start, outcome
+        )?;
+    }
+    Ok(())
+}
+
+/// Replaces the `missing` pixels of an image `width` pixels wide with the mean of their
+/// neighbors, working inwards from the pixels which aren't missing. Pixels with no
+/// neighbors which aren't missing anywhere in the image are left as they are.
+fn fill_from_neighbors(colors: &mut [Vec3], missing: &[bool], width: usize) {
+    let height = colors.len() / width.max(1);
+    let mut missing: Vec<bool> = missing.to_vec();
+    let mut unfilled: Vec<usize> = (0..colors.len()).filter(|&i| missing[i]).collect();
+    while !unfilled.is_empty() {
+        let filled: Vec<(usize, Vec3)> = unfilled
+            .iter()
+            .filter_map(|&i| {
+                let (x, y) = (i % width, i / width);
+                let neighbors = [
+                    (x > 0).then(|| i - 1),
+                    (x + 1 < width).then(|| i + 1),
+                    (y > 0).then(|| i - width),
+                    (y + 1 < height).then(|| i + width),
+                ];
+                let (sum, count) = neighbors
+                    .into_iter()
+                    .flatten()
+                    .filter(|&neighbor| !missing[neighbor])
+                    .fold((Vec3::ZERO, 0), |(sum, count), neighbor| {
+                        (sum + colors[neighbor], count + 1)
+                    });
+                (count > 0).then(|| (i, sum / count as f32))
+            })
+            .collect();
+        if filled.is_empty() {
+            break;
+        }
+        for &(i, color) in &filled {
+            colors[i] = color;
+            missing[i] = false;
+        }
+        unfilled.retain(|&i| missing[i]);
+    }
+}
+
+/// The octant a direction points into, as the signs of its components.
+fn octant(direction: Vec3) -> u8 {
+    (direction.x < 0.0) as u8 | ((direction.y < 0.0) as u8) << 1 | ((direction.z < 0.0) as u8) << 2
+}
+
+/// Interleaves the bits of the coordinates, so that nearby pixels have nearby codes.
+fn morton_code(x: u32, y: u32) -> u32 {
+    fn spread(mut v: u32) -> u32 {
+        v &= 0x0000_ffff;
+        v = (v | (v << 8)) & 0x00ff_00ff;
+        v = (v | (v << 4)) & 0x0f0f_0f0f;
+        v = (v | (v << 2)) & 0x3333_3333;
+        v = (v | (v << 1)) & 0x5555_5555;
+        v
+    }
+    spread(x) | spread(y) << 1
+}
+
 /// Stores the color of each pixel in an image.
 struct ImageColors {
     /// Matrix of colors in the image, flattened row-major.
@@ -193,6 +1772,7 @@ impl ImageColors {
     }
 }
 
+#[derive(Clone, Copy)]
 struct PixelCoordinates {
     pub x: usize,
     pub y: usize,
@@ -204,16 +1784,17 @@ impl PixelCoordinates {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-struct Tile {
+/// A rectangle of pixels of the image, rendered together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tile {
     /// Width of the tile, in pixels.
-    width: usize,
+    pub width: usize,
     /// Height of the tile, in pixels.
-    height: usize,
+    pub height: usize,
     /// The first pixel X coordinate of this tile in the full image.
-    x_coord_start: usize,
+    pub x_coord_start: usize,
     /// The first pixel Y coordinate of this tile in the full image.
-    y_coord_start: usize,
+    pub y_coord_start: usize,
 }
 
 impl Tile {
@@ -297,7 +1878,7 @@ impl Tile {
 
     /// Given the `x`, `y` coordinate within this tile, get the corresponding
     /// pixel coordinate in the full image.
-    pub fn get_full_image_pixel_coordinates(&self, x: usize, y: usize) -> PixelCoordinates {
+    fn get_full_image_pixel_coordinates(&self, x: usize, y: usize) -> PixelCoordinates {
         assert!(x < self.width);
         assert!(y < self.height);
         PixelCoordinates::new(self.x_coord_start + x, self.y_coord_start + y)
@@ -306,7 +1887,61 @@ impl Tile {
 
 #[cfg(test)]
 mod tests {
-    use super::Tile;
+    use std::cell::Cell;
+
+    use crate::film::{Film, FilmChannels};
+    use crate::filter::Filter;
+
+    use glam::Vec3;
+
+    use super::{catch_tile_panics, fill_from_neighbors, morton_code, Tile};
+
+    #[test]
+    fn tiles_are_retried_once_after_panicking() {
+        let film = || Film::new(0, 0, 1, 1, Filter::default(), FilmChannels::default());
+        let attempts = Cell::new(0);
+        let flaky = catch_tile_panics(|| {
+            attempts.set(attempts.get() + 1);
+            assert!(attempts.get() > 1, "the first attempt fails");
+            film()
+        });
+        assert!(flaky.is_some());
+        assert_eq!(attempts.get(), 2);
+
+        let attempts = Cell::new(0);
+        let broken = catch_tile_panics(|| -> Film {
+            attempts.set(attempts.get() + 1);
+            panic!("every attempt fails");
+        });
+        assert!(broken.is_none());
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[test]
+    fn failed_pixels_are_filled_from_their_neighbors() {
+        // A 4x3 image whose middle two pixels are missing, between a black and a white column.
+        let mut colors = vec![Vec3::ZERO; 12];
+        let mut missing = vec![false; 12];
+        for y in 0..3 {
+            colors[y * 4 + 3] = Vec3::ONE;
+        }
+        missing[5] = true;
+        missing[6] = true;
+        fill_from_neighbors(&mut colors, &missing, 4);
+
+        // Each takes the mean of the neighbors which weren't missing.
+        assert_eq!(colors[5], Vec3::ZERO);
+        assert!((colors[6] - Vec3::splat(1.0 / 3.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn morton_codes_interleave() {
+        assert_eq!(morton_code(0, 0), 0);
+        assert_eq!(morton_code(1, 0), 1);
+        assert_eq!(morton_code(0, 1), 2);
+        assert_eq!(morton_code(3, 3), 15);
+        assert_eq!(morton_code(4, 0), 16);
+    }
 
     #[test]
     fn tile_perfect_tiling() {
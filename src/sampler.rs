@@ -0,0 +1,165 @@
+//! Choosing where in the pixel, where on the lens and when during the shutter interval
+//! each camera ray of a pixel starts.
+//!
+//! Drawing these independently for each sample leaves clumps and gaps by chance, which
+//! show up as noise in depth of field and motion blur. The stratified sampler instead
+//! takes the samples of a pixel from a five dimensional Sobol sequence, so that they
+//! spread evenly over the pixel, the lens and the shutter interval at once, with lens
+//! and time samples correlated with the pixel position rather than drawn apart from it.
+//! The sequence is stratified at each power of two and well spread between them, so a
+//! render can stop after any pass. Each pixel's sequence is scrambled by its own random digital shift, which
+//! keeps the image unbiased and turns the structure of the sequence into noise rather
+//! than patterns.
+
+use std::sync::OnceLock;
+
+use glam::{vec2, Vec2};
+
+use crate::random::{self, random};
+
+/// How the camera rays of a pixel are spread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sampler {
+    /// Each sample is drawn independently of the pixel's other samples.
+    Independent,
+    /// The samples of a pixel are spread evenly over the pixel, the lens and the shutter
+    /// interval together, however many there are.
+    #[default]
+    Stratified,
+}
+
+/// Where a camera ray starts, as fractions in [0, 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraSamplePoint {
+    /// The position within the pixel.
+    pub pixel: Vec2,
+    /// The position on the lens, before mapping the unit square onto its disk.
+    pub lens: Vec2,
+    /// The time within the shutter interval.
+    pub time: f32,
+}
+
+impl Sampler {
+    /// The point of the `index`th sample of the pixel whose sequence is shifted by a
+    /// random offset drawn from `pixel_seed`. Independent samples draw from the current
+    /// random stream instead.
+    pub fn point(&self, index: u32, pixel_seed: u64) -> CameraSamplePoint {
+        match self {
+            Sampler::Independent => CameraSamplePoint {
+                pixel: vec2(random(), random()),
+                lens: vec2(random(), random()),
+                time: random(),
+            },
+            Sampler::Stratified => {
+                let shift: [u32; 5] = random::seeded(pixel_seed, random);
+                let point = sobol(index, shift);
+                CameraSamplePoint {
+                    pixel: vec2(point[0], point[1]),
+                    lens: vec2(point[2], point[3]),
+                    time: point[4],
+                }
+            }
+        }
+    }
+}
+
+/// The primitive polynomials and initial direction numbers of the second to fifth
+/// dimensions of the Sobol sequence, from Joe and Kuo's "new-joe-kuo-6.21201": the
+/// polynomial's degree, its coefficients, and the initial numbers.
+const SOBOL_PARAMETERS: [(usize, u32, [u32; 3]); 4] = [
+    (1, 0, [1, 0, 0]),
+    (2, 1, [1, 3, 0]),
+    (3, 1, [1, 3, 1]),
+    (3, 2, [1, 1, 1]),
+];
+
+/// The direction numbers of each dimension of the Sobol sequence, one for each bit of
+/// the index.
+fn direction_numbers() -> &'static [[u32; 32]; 5] {
+    static DIRECTIONS: OnceLock<[[u32; 32]; 5]> = OnceLock::new();
+    DIRECTIONS.get_or_init(|| {
+        let mut directions = [[0; 32]; 5];
+        // The first dimension is the van der Corput sequence in base 2.
+        for (bit, direction) in directions[0].iter_mut().enumerate() {
+            *direction = 1 << (31 - bit);
+        }
+        for (dimension, &(degree, coefficients, initial)) in SOBOL_PARAMETERS.iter().enumerate() {
+            let v = &mut directions[dimension + 1];
+            for bit in 0..32 {
+                v[bit] = if bit < degree {
+                    initial[bit] << (31 - bit)
+                } else {
+                    let mut direction = v[bit - degree] ^ (v[bit - degree] >> degree);
+                    for k in 1..degree {
+                        if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                            direction ^= v[bit - k];
+                        }
+                    }
+                    direction
+                };
+            }
+        }
+        directions
+    })
+}
+
+/// The `index`th point of the five dimensional Sobol sequence, with each dimension's bits
+/// flipped where `shift` has them set. Flipping bits keeps the points stratified: for
+/// each power of two, every run of that many points has one in each interval of that
+/// size along each dimension, and the first two dimensions are stratified together.
+fn sobol(index: u32, shift: [u32; 5]) -> [f32; 5] {
+    let directions = direction_numbers();
+    let mut point = [0.0; 5];
+    for (dimension, value) in point.iter_mut().enumerate() {
+        let mut bits = shift[dimension];
+        for (bit, direction) in directions[dimension].iter().enumerate() {
+            if (index >> bit) & 1 == 1 {
+                bits ^= direction;
+            }
+        }
+        // The top 24 bits, which an f32 in [0, 1) holds exactly.
+        *value = (bits >> 8) as f32 / (1 << 24) as f32;
+    }
+    point
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sobol;
+
+    #[test]
+    fn points_are_stratified_in_each_dimension() {
+        let shift = [
+            0x1234_5678,
+            0x9abc_def0,
+            0x0f0f_0f0f,
+            0xdead_beef,
+            0x7777_1111,
+        ];
+        for count in [4, 16, 64] {
+            // Any aligned run of `count` points, not only the first.
+            for start in [0, count] {
+                for dimension in 0..5 {
+                    let mut strata = vec![0; count as usize];
+                    for index in start..start + count {
+                        let value = sobol(index, shift)[dimension];
+                        strata[(value * count as f32) as usize] += 1;
+                    }
+                    assert!(strata.iter().all(|&n| n == 1), "{:?}", strata);
+                }
+                // The pixel position is stratified in both axes together.
+                let side = (count as f32).sqrt() as u32;
+                if side * side == count {
+                    let mut strata = vec![0; count as usize];
+                    for index in start..start + count {
+                        let point = sobol(index, shift);
+                        let x = (point[0] * side as f32) as u32;
+                        let y = (point[1] * side as f32) as u32;
+                        strata[(y * side + x) as usize] += 1;
+                    }
+                    assert!(strata.iter().all(|&n| n == 1), "{:?}", strata);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,994 @@
+//! The demo scenes rendered by the shimmer executable, for building standard scenes
+//! programmatically, e.g. for benchmarks.
+//!
+//! Each returns the scene's objects, along with the hash-based ray path predictors of
+//! its BVHs if it uses any and the cameras it places by name. Scenes with random
+//! placement take parameters for how many objects to place, over how large an area,
+//! and a seed; with the same seed they're built identically every time.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use ahash::AHashMap;
+use glam::{vec2, vec3, Vec3};
+use rand::Rng;
+
+use crate::bvh::{Bvh, BvhBuild, BvhId};
+use crate::camera::Camera;
+use crate::geometry::cube::Cube;
+use crate::geometry::groom::{self, GroomParams};
+use crate::geometry::instance::{RotateY, Translate};
+use crate::geometry::mesh::{Mesh, MeshImport};
+use crate::geometry::moving_sphere::MovingSphere;
+use crate::geometry::ocean::{GerstnerWave, Ocean};
+use crate::geometry::particles::{Particle, ParticleSet};
+use crate::geometry::rectangle::{XyRect, XzRect, YzRect};
+use crate::geometry::sphere::Sphere;
+use crate::geometry::vox;
+use crate::hittable::{ConstantMedium, HittableList};
+use crate::hrpp::Predictor;
+use crate::loading::Pending;
+use crate::materials::diffuse_light::DiffuseLight;
+use crate::materials::{
+    dialectric::Dialectric,
+    lambertian::Lambertian,
+    material::Material,
+    metal::Metal,
+    utils::{random_color, random_color_range},
+};
+use crate::random::{self, random};
+use crate::textures::checker::Checker;
+use crate::textures::image_texture::ImageTexture;
+use crate::textures::marble::Marble;
+
+/// Controls the scattering of small spheres by `random_spheres()` and
+/// `random_moving_spheres()`.
+#[derive(Clone, Copy, Debug)]
+pub struct RandomSpheresParams {
+    /// Small spheres are scattered over a grid of cells from -`extent` to `extent` along
+    /// both x and z, one per cell.
+    pub extent: i32,
+    /// If present, seeds where the small spheres are placed and what they're made of.
+    pub seed: Option<u64>,
+}
+
+impl Default for RandomSpheresParams {
+    fn default() -> Self {
+        RandomSpheresParams {
+            extent: 11,
+            seed: None,
+        }
+    }
+}
+
+/// Controls the random parts of `showcase()`.
+#[derive(Clone, Copy, Debug)]
+pub struct ShowcaseParams {
+    /// Number of boxes along each side of the square of boxes of random heights forming
+    /// the ground.
+    pub boxes_per_side: u32,
+    /// Number of white spheres in the cube of spheres.
+    pub spheres: u32,
+    /// If present, seeds the heights of the boxes and the positions of the spheres.
+    pub seed: Option<u64>,
+    /// How the BVH over the boxes is built.
+    pub bvh_build: BvhBuild,
+}
+
+impl Default for ShowcaseParams {
+    fn default() -> Self {
+        ShowcaseParams {
+            boxes_per_side: 20,
+            spheres: 1000,
+            seed: None,
+            bvh_build: BvhBuild::Median,
+        }
+    }
+}
+
+/// Controls the sparks thrown by `sparks()`.
+#[derive(Clone, Copy, Debug)]
+pub struct SparksParams {
+    /// Number of sparks thrown, of which those which have fallen below the ground by the
+    /// time they're captured are left out.
+    pub count: u32,
+    /// If present, seeds how each spark is thrown.
+    pub seed: Option<u64>,
+}
+
+impl Default for SparksParams {
+    fn default() -> Self {
+        SparksParams {
+            count: 2000,
+            seed: None,
+        }
+    }
+}
+
+/// The objects of a scene, the predictors of its BVHs if it uses any, and its named
+/// cameras.
+pub type SceneParts = (
+    HittableList,
+    Option<AHashMap<BvhId, Mutex<Predictor>>>,
+    Vec<NamedCamera>,
+);
+
+/// A view of a scene which it places itself, and which can be rendered by name.
+#[derive(Clone, Copy, Debug)]
+pub struct NamedCamera {
+    /// The name the camera is selected by, e.g. with --camera.
+    pub name: &'static str,
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    /// Vertical field of view, in degrees.
+    pub vertical_field_of_view: f32,
+    pub aperture: f32,
+    pub focus_dist: f32,
+}
+
+impl NamedCamera {
+    /// The camera, with +Y up, for an image of the given aspect ratio and with the shutter
+    /// open from `time_start` to `time_end`.
+    pub fn camera(&self, aspect_ratio: f32, time_start: f32, time_end: f32) -> Camera {
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            Vec3::Y,
+            self.vertical_field_of_view,
+            aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+            time_start,
+            time_end,
+        )
+    }
+}
+
+/// The named cameras of `random_spheres()` and `random_moving_spheres()`.
+fn scattered_spheres_cameras() -> Vec<NamedCamera> {
+    vec![
+        NamedCamera {
+            name: "wide",
+            look_from: vec3(13.0, 2.0, 3.0),
+            look_at: Vec3::ZERO,
+            vertical_field_of_view: 20.0,
+            aperture: 0.1,
+            focus_dist: 10.0,
+        },
+        NamedCamera {
+            name: "high",
+            look_from: vec3(0.0, 12.0, 10.0),
+            look_at: Vec3::ZERO,
+            vertical_field_of_view: 40.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+        },
+    ]
+}
+
+/// The named cameras of the scenes in the Cornell box.
+fn cornell_cameras() -> Vec<NamedCamera> {
+    vec![
+        NamedCamera {
+            name: "front",
+            look_from: vec3(278.0, 278.0, -800.0),
+            look_at: vec3(278.0, 278.0, 0.0),
+            vertical_field_of_view: 40.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+        },
+        NamedCamera {
+            name: "high",
+            look_from: vec3(278.0, 500.0, -500.0),
+            look_at: vec3(278.0, 150.0, 278.0),
+            vertical_field_of_view: 40.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+        },
+    ]
+}
+
+/// Runs `f` with random numbers drawn from the stream given by `seed`, if present.
+fn seeded<T>(seed: Option<u64>, f: impl FnOnce() -> T) -> T {
+    match seed {
+        Some(seed) => random::seeded(seed, f),
+        None => f(),
+    }
+}
+
+/// Small spheres of random materials scattered around three large spheres of glass,
+/// matte and metal, on a checkered ground; the cover of Ray Tracing in One Weekend.
+pub fn random_spheres(params: &RandomSpheresParams) -> SceneParts {
+    seeded(params.seed, || {
+        (
+            scattered_spheres(params.extent, false),
+            None,
+            scattered_spheres_cameras(),
+        )
+    })
+}
+
+/// As `random_spheres()`, with each small sphere rising by a random amount over the
+/// shutter interval from 0 to 1.
+pub fn random_moving_spheres(params: &RandomSpheresParams) -> SceneParts {
+    seeded(params.seed, || {
+        (
+            scattered_spheres(params.extent, true),
+            None,
+            scattered_spheres_cameras(),
+        )
+    })
+}
+
+fn scattered_spheres(extent: i32, moving: bool) -> HittableList {
+    let mut world = HittableList::new();
+
+    let material_ground = Arc::new(Lambertian::new(Arc::new(Checker::from_color(
+        10.0,
+        vec3(0.2, 0.3, 0.1),
+        vec3(0.9, 0.9, 0.9),
+    ))));
+    world.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    for a in -extent..extent {
+        for b in -extent..extent {
+            let choose_mat = random::<f32>();
+            let center = vec3(
+                a as f32 + 0.9 * random::<f32>(),
+                0.2,
+                b as f32 + 0.9 * random::<f32>(),
+            );
+
+            if (center - vec3(4.0, 0.2, 0.0)).length() > 0.9 {
+                let material: Arc<dyn Material> = if choose_mat < 0.8 {
+                    let albedo = random_color() * random_color();
+                    Arc::new(Lambertian::from_color(albedo))
+                } else if choose_mat < 0.95 {
+                    let albedo = random_color_range(0.5, 1.0);
+                    let fuzz = random::<f32>() * 0.5;
+                    Arc::new(Metal::new(albedo, fuzz))
+                } else {
+                    Arc::new(Dialectric::new(1.5))
+                };
+                if moving {
+                    let center_end = center + vec3(0.0, random::<f32>() * 0.5, 0.0);
+                    world.add(Arc::new(MovingSphere::new(
+                        center, center_end, 0.0, 1.0, 0.2, material,
+                    )));
+                } else {
+                    world.add(Arc::new(Sphere::new(center, 0.2, material)));
+                }
+            }
+        }
+    }
+
+    let large_sphere_radius = 1.0;
+    let glass_material = Arc::new(Dialectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, 1.0, 0.0),
+        large_sphere_radius,
+        glass_material,
+    )));
+
+    let diffuse_material = Arc::new(Lambertian::from_color(vec3(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(
+        vec3(-4.0, 1.0, 0.0),
+        large_sphere_radius,
+        diffuse_material,
+    )));
+
+    let metal_material = Arc::new(Metal::new(vec3(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(
+        vec3(4.0, 1.0, 0.0),
+        large_sphere_radius,
+        metal_material,
+    )));
+
+    world
+}
+
+/// Two large checkered spheres, one atop the other.
+pub fn two_spheres() -> SceneParts {
+    let mut world = HittableList::new();
+    let checkerboard = Arc::new(Lambertian::new(Arc::new(Checker::from_color(
+        10.0,
+        vec3(0.2, 0.3, 0.1),
+        vec3(0.9, 0.9, 0.9),
+    ))));
+
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, -10.0, 0.0),
+        10.0,
+        checkerboard.clone(),
+    )));
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, 10.0, 0.0),
+        10.0,
+        checkerboard.clone(),
+    )));
+
+    (world, None, Vec::new())
+}
+
+/// A marble sphere resting on a marble ground.
+pub fn two_marble_spheres() -> SceneParts {
+    let mut world = HittableList::new();
+
+    let marble_texture = Arc::new(Marble::new(4.0));
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(marble_texture.clone())),
+    )));
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::new(marble_texture)),
+    )));
+    (world, None, Vec::new())
+}
+
+// The relative filepath of the image texture means this works if running from the top level of the git repository,
+// but not from other working directories (such as if the built app is run elsewhere).
+// This is sufficient for now as these scenes are just demos for developers.
+// Ideally, the image file (and other file resources) would be specified by a scene defined in some file (in JSON, maybe)
+// and we wouldn't be defining sample scenes via code like this at all (we would provide sample scenes as separate files
+// and would just use Shimmer to parse and render the provided scene).
+/// The Earth, textured from images/earthmap.jpg.
+pub fn earth() -> SceneParts {
+    let earth_texture = load_texture("images/earthmap.jpg").wait();
+    let earth_surface = Arc::new(Lambertian::new(earth_texture));
+    let globe = Arc::new(Sphere::new(vec3(0.0, 0.0, 0.0), 2.0, earth_surface));
+    let mut world = HittableList::new();
+    world.add(globe);
+    (world, None, Vec::new())
+}
+
+/// A marble sphere lit by a rectangular and a spherical light, in the dark.
+pub fn simple_lights() -> SceneParts {
+    let mut world = HittableList::new();
+    let marble_texture = Arc::new(Marble::new(4.0));
+    let ground = Arc::new(Sphere::new(
+        vec3(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(marble_texture.clone())),
+    ));
+    world.add(ground);
+    let sphere = Arc::new(Sphere::new(
+        vec3(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::new(marble_texture)),
+    ));
+    world.add(sphere);
+
+    let light_mat = Arc::new(DiffuseLight::from_color(vec3(4.0, 4.0, 4.0)));
+    let light = Arc::new(XyRect::new(3.0, 5.0, 1.0, 3.0, -2.0, light_mat.clone()));
+    world.add_light(light);
+
+    let sphere_light = Arc::new(Sphere::new(vec3(0.0, 7.0, 0.0), 2.0, light_mat));
+    world.add_light(sphere_light);
+
+    (world, None, Vec::new())
+}
+
+/// The Cornell box, with two boxes named "box1" and "box2".
+pub fn cornell_box() -> SceneParts {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(vec3(15.0, 15.0, 15.0)));
+
+    world.add(Arc::new(YzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        green.clone(),
+    )));
+    world.add(Arc::new(YzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        red.clone(),
+    )));
+    world.add_light(Arc::new(XzRect::new(
+        213.0, 343.0, 227.0, 332.0, 554.0, light,
+    )));
+    world.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XyRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+
+    let box1 = Arc::new(Cube::new(
+        Vec3::ZERO,
+        vec3(165.0, 330.0, 165.0),
+        white.clone(),
+    ));
+    let box1 = Arc::new(RotateY::new(box1, 15.0));
+    let box1 = Arc::new(Translate::new(box1, vec3(265.0, 0.0, 295.0)));
+
+    let box2 = Arc::new(Cube::new(
+        Vec3::ZERO,
+        vec3(165.0, 165.0, 165.0),
+        white.clone(),
+    ));
+    let box2 = Arc::new(RotateY::new(box2, -18.0));
+    let box2 = Arc::new(Translate::new(box2, vec3(130.0, 0.0, 65.0)));
+
+    world.add_named("box1", box1);
+    world.add_named("box2", box2);
+
+    (world, None, cornell_cameras())
+}
+
+/// The Cornell box with boxes of black and white smoke.
+pub fn cornell_smoke() -> SceneParts {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
+
+    world.add(Arc::new(YzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        green.clone(),
+    )));
+    world.add(Arc::new(YzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        red.clone(),
+    )));
+    world.add_light(Arc::new(XzRect::new(
+        113.0, 443.0, 127.0, 432.0, 554.0, light,
+    )));
+    world.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XyRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+
+    let box1 = Arc::new(Cube::new(
+        Vec3::ZERO,
+        vec3(165.0, 330.0, 165.0),
+        white.clone(),
+    ));
+    let box1 = Arc::new(RotateY::new(box1, 15.0));
+    let box1 = Arc::new(Translate::new(box1, vec3(265.0, 0.0, 295.0)));
+
+    let box2 = Arc::new(Cube::new(
+        Vec3::ZERO,
+        vec3(165.0, 165.0, 165.0),
+        white.clone(),
+    ));
+    let box2 = Arc::new(RotateY::new(box2, -18.0));
+    let box2 = Arc::new(Translate::new(box2, vec3(130.0, 0.0, 65.0)));
+
+    world.add(Arc::new(ConstantMedium::new_with_color(
+        box1,
+        0.01,
+        Vec3::new(0.0, 0.0, 0.0),
+    )));
+    world.add(Arc::new(ConstantMedium::new_with_color(
+        box2,
+        0.01,
+        Vec3::new(1.0, 1.0, 1.0),
+    )));
+
+    (world, None, cornell_cameras())
+}
+
+/// Nearly every feature of Ray Tracing: The Next Week at once: a ground of boxes, a
+/// moving sphere, glass, metal, fog, a textured Earth and marble, and a rotated cube of
+/// spheres. The boxes and spheres are in BVHs with hash-based ray path predictors.
+pub fn showcase(params: &ShowcaseParams) -> SceneParts {
+    seeded(params.seed, || showcase_world(params))
+}
+
+fn showcase_world(params: &ShowcaseParams) -> SceneParts {
+    let earth_texture = load_texture("images/earthmap.jpg");
+    let mut rng = random::rng();
+
+    let mut predictors = AHashMap::<BvhId, Mutex<Predictor>>::new();
+
+    let mut boxes = HittableList::new();
+    let ground_mat = Arc::new(Lambertian::from_color(vec3(0.48, 0.83, 0.53)));
+    // The boxes cover the same ground however many there are.
+    let w = 2000.0 / params.boxes_per_side as f32;
+    for i in 0..params.boxes_per_side {
+        for j in 0..params.boxes_per_side {
+            let x0 = -1000.0 + i as f32 * w;
+            let z0 = -1000.0 + j as f32 * w;
+            let y0 = 0.0;
+            let x1 = x0 + w;
+            let y1 = rng.gen_range(1.0..101.0);
+            let z1 = z0 + w;
+
+            boxes.add(Arc::new(Cube::new(
+                vec3(x0, y0, z0),
+                vec3(x1, y1, z1),
+                ground_mat.clone(),
+            )));
+        }
+    }
+
+    let mut world = HittableList::new();
+    world.add(Arc::new(Bvh::build_with_predictor(
+        boxes,
+        0.0,
+        1.0,
+        params.bvh_build,
+        &mut predictors,
+    )));
+
+    let light_mat = Arc::new(DiffuseLight::from_color(vec3(7.0, 7.0, 7.0)));
+    world.add_light(Arc::new(XzRect::new(
+        123.0, 423.0, 147.0, 412.0, 554.0, light_mat,
+    )));
+
+    let center1 = vec3(400.0, 400.0, 200.0);
+    let center2 = center1 + vec3(30.0, 0.0, 0.0);
+
+    let moving_sphere_mat = Arc::new(Lambertian::from_color(vec3(0.7, 0.3, 0.1)));
+    world.add(Arc::new(MovingSphere::new(
+        center1,
+        center2,
+        0.0,
+        1.0,
+        50.0,
+        moving_sphere_mat,
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        vec3(260.0, 150.0, 45.0),
+        50.0,
+        Arc::new(Dialectric::new(1.5)),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, 150.0, 145.0),
+        50.0,
+        Arc::new(Metal::new(vec3(0.8, 0.8, 0.9), 1.0)),
+    )));
+
+    let boundary = Arc::new(Sphere::new(
+        vec3(360.0, 150.0, 145.0),
+        70.0,
+        Arc::new(Dialectric::new(1.5)),
+    ));
+    world.add(boundary.clone());
+    world.add(Arc::new(ConstantMedium::new_with_color(
+        boundary,
+        0.2,
+        vec3(0.2, 0.4, 0.9),
+    )));
+
+    let boundary = Arc::new(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        5000.0,
+        Arc::new(Dialectric::new(1.5)),
+    ));
+    world.add(Arc::new(ConstantMedium::new_with_color(
+        boundary,
+        0.0001,
+        vec3(1.0, 1.0, 1.0),
+    )));
+
+    let earth_mat = Arc::new(Lambertian::new(earth_texture.wait()));
+    world.add(Arc::new(Sphere::new(
+        vec3(400.0, 200.0, 400.0),
+        100.0,
+        earth_mat,
+    )));
+
+    let perlin_texture = Arc::new(Marble::new(0.1));
+    world.add(Arc::new(Sphere::new(
+        vec3(220.0, 280.0, 300.0),
+        80.0,
+        Arc::new(Lambertian::new(perlin_texture)),
+    )));
+
+    let mut spheres = HittableList::new();
+    let white_mat = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    for _ in 0..params.spheres {
+        let max_val = 165.0;
+        let random_x = rng.gen_range(0.0..max_val);
+        let random_y = rng.gen_range(0.0..max_val);
+        let random_z = rng.gen_range(0.0..max_val);
+        spheres.add(Arc::new(Sphere::new(
+            vec3(random_x, random_y, random_z),
+            10.0,
+            white_mat.clone(),
+        )));
+    }
+
+    world.add(Arc::new(Translate::new(
+        Arc::new(RotateY::new(
+            Arc::new(Bvh::with_predictor(spheres, 0.0, 1.0, &mut predictors)),
+            15.0,
+        )),
+        vec3(-100.0, 270.0, 395.0),
+    )));
+
+    (world, Some(predictors), Vec::new())
+}
+
+/// Starts loading the image texture at `path`, relative to the top of the repository.
+fn load_texture(path: &'static str) -> Pending<Arc<ImageTexture>> {
+    Pending::spawn_cached(path, path, move || {
+        Arc::new(ImageTexture::new(Path::new(path)))
+    })
+}
+
+/// The walls and light of the Cornell box, with nothing in it.
+pub fn cornell_boundaries() -> HittableList {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::from_color(vec3(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::from_color(vec3(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(vec3(15.0, 15.0, 15.0)));
+
+    world.add_light(Arc::new(XzRect::new(
+        200.0, 356.0, 200.0, 359.0, 554.0, light,
+    )));
+
+    world.add(Arc::new(YzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        green.clone(),
+    )));
+    world.add(Arc::new(YzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        red.clone(),
+    )));
+
+    world.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XyRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+
+    world
+}
+
+/// The Stanford bunny in the Cornell box, loaded from models/bunny_2000_scale.obj.
+pub fn bunny(import: MeshImport) -> SceneParts {
+    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let bunny = Pending::spawn_cached(
+        "models/bunny_2000_scale.obj",
+        format!("models/bunny_2000_scale.obj {:?}", import),
+        move || Mesh::from_obj_with_import("models/bunny_2000_scale.obj", white, import),
+    );
+    let mut world = cornell_boundaries();
+
+    let bunny = bunny.wait().expect("Failed to load OBJ file");
+    let bunny = Arc::new(Translate::new(bunny, vec3(325.0, 0.0, 200.0)));
+    world.add(bunny);
+
+    (world, None, cornell_cameras())
+}
+
+/// The Stanford bunny in the Cornell box, with fur grown over it.
+pub fn furry_bunny(import: MeshImport) -> SceneParts {
+    let mesh = Pending::spawn_cached(
+        "models/bunny_2000_scale.obj",
+        format!("models/bunny_2000_scale.obj mesh {:?}", import),
+        move || Mesh::load("models/bunny_2000_scale.obj", import).map(Arc::new),
+    );
+    let mut world = cornell_boundaries();
+
+    let mesh = mesh.wait().expect("Failed to load OBJ file");
+
+    let skin = Arc::new(Lambertian::from_color(vec3(0.35, 0.25, 0.2)));
+    let mut bunny = mesh.to_tris(skin);
+
+    let fur_mat = Arc::new(Lambertian::from_color(vec3(0.6, 0.45, 0.3)));
+    let fur_params = GroomParams {
+        density: 0.5,
+        length: 8.0,
+        root_radius: 0.15,
+        tip_radius: 0.03,
+        clump_density: 0.02,
+        clump_strength: 0.4,
+        ..Default::default()
+    };
+    for strand in groom::grow(&mesh.triangles, &fur_params, fur_mat).objects {
+        bunny.add(strand);
+    }
+
+    let bunny = Bvh::build(bunny, 0.0, 1.0, import.bvh_build);
+    let bunny = Arc::new(Translate::new(Arc::new(bunny), vec3(325.0, 0.0, 200.0)));
+    world.add(bunny);
+
+    (world, None, cornell_cameras())
+}
+
+/// A gargoyle in the Cornell box, loaded from models/gargoyle.obj.
+pub fn gargoyle(import: MeshImport) -> SceneParts {
+    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let garg = Pending::spawn_cached(
+        "models/gargoyle.obj",
+        format!("models/gargoyle.obj {:?}", import),
+        move || Mesh::from_obj_with_import("models/gargoyle.obj", white, import),
+    );
+    let mut world = cornell_boundaries();
+
+    let garg = garg.wait().expect("Failed to load OBJ file");
+    let garg = Arc::new(Translate::new(garg, vec3(275.0, 0.0, 200.0)));
+    world.add(garg);
+
+    (world, None, cornell_cameras())
+}
+
+/// The Igea head in the Cornell box, in a BVH with a hash-based ray path predictor.
+pub fn igea_hrpp(import: MeshImport) -> SceneParts {
+    let igea = Pending::spawn_cached(
+        "models/igea.obj",
+        format!("models/igea.obj mesh {:?}", import),
+        move || Mesh::load("models/igea.obj", import).map(Arc::new),
+    );
+    let mut world = cornell_boundaries();
+
+    let white = Arc::new(Lambertian::from_color(vec3(0.73, 0.73, 0.73)));
+    let igea = igea.wait().expect("Failed to load OBJ file").to_tris(white);
+
+    let mut predictors = AHashMap::<BvhId, Mutex<Predictor>>::new();
+    let igea = Bvh::with_predictor(igea, 0.0, 1.0, &mut predictors);
+    let igea = Arc::new(Translate::new(Arc::new(igea), vec3(275.0, 0.0, 200.0)));
+    world.add(igea);
+
+    (world, Some(predictors), cornell_cameras())
+}
+
+/// Gerstner waves of water over a sandy bottom, with a couple of spheres.
+pub fn ocean() -> SceneParts {
+    let mut world = HittableList::new();
+
+    let waves = GerstnerWave::from_wind(vec2(1.0, 0.3), 8.0, 0.25, 6);
+    world.add(Arc::new(Ocean::new(
+        Vec3::ZERO,
+        200.0,
+        200,
+        &waves,
+        0.0,
+        Arc::new(Dialectric::new(1.33)),
+    )));
+
+    let sand = Arc::new(Lambertian::from_color(vec3(0.76, 0.7, 0.5)));
+    world.add(Arc::new(Sphere::new(vec3(0.0, -1003.0, 0.0), 1000.0, sand)));
+
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, 0.6, 0.0),
+        1.0,
+        Arc::new(Lambertian::from_color(vec3(0.8, 0.2, 0.1))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        vec3(-4.0, 0.8, -2.0),
+        1.0,
+        Arc::new(Metal::new(vec3(0.8, 0.8, 0.8), 0.05)),
+    )));
+
+    (world, None, Vec::new())
+}
+
+/// The MagicaVoxel model at `path`, scaled to 2 units across, on a ground plane.
+pub fn voxels(path: &Path) -> SceneParts {
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from_color(vec3(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, -1000.0, 0.0),
+        1000.0,
+        ground,
+    )));
+
+    // Scale the model to be 2 units across at its largest, standing on the ground
+    // centered on the origin.
+    let model = vox::load(path, 1.0).expect("Failed to load .vox file");
+    let dims = model.dims();
+    let voxel_size = 2.0 / *dims.iter().max().unwrap() as f32;
+    let model = model.with_voxel_size(voxel_size);
+    let offset = vec3(
+        -0.5 * dims[0] as f32 * voxel_size,
+        0.0,
+        -0.5 * dims[2] as f32 * voxel_size,
+    );
+    world.add(Arc::new(Translate::new(Arc::new(model), offset)));
+
+    (world, None, Vec::new())
+}
+
+/// Glowing sparks thrown up from the origin, streaking as they fly, over a grey ground.
+pub fn sparks(params: &SparksParams) -> SceneParts {
+    seeded(params.seed, || {
+        (thrown_sparks(params.count), None, Vec::new())
+    })
+}
+
+fn thrown_sparks(count: u32) -> HittableList {
+    let mut rng = random::rng();
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from_color(vec3(0.3, 0.3, 0.3)));
+    world.add(Arc::new(Sphere::new(
+        vec3(0.0, -1000.0, 0.0),
+        1000.0,
+        ground,
+    )));
+
+    // Ballistic sparks thrown up from the origin, each captured at a random point in its flight.
+    let gravity = vec3(0.0, -9.81, 0.0);
+    let particles: Vec<Particle> = (0..count)
+        .map(|_| {
+            let launch_velocity = vec3(
+                rng.gen_range(-1.5..1.5),
+                rng.gen_range(5.0..8.0),
+                rng.gen_range(-1.5..1.5),
+            );
+            let flight_time = rng.gen_range(0.0..1.2);
+            let position = launch_velocity * flight_time + 0.5 * gravity * flight_time.powi(2);
+            let velocity = launch_velocity + gravity * flight_time;
+            let heat = rng.gen_range(0.5..1.0);
+            Particle::new(
+                position,
+                0.02,
+                vec3(8.0, 4.0 * heat, 1.0 * heat * heat),
+                // Short exposure; scale velocity so the streaks stay short.
+                Some(velocity * 0.02),
+            )
+        })
+        .filter(|particle| particle.position.y > 0.0)
+        .collect();
+
+    world.add(Arc::new(ParticleSet::new(&particles, 0.0, 1.0, |color| {
+        Arc::new(DiffuseLight::from_color(color))
+    })));
+
+    world
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::vec3;
+
+    use crate::hittable::Hittable;
+    use crate::random;
+    use crate::ray::Ray;
+    use crate::textures::{marble::Marble, texture::Texture};
+
+    use super::{random_spheres, showcase, RandomSpheresParams, ShowcaseParams};
+
+    /// The distances at which rays cast straight down from `top`, over a grid `spacing`
+    /// apart, hit the `world`. The grid is offset by half its spacing so that rays don't
+    /// graze the faces of boxes lined up on whole coordinates.
+    fn heights(world: &dyn Hittable, spacing: f32, top: f32) -> Vec<Option<f32>> {
+        let mut heights = Vec::new();
+        for i in -12..12 {
+            for j in -12..12 {
+                let (x, z) = (i as f32 + 0.5, j as f32 + 0.5);
+                let origin = vec3(x * spacing, top, z * spacing);
+                let ray = Ray::new(origin, vec3(0.0, -1.0, 0.0), 0.0);
+                let hit = world.hit(&ray, 0.001, f32::INFINITY, &Arc::new(None));
+                heights.push(hit.map(|hit| hit.t));
+            }
+        }
+        heights
+    }
+
+    #[test]
+    fn seeded_scenes_repeat() {
+        let params = |seed| RandomSpheresParams {
+            extent: 3,
+            seed: Some(seed),
+        };
+        let heights_of = |seed| heights(&random_spheres(&params(seed)).0, 0.25, 5.0);
+        let (first, _, _) = random_spheres(&params(5));
+        let (second, _, _) = random_spheres(&params(5));
+        assert_eq!(first.objects.len(), second.objects.len());
+        assert_eq!(heights_of(5), heights_of(5));
+        assert_ne!(heights_of(5), heights_of(6));
+
+        let params = |seed| ShowcaseParams {
+            boxes_per_side: 4,
+            spheres: 10,
+            seed: Some(seed),
+            ..Default::default()
+        };
+        // The boxes of the ground are the first object, so the fog isn't hit.
+        let ground_heights_of = |seed| heights(&*showcase(&params(seed)).0.objects[0], 80.0, 200.0);
+        assert_eq!(ground_heights_of(5), ground_heights_of(5));
+        assert_ne!(ground_heights_of(5), ground_heights_of(6));
+        let marble_of = |seed| {
+            let marble = random::seeded(seed, || Marble::new(0.1));
+            (0..8)
+                .map(|i| marble.value(0.0, 0.0, &vec3(i as f32 * 7.0, 3.0, 1.0)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(marble_of(5), marble_of(5));
+        assert_ne!(marble_of(5), marble_of(6));
+    }
+}
@@ -0,0 +1,180 @@
+//! Splitting a render into horizontal slices which can be rendered on separate machines,
+//! and assembling the slices back into the whole image.
+//!
+//! A slice is written as a PPM image of only its rows, with a `# slice i/N` comment in
+//! its header so that the slices can be put back in order. Samples near a slice's edges
+//! splat into the neighboring slices, so each slice also traces the rows within the
+//! filter's radius of it, and keeps only what lands in its own rows. With a seed, the
+//! assembled image is the same as rendering it whole.
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    ops::Range,
+    path::Path,
+};
+
+/// The `index`th of `count` horizontal slices of an image, numbered from 1 at the top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slice {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Slice {
+    pub fn new(index: usize, count: usize) -> Result<Slice, String> {
+        if count == 0 || index == 0 || index > count {
+            return Err(format!(
+                "slice {}/{} doesn't exist; slices are numbered from 1 to the count",
+                index, count
+            ));
+        }
+        Ok(Slice { index, count })
+    }
+
+    /// Parses a slice written as "i/N", e.g. "2/8".
+    pub fn parse(s: &str) -> Result<Slice, String> {
+        let (index, count) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("expected a slice like 2/8, got '{}'", s))?;
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("expected a slice like 2/8, got '{}'", s))
+        };
+        Slice::new(parse(index)?, parse(count)?)
+    }
+
+    /// The rows of an image `image_height` rows high which are in this slice, counted
+    /// from the bottom as the renderer stores them. Slices differ in height by at most a
+    /// row, and some are empty if there are more slices than rows.
+    pub fn rows(&self, image_height: usize) -> Range<usize> {
+        // Rows from the top of the image to the top and bottom of the slice.
+        let top = (self.index - 1) * image_height / self.count;
+        let bottom = self.index * image_height / self.count;
+        image_height - bottom..image_height - top
+    }
+}
+
+impl fmt::Display for Slice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// A slice read from a PPM image written by the renderer.
+struct SliceImage {
+    slice: Slice,
+    width: usize,
+    height: usize,
+    /// The pixel values following the header.
+    pixels: String,
+}
+
+impl SliceImage {
+    fn load(path: &Path) -> io::Result<SliceImage> {
+        let invalid = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), message),
+            )
+        };
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        if lines.next().map(str::trim) != Some("P3") {
+            return Err(invalid("not a plain PPM image"));
+        }
+        let mut slice = None;
+        let mut header = Vec::new();
+        let mut header_len = "P3\n".len();
+        for line in lines.by_ref() {
+            header_len += line.len() + 1;
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some(s) = comment.trim().strip_prefix("slice ") {
+                    slice = Some(Slice::parse(s).map_err(|e| invalid(&e))?);
+                }
+                continue;
+            }
+            header.extend(line.split_whitespace().map(String::from));
+            // The width, height and maximum value.
+            if header.len() >= 3 {
+                break;
+            }
+        }
+        let slice = slice.ok_or_else(|| invalid("not a slice; it has no slice comment"))?;
+        let dimension = |i: usize| -> io::Result<usize> {
+            header
+                .get(i)
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| invalid("malformed header"))
+        };
+        Ok(SliceImage {
+            slice,
+            width: dimension(0)?,
+            height: dimension(1)?,
+            pixels: contents.get(header_len..).unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// Assembles the slices written to the PPM images at `paths`, which may be given in any
+/// order, into the whole image, which is written to `output` as a PPM image. Every slice
+/// of the image must be present, once.
+pub fn assemble<P: AsRef<Path>>(paths: &[P], output: &mut impl Write) -> io::Result<()> {
+    let mut slices = paths
+        .iter()
+        .map(|path| SliceImage::load(path.as_ref()))
+        .collect::<io::Result<Vec<_>>>()?;
+    slices.sort_by_key(|slice| slice.slice.index);
+
+    let Some(first) = slices.first() else {
+        return Err(io::Error::other("no slices to assemble"));
+    };
+    let (width, count) = (first.width, first.slice.count);
+    let indices: Vec<usize> = slices.iter().map(|slice| slice.slice.index).collect();
+    if indices != (1..=count).collect::<Vec<_>>() {
+        return Err(io::Error::other(format!(
+            "expected each of {} slices once, got slices {:?}",
+            count, indices
+        )));
+    }
+    let height: usize = slices.iter().map(|slice| slice.height).sum();
+    for slice in slices.iter() {
+        if slice.slice.count != count
+            || slice.width != width
+            || slice.height != slice.slice.rows(height).len()
+        {
+            return Err(io::Error::other(format!(
+                "slice {} is {}x{} pixels, which doesn't fit with the other slices",
+                slice.slice, slice.width, slice.height
+            )));
+        }
+    }
+
+    write!(output, "P3\n{} {}\n255\n", width, height)?;
+    for slice in slices.iter() {
+        output.write_all(slice.pixels.as_bytes())?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slice;
+
+    #[test]
+    fn slices_cover_the_image_once() {
+        for (count, height) in [(1, 10), (3, 10), (4, 1080), (5, 3)] {
+            let mut rows: Vec<usize> = (1..=count)
+                .flat_map(|index| Slice::new(index, count).unwrap().rows(height))
+                .collect();
+            rows.sort_unstable();
+            assert_eq!(rows, (0..height).collect::<Vec<_>>());
+        }
+        // The first slice is at the top, where the renderer's rows end.
+        assert_eq!(Slice::parse("1/4").unwrap().rows(100), 75..100);
+        assert!(Slice::parse("0/4").is_err());
+        assert!(Slice::parse("5/4").is_err());
+    }
+}
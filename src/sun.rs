@@ -0,0 +1,161 @@
+//! Positions the sun from a date, time and location on Earth.
+//!
+//! Directions use +Y as up, +X as east and -Z as north.
+
+use std::{f32::consts::PI, sync::Arc};
+
+use glam::Vec3;
+
+use crate::{geometry::sphere::Sphere, materials::diffuse_light::DiffuseLight};
+
+/// Angular diameter of the sun as seen from Earth, in degrees.
+pub const SUN_ANGULAR_DIAMETER: f32 = 0.53;
+
+/// The sun as seen from a location on Earth at a particular moment.
+#[derive(Clone, Copy, Debug)]
+pub struct Sun {
+    /// Unit vector pointing towards the sun.
+    pub direction: Vec3,
+    /// Angle of the sun above the horizon, in degrees. Negative when the sun has set.
+    pub elevation: f32,
+    /// Direct normal irradiance reaching the ground, in kW/m^2, accounting for
+    /// attenuation through the atmosphere. Zero when the sun has set.
+    pub irradiance: f32,
+}
+
+impl Sun {
+    /// Positions the sun for a date, local time and location, following NOAA's
+    /// general solar position approximation.
+    ///
+    /// * `year`, `month`, `day` - The date, e.g. 2023, 6, 21.
+    /// * `hour` - Local time of day in hours, e.g. 15.5 for 3:30pm.
+    /// * `utc_offset` - Offset of the local time from UTC in hours, e.g. 2.0 for CEST.
+    /// * `latitude`, `longitude` - Location in degrees; north and east are positive.
+    pub fn at(
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: f32,
+        utc_offset: f32,
+        latitude: f32,
+        longitude: f32,
+    ) -> Sun {
+        let utc_hour = hour - utc_offset;
+        let day_of_year = day_of_year(year, month, day) as f32;
+        let days_in_year = if is_leap_year(year) { 366.0 } else { 365.0 };
+
+        // Fractional year, in radians
+        let gamma = 2.0 * PI / days_in_year * (day_of_year - 1.0 + (utc_hour - 12.0) / 24.0);
+
+        // Equation of time (minutes) and solar declination (radians)
+        let equation_of_time = 229.18
+            * (0.000075 + 0.001868 * f32::cos(gamma)
+                - 0.032077 * f32::sin(gamma)
+                - 0.014615 * f32::cos(2.0 * gamma)
+                - 0.040849 * f32::sin(2.0 * gamma));
+        let declination = 0.006918 - 0.399912 * f32::cos(gamma) + 0.070257 * f32::sin(gamma)
+            - 0.006758 * f32::cos(2.0 * gamma)
+            + 0.000907 * f32::sin(2.0 * gamma)
+            - 0.002697 * f32::cos(3.0 * gamma)
+            + 0.00148 * f32::sin(3.0 * gamma);
+
+        let true_solar_minutes = utc_hour * 60.0 + equation_of_time + 4.0 * longitude;
+        let hour_angle = f32::to_radians(true_solar_minutes / 4.0 - 180.0);
+        let latitude = f32::to_radians(latitude);
+
+        let east = -f32::cos(declination) * f32::sin(hour_angle);
+        let north = f32::sin(declination) * f32::cos(latitude)
+            - f32::cos(declination) * f32::cos(hour_angle) * f32::sin(latitude);
+        let up = f32::sin(declination) * f32::sin(latitude)
+            + f32::cos(declination) * f32::cos(hour_angle) * f32::cos(latitude);
+
+        let direction = Vec3::new(east, up, -north).normalize();
+        let elevation = f32::to_degrees(f32::asin(direction.y));
+
+        Sun {
+            direction,
+            elevation,
+            irradiance: irradiance(elevation),
+        }
+    }
+
+    /// Returns an emissive sphere in the direction of the sun, `distance` away from `center`,
+    /// which delivers the sun's irradiance (scaled by `intensity_scale`) to the scene.
+    ///
+    /// `angular_diameter` sets the apparent size of the sun in degrees. The real sun is
+    /// `SUN_ANGULAR_DIAMETER`; larger values give softer shadows and less noise.
+    pub fn to_sphere(
+        &self,
+        center: Vec3,
+        distance: f32,
+        angular_diameter: f32,
+        intensity_scale: f32,
+    ) -> Sphere {
+        let half_angle = f32::to_radians(angular_diameter / 2.0);
+        let radius = distance * f32::tan(half_angle);
+        // Radiance over the solid angle of the disk which produces the desired irradiance.
+        let solid_angle = 2.0 * PI * (1.0 - f32::cos(half_angle));
+        let radiance = self.irradiance * intensity_scale / solid_angle;
+        Sphere::new(
+            center + self.direction * distance,
+            radius,
+            Arc::new(DiffuseLight::blackbody(5800.0, radiance)),
+        )
+    }
+}
+
+/// Direct normal irradiance in kW/m^2 for the sun at `elevation` degrees, using the
+/// Kasten-Young air mass formula and Meinel's empirical attenuation.
+fn irradiance(elevation: f32) -> f32 {
+    if elevation <= 0.0 {
+        return 0.0;
+    }
+    let zenith = 90.0 - elevation;
+    let air_mass =
+        1.0 / (f32::cos(f32::to_radians(zenith)) + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+    1.353 * 0.7_f32.powf(air_mass.powf(0.678))
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn day_of_year(year: i32, month: u32, day: u32) -> u32 {
+    const CUMULATIVE_DAYS: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    let month_idx = (month.clamp(1, 12) - 1) as usize;
+    let leap_day = if month > 2 && is_leap_year(year) {
+        1
+    } else {
+        0
+    };
+    CUMULATIVE_DAYS[month_idx] + leap_day + day
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sun;
+
+    #[test]
+    fn oslo_midsummer_afternoon() {
+        // 3pm CEST on the summer solstice in Oslo
+        let sun = Sun::at(2023, 6, 21, 15.0, 2.0, 59.91, 10.75);
+        // Reference elevation ~49.4 degrees, in the south-west.
+        assert!((sun.elevation - 49.4).abs() < 1.0);
+        assert!(sun.direction.x < 0.0);
+        assert!(sun.direction.z > 0.0);
+        assert!(sun.irradiance > 0.5);
+    }
+
+    #[test]
+    fn equator_equinox_noon_is_overhead() {
+        let sun = Sun::at(2023, 3, 20, 12.0, 0.0, 0.0, 0.0);
+        assert!(sun.elevation > 85.0);
+    }
+
+    #[test]
+    fn night_has_no_irradiance() {
+        let sun = Sun::at(2023, 12, 21, 0.0, 1.0, 59.91, 10.75);
+        assert!(sun.elevation < 0.0);
+        assert_eq!(sun.irradiance, 0.0);
+    }
+}
@@ -0,0 +1,535 @@
+//! Textures composed from a graph of nodes, such as images, noise, math, mixes and color
+//! ramps, which is evaluated at shading time. Unlike the concrete texture structs, a
+//! graph is data, so it can be written to and read from a text file.
+//!
+//! In the text format, each line adds a node named by its left-hand side, whose inputs
+//! are nodes named on earlier lines. The last node is the graph's output, unless another
+//! is given by an `output NAME` line. Blank lines and lines starting with `#` are ignored.
+//! For example, marble-like veins:
+//!
+//! ```text
+//! p = position
+//! z = component p z
+//! n = noise 1 6 7
+//! ten = constant 10 10 10
+//! wobble = multiply n ten
+//! phase = add z wobble
+//! wave = sin phase
+//! veins = ramp wave -1 0.1 0.1 0.1 1 0.9 0.9 0.9
+//! ```
+//!
+//! The kinds of nodes are:
+//! * `constant R G B` - A constant color.
+//! * `uv` - The surface's texture coordinates, as (u, v, 0).
+//! * `position` - The point being shaded.
+//! * `component INPUT x|y|z` - One component of the input, in all three components.
+//! * `image PATH` - The image at the path, relative to the working directory, looked up
+//!   at the texture coordinates. The path is the rest of the line, so it may have spaces.
+//! * `noise FREQUENCY OCTAVES SEED` - Fractal Perlin noise over the point being shaded,
+//!   mostly between 0 and 1.
+//! * `add|subtract|multiply|divide|power|min|max A B` - Math on each component.
+//! * `sin INPUT` - The sine of each component.
+//! * `mix A B FACTOR` - Blends from A to B by FACTOR, for each component.
+//! * `ramp INPUT POSITION R G B [POSITION R G B]...` - Maps the mean of the input's
+//!   components to a color, interpolating between colors at ascending positions.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::{vec3, Vec3};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use super::image_texture::ImageTexture;
+use super::texture::Texture;
+
+/// A node in a `TextureGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeId(usize);
+
+/// A binary operation on each component of two nodes' values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Min,
+    Max,
+}
+
+const MATH_OPS: [(MathOp, &str); 7] = [
+    (MathOp::Add, "add"),
+    (MathOp::Subtract, "subtract"),
+    (MathOp::Multiply, "multiply"),
+    (MathOp::Divide, "divide"),
+    (MathOp::Power, "power"),
+    (MathOp::Min, "min"),
+    (MathOp::Max, "max"),
+];
+
+impl MathOp {
+    fn apply(self, a: Vec3, b: Vec3) -> Vec3 {
+        match self {
+            MathOp::Add => a + b,
+            MathOp::Subtract => a - b,
+            MathOp::Multiply => a * b,
+            MathOp::Divide => a / b,
+            MathOp::Power => vec3(a.x.powf(b.x), a.y.powf(b.y), a.z.powf(b.z)),
+            MathOp::Min => a.min(b),
+            MathOp::Max => a.max(b),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        MATH_OPS.iter().find(|(op, _)| *op == self).unwrap().1
+    }
+}
+
+/// What a node computes, from the nodes given as its inputs. See the module
+/// documentation for what each does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Constant(Vec3),
+    Uv,
+    Position,
+    Component {
+        input: NodeId,
+        axis: usize,
+    },
+    Image(PathBuf),
+    Noise {
+        frequency: f32,
+        octaves: usize,
+        seed: u32,
+    },
+    Math {
+        op: MathOp,
+        a: NodeId,
+        b: NodeId,
+    },
+    Sin(NodeId),
+    Mix {
+        a: NodeId,
+        b: NodeId,
+        factor: NodeId,
+    },
+    Ramp {
+        input: NodeId,
+        /// Positions and their colors, in ascending order of position.
+        stops: Vec<(f32, Vec3)>,
+    },
+}
+
+impl Node {
+    fn inputs(&self) -> Vec<NodeId> {
+        match self {
+            Node::Component { input, .. } | Node::Sin(input) | Node::Ramp { input, .. } => {
+                vec![*input]
+            }
+            Node::Math { a, b, .. } => vec![*a, *b],
+            Node::Mix { a, b, factor } => vec![*a, *b, *factor],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// What a node needs at shading time, beyond its description.
+enum Evaluator {
+    None,
+    Image(Arc<ImageTexture>),
+    Noise(Fbm<Perlin>),
+}
+
+/// A texture evaluated from a graph of nodes.
+pub struct TextureGraph {
+    names: Vec<String>,
+    nodes: Vec<Node>,
+    evaluators: Vec<Evaluator>,
+    output: Option<NodeId>,
+}
+
+impl TextureGraph {
+    pub fn new() -> TextureGraph {
+        TextureGraph {
+            names: Vec::new(),
+            nodes: Vec::new(),
+            evaluators: Vec::new(),
+            output: None,
+        }
+    }
+
+    /// Adds the `node` with a `name` unique within the graph, and makes it the output.
+    /// Its inputs must already be in the graph, and any image it reads must load.
+    pub fn add(&mut self, name: &str, node: Node) -> Result<NodeId, String> {
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '=') {
+            return Err(format!("'{}' can't be a node name", name));
+        }
+        if self.id(name).is_some() {
+            return Err(format!("there's already a node named '{}'", name));
+        }
+        if node
+            .inputs()
+            .iter()
+            .any(|input| input.0 >= self.nodes.len())
+        {
+            return Err(format!("an input of '{}' isn't in the graph", name));
+        }
+        let evaluator = match &node {
+            Node::Image(path) => Evaluator::Image(Arc::new(
+                ImageTexture::load(path)
+                    .map_err(|e| format!("couldn't load {}: {}", path.display(), e))?,
+            )),
+            Node::Noise {
+                frequency,
+                octaves,
+                seed,
+            } => Evaluator::Noise(
+                Fbm::<Perlin>::new(*seed)
+                    .set_frequency(*frequency as f64)
+                    .set_octaves(*octaves),
+            ),
+            Node::Ramp { stops, .. } => {
+                if stops.is_empty() || stops.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+                    return Err(format!(
+                        "the ramp '{}' needs stops in ascending order",
+                        name
+                    ));
+                }
+                Evaluator::None
+            }
+            Node::Component { axis, .. } if *axis > 2 => {
+                return Err(format!("'{}' has no component {}", name, axis));
+            }
+            _ => Evaluator::None,
+        };
+        let id = NodeId(self.nodes.len());
+        self.names.push(name.to_string());
+        self.nodes.push(node);
+        self.evaluators.push(evaluator);
+        self.output = Some(id);
+        Ok(id)
+    }
+
+    /// Makes the node `id` the graph's output, rather than the last node added.
+    pub fn set_output(&mut self, id: NodeId) {
+        assert!(id.0 < self.nodes.len());
+        self.output = Some(id);
+    }
+
+    /// The node with the `name`, if there is one.
+    pub fn id(&self, name: &str) -> Option<NodeId> {
+        self.names.iter().position(|n| n == name).map(NodeId)
+    }
+
+    /// Parses a graph from the text format described in the module documentation.
+    pub fn parse(text: &str) -> Result<TextureGraph, String> {
+        let mut graph = TextureGraph::new();
+        for (number, line) in text.lines().enumerate() {
+            graph
+                .parse_line(line)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        if graph.output.is_none() {
+            return Err(String::from("the graph has no nodes"));
+        }
+        Ok(graph)
+    }
+
+    /// Reads a graph from the text file at `path`.
+    pub fn load(path: &Path) -> Result<TextureGraph, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        TextureGraph::parse(&text)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        if let Some(name) = line.strip_prefix("output ") {
+            let id = self.input(name.trim())?;
+            self.set_output(id);
+            return Ok(());
+        }
+        let (name, definition) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected 'NAME = NODE' in '{}'", line))?;
+        let definition = definition.trim();
+        let (kind, rest) = definition
+            .split_once(char::is_whitespace)
+            .unwrap_or((definition, ""));
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let arity = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(format!("'{}' takes {} arguments", kind, count))
+            }
+        };
+        let node = match kind {
+            "constant" => {
+                arity(3)?;
+                Node::Constant(vec3(number(args[0])?, number(args[1])?, number(args[2])?))
+            }
+            "uv" => {
+                arity(0)?;
+                Node::Uv
+            }
+            "position" => {
+                arity(0)?;
+                Node::Position
+            }
+            "component" => {
+                arity(2)?;
+                let axis = ["x", "y", "z"]
+                    .iter()
+                    .position(|axis| *axis == args[1])
+                    .ok_or_else(|| format!("'{}' isn't x, y or z", args[1]))?;
+                Node::Component {
+                    input: self.input(args[0])?,
+                    axis,
+                }
+            }
+            "image" => {
+                if rest.trim().is_empty() {
+                    return Err(String::from("'image' takes a path"));
+                }
+                Node::Image(PathBuf::from(rest.trim()))
+            }
+            "noise" => {
+                arity(3)?;
+                Node::Noise {
+                    frequency: number(args[0])?,
+                    octaves: args[1]
+                        .parse()
+                        .map_err(|_| format!("'{}' isn't a number of octaves", args[1]))?,
+                    seed: args[2]
+                        .parse()
+                        .map_err(|_| format!("'{}' isn't a seed", args[2]))?,
+                }
+            }
+            "sin" => {
+                arity(1)?;
+                Node::Sin(self.input(args[0])?)
+            }
+            "mix" => {
+                arity(3)?;
+                Node::Mix {
+                    a: self.input(args[0])?,
+                    b: self.input(args[1])?,
+                    factor: self.input(args[2])?,
+                }
+            }
+            "ramp" => {
+                if args.len() < 5 || !(args.len() - 1).is_multiple_of(4) {
+                    return Err(String::from(
+                        "'ramp' takes an input and stops of a position and a color",
+                    ));
+                }
+                let stops = args[1..]
+                    .chunks_exact(4)
+                    .map(|stop| {
+                        Ok((
+                            number(stop[0])?,
+                            vec3(number(stop[1])?, number(stop[2])?, number(stop[3])?),
+                        ))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Node::Ramp {
+                    input: self.input(args[0])?,
+                    stops,
+                }
+            }
+            _ => match MATH_OPS.iter().find(|(_, name)| *name == kind) {
+                Some((op, _)) => {
+                    arity(2)?;
+                    Node::Math {
+                        op: *op,
+                        a: self.input(args[0])?,
+                        b: self.input(args[1])?,
+                    }
+                }
+                None => return Err(format!("unknown node '{}'", kind)),
+            },
+        };
+        self.add(name.trim(), node)?;
+        Ok(())
+    }
+
+    fn input(&self, name: &str) -> Result<NodeId, String> {
+        self.id(name)
+            .ok_or_else(|| format!("no node named '{}' comes before it", name))
+    }
+
+    fn evaluate(&self, id: NodeId, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        let value = |id| self.evaluate(id, u, v, p);
+        match &self.nodes[id.0] {
+            Node::Constant(color) => *color,
+            Node::Uv => vec3(u, v, 0.0),
+            Node::Position => *p,
+            Node::Component { input, axis } => Vec3::splat(value(*input)[*axis]),
+            Node::Image(_) => match &self.evaluators[id.0] {
+                Evaluator::Image(image) => image.value(u, v, p),
+                _ => unreachable!("images are loaded when added"),
+            },
+            Node::Noise { .. } => match &self.evaluators[id.0] {
+                Evaluator::Noise(noise) => {
+                    let n = noise.get([p.x as f64, p.y as f64, p.z as f64]) as f32;
+                    Vec3::splat(0.5 * (1.0 + n))
+                }
+                _ => unreachable!("noise is built when added"),
+            },
+            Node::Math { op, a, b } => op.apply(value(*a), value(*b)),
+            Node::Sin(input) => {
+                let x = value(*input);
+                vec3(x.x.sin(), x.y.sin(), x.z.sin())
+            }
+            Node::Mix { a, b, factor } => {
+                let (a, b) = (value(*a), value(*b));
+                a + (b - a) * value(*factor)
+            }
+            Node::Ramp { input, stops } => {
+                let x = value(*input);
+                ramp(stops, (x.x + x.y + x.z) / 3.0)
+            }
+        }
+    }
+}
+
+impl Default for TextureGraph {
+    fn default() -> Self {
+        TextureGraph::new()
+    }
+}
+
+/// The color of the `stops` at `position`, interpolated linearly between them.
+fn ramp(stops: &[(f32, Vec3)], position: f32) -> Vec3 {
+    let next = stops.partition_point(|(stop, _)| *stop <= position);
+    if next == 0 {
+        return stops[0].1;
+    }
+    if next == stops.len() {
+        return stops[next - 1].1;
+    }
+    let ((start, from), (end, to)) = (stops[next - 1], stops[next]);
+    from.lerp(to, (position - start) / (end - start))
+}
+
+fn number(arg: &str) -> Result<f32, String> {
+    arg.parse().map_err(|_| format!("'{}' isn't a number", arg))
+}
+
+impl Texture for TextureGraph {
+    fn value(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        match self.output {
+            Some(output) => self.evaluate(output, u, v, p),
+            None => Vec3::ZERO,
+        }
+    }
+}
+
+/// Writes the graph in the text format read by `TextureGraph::parse()`.
+impl fmt::Display for TextureGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |id: &NodeId| &self.names[id.0];
+        for (node_name, node) in self.names.iter().zip(&self.nodes) {
+            write!(f, "{} = ", node_name)?;
+            match node {
+                Node::Constant(c) => writeln!(f, "constant {} {} {}", c.x, c.y, c.z)?,
+                Node::Uv => writeln!(f, "uv")?,
+                Node::Position => writeln!(f, "position")?,
+                Node::Component { input, axis } => {
+                    writeln!(f, "component {} {}", name(input), ["x", "y", "z"][*axis])?
+                }
+                Node::Image(path) => writeln!(f, "image {}", path.display())?,
+                Node::Noise {
+                    frequency,
+                    octaves,
+                    seed,
+                } => writeln!(f, "noise {} {} {}", frequency, octaves, seed)?,
+                Node::Math { op, a, b } => writeln!(f, "{} {} {}", op.name(), name(a), name(b))?,
+                Node::Sin(input) => writeln!(f, "sin {}", name(input))?,
+                Node::Mix { a, b, factor } => {
+                    writeln!(f, "mix {} {} {}", name(a), name(b), name(factor))?
+                }
+                Node::Ramp { input, stops } => {
+                    write!(f, "ramp {}", name(input))?;
+                    for (position, c) in stops {
+                        write!(f, " {} {} {} {}", position, c.x, c.y, c.z)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        if let Some(output) = self.output {
+            if output.0 + 1 != self.nodes.len() {
+                writeln!(f, "output {}", name(&output))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use crate::textures::texture::Texture;
+
+    use super::TextureGraph;
+
+    const MARBLE: &str = "
+        # Stripes along z, wobbled by noise.
+        p = position
+        z = component p z
+        n = noise 1 6 7
+        ten = constant 10 10 10
+        wobble = multiply n ten
+        phase = add z wobble
+        wave = sin phase
+        veins = ramp wave -1 0.1 0.1 0.1 1 0.9 0.9 0.9
+    ";
+
+    #[test]
+    fn evaluates_math_mixes_and_ramps() {
+        let graph = TextureGraph::parse(
+            "red = constant 1 0 0
+             blue = constant 0 0 1
+             uv = uv
+             u = component uv x
+             mixed = mix red blue u
+             half = constant 0.5 0.5 0.5
+             scaled = multiply mixed half
+             ramped = ramp u 0 0 0 0 0.5 1 1 1 1 0 1 0
+             output scaled",
+        )
+        .unwrap();
+        assert_eq!(graph.value(0.25, 0.0, &Vec3::ZERO), vec3(0.375, 0.0, 0.125));
+
+        let mut graph = graph;
+        graph.set_output(graph.id("ramped").unwrap());
+        assert_eq!(graph.value(0.25, 0.0, &Vec3::ZERO), Vec3::splat(0.5));
+        assert_eq!(graph.value(0.75, 0.0, &Vec3::ZERO), vec3(0.5, 1.0, 0.5));
+        assert_eq!(graph.value(2.0, 0.0, &Vec3::ZERO), vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let graph = TextureGraph::parse(MARBLE).unwrap();
+        let reparsed = TextureGraph::parse(&graph.to_string()).unwrap();
+        assert_eq!(graph.to_string(), reparsed.to_string());
+        for i in 0..10 {
+            let p = vec3(i as f32 * 0.37, 1.3, i as f32 * -0.71);
+            assert_eq!(graph.value(0.0, 0.0, &p), reparsed.value(0.0, 0.0, &p));
+        }
+    }
+
+    #[test]
+    fn rejects_inputs_which_come_later() {
+        let error = TextureGraph::parse("a = sin b\nb = uv").err().unwrap();
+        assert!(error.contains("line 1"), "{}", error);
+    }
+}
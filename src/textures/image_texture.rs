@@ -1,4 +1,7 @@
-use super::texture::Texture;
+use super::{
+    panorama::{self, PanoramaLayout},
+    texture::Texture,
+};
 
 use glam::Vec3;
 use image::{io::Reader as ImageReader, ImageBuffer, Rgb};
@@ -12,9 +15,29 @@ pub struct ImageTexture {
 impl ImageTexture {
     pub fn new(path: &Path) -> ImageTexture {
         // TODO propogate errors
-        let image = ImageReader::open(path).unwrap().decode().unwrap().to_rgb8();
+        ImageTexture::load(path).unwrap()
+    }
+
+    /// Loads the image at `path`, or returns why it couldn't be read.
+    pub fn load(path: &Path) -> Result<ImageTexture, String> {
+        let image = ImageReader::open(path)
+            .map_err(|e| e.to_string())?
+            .decode()
+            .map_err(|e| e.to_string())?
+            .to_rgb8();
+        Ok(ImageTexture { image })
+    }
 
-        ImageTexture { image }
+    /// Loads the panorama at `path`, laid out as `layout`, converting it to the
+    /// equirectangular layout which image backgrounds are looked up in.
+    pub fn panorama(path: &Path, layout: PanoramaLayout) -> ImageTexture {
+        let texture = ImageTexture::new(path);
+        match layout {
+            PanoramaLayout::Equirectangular => texture,
+            _ => ImageTexture {
+                image: panorama::to_equirectangular(&texture.image, layout),
+            },
+        }
     }
 }
 
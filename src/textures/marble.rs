@@ -1,6 +1,7 @@
-use glam::{vec3};
+use glam::vec3;
 use noise::{NoiseFn, Perlin, Turbulence};
-use rand::random;
+
+use crate::random::random;
 
 use super::texture::Texture;
 
@@ -24,6 +25,10 @@ impl Texture for Marble {
     fn value(&self, _u: f32, _v: f32, p: &glam::Vec3) -> glam::Vec3 {
         vec3(1.0, 1.0, 1.0)
             * 0.5
-            * (1.0 + f32::sin(self.scale * p.z + 10.0 * self.noise.get([p.x as f64, p.y as f64, p.z as f64]) as f32))
+            * (1.0
+                + f32::sin(
+                    self.scale * p.z
+                        + 10.0 * self.noise.get([p.x as f64, p.y as f64, p.z as f64]) as f32,
+                ))
     }
 }
@@ -1,5 +1,8 @@
 pub mod checker;
+pub mod graph;
 pub mod image_texture;
 pub mod marble;
+pub mod panorama;
 pub mod solid_color;
 pub mod texture;
+pub mod vertex_color;
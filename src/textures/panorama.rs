@@ -0,0 +1,155 @@
+//! Converting panoramas to the equirectangular (latitude-longitude) layout which image
+//! backgrounds are looked up in, so that environments can be loaded in whichever layout
+//! they were captured or shared in.
+//!
+//! Each layout faces the same way once converted: its front (the center of an angular
+//! map, or the center face of a cubemap cross) looks along -Z, with +Y up.
+
+use std::f32::consts::PI;
+
+use glam::{vec3, Vec3};
+use image::RgbImage;
+
+/// How the directions around a point are laid out in a panorama image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanoramaLayout {
+    /// Longitude across and latitude down, in an image twice as wide as it's tall.
+    #[default]
+    Equirectangular,
+    /// A light probe's angular map: a disc filling a square image, whose center looks
+    /// forward and whose distance from the center is proportional to the angle from
+    /// forward, reaching straight backward at its rim.
+    AngularMap,
+    /// The six faces of a cube laid out in a horizontal cross, four faces wide and three
+    /// tall: left, front, right and back across the middle, with up above the front face
+    /// and down below it.
+    Cubemap,
+}
+
+/// Converts the `image`, laid out as `layout`, to an equirectangular image.
+pub fn to_equirectangular(image: &RgbImage, layout: PanoramaLayout) -> RgbImage {
+    let (width, height) = match layout {
+        PanoramaLayout::Equirectangular => return image.clone(),
+        // Keep about the same resolution around the horizon.
+        PanoramaLayout::AngularMap => (image.width() * 2, image.width()),
+        PanoramaLayout::Cubemap => (image.width(), image.width() / 2),
+    };
+    RgbImage::from_fn(width, height, |i, j| {
+        // The inverse of the lookup of background images with `Sphere::get_uv()`.
+        let u = (i as f32 + 0.5) / width as f32;
+        let v = 1.0 - (j as f32 + 0.5) / height as f32;
+        let (theta, phi) = (v * PI, u * 2.0 * PI);
+        let direction = vec3(
+            -theta.sin() * phi.cos(),
+            -theta.cos(),
+            theta.sin() * phi.sin(),
+        );
+        let (x, y) = match layout {
+            PanoramaLayout::Equirectangular => unreachable!(),
+            PanoramaLayout::AngularMap => angular_map_position(direction),
+            PanoramaLayout::Cubemap => cubemap_position(direction),
+        };
+        let x = ((x * image.width() as f32) as u32).min(image.width() - 1);
+        let y = ((y * image.height() as f32) as u32).min(image.height() - 1);
+        *image.get_pixel(x, y)
+    })
+}
+
+/// The position in an angular map of the `direction`, as fractions of its width and
+/// height from the top left.
+fn angular_map_position(direction: Vec3) -> (f32, f32) {
+    let sideways = (direction.x * direction.x + direction.y * direction.y).sqrt();
+    // Straight ahead, any angle around the center will do.
+    let r = if sideways > 0.0 {
+        (-direction.z).clamp(-1.0, 1.0).acos() / PI / sideways
+    } else {
+        0.0
+    };
+    let (a, b) = (direction.x * r, direction.y * r);
+    ((a + 1.0) / 2.0, (1.0 - b) / 2.0)
+}
+
+/// The faces of a cubemap cross, as their column and row in the cross and the
+/// directions of their forward, right and up.
+const CUBE_FACES: [((u32, u32), [Vec3; 3]); 6] = [
+    ((0, 1), [Vec3::NEG_X, Vec3::NEG_Z, Vec3::Y]),
+    ((1, 1), [Vec3::NEG_Z, Vec3::X, Vec3::Y]),
+    ((2, 1), [Vec3::X, Vec3::Z, Vec3::Y]),
+    ((3, 1), [Vec3::Z, Vec3::NEG_X, Vec3::Y]),
+    ((1, 0), [Vec3::Y, Vec3::X, Vec3::Z]),
+    ((1, 2), [Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z]),
+];
+
+/// The position in a cubemap cross of the `direction`, as fractions of its width and
+/// height from the top left.
+fn cubemap_position(direction: Vec3) -> (f32, f32) {
+    let ((column, row), [forward, right, up]) = CUBE_FACES
+        .into_iter()
+        .max_by(|(_, [a, _, _]), (_, [b, _, _])| direction.dot(*a).total_cmp(&direction.dot(*b)))
+        .unwrap();
+    let depth = direction.dot(forward);
+    let a = direction.dot(right) / depth;
+    let b = direction.dot(up) / depth;
+    (
+        (column as f32 + (a + 1.0) / 2.0) / 4.0,
+        (row as f32 + (1.0 - b) / 2.0) / 3.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use image::{Rgb, RgbImage};
+
+    use crate::{geometry::sphere::Sphere, textures::panorama::CUBE_FACES};
+
+    use super::{to_equirectangular, PanoramaLayout};
+
+    /// The pixel of an equirectangular image seen looking along the `direction`.
+    fn pixel_towards(image: &RgbImage, direction: Vec3) -> Rgb<u8> {
+        let (u, v) = Sphere::get_uv(&direction);
+        let x = ((u * image.width() as f32) as u32).min(image.width() - 1);
+        let y = (((1.0 - v) * image.height() as f32) as u32).min(image.height() - 1);
+        *image.get_pixel(x, y)
+    }
+
+    #[test]
+    fn cubemap_faces_keep_their_directions() {
+        // Color each face of the cross by its index.
+        let size = 8;
+        let mut cubemap = RgbImage::new(4 * size, 3 * size);
+        for (index, ((column, row), _)) in CUBE_FACES.iter().enumerate() {
+            for y in 0..size {
+                for x in 0..size {
+                    cubemap.put_pixel(column * size + x, row * size + y, Rgb([index as u8; 3]));
+                }
+            }
+        }
+        let equirectangular = to_equirectangular(&cubemap, PanoramaLayout::Cubemap);
+        for (index, (_, [forward, _, _])) in CUBE_FACES.iter().enumerate() {
+            assert_eq!(
+                pixel_towards(&equirectangular, *forward),
+                Rgb([index as u8; 3])
+            );
+        }
+    }
+
+    #[test]
+    fn angular_map_centers_forward() {
+        // White in the middle, fading to black at the rim.
+        let size = 64;
+        let angular_map = RgbImage::from_fn(size, size, |x, y| {
+            let dx = x as f32 + 0.5 - size as f32 / 2.0;
+            let dy = y as f32 + 0.5 - size as f32 / 2.0;
+            let r = (dx * dx + dy * dy).sqrt() / (size as f32 / 2.0);
+            Rgb([(255.0 * (1.0 - r).max(0.0)) as u8; 3])
+        });
+        let equirectangular = to_equirectangular(&angular_map, PanoramaLayout::AngularMap);
+        let forward = pixel_towards(&equirectangular, Vec3::NEG_Z).0[0];
+        let sideways = pixel_towards(&equirectangular, Vec3::X).0[0];
+        let backward = pixel_towards(&equirectangular, Vec3::Z).0[0];
+        assert!(forward > 240);
+        assert!((110..145).contains(&sideways));
+        assert!(backward < 15);
+    }
+}
@@ -1,4 +1,4 @@
-use glam::{Vec3, vec3};
+use glam::{vec3, Vec3};
 
 use super::texture::Texture;
 
@@ -1,5 +1,13 @@
 use glam::Vec3;
 
+use crate::hittable::HitRecord;
+
 pub trait Texture: Send + Sync {
     fn value(&self, u: f32, v: f32, p: &Vec3) -> Vec3;
+
+    /// The value of the texture at the surface of the `hit_record`, for textures which
+    /// depend on more of the surface than its UVs and point, such as `VertexColor`.
+    fn value_at(&self, hit_record: &HitRecord) -> Vec3 {
+        self.value(hit_record.u, hit_record.v, &hit_record.point)
+    }
 }
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::hittable::HitRecord;
+
+use super::{solid_color::SolidColor, texture::Texture};
+
+/// The colors baked into the vertices of a mesh, e.g. by a scanner, interpolated across
+/// each triangle. Surfaces without vertex colors take the fallback texture instead.
+pub struct VertexColor {
+    fallback: Arc<dyn Texture>,
+}
+
+impl VertexColor {
+    /// Creates a vertex color texture which is white where there are no vertex colors.
+    pub fn new() -> VertexColor {
+        VertexColor {
+            fallback: Arc::new(SolidColor::new(Vec3::ONE)),
+        }
+    }
+
+    /// Uses the `fallback` texture where there are no vertex colors.
+    pub fn with_fallback(mut self, fallback: Arc<dyn Texture>) -> VertexColor {
+        self.fallback = fallback;
+        self
+    }
+}
+
+impl Default for VertexColor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Texture for VertexColor {
+    fn value(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        // Without the hit record, there are no vertex colors to go on.
+        self.fallback.value(u, v, p)
+    }
+
+    fn value_at(&self, hit_record: &HitRecord) -> Vec3 {
+        match hit_record.color {
+            Some(color) => color,
+            None => self.fallback.value_at(hit_record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        geometry::{sphere::Sphere, triangle::Tri},
+        hittable::Hittable,
+        materials::lambertian::Lambertian,
+        ray::Ray,
+        textures::solid_color::SolidColor,
+    };
+
+    use super::VertexColor;
+
+    #[test]
+    fn vertex_colors_are_interpolated_at_the_hit() {
+        let texture = VertexColor::new().with_fallback(Arc::new(SolidColor::new(Vec3::splat(0.5))));
+        let material = Arc::new(Lambertian::new(Arc::new(texture)));
+        let tri = Tri::new(Vec3::ZERO, Vec3::X, Vec3::Y, material.clone()).with_colors([
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+        ]);
+        let albedo = |object: &dyn Hittable, origin: Vec3| {
+            let ray = Ray::new(origin, -Vec3::Z, 0.0);
+            let hit_record = object
+                .hit(&ray, 0.0, f32::INFINITY, &Arc::new(None))
+                .unwrap();
+            let scatter_record = hit_record.material.scatter(&ray, &hit_record).unwrap();
+            scatter_record.attenuation
+        };
+
+        let color = albedo(&tri, vec3(0.25, 0.5, 1.0));
+        assert!(color.distance(vec3(0.25, 0.25, 0.5)) < 1e-5);
+        let color = albedo(&tri, vec3(0.0, 0.0, 1.0));
+        assert!(color.distance(vec3(1.0, 0.0, 0.0)) < 1e-5);
+        // Surfaces without vertex colors take the fallback.
+        let sphere = Sphere::new(Vec3::ZERO, 1.0, material);
+        assert_eq!(albedo(&sphere, vec3(0.0, 0.0, 2.0)), Vec3::splat(0.5));
+    }
+}
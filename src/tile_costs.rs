@@ -0,0 +1,126 @@
+//! Estimating how long each tile of an image takes to render, so that the most expensive
+//! tiles can be started first. Rendering tiles in the order they lie in leaves whichever
+//! expensive tiles come last, e.g. those around the light and the glass of a Cornell box,
+//! running on a few threads while the rest sit idle; starting them first lets the cheap
+//! tiles fill in around them.
+//!
+//! A tile's cost is the time its samples took in the last pass over the image, or before
+//! the first pass, the time taken by a low resolution probe of it or by the same tile in a
+//! previous render, such as the last frame of an animation, saved to a file.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::renderer::Tile;
+
+/// The cost of each tile of an image, in seconds.
+pub(crate) struct TileCosts {
+    seconds: Vec<f32>,
+}
+
+impl TileCosts {
+    /// The costs of `len` tiles, none of which are known yet.
+    pub fn new(len: usize) -> TileCosts {
+        TileCosts {
+            seconds: vec![0.0; len],
+        }
+    }
+
+    /// Loads the costs saved at `path`, if there are any for the same `tiles`.
+    pub fn load(path: &Path, tiles: &[Tile]) -> io::Result<Option<TileCosts>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // Each line is a tile's x, y, width and height, and its cost.
+        let parse = |line: &str| -> Option<(Tile, f32)> {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [x, y, width, height, seconds] = fields[..] else {
+                return None;
+            };
+            let tile = Tile::new(
+                width.parse().ok()?,
+                height.parse().ok()?,
+                x.parse().ok()?,
+                y.parse().ok()?,
+            );
+            Some((tile, seconds.parse().ok()?))
+        };
+        let saved: Option<Vec<(Tile, f32)>> = text.lines().map(parse).collect();
+        match saved {
+            Some(saved)
+                if saved.len() == tiles.len()
+                    && saved.iter().zip(tiles).all(|((a, _), b)| a == b) =>
+            {
+                Ok(Some(TileCosts {
+                    seconds: saved.into_iter().map(|(_, seconds)| seconds).collect(),
+                }))
+            }
+            _ => {
+                eprintln!(
+                    "Tile costs {} don't match this render's tiles; measuring them again.",
+                    path.display()
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Saves the costs of the `tiles` to `path`, for a later render to load.
+    pub fn save(&self, path: &Path, tiles: &[Tile]) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        for (tile, seconds) in tiles.iter().zip(&self.seconds) {
+            writeln!(
+                writer,
+                "{} {} {} {} {}",
+                tile.x_coord_start, tile.y_coord_start, tile.width, tile.height, seconds
+            )?;
+        }
+        writer.flush()
+    }
+
+    pub fn set(&mut self, index: usize, seconds: f32) {
+        self.seconds[index] = seconds;
+    }
+
+    /// The indices of the tiles, most expensive first. Tiles of equal cost stay in order.
+    pub fn order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.seconds.len()).collect();
+        order.sort_by(|a, b| self.seconds[*b].total_cmp(&self.seconds[*a]));
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::renderer::Tile;
+
+    use super::TileCosts;
+
+    #[test]
+    fn expensive_tiles_come_first_and_costs_are_reloaded() {
+        let tiles = Tile::tile(8, 4, 4, 2);
+        let mut costs = TileCosts::new(tiles.len());
+        costs.set(1, 0.5);
+        costs.set(2, 2.0);
+        costs.set(3, 0.5);
+        assert_eq!(costs.order(), vec![2, 1, 3, 0]);
+
+        let path = std::env::temp_dir().join("shimmer_tile_costs.txt");
+        costs.save(&path, &tiles).unwrap();
+        let loaded = TileCosts::load(&path, &tiles).unwrap().unwrap();
+        assert_eq!(loaded.order(), costs.order());
+        // Costs of differently tiled images don't apply.
+        assert!(TileCosts::load(&path, &Tile::tile(8, 4, 2, 2))
+            .unwrap()
+            .is_none());
+        fs::remove_file(&path).unwrap();
+        assert!(TileCosts::load(&path, &tiles).unwrap().is_none());
+    }
+}
@@ -0,0 +1,248 @@
+//! Handing out the tiles of an image to be rendered and gathering their results, for
+//! frontends which drive rendering a tile at a time rather than calling
+//! `Renderer::render()`, such as GUIs showing tiles as they finish, coordinators farming
+//! tiles out to other machines, or tests.
+//!
+//! A frontend takes tasks from the scheduler with `next_tile()`, renders each with
+//! `Renderer::render_tile()` (or has a worker with the same renderer and scene do so),
+//! and gives the results back with `submit_result()`. Once none remain, the film holds
+//! the whole image, to write with `Renderer::write_film()`. Tasks may be taken and
+//! submitted from any number of threads at once, and with a seed, the image comes out
+//! the same as `render()`'s whichever order the tiles finish in.
+
+use std::{collections::VecDeque, ops::Range, sync::Mutex};
+
+use ahash::{AHashMap, AHashSet};
+
+use crate::{film::Film, renderer::Tile};
+
+/// A tile to be rendered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileTask {
+    /// Identifies the tile to its scheduler.
+    pub index: usize,
+    pub tile: Tile,
+    /// The indices of the samples to take of each pixel of the tile.
+    pub samples: Range<u32>,
+}
+
+/// The samples of a rendered tile, as returned by `Renderer::render_tile()`.
+pub struct TileResult {
+    /// The index of the task the tile was rendered for.
+    pub index: usize,
+    /// The tile's samples, which may extend past it by the filter's radius.
+    pub film: Film,
+}
+
+struct Progress {
+    /// The tasks not yet handed out, in the order they're handed out.
+    queue: VecDeque<usize>,
+    /// The tasks handed out whose results haven't been submitted.
+    outstanding: AHashSet<usize>,
+}
+
+/// The film of the whole image, into which results are merged in the order of their
+/// tasks' indices, whatever order they're submitted in, so that rounding in the sums of
+/// samples splatted across tiles' edges comes out the same every time.
+struct Accumulation {
+    film: Film,
+    /// The index of the next task whose result is to be merged.
+    next: usize,
+    /// Results submitted ahead of `next`, waiting to be merged.
+    waiting: AHashMap<usize, Film>,
+}
+
+impl Accumulation {
+    /// Merges the waiting results from `next` on, up to the first which hasn't arrived.
+    fn merge_ready(&mut self) {
+        while let Some(film) = self.waiting.remove(&self.next) {
+            self.film.merge(&film);
+            self.next += 1;
+        }
+    }
+}
+
+/// Hands out each tile of an image once, and accumulates the results into a film of the
+/// whole image. Create one with `Renderer::tile_scheduler()`.
+pub struct TileScheduler {
+    tiles: Vec<Tile>,
+    samples_per_pixel: u32,
+    progress: Mutex<Progress>,
+    accumulation: Mutex<Accumulation>,
+}
+
+impl TileScheduler {
+    /// Schedules the `tiles`, each to be rendered with `samples_per_pixel` samples into
+    /// the `film`, which covers the whole image.
+    pub(crate) fn new(tiles: Vec<Tile>, samples_per_pixel: u32, mut film: Film) -> TileScheduler {
+        film.set_samples_per_pixel(samples_per_pixel);
+        let progress = Progress {
+            queue: (0..tiles.len()).collect(),
+            outstanding: AHashSet::new(),
+        };
+        TileScheduler {
+            tiles,
+            samples_per_pixel,
+            progress: Mutex::new(progress),
+            accumulation: Mutex::new(Accumulation {
+                film,
+                next: 0,
+                waiting: AHashMap::new(),
+            }),
+        }
+    }
+
+    /// The next tile to render, or `None` if every tile has been handed out.
+    pub fn next_tile(&self) -> Option<TileTask> {
+        let mut progress = self.progress.lock().unwrap();
+        let index = progress.queue.pop_front()?;
+        progress.outstanding.insert(index);
+        Some(TileTask {
+            index,
+            tile: self.tiles[index],
+            samples: 0..self.samples_per_pixel,
+        })
+    }
+
+    /// Adds the samples of a rendered tile to the image. Returns false, ignoring the
+    /// result, if its task isn't outstanding, e.g. because a result was already submitted
+    /// for it.
+    pub fn submit_result(&self, result: TileResult) -> bool {
+        if !self
+            .progress
+            .lock()
+            .unwrap()
+            .outstanding
+            .remove(&result.index)
+        {
+            return false;
+        }
+        let mut accumulation = self.accumulation.lock().unwrap();
+        accumulation.waiting.insert(result.index, result.film);
+        accumulation.merge_ready();
+        true
+    }
+
+    /// Hands out the outstanding task with the `index` again, e.g. because the worker
+    /// rendering it failed. Returns false if it isn't outstanding.
+    pub fn retry(&self, index: usize) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+        if !progress.outstanding.remove(&index) {
+            return false;
+        }
+        progress.queue.push_back(index);
+        true
+    }
+
+    /// The number of tiles whose results haven't been submitted yet.
+    pub fn remaining(&self) -> usize {
+        let progress = self.progress.lock().unwrap();
+        progress.queue.len() + progress.outstanding.len()
+    }
+
+    /// Whether the results of every tile have been submitted.
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Every tile of the image, in the order they were first handed out.
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// The film of the whole image, holding the samples of the tiles submitted so far.
+    pub fn into_film(self) -> Film {
+        let Accumulation {
+            mut film, waiting, ..
+        } = self.accumulation.into_inner().unwrap();
+        // The results still waiting on tiles which never arrived.
+        let mut waiting: Vec<(usize, Film)> = waiting.into_iter().collect();
+        waiting.sort_by_key(|(index, _)| *index);
+        for (_, tile_film) in &waiting {
+            film.merge(tile_film);
+        }
+        film
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use glam::{vec3, Vec3};
+
+    use crate::{
+        background::Background,
+        camera::Camera,
+        filter::Filter,
+        geometry::sphere::Sphere,
+        hittable::HittableList,
+        materials::lambertian::Lambertian,
+        renderer::{RenderSettings, Renderer},
+    };
+
+    #[test]
+    fn tiles_render_as_the_renderer_does_in_any_order() {
+        let mut world = HittableList::new();
+        let material = Arc::new(Lambertian::from_color(vec3(0.7, 0.3, 0.2)));
+        world.add(Arc::new(Sphere::new(Vec3::ZERO, 1.0, material)));
+        let camera = Camera::new(
+            vec3(0.0, 0.0, 4.0),
+            Vec3::ZERO,
+            Vec3::Y,
+            45.0,
+            1.5,
+            0.0,
+            4.0,
+            0.0,
+            1.0,
+        );
+        let background = Background::Solid(vec3(0.5, 0.7, 1.0));
+        let predictors = Arc::new(None);
+        let directory = std::env::temp_dir();
+        let path = |name: &str| directory.join(format!("shimmer_tiles_{}.ppm", name));
+        // Samples splat past the edges of their tiles.
+        let renderer = |name: &str| {
+            Renderer::new(15, 10)
+                .with_seed(7)
+                .with_filter(Filter::Tent { radius: 1.5 })
+                .with_output(path(name))
+        };
+
+        let settings = RenderSettings {
+            camera: &camera,
+            world: &world,
+            background: &background,
+            max_depth: 5,
+            predictors: &predictors,
+        };
+        renderer("whole").render(&settings, 4, 4, 4).unwrap();
+
+        let renderer = renderer("tiles");
+        let scheduler = renderer.tile_scheduler(4, 4, 4);
+        let mut tasks = Vec::new();
+        while let Some(task) = scheduler.next_tile() {
+            tasks.push(task);
+        }
+        assert_eq!(tasks.len(), scheduler.tiles().len());
+        // A failed task is handed out again.
+        assert!(scheduler.retry(tasks[0].index));
+        assert_eq!(scheduler.next_tile(), Some(tasks[0].clone()));
+        for task in tasks.iter().rev() {
+            assert!(!scheduler.is_finished());
+            let result = renderer.render_tile(task, &settings);
+            assert!(scheduler.submit_result(result));
+        }
+        assert!(scheduler.is_finished());
+        // Results are only taken once.
+        let again = renderer.render_tile(&tasks[0], &settings);
+        assert!(!scheduler.submit_result(again));
+        renderer.write_film(&scheduler.into_film()).unwrap();
+
+        let whole = fs::read(path("whole")).unwrap();
+        let tiles = fs::read(path("tiles")).unwrap();
+        fs::remove_file(path("whole")).unwrap();
+        fs::remove_file(path("tiles")).unwrap();
+        assert_eq!(whole, tiles);
+    }
+}
@@ -0,0 +1,108 @@
+//! Physical units for lights and exposure, so that scenes modelled on real-world
+//! references come out as bright as they would be photographed.
+//!
+//! Scenes usually give lights an emitted color in arbitrary units, and the image is
+//! written without scaling, so a light's brightness is chosen by eye. In physical units,
+//! the scene is modelled in meters, the luminance (Rec. 709 Y) of colors along rays is in
+//! candelas per square meter (nits), lights are given their total output in lumens or
+//! watts, and the camera's exposure is given as an exposure value at ISO 100 (EV100), as
+//! a photographer would meter it. See `Renderer::with_exposure()`.
+//!
+//! The conversions follow Lagarde and de Rousiers, "Moving Frostbite to Physically Based
+//! Rendering" (2014).
+
+use std::f32::consts::PI;
+
+use glam::Vec3;
+
+/// The luminous efficacy of radiation at 555nm, where the eye is most sensitive, in
+/// lumens per watt. Radiant watts are converted to lumens at this rate.
+pub const LUMINOUS_EFFICACY: f32 = 683.0;
+
+/// The total light sent out by a light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightPower {
+    /// Luminous flux, as printed on bulbs' packaging, e.g. 800 for a 60W incandescent
+    /// bulb or its LED replacement.
+    Lumens(f32),
+    /// Radiant flux, the power carried by the light itself. This isn't a bulb's rated
+    /// power, most of which is lost as heat.
+    Watts(f32),
+}
+
+impl LightPower {
+    /// The luminous flux of the light, in lumens.
+    pub fn lumens(&self) -> f32 {
+        match self {
+            LightPower::Lumens(lumens) => *lumens,
+            LightPower::Watts(watts) => watts * LUMINOUS_EFFICACY,
+        }
+    }
+}
+
+/// The luminance of a linear color with sRGB (Rec. 709) primaries.
+pub fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// The radiance of a diffuse light of `area` square meters which sends out `power` in
+/// total, with the hue of `color`, whose own brightness is ignored. Lights emit from both
+/// sides of their surfaces, so half of the power leaves from each.
+pub fn area_light_radiance(color: Vec3, power: LightPower, area: f32) -> Vec3 {
+    let color_luminance = luminance(color);
+    if color_luminance <= 0.0 || area <= 0.0 {
+        return Vec3::ZERO;
+    }
+    // A Lambertian surface of radiance L sends out a flux of L * pi * A from each side.
+    let nits = power.lumens() / (2.0 * PI * area);
+    color / color_luminance * nits
+}
+
+/// The exposure value at ISO 100 of a camera with an aperture of `f_number`, a shutter
+/// open for `shutter_time` seconds and a sensitivity of `iso`.
+pub fn ev100(f_number: f32, shutter_time: f32, iso: f32) -> f32 {
+    (f_number * f_number / shutter_time * 100.0 / iso).log2()
+}
+
+/// The factor which scales luminances for a camera exposed at `ev100`, so that the
+/// brightest luminance it captures without clipping becomes 1.
+pub fn exposure_scale(ev100: f32) -> f32 {
+    // The saturation-based sensitivity of a sensor, with its usual factor of 78 / 65.
+    1.0 / (1.2 * 2.0_f32.powf(ev100))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use glam::vec3;
+
+    use super::{area_light_radiance, ev100, exposure_scale, luminance, LightPower};
+
+    #[test]
+    fn light_power_is_spread_over_the_light() {
+        let color = vec3(1.0, 0.5, 0.25);
+        let area = 0.25;
+        let radiance = area_light_radiance(color, LightPower::Lumens(800.0), area);
+        // The luminous flux leaving both sides of the light adds back up to its power.
+        assert!((luminance(radiance) * 2.0 * PI * area - 800.0).abs() < 1e-2);
+        // Only the color's hue counts.
+        assert_eq!(
+            radiance,
+            area_light_radiance(color * 10.0, LightPower::Lumens(800.0), area)
+        );
+        assert_eq!(
+            area_light_radiance(color, LightPower::Watts(2.0), area),
+            area_light_radiance(color, LightPower::Lumens(1366.0), area)
+        );
+    }
+
+    #[test]
+    fn exposure_matches_a_camera() {
+        // f/16 at 1/100s and ISO 100, the "sunny 16" rule, is about EV 15.
+        assert!((ev100(16.0, 0.01, 100.0) - 14.64).abs() < 0.01);
+        // Doubling the sensitivity takes one stop less light.
+        assert!((ev100(16.0, 0.01, 200.0) - ev100(16.0, 0.01, 100.0) + 1.0).abs() < 1e-5);
+        assert!((exposure_scale(3.0) * 1.2 * 8.0 - 1.0).abs() < 1e-6);
+    }
+}
@@ -1,13 +1,11 @@
-use glam::{Vec3, vec3};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use glam::{vec2, vec3, Vec2, Vec3};
 use palette::Srgb;
 use rand::Rng;
 
-pub fn near_zero(vec: &Vec3) -> bool {
-    vec.x.abs() < f32::EPSILON && vec.y.abs() < f32::EPSILON && vec.z.abs() < f32::EPSILON
-}
-
 pub fn random_in_unit_disk() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::random::rng();
     loop {
         let p = vec3(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
         if p.length_squared() < 1.0 {
@@ -16,8 +14,77 @@ pub fn random_in_unit_disk() -> Vec3 {
     }
 }
 
+/// Maps a point of the unit square onto the unit disk with Shirley and Chiu's concentric
+/// mapping, which keeps evenly spread points evenly spread.
+pub fn square_to_unit_disk(point: Vec2) -> Vec2 {
+    let offset = 2.0 * point - Vec2::ONE;
+    if offset == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+    let (radius, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, FRAC_PI_4 * (offset.y / offset.x))
+    } else {
+        (offset.y, FRAC_PI_2 - FRAC_PI_4 * (offset.x / offset.y))
+    };
+    radius * vec2(theta.cos(), theta.sin())
+}
+
+/// An orthonormal basis, for working with directions relative to a surface.
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// A basis whose `w` axis points along `w`, e.g. a surface normal, with `u` and `v`
+    /// in some orientation across it.
+    pub fn from_w(w: Vec3) -> Onb {
+        let w = w.normalize();
+        let (u, v) = w.any_orthonormal_pair();
+        Onb { u, v, w }
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Transforms `a`, in coordinates along the `u`, `v` and `w` axes, to world space.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
+
 pub fn srgb_from_vec3(vec: Vec3) -> Srgb {
     // Our colors from ray tracing are already in linear rgb space, so
     // we make no conversions.
     Srgb::from_components((vec.x, vec.y, vec.z))
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::Onb;
+
+    #[test]
+    fn onb_is_orthonormal() {
+        for w in [
+            Vec3::X,
+            -Vec3::Y,
+            vec3(1.0, 2.0, -3.0),
+            vec3(0.0, 0.0, -1e-3),
+        ] {
+            let onb = Onb::from_w(w);
+            assert!(onb.local(Vec3::Z).abs_diff_eq(w.normalize(), 1e-6));
+            let (u, v) = (onb.local(Vec3::X), onb.local(Vec3::Y));
+            for (a, b) in [(u, v), (v, onb.w()), (onb.w(), u)] {
+                assert!((a.length() - 1.0).abs() < 1e-6);
+                assert!(a.dot(b).abs() < 1e-6);
+            }
+            // Right-handed, so that w = u x v.
+            assert!(u.cross(v).abs_diff_eq(onb.w(), 1e-6));
+        }
+    }
+}